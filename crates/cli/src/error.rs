@@ -28,4 +28,8 @@ pub enum Error {
 
     #[error("Invalid hostname: {0}")]
     InvalidHostname(String),
+
+    #[cfg(feature = "packet_capture")]
+    #[error("The capture has no connections")]
+    EmptyCapture,
 }
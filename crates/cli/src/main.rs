@@ -1,6 +1,6 @@
 use std::net::{IpAddr, ToSocketAddrs};
 
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use gamedig::{
     games::*,
     protocols::types::{CommonResponse, ExtraRequestSettings, TimeoutSettings},
@@ -35,23 +35,70 @@ struct Cli {
     action: Action,
 }
 
+// Boxed in Action::Query, the settings make these much larger than the other
+// commands' arguments.
+#[derive(Args, Debug)]
+struct QueryArgs {
+    /// Unique identifier of the game for which server information is being
+    /// queried.
+    #[arg(short, long)]
+    game: String,
+
+    /// Hostname or IP address of the server.
+    #[arg(short, long)]
+    ip: String,
+
+    /// Optional query port number for the server. If not provided the
+    /// default port for the game is used.
+    #[arg(short, long)]
+    port: Option<u16>,
+
+    /// Specifies the output format
+    #[arg(short, long, default_value = "debug", value_enum)]
+    format: OutputFormat,
+
+    /// Which response variant to use when outputting
+    #[arg(short, long, default_value = "generic")]
+    output_mode: OutputMode,
+
+    /// Optional file path for packet capture file writer
+    ///
+    /// When set a PCAP file will be written to the location. This file can
+    /// be read with a tool like wireshark. The PCAP contains a log of the
+    /// TCP and UDP data sent/recieved by the gamedig library, it does not
+    /// contain an accurate representation of the real packets sent on the
+    /// wire as some information has to be hallucinated in order for it to
+    /// display nicely.
+    #[cfg(feature = "packet_capture")]
+    #[arg(short, long)]
+    capture: Option<std::path::PathBuf>,
+
+    /// Optional timeout settings for the server query
+    #[command(flatten, next_help_heading = "Timeouts")]
+    timeout_settings: Option<TimeoutSettings>,
+
+    /// Optional extra settings for the server query
+    #[command(flatten, next_help_heading = "Query options")]
+    extra_options: Option<ExtraRequestSettings>,
+}
+
 #[derive(Subcommand, Debug)]
 enum Action {
     /// Query game server information
-    Query {
-        /// Unique identifier of the game for which server information is being
-        /// queried.
-        #[arg(short, long)]
-        game: String,
+    Query(Box<QueryArgs>),
 
-        /// Hostname or IP address of the server.
+    /// Replay a packet capture, re-running the query it recorded against the
+    /// current version of the library
+    #[cfg(feature = "packet_capture")]
+    Replay {
+        /// Unique identifier of the game the capture was made for.
         #[arg(short, long)]
-        ip: String,
+        game: String,
 
-        /// Optional query port number for the server. If not provided the
-        /// default port for the game is used.
+        /// Path of the capture file, as written by the query's capture option.
+        /// The query is made to the address it was captured with.
         #[arg(short, long)]
-        port: Option<u16>,
+        capture: std::path::PathBuf,
 
         /// Specifies the output format
         #[arg(short, long, default_value = "debug", value_enum)]
@@ -61,23 +108,7 @@ enum Action {
         #[arg(short, long, default_value = "generic")]
         output_mode: OutputMode,
 
-        /// Optional file path for packet capture file writer
-        ///
-        /// When set a PCAP file will be written to the location. This file can
-        /// be read with a tool like wireshark. The PCAP contains a log of the
-        /// TCP and UDP data sent/recieved by the gamedig library, it does not
-        /// contain an accurate representation of the real packets sent on the
-        /// wire as some information has to be hallucinated in order for it to
-        /// display nicely.
-        #[cfg(feature = "packet_capture")]
-        #[arg(short, long)]
-        capture: Option<std::path::PathBuf>,
-
-        /// Optional timeout settings for the server query
-        #[command(flatten, next_help_heading = "Timeouts")]
-        timeout_settings: Option<TimeoutSettings>,
-
-        /// Optional extra settings for the server query
+        /// Optional extra settings the capture was made with
         #[command(flatten, next_help_heading = "Query options")]
         extra_options: Option<ExtraRequestSettings>,
     },
@@ -423,28 +454,53 @@ fn main() -> Result<()> {
     let args = Cli::parse();
 
     match args.action {
-        Action::Query {
-            game,
-            ip,
-            port,
-            format,
-            output_mode,
-            #[cfg(feature = "packet_capture")]
-            capture,
-            timeout_settings,
-            extra_options,
-        } => {
+        Action::Query(args) => {
+            let QueryArgs {
+                game,
+                ip,
+                port,
+                format,
+                output_mode,
+                #[cfg(feature = "packet_capture")]
+                capture,
+                timeout_settings,
+                extra_options,
+            } = *args;
+
             // Process the query command
             let game = find_game(&game)?;
             let mut extra_options = extra_options;
             let ip = resolve_ip_or_domain(&ip, &mut extra_options)?;
 
             #[cfg(feature = "packet_capture")]
-            gamedig::capture::setup_capture(capture);
+            gamedig::capture::setup_capture(capture)?;
 
             let result = query_with_timeout_and_extra_settings(game, &ip, port, timeout_settings, extra_options)?;
             output_result(output_mode, format, result.as_ref());
         }
+        #[cfg(feature = "packet_capture")]
+        Action::Replay {
+            game,
+            capture,
+            format,
+            output_mode,
+            extra_options,
+        } => {
+            let game = find_game(&game)?;
+            let capture = gamedig::capture::replay::Capture::open(capture)?;
+            let address = capture.remote_address().ok_or(Error::EmptyCapture)?;
+
+            let result = capture.replay(|| {
+                query_with_timeout_and_extra_settings(
+                    game,
+                    &address.ip(),
+                    Some(address.port()),
+                    None,
+                    extra_options,
+                )
+            })?;
+            output_result(output_mode, format, result.as_ref());
+        }
         Action::Source => {
             println!("{GAMEDIG_HEADER}");
 
@@ -0,0 +1,11 @@
+/// Append a null-terminated string.
+pub(crate) fn push_string(packet: &mut Vec<u8>, value: &str) {
+    packet.extend_from_slice(value.as_bytes());
+    packet.push(0);
+}
+
+/// Encode a string as UTF-16 (big endian), without a terminator.
+pub(crate) fn utf16_be(value: &str) -> Vec<u8> { value.encode_utf16().flat_map(u16::to_be_bytes).collect() }
+
+/// Encode a string as UTF-16 (little endian), without a terminator.
+pub(crate) fn utf16_le(value: &str) -> Vec<u8> { value.encode_utf16().flat_map(u16::to_le_bytes).collect() }
@@ -0,0 +1,420 @@
+//! GameSpy 1, 2 and 3.
+
+use crate::encoding::push_string;
+use crate::{Fake, Script, Transport};
+
+use gamedig::protocols::gamespy::three::{Player, Response, Team};
+use gamedig::responder::gamespy::encode;
+
+use std::collections::HashMap;
+
+/// Set a key's value, replacing the previous one.
+fn set(values: &mut Vec<(String, String)>, key: &str, value: &str) {
+    match values.iter_mut().find(|(k, _)| k == key) {
+        Some((_, v)) => *v = value.to_string(),
+        None => values.push((key.to_string(), value.to_string())),
+    }
+}
+
+fn default_values(values: &[(&str, &str)]) -> Vec<(String, String)> {
+    values
+        .iter()
+        .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+        .collect()
+}
+
+/// A GameSpy 1 server, its values are sent as backslash separated key/value
+/// pairs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameSpy1 {
+    pub values: Vec<(String, String)>,
+    /// The fields of every player, keys get suffixed by `_<index>`.
+    pub players: Vec<Vec<(String, String)>>,
+    pub query_id: u32,
+    /// Send at most this many key/value pairs per packet.
+    pub values_per_packet: Option<usize>,
+}
+
+impl Default for GameSpy1 {
+    fn default() -> Self {
+        Self {
+            values: default_values(&[
+                ("hostname", "Fake server"),
+                ("mapname", "DM-Deck16]["),
+                ("gametype", "DeathMatch"),
+                ("gamever", "436"),
+                ("maxplayers", "16"),
+                ("password", "0"),
+            ]),
+            players: Vec::new(),
+            query_id: 1,
+            values_per_packet: None,
+        }
+    }
+}
+
+impl GameSpy1 {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn value(mut self, key: &str, value: &str) -> Self {
+        set(&mut self.values, key, value);
+        self
+    }
+
+    /// Add a player, such as `[("player", "Bob"), ("ping", "20"), ("frags",
+    /// "3")]`.
+    pub fn player(mut self, fields: &[(&str, &str)]) -> Self {
+        self.players.push(default_values(fields));
+        self
+    }
+
+    /// Send the values over multiple packets.
+    pub const fn with_values_per_packet(mut self, values: usize) -> Self {
+        self.values_per_packet = Some(values);
+        self
+    }
+
+    /// Every packet of the status response.
+    pub fn packets(&self) -> Vec<Vec<u8>> {
+        let mut pairs = self.values.clone();
+        for (index, player) in self.players.iter().enumerate() {
+            pairs.extend(
+                player
+                    .iter()
+                    .map(|(key, value)| (format!("{key}_{index}"), value.clone())),
+            );
+        }
+
+        let chunk_size = self.values_per_packet.unwrap_or(pairs.len()).max(1);
+        let chunks: Vec<&[(String, String)]> = match pairs.is_empty() {
+            true => vec![&[]],
+            false => pairs.chunks(chunk_size).collect(),
+        };
+        let last = chunks.len() - 1;
+
+        chunks
+            .into_iter()
+            .enumerate()
+            .map(|(index, chunk)| {
+                let mut packet = String::new();
+                for (key, value) in chunk {
+                    packet.push_str(&format!("\\{key}\\{value}"));
+                }
+                packet.push_str(&format!("\\queryid\\{}.{}", self.query_id, index + 1));
+                if index == last {
+                    packet.push_str("\\final\\");
+                }
+                packet.into_bytes()
+            })
+            .collect()
+    }
+}
+
+impl Fake for GameSpy1 {
+    fn transport(&self) -> Transport { Transport::Udp }
+
+    fn script(&self) -> Script { Script::new().reply_with(b"\\status\\".to_vec(), self.packets()) }
+}
+
+/// A player of a GameSpy 2 server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameSpy2Player {
+    pub name: String,
+    pub score: i32,
+    pub ping: u16,
+    pub team: u8,
+}
+
+/// A GameSpy 2 server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameSpy2 {
+    pub values: Vec<(String, String)>,
+    pub players: Vec<GameSpy2Player>,
+    /// The teams' names and scores.
+    pub teams: Vec<(String, i32)>,
+}
+
+impl Default for GameSpy2 {
+    fn default() -> Self {
+        Self {
+            values: default_values(&[
+                ("hostname", "Fake server"),
+                ("mapname", "Backwater"),
+                ("maxplayers", "32"),
+                ("password", "0"),
+            ]),
+            players: Vec::new(),
+            teams: Vec::new(),
+        }
+    }
+}
+
+impl GameSpy2 {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn value(mut self, key: &str, value: &str) -> Self {
+        set(&mut self.values, key, value);
+        self
+    }
+
+    pub fn player(mut self, name: &str, score: i32, ping: u16, team: u8) -> Self {
+        self.players.push(GameSpy2Player {
+            name: name.to_string(),
+            score,
+            ping,
+            team,
+        });
+        self
+    }
+
+    pub fn team(mut self, name: &str, score: i32) -> Self {
+        self.teams.push((name.to_string(), score));
+        self
+    }
+
+    /// The status response.
+    pub fn packet(&self) -> Vec<u8> {
+        let mut packet = vec![0x00, 0x00, 0x00, 0x00, 0x01];
+        for (key, value) in &self.values {
+            push_string(&mut packet, key);
+            push_string(&mut packet, value);
+        }
+        // An empty key and value, the last one also starting the players table.
+        packet.push(0);
+
+        push_table(
+            &mut packet,
+            &["player_", "score_", "ping_", "team_"],
+            self.players.iter().map(|player| {
+                vec![
+                    player.name.clone(),
+                    player.score.to_string(),
+                    player.ping.to_string(),
+                    player.team.to_string(),
+                ]
+            }),
+        );
+        push_table(
+            &mut packet,
+            &["team_t", "score_t"],
+            self.teams
+                .iter()
+                .map(|(name, score)| vec![name.clone(), score.to_string()]),
+        );
+
+        packet
+    }
+}
+
+/// Append a table: the row count, the column heads and then every row.
+fn push_table(packet: &mut Vec<u8>, heads: &[&str], rows: impl ExactSizeIterator<Item = Vec<String>>) {
+    packet.push(0);
+    packet.push(rows.len() as u8);
+    if rows.len() == 0 {
+        return;
+    }
+
+    for head in heads {
+        push_string(packet, head);
+    }
+    packet.push(0);
+
+    for row in rows {
+        for value in row {
+            push_string(packet, &value);
+        }
+    }
+}
+
+impl Fake for GameSpy2 {
+    fn transport(&self) -> Transport { Transport::Udp }
+
+    fn script(&self) -> Script { Script::new().reply([0xFE, 0xFD, 0x00], self.packet()) }
+}
+
+/// A player of a GameSpy 3 server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameSpy3Player {
+    pub name: String,
+    pub score: i32,
+    pub ping: u16,
+    pub team: u8,
+    pub deaths: u32,
+    pub skill: u32,
+}
+
+/// A GameSpy 3 server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameSpy3 {
+    pub values: Vec<(String, String)>,
+    pub players: Vec<GameSpy3Player>,
+    /// The teams' names and scores.
+    pub teams: Vec<(String, i32)>,
+    /// Ask for this challenge before answering, 0 means none.
+    pub challenge: i32,
+    /// Send every field of the players and teams in a packet of its own.
+    pub split: bool,
+}
+
+impl Default for GameSpy3 {
+    fn default() -> Self {
+        Self {
+            values: default_values(&[
+                ("hostname", "Fake server"),
+                ("mapname", "Strike at Karkand"),
+                ("gametype", "gpm_cq"),
+                ("gamever", "1.5"),
+                ("maxplayers", "64"),
+                ("password", "0"),
+            ]),
+            players: Vec::new(),
+            teams: Vec::new(),
+            challenge: 0,
+            split: false,
+        }
+    }
+}
+
+const SESSION_ID: [u8; 4] = [0x00, 0x00, 0x00, 0x01];
+
+impl GameSpy3 {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn value(mut self, key: &str, value: &str) -> Self {
+        set(&mut self.values, key, value);
+        self
+    }
+
+    pub fn player(mut self, name: &str, score: i32, ping: u16, team: u8) -> Self {
+        self.players.push(GameSpy3Player {
+            name: name.to_string(),
+            score,
+            ping,
+            team,
+            deaths: 0,
+            skill: 0,
+        });
+        self
+    }
+
+    pub fn team(mut self, name: &str, score: i32) -> Self {
+        self.teams.push((name.to_string(), score));
+        self
+    }
+
+    pub const fn with_challenge(mut self, challenge: i32) -> Self {
+        self.challenge = challenge;
+        self
+    }
+
+    pub const fn with_split(mut self) -> Self {
+        self.split = true;
+        self
+    }
+
+    /// The handshake response, holding the challenge.
+    pub fn handshake_packet(&self) -> Vec<u8> {
+        let mut packet = [&[0x09], &SESSION_ID[..]].concat();
+        push_string(&mut packet, &self.challenge.to_string());
+        packet
+    }
+
+    /// The response the values, players and teams make up.
+    pub fn response(&self) -> Response {
+        let mut values: HashMap<String, String> = self.values.iter().cloned().collect();
+        let mut take = |key: &str| values.remove(key).unwrap_or_default();
+
+        let name = take("hostname");
+        let map = take("mapname");
+        let has_password = matches!(take("password").to_lowercase().as_str(), "1" | "true");
+        let game_mode = take("gametype");
+        let game_version = take("gamever");
+        let players_maximum = take("maxplayers").parse().unwrap_or_default();
+        let players_online = take("numplayers")
+            .parse()
+            .unwrap_or(self.players.len() as u32);
+        let players_minimum = take("minplayers").parse().ok();
+        // Clients take a missing value as a tournament.
+        let tournament = take("tournament").to_lowercase().parse().unwrap_or(true);
+
+        Response {
+            name,
+            map,
+            has_password,
+            game_mode,
+            game_version,
+            players_maximum,
+            players_online,
+            players_minimum,
+            players: self
+                .players
+                .iter()
+                .map(|player| {
+                    Player {
+                        name: player.name.clone(),
+                        score: player.score,
+                        ping: player.ping,
+                        team: player.team,
+                        deaths: player.deaths,
+                        skill: player.skill,
+                    }
+                })
+                .collect(),
+            teams: self
+                .teams
+                .iter()
+                .map(|(name, score)| {
+                    Team {
+                        name: name.clone(),
+                        score: *score,
+                    }
+                })
+                .collect(),
+            tournament,
+            unused_entries: values,
+            latency: None,
+        }
+    }
+
+    /// Every packet of the data response, as gamedig's responder encodes it.
+    pub fn packets(&self) -> Vec<Vec<u8>> {
+        // Splitting puts every field of the players and teams in a packet of its own.
+        let packet_size = match self.split {
+            true => 0,
+            false => usize::MAX,
+        };
+        let datas = encode(&self.response(), packet_size);
+        let last = datas.len() - 1;
+
+        datas
+            .into_iter()
+            .enumerate()
+            .map(|(index, data)| {
+                let mut packet = [&[0x00], &SESSION_ID[..]].concat();
+                push_string(&mut packet, "splitnum");
+                packet.push(match index == last {
+                    true => 0x80 | index as u8,
+                    false => index as u8,
+                });
+                packet.push(0x01);
+                packet.extend(data);
+                packet
+            })
+            .collect()
+    }
+}
+
+impl Fake for GameSpy3 {
+    fn transport(&self) -> Transport { Transport::Udp }
+
+    fn script(&self) -> Script {
+        let mut data_request = [&[0xFE, 0xFD, 0x00], &SESSION_ID[..]].concat();
+        if self.challenge != 0 {
+            data_request.extend_from_slice(&self.challenge.to_be_bytes());
+        }
+
+        Script::new()
+            .reply([0xFE, 0xFD, 0x09], self.handshake_packet())
+            .reply_with(data_request, self.packets())
+    }
+}
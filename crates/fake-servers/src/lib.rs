@@ -0,0 +1,355 @@
+//! In-process fake game servers, bound to localhost and answering with canned
+//! responses, to test querying without a network.
+//!
+//! A [FakeServer] follows a [Script]: every received request is matched
+//! against the script's exchanges (in order) and the first one that matches
+//! sends its replies. The protocol modules build scripts out of plain values
+//! and take care of the protocol's framing (challenges, split packets,
+//! compression...), the responses that [gamedig::responder] can encode are
+//! encoded by it.
+//!
+//! ```
+//! use gamedig_fake_servers::{quake::Quake, FakeServer};
+//!
+//! let server = FakeServer::start(&Quake::three().value("sv_hostname", "Fake")).unwrap();
+//! // query `server.address()`...
+//! ```
+
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+pub mod gamespy;
+pub mod mindustry;
+pub mod minecraft;
+pub mod quake;
+pub mod savage2;
+pub mod unreal2;
+pub mod valve;
+
+mod encoding;
+
+/// How often the serving thread checks whether it should stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long a TCP connection is kept open after answering, waiting for the
+/// client to close it.
+const LINGER: Duration = Duration::from_secs(1);
+
+/// The transport protocol a server listens on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Transport {
+    Udp,
+    Tcp,
+}
+
+/// A request/replies pair of a [Script].
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Exchange {
+    /// The prefix a request has to start with.
+    request: Vec<u8>,
+    /// The datagrams (or stream chunks) sent back.
+    replies: Vec<Vec<u8>>,
+    /// How many more times the exchange can be used, None means forever.
+    uses: Option<usize>,
+}
+
+/// The canned responses of a fake server.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Script {
+    exchanges: Vec<Exchange>,
+    /// How long to wait before replying.
+    delay: Duration,
+}
+
+impl Script {
+    pub fn new() -> Self { Self::default() }
+
+    /// Answer requests starting with `request` with a single reply.
+    pub fn reply(self, request: impl Into<Vec<u8>>, reply: impl Into<Vec<u8>>) -> Self {
+        self.reply_with(request, vec![reply.into()])
+    }
+
+    /// Answer requests starting with `request` with every reply, in order.
+    pub fn reply_with(mut self, request: impl Into<Vec<u8>>, replies: Vec<Vec<u8>>) -> Self {
+        self.exchanges.push(Exchange {
+            request: request.into(),
+            replies,
+            uses: None,
+        });
+        self
+    }
+
+    /// Don't answer the first `times` requests starting with `request`, to
+    /// make the client time out.
+    pub fn ignore(mut self, request: impl Into<Vec<u8>>, times: usize) -> Self {
+        // Ignoring has to take precedence over the replies already scripted.
+        self.exchanges.insert(
+            0,
+            Exchange {
+                request: request.into(),
+                replies: Vec::new(),
+                uses: Some(times),
+            },
+        );
+        self
+    }
+
+    /// Use every exchange scripted so far at most `times` times, the later
+    /// requests are left to the exchanges scripted after.
+    pub fn times(mut self, times: usize) -> Self {
+        for exchange in &mut self.exchanges {
+            exchange.uses = Some(exchange.uses.map_or(times, |uses| uses.min(times)));
+        }
+        self
+    }
+
+    /// Wait `delay` before sending the replies of every request.
+    pub const fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Append the exchanges of another script, which are used after the
+    /// current ones.
+    pub fn then(mut self, other: Self) -> Self {
+        self.exchanges.extend(other.exchanges);
+        self
+    }
+
+    /// Get the replies to a request, None if nothing in the script matches
+    /// it.
+    fn answer(&mut self, request: &[u8]) -> Option<Vec<Vec<u8>>> {
+        let exchange = self
+            .exchanges
+            .iter_mut()
+            .find(|exchange| exchange.uses != Some(0) && request.starts_with(&exchange.request))?;
+
+        if let Some(uses) = exchange.uses.as_mut() {
+            *uses -= 1;
+        }
+
+        Some(exchange.replies.clone())
+    }
+}
+
+/// Something that can be served by a [FakeServer].
+pub trait Fake {
+    /// The transport protocol of the game.
+    fn transport(&self) -> Transport;
+
+    /// The script that answers the game's queries.
+    fn script(&self) -> Script;
+}
+
+/// A fake game server running on its own thread, it stops when dropped.
+pub struct FakeServer {
+    address: SocketAddr,
+    stop: Arc<AtomicBool>,
+    requests: Arc<Mutex<Vec<Vec<u8>>>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl FakeServer {
+    /// Serve a game on a random localhost port.
+    pub fn start(fake: &impl Fake) -> io::Result<Self> {
+        match fake.transport() {
+            Transport::Udp => Self::udp(fake.script()),
+            Transport::Tcp => Self::tcp(fake.script()),
+        }
+    }
+
+    /// Serve a script over UDP on a random localhost port.
+    pub fn udp(script: Script) -> io::Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?;
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+        let address = socket.local_addr()?;
+
+        Ok(Self::spawn(address, move |stop, requests| {
+            serve_udp(&socket, script, &stop, &requests)
+        }))
+    }
+
+    /// Serve a script over TCP on a random localhost port, every connection
+    /// is answered once and then closed.
+    pub fn tcp(script: Script) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+
+        Ok(Self::spawn(address, move |stop, requests| {
+            serve_tcp(&listener, script, &stop, &requests)
+        }))
+    }
+
+    fn spawn(
+        address: SocketAddr,
+        serve: impl FnOnce(Arc<AtomicBool>, Arc<Mutex<Vec<Vec<u8>>>>) + Send + 'static,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let handle = {
+            let stop = stop.clone();
+            let requests = requests.clone();
+            thread::spawn(move || serve(stop, requests))
+        };
+
+        Self {
+            address,
+            stop,
+            requests,
+            handle: Some(handle),
+        }
+    }
+
+    /// The address the server listens on.
+    pub const fn address(&self) -> SocketAddr { self.address }
+
+    /// The port the server listens on.
+    pub const fn port(&self) -> u16 { self.address.port() }
+
+    /// Every request received so far, in order.
+    pub fn requests(&self) -> Vec<Vec<u8>> { self.requests.lock().map(|r| r.clone()).unwrap_or_default() }
+}
+
+impl Drop for FakeServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn record(requests: &Mutex<Vec<Vec<u8>>>, request: &[u8]) {
+    if let Ok(mut requests) = requests.lock() {
+        requests.push(request.to_vec());
+    }
+}
+
+fn serve_udp(socket: &UdpSocket, mut script: Script, stop: &AtomicBool, requests: &Mutex<Vec<Vec<u8>>>) {
+    let mut buf = [0; 65535];
+
+    while !stop.load(Ordering::Relaxed) {
+        let (size, peer) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            // Either nothing was received in time or an earlier reply was refused.
+            Err(_) => continue,
+        };
+
+        let request = &buf[.. size];
+        record(requests, request);
+
+        let replies = script.answer(request).unwrap_or_default();
+        if !replies.is_empty() {
+            thread::sleep(script.delay);
+        }
+        for reply in replies {
+            let _ = socket.send_to(&reply, peer);
+        }
+    }
+}
+
+fn serve_tcp(listener: &TcpListener, mut script: Script, stop: &AtomicBool, requests: &Mutex<Vec<Vec<u8>>>) {
+    while !stop.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let _ = answer_connection(stream, &mut script, requests);
+            }
+            Err(_) => thread::sleep(POLL_INTERVAL),
+        }
+    }
+}
+
+/// Answer the first request of a connection, then close it.
+fn answer_connection(mut stream: TcpStream, script: &mut Script, requests: &Mutex<Vec<Vec<u8>>>) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(LINGER))?;
+
+    let mut buf = [0; 4096];
+    let size = stream.read(&mut buf)?;
+    let mut request = buf[.. size].to_vec();
+
+    if let Some(replies) = script.answer(&request) {
+        thread::sleep(script.delay);
+        for reply in replies {
+            stream.write_all(&reply)?;
+        }
+    }
+
+    // Let the client know that everything was sent, then read whatever is left
+    // (closing a socket with unread data resets the connection).
+    stream.shutdown(std::net::Shutdown::Write)?;
+    while let Ok(size @ 1 ..) = stream.read(&mut buf) {
+        request.extend_from_slice(&buf[.. size]);
+    }
+
+    record(requests, &request);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exchange(server: &FakeServer, request: &[u8]) -> Option<Vec<u8>> {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_millis(200)))
+            .unwrap();
+        socket.send_to(request, server.address()).unwrap();
+
+        let mut buf = [0; 1024];
+        socket.recv(&mut buf).ok().map(|size| buf[.. size].to_vec())
+    }
+
+    #[test]
+    fn answers_first_matching_exchange() {
+        let script = Script::new()
+            .reply("ab", "long")
+            .reply("a", "short")
+            .ignore("x", 1)
+            .reply("x", "late");
+        let server = FakeServer::udp(script).unwrap();
+
+        assert_eq!(exchange(&server, b"abc"), Some(b"long".to_vec()));
+        assert_eq!(exchange(&server, b"ac"), Some(b"short".to_vec()));
+        assert_eq!(exchange(&server, b"b"), None);
+        assert_eq!(exchange(&server, b"x"), None);
+        assert_eq!(exchange(&server, b"x"), Some(b"late".to_vec()));
+
+        assert_eq!(server.requests().len(), 5);
+    }
+
+    #[test]
+    fn limits_exchanges() {
+        let server = FakeServer::udp(
+            Script::new()
+                .reply("a", "first")
+                .times(1)
+                .reply("a", "second"),
+        )
+        .unwrap();
+
+        assert_eq!(exchange(&server, b"a"), Some(b"first".to_vec()));
+        assert_eq!(exchange(&server, b"a"), Some(b"second".to_vec()));
+        assert_eq!(exchange(&server, b"a"), Some(b"second".to_vec()));
+    }
+
+    #[test]
+    fn tcp_answers_and_closes() {
+        let server = FakeServer::tcp(Script::new().reply_with("", vec![b"he".to_vec(), b"llo".to_vec()])).unwrap();
+
+        let mut stream = TcpStream::connect(server.address()).unwrap();
+        stream.write_all(b"hi").unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        drop(stream);
+
+        assert_eq!(response, b"hello");
+    }
+}
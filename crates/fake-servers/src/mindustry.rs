@@ -0,0 +1,71 @@
+//! Mindustry.
+
+use crate::{Fake, Script, Transport};
+
+/// A Mindustry server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mindustry {
+    pub host: String,
+    pub map: String,
+    pub players: i32,
+    pub wave: i32,
+    pub version: i32,
+    pub version_type: String,
+    /// 0 (survival), 1 (sandbox), 2 (attack), 3 (PvP) or 4 (editor).
+    pub game_mode: u8,
+    pub player_limit: i32,
+    pub description: String,
+    pub mode_name: Option<String>,
+}
+
+impl Default for Mindustry {
+    fn default() -> Self {
+        Self {
+            host: "Fake server".to_string(),
+            map: "Ground Zero".to_string(),
+            players: 0,
+            wave: 1,
+            version: 146,
+            version_type: "official".to_string(),
+            game_mode: 0,
+            player_limit: 0,
+            description: "A Mindustry server".to_string(),
+            mode_name: None,
+        }
+    }
+}
+
+impl Mindustry {
+    pub fn new() -> Self { Self::default() }
+
+    /// The ping response.
+    pub fn packet(&self) -> Vec<u8> {
+        let mut packet = Vec::new();
+        push_string(&mut packet, &self.host);
+        push_string(&mut packet, &self.map);
+        packet.extend_from_slice(&self.players.to_be_bytes());
+        packet.extend_from_slice(&self.wave.to_be_bytes());
+        packet.extend_from_slice(&self.version.to_be_bytes());
+        push_string(&mut packet, &self.version_type);
+        packet.push(self.game_mode);
+        packet.extend_from_slice(&self.player_limit.to_be_bytes());
+        push_string(&mut packet, &self.description);
+        if let Some(mode_name) = &self.mode_name {
+            push_string(&mut packet, mode_name);
+        }
+        packet
+    }
+}
+
+/// Append a string prefixed by its length (in a byte).
+fn push_string(packet: &mut Vec<u8>, value: &str) {
+    let bytes = &value.as_bytes()[.. value.len().min(u8::MAX as usize)];
+    packet.push(bytes.len() as u8);
+    packet.extend_from_slice(bytes);
+}
+
+impl Fake for Mindustry {
+    fn transport(&self) -> Transport { Transport::Udp }
+
+    fn script(&self) -> Script { Script::new().reply([0xFE, 0x01], self.packet()) }
+}
@@ -0,0 +1,240 @@
+//! Minecraft: Java, Bedrock and the legacy (Java) versions.
+
+use crate::encoding::utf16_be;
+use crate::{Fake, Script, Transport};
+
+use gamedig::games::minecraft::{BedrockResponse, GameMode, JavaResponse, Player, Server};
+use gamedig::responder::minecraft::{encode_bedrock_status, encode_java_status};
+
+/// A Java server, answering the status request with its JSON.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Java {
+    pub version_name: String,
+    pub protocol: i32,
+    pub players_online: u32,
+    pub players_maximum: u32,
+    /// The players' names and UUIDs.
+    pub sample: Vec<(String, String)>,
+    pub description: String,
+}
+
+impl Default for Java {
+    fn default() -> Self {
+        Self {
+            version_name: "1.20.4".to_string(),
+            protocol: 765,
+            players_online: 0,
+            players_maximum: 20,
+            sample: Vec::new(),
+            description: "A Minecraft Server".to_string(),
+        }
+    }
+}
+
+impl Java {
+    pub fn new() -> Self { Self::default() }
+
+    /// Add a player to the sample, counting it as online.
+    pub fn player(mut self, name: &str, id: &str) -> Self {
+        self.sample.push((name.to_string(), id.to_string()));
+        self.players_online = self.sample.len() as u32;
+        self
+    }
+
+    /// The status response.
+    pub fn response(&self) -> JavaResponse {
+        JavaResponse {
+            game_version: self.version_name.clone(),
+            protocol_version: self.protocol,
+            players_maximum: self.players_maximum,
+            players_online: self.players_online,
+            players: Some(
+                self.sample
+                    .iter()
+                    .map(|(name, id)| {
+                        Player {
+                            name: name.clone(),
+                            id: id.clone(),
+                        }
+                    })
+                    .collect(),
+            ),
+            description: self.description.clone(),
+            favicon: None,
+            previews_chat: None,
+            enforces_secure_chat: None,
+            server_type: Server::Java,
+            latency: None,
+        }
+    }
+
+    /// The status JSON.
+    pub fn status(&self) -> String {
+        // Skip the packet's length, its id and the JSON's length.
+        let packet = self.packet();
+        let data = &packet[varint_length(&packet) + 1 ..];
+        let json = &data[varint_length(data) ..];
+
+        String::from_utf8(json.to_vec()).expect("The status is JSON")
+    }
+
+    /// The (framed) status response, as gamedig's responder encodes it.
+    pub fn packet(&self) -> Vec<u8> { encode_java_status(&self.response()) }
+}
+
+/// The length of the varint `bytes` start with.
+fn varint_length(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .position(|byte| byte & 0x80 == 0)
+        .map_or(bytes.len(), |last| last + 1)
+}
+
+impl Fake for Java {
+    fn transport(&self) -> Transport { Transport::Tcp }
+
+    // Every connection starts with the handshake, which has no fixed prefix.
+    fn script(&self) -> Script { Script::new().reply(Vec::new(), self.packet()) }
+}
+
+/// The nonce sent by clients, which is echoed back.
+const NONCE: [u8; 8] = [0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88];
+
+/// A Bedrock server, answering the unconnected ping with its status.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bedrock {
+    pub edition: String,
+    pub name: String,
+    pub protocol: String,
+    pub version_name: String,
+    pub players_online: u32,
+    pub players_maximum: u32,
+    pub id: String,
+    pub map: String,
+    pub game_mode: GameMode,
+}
+
+impl Default for Bedrock {
+    fn default() -> Self {
+        Self {
+            edition: "MCPE".to_string(),
+            name: "Dedicated Server".to_string(),
+            protocol: "649".to_string(),
+            version_name: "1.20.62".to_string(),
+            players_online: 0,
+            players_maximum: 10,
+            id: "12345678901234567890".to_string(),
+            map: "Bedrock level".to_string(),
+            game_mode: GameMode::Survival,
+        }
+    }
+}
+
+impl Bedrock {
+    pub fn new() -> Self { Self::default() }
+
+    /// The status of the unconnected pong.
+    pub fn response(&self) -> BedrockResponse {
+        BedrockResponse {
+            edition: self.edition.clone(),
+            name: self.name.clone(),
+            version_name: self.version_name.clone(),
+            protocol_version: self.protocol.clone(),
+            players_maximum: self.players_maximum,
+            players_online: self.players_online,
+            id: Some(self.id.clone()),
+            map: Some(self.map.clone()),
+            game_mode: Some(self.game_mode),
+            server_type: Server::Bedrock,
+            latency: None,
+        }
+    }
+
+    /// The unconnected pong, as gamedig's responder encodes it.
+    pub fn packet(&self) -> Vec<u8> { encode_bedrock_status(&self.response(), NONCE) }
+}
+
+impl Fake for Bedrock {
+    fn transport(&self) -> Transport { Transport::Udp }
+
+    fn script(&self) -> Script { Script::new().reply([&[0x01][..], &NONCE].concat(), self.packet()) }
+}
+
+/// The legacy versions, which differ by their ping requests and responses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LegacyVersion {
+    /// 1.6
+    V1_6,
+    /// 1.4 and 1.5
+    V1_4,
+    /// Beta 1.8 to 1.3
+    VB1_8,
+}
+
+/// A legacy Java server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Legacy {
+    pub version: LegacyVersion,
+    /// Only sent by 1.6 servers.
+    pub protocol: i32,
+    /// Only sent by 1.6 servers.
+    pub version_name: String,
+    pub description: String,
+    pub players_online: u32,
+    pub players_maximum: u32,
+}
+
+impl Legacy {
+    pub fn new(version: LegacyVersion) -> Self {
+        Self {
+            version,
+            protocol: 78,
+            version_name: "1.6.4".to_string(),
+            description: "A Minecraft Server".to_string(),
+            players_online: 0,
+            players_maximum: 20,
+        }
+    }
+
+    /// The kick packet holding the status.
+    pub fn packet(&self) -> Vec<u8> {
+        let status = match self.version {
+            LegacyVersion::V1_6 => {
+                [
+                    "§1".to_string(),
+                    self.protocol.to_string(),
+                    self.version_name.clone(),
+                    self.description.clone(),
+                    self.players_online.to_string(),
+                    self.players_maximum.to_string(),
+                ]
+                .join("\0")
+            }
+            LegacyVersion::V1_4 | LegacyVersion::VB1_8 => {
+                format!(
+                    "{}§{}§{}",
+                    self.description, self.players_online, self.players_maximum
+                )
+            }
+        };
+
+        let mut packet = vec![0xFF];
+        packet.extend_from_slice(&(status.encode_utf16().count() as u16).to_be_bytes());
+        packet.extend(utf16_be(&status));
+        packet
+    }
+
+    const fn request(&self) -> &'static [u8] {
+        match self.version {
+            LegacyVersion::V1_6 => &[0xFE, 0x01, 0xFA],
+            LegacyVersion::V1_4 => &[0xFE, 0x01],
+            LegacyVersion::VB1_8 => &[0xFE],
+        }
+    }
+}
+
+impl Fake for Legacy {
+    fn transport(&self) -> Transport { Transport::Tcp }
+
+    fn script(&self) -> Script { Script::new().reply(self.request(), self.packet()) }
+}
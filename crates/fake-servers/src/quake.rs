@@ -0,0 +1,110 @@
+//! Quake 1, 2 and 3.
+
+use crate::{Fake, Script, Transport};
+
+/// The version of the protocol, they only differ by their headers and player
+/// lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Version {
+    One,
+    Two,
+    Three,
+}
+
+impl Version {
+    const fn request(self) -> &'static [u8] {
+        match self {
+            Self::One | Self::Two => b"\xFF\xFF\xFF\xFFstatus",
+            Self::Three => b"\xFF\xFF\xFF\xFFgetstatus",
+        }
+    }
+
+    const fn response_header(self) -> &'static [u8] {
+        match self {
+            Self::One => b"\xFF\xFF\xFF\xFFn",
+            Self::Two => b"\xFF\xFF\xFF\xFFprint\n",
+            Self::Three => b"\xFF\xFF\xFF\xFFstatusResponse\n",
+        }
+    }
+}
+
+/// A Quake server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Quake {
+    pub version: Version,
+    pub values: Vec<(String, String)>,
+    /// The players' lines, which are `id score time ping "name" "skin" color
+    /// color` for Quake 1 and `score ping "name"` otherwise.
+    pub players: Vec<String>,
+}
+
+impl Quake {
+    pub fn new(version: Version) -> Self {
+        let values: &[(&str, &str)] = match version {
+            Version::One | Version::Two => {
+                &[
+                    ("hostname", "Fake server"),
+                    ("map", "e1m1"),
+                    ("maxclients", "16"),
+                ]
+            }
+            Version::Three => {
+                &[
+                    ("sv_hostname", "Fake server"),
+                    ("mapname", "q3dm17"),
+                    ("sv_maxclients", "16"),
+                ]
+            }
+        };
+
+        Self {
+            version,
+            values: values
+                .iter()
+                .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+                .collect(),
+            players: Vec::new(),
+        }
+    }
+
+    pub fn one() -> Self { Self::new(Version::One) }
+
+    pub fn two() -> Self { Self::new(Version::Two) }
+
+    pub fn three() -> Self { Self::new(Version::Three) }
+
+    pub fn value(mut self, key: &str, value: &str) -> Self {
+        match self.values.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.values.push((key.to_string(), value.to_string())),
+        }
+        self
+    }
+
+    pub fn player(mut self, line: &str) -> Self {
+        self.players.push(line.to_string());
+        self
+    }
+
+    /// The status response.
+    pub fn packet(&self) -> Vec<u8> {
+        let mut packet = self.version.response_header().to_vec();
+        for (key, value) in &self.values {
+            packet.extend_from_slice(format!("\\{key}\\{value}").as_bytes());
+        }
+        packet.push(b'\n');
+
+        for player in &self.players {
+            packet.extend_from_slice(player.as_bytes());
+            packet.push(b'\n');
+        }
+
+        packet
+    }
+}
+
+impl Fake for Quake {
+    fn transport(&self) -> Transport { Transport::Udp }
+
+    fn script(&self) -> Script { Script::new().reply(self.version.request(), self.packet()) }
+}
@@ -0,0 +1,65 @@
+//! Savage 2.
+
+use crate::encoding::push_string;
+use crate::{Fake, Script, Transport};
+
+/// A Savage 2 server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Savage2 {
+    pub name: String,
+    pub players_online: u8,
+    pub players_maximum: u8,
+    pub time: String,
+    pub map: String,
+    pub next_map: String,
+    pub location: String,
+    pub players_minimum: u8,
+    pub game_mode: String,
+    pub protocol_version: String,
+    pub level_minimum: u8,
+}
+
+impl Default for Savage2 {
+    fn default() -> Self {
+        Self {
+            name: "Fake server".to_string(),
+            players_online: 0,
+            players_maximum: 32,
+            time: "Match in progress".to_string(),
+            map: "eden2".to_string(),
+            next_map: "crossroads".to_string(),
+            location: "Europe".to_string(),
+            players_minimum: 0,
+            game_mode: "normal".to_string(),
+            protocol_version: "2.1.0.0".to_string(),
+            level_minimum: 0,
+        }
+    }
+}
+
+impl Savage2 {
+    pub fn new() -> Self { Self::default() }
+
+    /// The info response.
+    pub fn packet(&self) -> Vec<u8> {
+        // The header isn't read by clients.
+        let mut packet = vec![0; 12];
+        push_string(&mut packet, &self.name);
+        packet.extend_from_slice(&[self.players_online, self.players_maximum]);
+        push_string(&mut packet, &self.time);
+        push_string(&mut packet, &self.map);
+        push_string(&mut packet, &self.next_map);
+        push_string(&mut packet, &self.location);
+        packet.push(self.players_minimum);
+        push_string(&mut packet, &self.game_mode);
+        push_string(&mut packet, &self.protocol_version);
+        packet.push(self.level_minimum);
+        packet
+    }
+}
+
+impl Fake for Savage2 {
+    fn transport(&self) -> Transport { Transport::Udp }
+
+    fn script(&self) -> Script { Script::new().reply([0x01], self.packet()) }
+}
@@ -0,0 +1,160 @@
+//! Unreal 2.
+
+use crate::encoding::utf16_le;
+use crate::{Fake, Script, Transport};
+
+const SERVER_INFO: u8 = 0;
+const MUTATORS_AND_RULES: u8 = 1;
+const PLAYERS: u8 = 2;
+
+/// A player of the players response, a ping of 0 marks a bot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Player {
+    pub id: u32,
+    pub name: String,
+    pub ping: u32,
+    pub score: i32,
+    pub stats_id: u32,
+}
+
+/// An Unreal 2 server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unreal2 {
+    pub server_id: u32,
+    pub ip: String,
+    pub game_port: u32,
+    pub query_port: u32,
+    pub name: String,
+    pub map: String,
+    pub game_type: String,
+    pub num_players: u32,
+    pub max_players: u32,
+    pub mutators: Vec<String>,
+    pub rules: Vec<(String, String)>,
+    pub players: Vec<Player>,
+}
+
+impl Default for Unreal2 {
+    fn default() -> Self {
+        Self {
+            server_id: 0,
+            ip: "127.0.0.1".to_string(),
+            game_port: 7777,
+            query_port: 7778,
+            name: "Fake server".to_string(),
+            map: "DM-Rankin".to_string(),
+            game_type: "xDeathMatch".to_string(),
+            num_players: 0,
+            max_players: 16,
+            mutators: Vec::new(),
+            rules: Vec::new(),
+            players: Vec::new(),
+        }
+    }
+}
+
+impl Unreal2 {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn mutator(mut self, name: &str) -> Self {
+        self.mutators.push(name.to_string());
+        self
+    }
+
+    pub fn rule(mut self, key: &str, value: &str) -> Self {
+        self.rules.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Add a player, counting it in the server info.
+    pub fn player(mut self, name: &str, ping: u32, score: i32) -> Self {
+        self.players.push(Player {
+            id: self.players.len() as u32,
+            name: name.to_string(),
+            ping,
+            score,
+            stats_id: 0,
+        });
+        self.num_players = self.players.len() as u32;
+        self
+    }
+
+    /// The server info response.
+    pub fn server_info_packet(&self) -> Vec<u8> {
+        let mut packet = header(SERVER_INFO);
+        packet.extend_from_slice(&self.server_id.to_le_bytes());
+        push_string(&mut packet, &self.ip);
+        packet.extend_from_slice(&self.game_port.to_le_bytes());
+        packet.extend_from_slice(&self.query_port.to_le_bytes());
+        push_string(&mut packet, &self.name);
+        push_string(&mut packet, &self.map);
+        push_string(&mut packet, &self.game_type);
+        packet.extend_from_slice(&self.num_players.to_le_bytes());
+        packet.extend_from_slice(&self.max_players.to_le_bytes());
+        packet
+    }
+
+    /// The mutators and rules response.
+    pub fn mutators_and_rules_packet(&self) -> Vec<u8> {
+        let mut packet = header(MUTATORS_AND_RULES);
+        for mutator in &self.mutators {
+            push_string(&mut packet, "Mutator");
+            push_string(&mut packet, mutator);
+        }
+        for (key, value) in &self.rules {
+            push_string(&mut packet, key);
+            push_string(&mut packet, value);
+        }
+        packet
+    }
+
+    /// The players response.
+    pub fn players_packet(&self) -> Vec<u8> {
+        let mut packet = header(PLAYERS);
+        for player in &self.players {
+            packet.extend_from_slice(&player.id.to_le_bytes());
+            push_string(&mut packet, &player.name);
+            packet.extend_from_slice(&player.ping.to_le_bytes());
+            packet.extend_from_slice(&player.score.to_le_bytes());
+            packet.extend_from_slice(&player.stats_id.to_le_bytes());
+        }
+        packet
+    }
+}
+
+fn header(kind: u8) -> Vec<u8> { vec![0x80, 0x00, 0x00, 0x00, kind] }
+
+/// Append a length prefixed string: short ASCII ones are sent as latin1 and
+/// the others as UCS-2 (its length having the high bit set), both with a
+/// terminator.
+fn push_string(packet: &mut Vec<u8>, value: &str) {
+    // Clients drop control characters, which includes the length of latin1
+    // strings as long as it is below 0x1b.
+    let length = value.encode_utf16().count() + 1;
+    if value.is_ascii() && length < 0x1b {
+        packet.push(length as u8);
+        packet.extend_from_slice(value.as_bytes());
+        packet.push(0);
+    } else {
+        packet.push(0x80 | length.min(0x7F) as u8);
+        packet.extend(utf16_le(value));
+        packet.extend_from_slice(&[0, 0]);
+    }
+}
+
+impl Fake for Unreal2 {
+    fn transport(&self) -> Transport { Transport::Udp }
+
+    fn script(&self) -> Script {
+        Script::new()
+            .reply(
+                [0x79, 0x00, 0x00, 0x00, SERVER_INFO],
+                self.server_info_packet(),
+            )
+            .reply(
+                [0x79, 0x00, 0x00, 0x00, MUTATORS_AND_RULES],
+                self.mutators_and_rules_packet(),
+            )
+            .reply([0x79, 0x00, 0x00, 0x00, PLAYERS], self.players_packet())
+    }
+}
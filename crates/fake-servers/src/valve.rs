@@ -0,0 +1,283 @@
+//! Valve's A2S protocol (Source engine layout).
+
+use crate::{Fake, Script, Transport};
+
+use gamedig::protocols::valve::{Environment, ExtraData, Server, ServerInfo, ServerPlayer};
+use gamedig::responder::valve::{encode_info, encode_players, encode_rules};
+
+use bzip2::write::BzEncoder;
+use bzip2::Compression;
+use std::collections::HashMap;
+use std::io::Write;
+
+const HEADER: [u8; 4] = [0xFF; 4];
+const SPLIT_HEADER: [u8; 4] = [0xFE, 0xFF, 0xFF, 0xFF];
+
+const INFO_REQUEST: u8 = 0x54;
+const PLAYERS_REQUEST: u8 = 0x55;
+const RULES_REQUEST: u8 = 0x56;
+
+const CHALLENGE_RESPONSE: u8 = 0x41;
+
+/// A player of the players response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Player {
+    pub name: String,
+    pub score: i32,
+    /// Seconds since the player connected.
+    pub duration: f32,
+}
+
+/// A Source engine server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Valve {
+    pub protocol: u8,
+    pub name: String,
+    pub map: String,
+    pub folder: String,
+    pub game: String,
+    pub appid: u16,
+    pub players_online: u8,
+    pub players_maximum: u8,
+    pub bots: u8,
+    pub server_type: Server,
+    pub environment: Environment,
+    pub has_password: bool,
+    pub vac_secured: bool,
+    pub version: String,
+    /// The game port of the extra data field.
+    pub port: Option<u16>,
+    /// The keywords of the extra data field.
+    pub keywords: Option<String>,
+    /// The 64 bit game id of the extra data field.
+    pub game_id: Option<u64>,
+    pub players: Vec<Player>,
+    pub rules: Vec<(String, String)>,
+    /// Ask for this challenge before answering any request.
+    pub challenge: Option<u32>,
+    /// Split responses in datagrams of at most this many bytes.
+    pub split: Option<usize>,
+    /// Compress split responses with bzip2.
+    pub compressed: bool,
+}
+
+impl Default for Valve {
+    fn default() -> Self {
+        Self {
+            protocol: 17,
+            name: "Fake server".to_string(),
+            map: "ctf_2fort".to_string(),
+            folder: "tf".to_string(),
+            game: "Team Fortress".to_string(),
+            appid: 440,
+            players_online: 0,
+            players_maximum: 24,
+            bots: 0,
+            server_type: Server::Dedicated,
+            environment: Environment::Linux,
+            has_password: false,
+            vac_secured: true,
+            version: "1.0.0.0".to_string(),
+            port: None,
+            keywords: None,
+            game_id: None,
+            players: Vec::new(),
+            rules: Vec::new(),
+            challenge: None,
+            split: None,
+            compressed: false,
+        }
+    }
+}
+
+impl Valve {
+    pub fn new() -> Self { Self::default() }
+
+    /// Add a player, counting it as online.
+    pub fn player(mut self, name: &str, score: i32, duration: f32) -> Self {
+        self.players.push(Player {
+            name: name.to_string(),
+            score,
+            duration,
+        });
+        self.players_online = self.players.len() as u8;
+        self
+    }
+
+    pub fn rule(mut self, name: &str, value: &str) -> Self {
+        self.rules.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub const fn with_challenge(mut self, challenge: u32) -> Self {
+        self.challenge = Some(challenge);
+        self
+    }
+
+    /// Split every response in datagrams of at most `size` bytes (excluding
+    /// the split header), compressing them if asked to.
+    pub const fn with_split(mut self, size: usize, compressed: bool) -> Self {
+        self.split = Some(size);
+        self.compressed = compressed;
+        self
+    }
+
+    /// The info response, as gamedig's responder encodes it.
+    pub fn info_packet(&self) -> Vec<u8> {
+        let has_extra_data = self.port.is_some() || self.keywords.is_some() || self.game_id.is_some();
+
+        encode_info(&ServerInfo {
+            protocol_version: self.protocol,
+            name: self.name.clone(),
+            map: self.map.clone(),
+            folder: self.folder.clone(),
+            game_mode: self.game.clone(),
+            appid: u32::from(self.appid),
+            players_online: self.players_online,
+            players_maximum: self.players_maximum,
+            players_bots: self.bots,
+            server_type: self.server_type,
+            environment_type: self.environment,
+            has_password: self.has_password,
+            vac_secured: self.vac_secured,
+            the_ship: None,
+            game_version: self.version.clone(),
+            extra_data: has_extra_data.then(|| {
+                ExtraData {
+                    port: self.port,
+                    steam_id: None,
+                    tv_port: None,
+                    tv_name: None,
+                    keywords: self.keywords.clone(),
+                    game_id: self.game_id,
+                }
+            }),
+            is_mod: false,
+            mod_data: None,
+        })
+        .expect("16 bit app ids always fit")
+    }
+
+    /// The players response.
+    pub fn players_packet(&self) -> Vec<u8> {
+        let players: Vec<ServerPlayer> = self
+            .players
+            .iter()
+            .map(|player| {
+                ServerPlayer {
+                    name: player.name.clone(),
+                    score: player.score,
+                    duration: player.duration,
+                    deaths: None,
+                    money: None,
+                }
+            })
+            .collect();
+
+        encode_players(&players)
+    }
+
+    /// The rules response.
+    pub fn rules_packet(&self) -> Vec<u8> {
+        let rules: HashMap<String, String> = self.rules.iter().cloned().collect();
+
+        encode_rules(&rules)
+    }
+
+    /// The datagrams of a response, split (and compressed) if asked to.
+    fn datagrams(&self, packet: Vec<u8>, has_size: bool) -> Vec<Vec<u8>> {
+        // The kind of the response, as good as any id.
+        let id = u32::from(packet[HEADER.len()]);
+
+        match self.split {
+            Some(size) if self.compressed || packet.len() > size => split(&packet, id, size, has_size, self.compressed),
+            _ => vec![packet],
+        }
+    }
+
+    /// Answer `request` by `response`, the challenged request has the challenge
+    /// after `before_challenge`.
+    fn exchange(&self, script: Script, request: u8, before_challenge: &[u8], response: Vec<Vec<u8>>) -> Script {
+        let request = [&HEADER[..], &[request]].concat();
+
+        match self.challenge {
+            None => script.reply_with(request, response),
+            Some(challenge) => {
+                let challenge = challenge.to_le_bytes();
+                script
+                    .reply_with([&request, before_challenge, &challenge].concat(), response)
+                    .reply(
+                        request,
+                        [&HEADER[..], &[CHALLENGE_RESPONSE], &challenge].concat(),
+                    )
+            }
+        }
+    }
+}
+
+/// Split a packet the Source engine way, the decompressed size and checksum
+/// are only present in the first datagram.
+fn split(packet: &[u8], id: u32, size: usize, has_size: bool, compressed: bool) -> Vec<Vec<u8>> {
+    let (id, payload) = match compressed {
+        false => (id, packet.to_vec()),
+        true => {
+            let mut encoder = BzEncoder::new(Vec::new(), Compression::default());
+            encoder
+                .write_all(packet)
+                .and_then(|_| encoder.finish())
+                .map(|compressed| (id | 1 << 31, compressed))
+                .expect("Compressing in memory can't fail")
+        }
+    };
+
+    let chunks: Vec<&[u8]> = payload.chunks(size.max(1)).collect();
+    let total = chunks.len() as u8;
+
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(number, chunk)| {
+            let mut datagram = SPLIT_HEADER.to_vec();
+            datagram.extend_from_slice(&id.to_le_bytes());
+            datagram.extend_from_slice(&[total, number as u8]);
+            if has_size {
+                datagram.extend_from_slice(&(size as u16).to_le_bytes());
+            }
+            if compressed && number == 0 {
+                datagram.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+                datagram.extend_from_slice(&crc32fast::hash(packet).to_le_bytes());
+            }
+            datagram.extend_from_slice(chunk);
+            datagram
+        })
+        .collect()
+}
+
+impl Fake for Valve {
+    fn transport(&self) -> Transport { Transport::Udp }
+
+    fn script(&self) -> Script {
+        // Counter-Strike: Source on protocol 7 omits the size of split datagrams,
+        // clients only know the protocol after the info response.
+        let has_size = !(self.appid == 240 && self.protocol == 7);
+
+        let script = self.exchange(
+            Script::new(),
+            INFO_REQUEST,
+            b"Source Engine Query\0",
+            self.datagrams(self.info_packet(), true),
+        );
+        let script = self.exchange(
+            script,
+            PLAYERS_REQUEST,
+            &[],
+            self.datagrams(self.players_packet(), has_size),
+        );
+        self.exchange(
+            script,
+            RULES_REQUEST,
+            &[],
+            self.datagrams(self.rules_packet(), has_size),
+        )
+    }
+}
@@ -0,0 +1,137 @@
+//! Capturing the exchanges of the HTTP based protocols, which don't go through
+//! the captured sockets.
+//!
+//! An exchange is written as a made up TCP connection carrying its request and
+//! response in plain HTTP/1.1 (even when it was made over TLS), with the
+//! headers set by this crate and the response body as it was decoded. Its
+//! packets are commented as generated, so replaying a capture skips them.
+//!
+//! Only the exchanges of the sync client are captured, like only the sync
+//! sockets are: the async client's ones aren't.
+
+use super::{
+    packet::{CapturePacket, Direction, Protocol},
+    writer::capture,
+};
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::atomic::{AtomicU16, Ordering},
+};
+use url::{Position, Url};
+
+/// The first made up local port, the start of the dynamic ports range.
+const FIRST_LOCAL_PORT: u16 = 49152;
+
+/// The made up local port of the next exchange, so that each one is a distinct
+/// connection.
+static NEXT_LOCAL_PORT: AtomicU16 = AtomicU16::new(FIRST_LOCAL_PORT);
+
+/// The size of the TCP segments the exchanges are split in, the usual
+/// Ethernet MSS.
+const SEGMENT_SIZE: usize = 1460;
+
+/// Response headers that no longer describe the body once it is decoded.
+const DECODED_HEADERS: [&str; 3] = ["content-encoding", "content-length", "transfer-encoding"];
+
+/// An HTTP exchange being captured.
+pub(crate) struct HttpCapture {
+    remote_address: SocketAddr,
+    request: Vec<u8>,
+}
+
+impl HttpCapture {
+    /// Start capturing an exchange with its request, sent to `url`.
+    pub(crate) fn new(remote_address: &SocketAddr, url: &Url, request: &ureq::Request, body: &[u8]) -> Self {
+        let host = &url[Position::BeforeHost .. Position::BeforePath];
+        let target = &url[Position::BeforePath .. Position::AfterQuery];
+
+        let mut head = format!("{} {target} HTTP/1.1\r\nHost: {host}\r\n", request.method());
+        for name in request.header_names() {
+            if let Some(value) = request.header(&name) {
+                head.push_str(&format!("{name}: {value}\r\n"));
+            }
+        }
+        if !body.is_empty() {
+            head.push_str(&format!("Content-Length: {}\r\n", body.len()));
+        }
+        head.push_str("\r\n");
+
+        let mut request = head.into_bytes();
+        request.extend_from_slice(body);
+
+        Self {
+            remote_address: *remote_address,
+            request,
+        }
+    }
+
+    /// Write the exchange to the current capture, with the head (see
+    /// [response_head]) and body of its response, if one was received.
+    ///
+    /// Like the sockets' packets, failing to capture it doesn't fail the
+    /// request.
+    pub(crate) fn record(self, response_head: Option<Vec<u8>>, body: &[u8]) {
+        let local_ip = match self.remote_address.ip() {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        let port = NEXT_LOCAL_PORT
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |port| {
+                Some(port.checked_add(1).unwrap_or(FIRST_LOCAL_PORT))
+            })
+            .unwrap_or(FIRST_LOCAL_PORT);
+        let local_address = SocketAddr::new(local_ip, port);
+
+        let mut packet = CapturePacket {
+            direction: Direction::Send,
+            protocol: Protocol::Tcp,
+            remote_address: &self.remote_address,
+            local_address: &local_address,
+            generated: true,
+        };
+
+        capture(|writer| {
+            writer.new_connect(&packet)?;
+            for segment in self.request.chunks(SEGMENT_SIZE) {
+                writer.write(&packet, segment)?;
+            }
+
+            if let Some(mut response) = response_head {
+                response.extend_from_slice(format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes());
+                response.extend_from_slice(body);
+
+                packet.direction = Direction::Receive;
+                for segment in response.chunks(SEGMENT_SIZE) {
+                    writer.write(&packet, segment)?;
+                }
+                packet.direction = Direction::Send;
+            }
+
+            writer.close_connection(&packet)
+        });
+    }
+}
+
+/// The status line and headers of a response, less the ones describing its
+/// encoded body (the length of the decoded one is added when recording).
+pub(crate) fn response_head(response: &ureq::Response) -> Vec<u8> {
+    let mut head = format!(
+        "{} {} {}\r\n",
+        response.http_version(),
+        response.status(),
+        response.status_text()
+    );
+
+    for name in response.headers_names() {
+        if DECODED_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+            continue;
+        }
+
+        if let Some(value) = response.header(&name) {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+    }
+
+    head.into_bytes()
+}
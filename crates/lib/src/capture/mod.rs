@@ -1,39 +1,128 @@
+//! Capturing the packets the queries send and receive.
+//!
+//! Packets are captured to a [Writer], either scoped to the queries run in
+//! [with_writer] or set for the whole process with [setup_capture]. [Pcap]
+//! writes them as a PCAPNG capture, to a file or in memory. Failing to capture
+//! a packet doesn't fail its query.
+//!
+//! Only the sync sockets are captured, the packets of async queries are not.
+//!
+//! ```no_run
+//! use gamedig::capture::{self, Pcap};
+//! use gamedig::protocols::valve::{self, Engine};
+//! use std::sync::{Arc, Mutex};
+//!
+//! let address = "127.0.0.1:27015".parse().unwrap();
+//! let pcap = Arc::new(Mutex::new(Pcap::new(Vec::new()).unwrap()));
+//!
+//! let result = capture::with_writer(pcap.clone(), || valve::query(&address, Engine::new(440), None, None));
+//! if result.is_err() {
+//!     let pcap = pcap.lock().unwrap();
+//!     let capture: &Vec<u8> = pcap.get_ref();
+//!     // Attach the capture to a bug report...
+//! }
+//! ```
+
+pub(crate) mod http;
 pub(crate) mod packet;
 mod pcap;
+pub mod replay;
 pub(crate) mod socket;
 pub(crate) mod writer;
 
-use self::{pcap::Pcap, writer::Writer};
-use pcap_file::pcapng::{blocks::interface_description::InterfaceDescriptionBlock, PcapNgBlock, PcapNgWriter};
-use std::path::PathBuf;
+pub use self::{
+    packet::{CapturePacket, Direction, Protocol},
+    pcap::Pcap,
+    writer::Writer,
+};
+
+use self::writer::SCOPED_WRITER;
+use crate::GDResult;
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
 
-pub fn setup_capture(file_path: Option<PathBuf>) {
+/// Capture the packets of every query of the process to a `.pcap` file at
+/// `file_path`, if any.
+///
+/// # Errors
+/// Returns an Error if the file already exists or couldn't be created, or if
+/// a capture is already set up.
+pub fn setup_capture(file_path: Option<PathBuf>) -> GDResult<()> {
     if let Some(file_path) = file_path {
-        let file = std::fs::OpenOptions::new()
-            .create_new(true)
-            .write(true)
-            .open(file_path.with_extension("pcap"))
-            .unwrap();
-
-        let mut pcap_writer = PcapNgWriter::new(file).unwrap();
-
-        // Write headers
-        let _ = pcap_writer.write_block(
-            &InterfaceDescriptionBlock {
-                linktype: pcap_file::DataLink::ETHERNET,
-                snaplen: 0xFFFF,
-                options: vec![],
-            }
-            .into_block(),
-        );
-
-        let writer = Box::new(Pcap::new(pcap_writer));
-        attach(writer)
+        let writer = Box::new(Pcap::create(file_path.with_extension("pcap"))?);
+        attach(writer)?;
     }
+
+    Ok(())
+}
+
+/// Restores the previous scoped writer of the thread when dropped.
+struct WriterGuard {
+    previous: Option<Arc<Mutex<dyn Writer + Send>>>,
+}
+
+impl Drop for WriterGuard {
+    fn drop(&mut self) { SCOPED_WRITER.with_borrow_mut(|writer| *writer = self.previous.take()); }
+}
+
+/// Run a (sync) query, capturing its packets to `writer` (instead of the
+/// writer set up with [setup_capture], if any).
+///
+/// The capture applies to the sync queries made on the current thread only,
+/// async queries are not captured.
+pub fn with_writer<T>(writer: Arc<Mutex<dyn Writer + Send>>, query: impl FnOnce() -> T) -> T {
+    let _guard = WriterGuard {
+        previous: SCOPED_WRITER.with_borrow_mut(|current| current.replace(writer)),
+    };
+
+    query()
 }
 
 /// Attaches a writer to the capture module.
 ///
 /// # Errors
 /// Returns an Error if the writer is already set.
-fn attach(writer: Box<dyn Writer + Send + Sync>) { crate::capture::socket::set_writer(writer); }
+fn attach(writer: Box<dyn Writer + Send + Sync>) -> GDResult<()> { crate::capture::socket::set_writer(writer) }
+
+#[cfg(test)]
+mod tests {
+    use super::replay::Capture;
+    use super::*;
+    use crate::protocols::quake::one;
+    use crate::GDErrorKind::PacketCapture;
+
+    use gamedig_fake_servers::{quake::Quake, FakeServer};
+
+    #[test]
+    fn captures_in_scope() {
+        let server = FakeServer::start(&Quake::one()).unwrap();
+        let address = server.address();
+
+        let pcap = Arc::new(Mutex::new(Pcap::new(Vec::new()).unwrap()));
+        let response = with_writer(pcap.clone(), || one::query(&address, None)).unwrap();
+
+        let capture = Capture::read(pcap.lock().unwrap().get_ref().as_slice()).unwrap();
+        assert_eq!(capture.remote_address(), Some(address));
+
+        let replayed = capture.replay(|| one::query(&address, None)).unwrap();
+        assert_eq!(replayed.name, response.name);
+    }
+
+    /// Fails to write every packet.
+    struct Failing;
+
+    impl Writer for Failing {
+        fn write(&mut self, _packet: &CapturePacket, _data: &[u8]) -> GDResult<()> { Err(PacketCapture.into()) }
+    }
+
+    #[test]
+    fn failing_capture_does_not_fail_query() {
+        let server = FakeServer::start(&Quake::one()).unwrap();
+        let address = server.address();
+
+        let response = with_writer(Arc::new(Mutex::new(Failing)), || one::query(&address, None)).unwrap();
+        assert_eq!(response.name, "Fake server");
+    }
+}
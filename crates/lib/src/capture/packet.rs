@@ -13,7 +13,7 @@ pub(crate) const HEADER_SIZE_UDP: usize = 4;
 
 /// Represents the direction of a network packet.
 #[derive(Clone, Copy, Debug, PartialEq)]
-pub(crate) enum Direction {
+pub enum Direction {
     /// Packet is outgoing (sent by us).
     Send,
     /// Packet is incoming (received by us).
@@ -22,7 +22,7 @@ pub(crate) enum Direction {
 
 /// Defines the protocol of a network packet.
 #[derive(Clone, Copy, Debug, PartialEq)]
-pub(crate) enum Protocol {
+pub enum Protocol {
     /// Transmission Control Protocol.
     Tcp,
     /// User Datagram Protocol.
@@ -38,15 +38,18 @@ pub(crate) trait IpAddress: Sized {
 
 /// Represents a captured network packet with metadata.
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) struct CapturePacket<'a> {
+pub struct CapturePacket<'a> {
     /// Direction of the packet (Send/Receive).
-    pub(crate) direction: Direction,
+    pub direction: Direction,
     /// Protocol of the packet (Tcp/UDP).
-    pub(crate) protocol: Protocol,
+    pub protocol: Protocol,
     /// Remote socket address.
-    pub(crate) remote_address: &'a SocketAddr,
+    pub remote_address: &'a SocketAddr,
     /// Local socket address.
-    pub(crate) local_address: &'a SocketAddr,
+    pub local_address: &'a SocketAddr,
+    /// Whether the packet is made up from an exchange that doesn't go through
+    /// the sockets (an HTTP one), rather than sent or received as is.
+    pub generated: bool,
 }
 
 impl CapturePacket<'_> {
@@ -138,6 +141,7 @@ mod tests {
             protocol: Protocol::Tcp,
             local_address: &socket_addr("127.0.0.1:8080"),
             remote_address: &socket_addr("192.168.1.1:80"),
+            generated: false,
         };
 
         let packet_receive = CapturePacket {
@@ -145,6 +149,7 @@ mod tests {
             protocol: Protocol::Tcp,
             local_address: &socket_addr("127.0.0.1:8080"),
             remote_address: &socket_addr("192.168.1.1:80"),
+            generated: false,
         };
 
         assert_eq!(packet_send.ports_by_direction(), (8080, 80));
@@ -158,6 +163,7 @@ mod tests {
             protocol: Protocol::Tcp,
             local_address: &socket_addr("127.0.0.1:8080"),
             remote_address: &socket_addr("192.168.1.1:80"),
+            generated: false,
         };
 
         let packet_receive = CapturePacket {
@@ -165,6 +171,7 @@ mod tests {
             protocol: Protocol::Tcp,
             local_address: &socket_addr("127.0.0.1:8080"),
             remote_address: &socket_addr("192.168.1.1:80"),
+            generated: false,
         };
 
         assert_eq!(
@@ -190,6 +197,7 @@ mod tests {
             protocol: Protocol::Tcp,
             local_address: &socket_addr("127.0.0.1:8080"),
             remote_address: &socket_addr("192.168.1.1:80"),
+            generated: false,
         };
 
         let ipv4_result: Result<(Ipv4Addr, Ipv4Addr), _> =
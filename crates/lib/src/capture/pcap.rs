@@ -1,4 +1,8 @@
-use pcap_file::pcapng::{blocks::enhanced_packet::EnhancedPacketOption, PcapNgBlock, PcapNgWriter};
+use pcap_file::pcapng::{
+    blocks::{enhanced_packet::EnhancedPacketOption, interface_description::InterfaceDescriptionBlock},
+    PcapNgBlock,
+    PcapNgWriter,
+};
 use pnet_packet::{
     ethernet::{EtherType, MutableEthernetPacket},
     ip::{IpNextHeaderProtocol, IpNextHeaderProtocols},
@@ -8,7 +12,13 @@ use pnet_packet::{
     udp::MutableUdpPacket,
     PacketSize,
 };
-use std::{io::Write, net::IpAddr, time::Instant};
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    net::IpAddr,
+    path::Path,
+    time::Instant,
+};
 
 use super::packet::{
     CapturePacket,
@@ -20,10 +30,17 @@ use super::packet::{
     HEADER_SIZE_UDP,
     PACKET_SIZE,
 };
+use crate::GDErrorKind::PacketCapture;
+use crate::GDResult;
 
 const BUFFER_SIZE: usize = PACKET_SIZE - HEADER_SIZE_IP6 - HEADER_SIZE_ETHERNET;
 
-pub(crate) struct Pcap<W: Write> {
+/// Writes the captured packets as a PCAPNG capture, which can be read with a
+/// tool like Wireshark (or replayed, see [replay](super::replay)).
+///
+/// Only the data sent and received is real, the rest of the packets (their
+/// headers, the TCP handshakes and ACKs...) is made up.
+pub struct Pcap<W: Write> {
     writer: PcapNgWriter<W>,
     pub(crate) state: State,
 }
@@ -36,15 +53,48 @@ pub(crate) struct State {
     pub(crate) stream_count: u32,
 }
 
+impl Pcap<File> {
+    /// Create a capture file, failing if it already exists.
+    pub fn create(path: impl AsRef<Path>) -> GDResult<Self> {
+        let file = OpenOptions::new()
+            .create_new(true)
+            .write(true)
+            .open(path)
+            .map_err(|e| PacketCapture.context(e))?;
+
+        Self::new(file)
+    }
+}
+
 impl<W: Write> Pcap<W> {
-    pub(crate) fn new(writer: PcapNgWriter<W>) -> Self {
-        Self {
+    /// Start a capture, writing its headers to `writer`.
+    pub fn new(writer: W) -> GDResult<Self> {
+        let mut writer = PcapNgWriter::new(writer).map_err(|e| PacketCapture.context(e))?;
+
+        writer
+            .write_block(
+                &InterfaceDescriptionBlock {
+                    linktype: pcap_file::DataLink::ETHERNET,
+                    snaplen: 0xFFFF,
+                    options: vec![],
+                }
+                .into_block(),
+            )
+            .map_err(|e| PacketCapture.context(e))?;
+
+        Ok(Self {
             writer,
             state: State::default(),
-        }
+        })
     }
 
-    pub(crate) fn write_transport_packet(&mut self, info: &CapturePacket, payload: &[u8]) {
+    /// The capture written so far, e.g. the bytes of an in-memory capture.
+    pub fn get_ref(&self) -> &W { self.writer.get_ref() }
+
+    /// Stop capturing, returning the writer.
+    pub fn into_inner(self) -> W { self.writer.into_inner() }
+
+    pub(crate) fn write_transport_packet(&mut self, info: &CapturePacket, payload: &[u8]) -> GDResult<()> {
         let mut buffer_array: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE];
         let buf: &mut [u8] = &mut buffer_array[..];
 
@@ -83,7 +133,7 @@ impl<W: Write> Pcap<W> {
                     IpNextHeaderProtocols::Tcp,
                     &buf[.. buf_size + payload.len()],
                     vec![],
-                );
+                )?;
 
                 let mut info = info.clone();
                 let buf_size = {
@@ -116,7 +166,7 @@ impl<W: Write> Pcap<W> {
                     IpNextHeaderProtocols::Tcp,
                     &buf[.. buf_size],
                     vec![EnhancedPacketOption::Comment("Generated TCP ACK".into())],
-                );
+                )
             }
             Protocol::Udp => {
                 let buf_size = {
@@ -134,7 +184,7 @@ impl<W: Write> Pcap<W> {
                     IpNextHeaderProtocols::Udp,
                     &buf[.. buf_size + payload.len()],
                     vec![],
-                );
+                )
             }
         }
     }
@@ -212,7 +262,7 @@ impl<W: Write> Pcap<W> {
     }
 
     /// Write a TCP handshake.
-    pub(crate) fn write_tcp_handshake(&mut self, info: &CapturePacket) {
+    pub(crate) fn write_tcp_handshake(&mut self, info: &CapturePacket) -> GDResult<()> {
         let (source_port, dest_port) = (info.local_address.port(), info.remote_address.port());
 
         let mut info = info.clone();
@@ -242,7 +292,7 @@ impl<W: Write> Pcap<W> {
             IpNextHeaderProtocols::Tcp,
             &buf[.. buf_size],
             options.clone(),
-        );
+        )?;
 
         // SYN + ACK
         info.direction = Direction::Receive;
@@ -265,7 +315,7 @@ impl<W: Write> Pcap<W> {
             IpNextHeaderProtocols::Tcp,
             &buf[.. buf_size],
             options.clone(),
-        );
+        )?;
 
         // ACK
         info.direction = Direction::Send;
@@ -287,12 +337,14 @@ impl<W: Write> Pcap<W> {
             IpNextHeaderProtocols::Tcp,
             &buf[.. buf_size],
             options,
-        );
+        )?;
 
         self.state.has_sent_handshake = true;
+
+        Ok(())
     }
 
-    pub(crate) fn send_tcp_fin(&mut self, info: &CapturePacket) {
+    pub(crate) fn send_tcp_fin(&mut self, info: &CapturePacket) -> GDResult<()> {
         let mut buffer_array: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE];
         let buf: &mut [u8] = &mut buffer_array[..];
         let (source_port, dest_port) = info.ports_by_direction();
@@ -324,7 +376,7 @@ impl<W: Write> Pcap<W> {
             IpNextHeaderProtocols::Tcp,
             &buf[.. buf_size],
             vec![EnhancedPacketOption::Comment("Generated TCP FIN".into())],
-        );
+        )?;
 
         // Update sequence number
         match info.direction {
@@ -335,6 +387,8 @@ impl<W: Write> Pcap<W> {
                 self.state.rec_seq = self.state.rec_seq.wrapping_add(1);
             }
         }
+
+        Ok(())
     }
 
     fn write_transport_payload(
@@ -342,8 +396,16 @@ impl<W: Write> Pcap<W> {
         info: &CapturePacket,
         protocol: IpNextHeaderProtocol,
         payload: &[u8],
-        options: Vec<pcap_file::pcapng::blocks::enhanced_packet::EnhancedPacketOption>,
-    ) {
+        mut options: Vec<pcap_file::pcapng::blocks::enhanced_packet::EnhancedPacketOption>,
+    ) -> GDResult<()> {
+        // The exchanges that didn't go through the sockets are made up as a
+        // whole, so that replaying skips them.
+        if info.generated && options.is_empty() {
+            options.push(EnhancedPacketOption::Comment(
+                "Generated from an HTTP exchange".into(),
+            ));
+        }
+
         let mut network_packet = vec![0; PACKET_SIZE - HEADER_SIZE_ETHERNET];
         let (network_size, ethertype) = self.encode_ip_packet(&mut network_packet, info, protocol, payload);
         let network_size = network_size + payload.len();
@@ -366,7 +428,9 @@ impl<W: Write> Pcap<W> {
                 }
                 .into_block(),
             )
-            .unwrap();
+            .map_err(|e| PacketCapture.context(e))?;
+
+        Ok(())
     }
 }
 
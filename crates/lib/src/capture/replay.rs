@@ -0,0 +1,423 @@
+//! Replaying captures, to turn a query captured once (such as one failing in
+//! the field) into a deterministic regression test.
+//!
+//! A [Capture] reads a file written by [setup_capture](super::setup_capture)
+//! and serves the recorded server responses to the (sync) queries run in
+//! [Capture::replay], in place of the network, checking that the queries send
+//! what was captured.
+//!
+//! Only sync UDP and TCP queries can be replayed: replaying goes through
+//! [transport::with_factory], which async sockets don't use, and the HTTP
+//! exchanges of a capture are only there to be read (they are skipped, as they
+//! don't go through the transports).
+//!
+//! ```no_run
+//! use gamedig::capture::replay::Capture;
+//! use gamedig::protocols::valve::{self, Engine};
+//!
+//! let capture = Capture::open("tf2.pcap").unwrap();
+//! let address = capture.remote_address().unwrap();
+//!
+//! let response = capture
+//!     .replay(|| valve::query(&address, Engine::new(440), None, None))
+//!     .unwrap();
+//! assert_eq!(response.info.map, "ctf_2fort");
+//! ```
+
+use super::packet::Protocol;
+use crate::protocols::types::TimeoutSettings;
+use crate::transport::{self, Transport, TransportFactory};
+use crate::GDErrorKind::{InvalidInput, PacketReceive, PacketSend, SocketConnect};
+use crate::GDResult;
+
+use pcap_file::pcapng::{blocks::enhanced_packet::EnhancedPacketOption, Block, PcapNgReader};
+use pnet_packet::{
+    ethernet::{EtherTypes, EthernetPacket},
+    ip::IpNextHeaderProtocols,
+    ipv4::Ipv4Packet,
+    ipv6::Ipv6Packet,
+    tcp::TcpPacket,
+    udp::UdpPacket,
+    Packet,
+};
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufReader, Read},
+    net::{IpAddr, SocketAddr},
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+/// A connection (a socket) of a capture.
+#[derive(Debug, Clone, PartialEq)]
+struct Connection {
+    protocol: Protocol,
+    local_address: SocketAddr,
+    remote_address: SocketAddr,
+    /// The data sent to the server, in order.
+    sent: VecDeque<Vec<u8>>,
+    /// The data received from the server, in order.
+    received: VecDeque<Vec<u8>>,
+}
+
+/// A transport packet of a capture.
+struct CapturedPacket {
+    protocol: Protocol,
+    source: SocketAddr,
+    destination: SocketAddr,
+    payload: Vec<u8>,
+}
+
+/// The connections of a capture and the data received on them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Capture {
+    /// In the order they were opened.
+    connections: Vec<Connection>,
+}
+
+impl Capture {
+    /// Read a capture file.
+    pub fn open(path: impl AsRef<Path>) -> GDResult<Self> {
+        let file = File::open(path).map_err(|e| InvalidInput.context(e))?;
+
+        Self::read(BufReader::new(file))
+    }
+
+    /// Read a capture.
+    pub fn read(reader: impl Read) -> GDResult<Self> {
+        let mut reader = PcapNgReader::new(reader).map_err(|e| InvalidInput.context(e))?;
+
+        let mut capture = Self::default();
+        while let Some(block) = reader.next_block() {
+            let Block::EnhancedPacket(packet) = block.map_err(|e| InvalidInput.context(e))? else {
+                continue;
+            };
+
+            // The TCP handshakes, ACKs and FINs are made up by the writer.
+            let generated = packet.options.iter().any(
+                |option| matches!(option, EnhancedPacketOption::Comment(comment) if comment.starts_with("Generated")),
+            );
+            if generated {
+                continue;
+            }
+
+            let packet =
+                parse_frame(&packet.data).ok_or_else(|| InvalidInput.context("Couldn't parse a captured packet"))?;
+            capture.add(packet);
+        }
+
+        Ok(capture)
+    }
+
+    /// The server address of the first connection, the address the captured
+    /// query was made to.
+    pub fn remote_address(&self) -> Option<SocketAddr> {
+        self.connections
+            .first()
+            .map(|connection| connection.remote_address)
+    }
+
+    /// Run a (sync) query, serving it the data received in the capture instead
+    /// of the network.
+    ///
+    /// Each socket the query opens replays the first unused connection to the
+    /// same address (so the query has to be made to the captured address, see
+    /// [Capture::remote_address]) and receives what was received on it, in
+    /// order. Sending anything else than what was sent on it is a
+    /// [PacketSend] error.
+    pub fn replay<T>(&self, query: impl FnOnce() -> T) -> T {
+        let replay = Replay {
+            connections: Mutex::new(self.connections.clone()),
+        };
+
+        transport::with_factory(Arc::new(replay), query)
+    }
+
+    fn add(&mut self, packet: CapturedPacket) {
+        // Empty TCP segments carry nothing to replay.
+        if packet.payload.is_empty() {
+            return;
+        }
+
+        // Searching from the end as closed sockets' ports can be reused.
+        let connection = self.connections.iter_mut().rev().find(|connection| {
+            connection.protocol == packet.protocol
+                && ((connection.local_address == packet.source && connection.remote_address == packet.destination)
+                    || (connection.local_address == packet.destination && connection.remote_address == packet.source))
+        });
+
+        match connection {
+            Some(connection) => {
+                match connection.remote_address == packet.source {
+                    true => connection.received.push_back(packet.payload),
+                    false => connection.sent.push_back(packet.payload),
+                }
+            }
+            // The queries always send first.
+            None => {
+                self.connections.push(Connection {
+                    protocol: packet.protocol,
+                    local_address: packet.source,
+                    remote_address: packet.destination,
+                    sent: VecDeque::from([packet.payload]),
+                    received: VecDeque::new(),
+                })
+            }
+        }
+    }
+}
+
+/// Parse a captured Ethernet frame down to its transport packet.
+fn parse_frame(frame: &[u8]) -> Option<CapturedPacket> {
+    let ethernet = EthernetPacket::new(frame)?;
+
+    let ethertype = ethernet.get_ethertype();
+    let (source, destination, protocol, payload): (IpAddr, IpAddr, _, _) = if ethertype == EtherTypes::Ipv4 {
+        let ip = Ipv4Packet::new(ethernet.payload())?;
+        (
+            ip.get_source().into(),
+            ip.get_destination().into(),
+            ip.get_next_level_protocol(),
+            ip.payload().to_vec(),
+        )
+    } else if ethertype == EtherTypes::Ipv6 {
+        let ip = Ipv6Packet::new(ethernet.payload())?;
+        (
+            ip.get_source().into(),
+            ip.get_destination().into(),
+            ip.get_next_header(),
+            ip.payload().to_vec(),
+        )
+    } else {
+        return None;
+    };
+
+    if protocol == IpNextHeaderProtocols::Udp {
+        let udp = UdpPacket::new(&payload)?;
+        Some(CapturedPacket {
+            protocol: Protocol::Udp,
+            source: SocketAddr::new(source, udp.get_source()),
+            destination: SocketAddr::new(destination, udp.get_destination()),
+            payload: udp.payload().to_vec(),
+        })
+    } else if protocol == IpNextHeaderProtocols::Tcp {
+        let tcp = TcpPacket::new(&payload)?;
+        Some(CapturedPacket {
+            protocol: Protocol::Tcp,
+            source: SocketAddr::new(source, tcp.get_source()),
+            destination: SocketAddr::new(destination, tcp.get_destination()),
+            payload: tcp.payload().to_vec(),
+        })
+    } else {
+        None
+    }
+}
+
+/// Hands out the connections of a capture, see [Capture::replay].
+struct Replay {
+    connections: Mutex<Vec<Connection>>,
+}
+
+impl Replay {
+    fn take(&self, protocol: Protocol, address: &SocketAddr) -> GDResult<Box<dyn Transport>> {
+        let mut connections = self
+            .connections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        let index = connections
+            .iter()
+            .position(|connection| connection.protocol == protocol && connection.remote_address == *address)
+            .ok_or_else(|| {
+                SocketConnect.context(format!(
+                    "The capture has no more {protocol:?} connections to {address}"
+                ))
+            })?;
+
+        let connection = connections.remove(index);
+        Ok(Box::new(ReplayTransport {
+            sent: connection.sent,
+            received: connection.received,
+        }))
+    }
+}
+
+impl TransportFactory for Replay {
+    fn udp(&self, address: &SocketAddr, _: &Option<TimeoutSettings>) -> GDResult<Box<dyn Transport>> {
+        self.take(Protocol::Udp, address)
+    }
+
+    fn tcp(&self, address: &SocketAddr, _: &Option<TimeoutSettings>) -> GDResult<Box<dyn Transport>> {
+        self.take(Protocol::Tcp, address)
+    }
+}
+
+/// A replayed connection.
+struct ReplayTransport {
+    sent: VecDeque<Vec<u8>>,
+    received: VecDeque<Vec<u8>>,
+}
+
+impl Transport for ReplayTransport {
+    fn send(&mut self, data: &[u8]) -> GDResult<()> {
+        match self.sent.pop_front() {
+            Some(sent) if sent == data => Ok(()),
+            Some(sent) => {
+                Err(PacketSend.context(format!(
+                    "Sent {data:02x?} instead of the captured {sent:02x?}"
+                )))
+            }
+            None => Err(PacketSend.context("Nothing more was sent on the captured connection")),
+        }
+    }
+
+    fn receive(&mut self, _: Option<usize>) -> GDResult<Vec<u8>> {
+        // Like a timeout, so that the query fails the way it would have.
+        self.received
+            .pop_front()
+            .ok_or_else(|| PacketReceive.context("Nothing more was received on the captured connection"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::{CapturePacket, Direction, Pcap, Writer};
+    use crate::protocols::quake::one;
+
+    const REQUEST: &[u8] = b"\xFF\xFF\xFF\xFFstatus\0";
+    const RESPONSE: &[u8] = b"\xFF\xFF\xFF\xFFn\\hostname\\Test\\map\\e1m1\\maxclients\\8\n";
+
+    /// Write a capture of a single exchange.
+    fn capture(
+        request: &[u8],
+        protocol: Protocol,
+        local_address: &SocketAddr,
+        remote_address: &SocketAddr,
+        generated: bool,
+    ) -> Capture {
+        let mut file = Vec::new();
+        {
+            let mut writer = Pcap::new(&mut file).unwrap();
+            let mut packet = CapturePacket {
+                direction: Direction::Send,
+                protocol,
+                remote_address,
+                local_address,
+                generated,
+            };
+
+            writer.new_connect(&packet).unwrap();
+            writer.write(&packet, request).unwrap();
+            packet.direction = Direction::Receive;
+            writer.write(&packet, RESPONSE).unwrap();
+            writer.close_connection(&packet).unwrap();
+        }
+
+        Capture::read(file.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn replays_udp() {
+        let local_address = "127.0.0.1:50000".parse().unwrap();
+        let remote_address = "127.0.0.1:27500".parse().unwrap();
+        let capture = capture(
+            REQUEST,
+            Protocol::Udp,
+            &local_address,
+            &remote_address,
+            false,
+        );
+        assert_eq!(capture.remote_address(), Some(remote_address));
+
+        let response = capture
+            .replay(|| one::query(&remote_address, None))
+            .unwrap();
+        assert_eq!(response.name, "Test");
+
+        // The only connection was replayed.
+        assert!(capture
+            .replay(|| {
+                one::query(&remote_address, None)?;
+                one::query(&remote_address, None)
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn checks_what_is_sent() {
+        let local_address = "127.0.0.1:50000".parse().unwrap();
+        let remote_address = "127.0.0.1:27500".parse().unwrap();
+
+        let other_request = capture(
+            b"\xFF\xFF\xFF\xFFgetstatus\0",
+            Protocol::Udp,
+            &local_address,
+            &remote_address,
+            false,
+        );
+        let error = other_request
+            .replay(|| one::query(&remote_address, None))
+            .unwrap_err();
+        assert_eq!(error.kind, PacketSend);
+
+        // Nor is a query to another address replayed.
+        let capture = capture(
+            REQUEST,
+            Protocol::Udp,
+            &local_address,
+            &remote_address,
+            false,
+        );
+        let other_address = "127.0.0.1:27501".parse().unwrap();
+        let error = capture
+            .replay(|| one::query(&other_address, None))
+            .unwrap_err();
+        assert_eq!(error.kind, SocketConnect);
+    }
+
+    #[test]
+    fn reads_tcp() {
+        let local_address = "[::1]:50000".parse().unwrap();
+        let remote_address = "[::1]:25565".parse().unwrap();
+
+        assert_eq!(
+            capture(
+                REQUEST,
+                Protocol::Tcp,
+                &local_address,
+                &remote_address,
+                false
+            ),
+            Capture {
+                connections: vec![Connection {
+                    protocol: Protocol::Tcp,
+                    local_address,
+                    remote_address,
+                    sent: VecDeque::from([REQUEST.to_vec()]),
+                    received: VecDeque::from([RESPONSE.to_vec()]),
+                }],
+            }
+        );
+    }
+
+    #[test]
+    fn skips_generated_exchanges() {
+        let local_address = "127.0.0.1:50000".parse().unwrap();
+        let remote_address = "127.0.0.1:443".parse().unwrap();
+
+        assert_eq!(
+            capture(
+                REQUEST,
+                Protocol::Tcp,
+                &local_address,
+                &remote_address,
+                true
+            ),
+            Capture {
+                connections: vec![],
+            }
+        );
+    }
+}
@@ -1,31 +1,37 @@
-use std::{marker::PhantomData, net::SocketAddr};
+use std::{marker::PhantomData, net::SocketAddr, sync::PoisonError};
 
 use crate::{
     capture::{
         packet::CapturePacket,
         packet::{Direction, Protocol},
-        writer::{Writer, CAPTURE_WRITER},
+        writer::{capture, Writer, CAPTURE_WRITER},
     },
     protocols::types::TimeoutSettings,
     socket::{Socket, TcpSocketImpl, UdpSocketImpl},
+    transport::Transport,
+    GDErrorKind::PacketCapture,
     GDResult,
 };
 
 /// Sets a global capture writer for handling all packet data.
 ///
-/// # Panics
-/// Panics if a capture writer is already set.
+/// # Errors
+/// Returns an Error if a capture writer is already set.
 ///
 /// # Arguments
 /// * `writer` - A boxed writer that implements the `Writer` trait.
-pub(crate) fn set_writer(writer: Box<dyn Writer + Send + Sync>) {
-    let mut lock = CAPTURE_WRITER.lock().unwrap();
+pub(crate) fn set_writer(writer: Box<dyn Writer + Send + Sync>) -> GDResult<()> {
+    let mut lock = CAPTURE_WRITER
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
 
     if lock.is_some() {
-        panic!("Capture writer already set");
+        return Err(PacketCapture.context("Capture writer already set"));
     }
 
     *lock = Some(writer);
+
+    Ok(())
 }
 
 /// A trait representing a provider of a network protocol.
@@ -84,11 +90,10 @@ impl<I: Socket, P: ProtocolProvider> Socket for WrappedCaptureSocket<I, P> {
             protocol: P::protocol(),
             remote_address: address,
             local_address: &v.local_addr().unwrap(),
+            generated: false,
         };
 
-        if let Some(writer) = CAPTURE_WRITER.lock().unwrap().as_mut() {
-            writer.new_connect(&info)?;
-        }
+        capture(|writer| writer.new_connect(&info));
 
         Ok(v)
     }
@@ -96,7 +101,7 @@ impl<I: Socket, P: ProtocolProvider> Socket for WrappedCaptureSocket<I, P> {
     /// Sends data over the socket and captures the packet.
     ///
     /// The method sends data using the inner socket and captures the sent
-    /// packet if a capture writer is set.
+    /// packet if a (scoped or global) capture writer is set.
     ///
     /// # Arguments
     /// * `data` - Data to be sent.
@@ -109,11 +114,10 @@ impl<I: Socket, P: ProtocolProvider> Socket for WrappedCaptureSocket<I, P> {
             protocol: P::protocol(),
             remote_address: &self.remote_address,
             local_address: &self.local_addr().unwrap(),
+            generated: false,
         };
 
-        if let Some(writer) = CAPTURE_WRITER.lock().unwrap().as_mut() {
-            writer.write(&info, data)?;
-        }
+        capture(|writer| writer.write(&info, data));
 
         self.inner.send(data)
     }
@@ -121,7 +125,7 @@ impl<I: Socket, P: ProtocolProvider> Socket for WrappedCaptureSocket<I, P> {
     /// Receives data from the socket and captures the packet.
     ///
     /// The method receives data using the inner socket and captures the
-    /// incoming packet if a capture writer is set.
+    /// incoming packet if a (scoped or global) capture writer is set.
     ///
     /// # Arguments
     /// * `size` - Optional size of data to receive.
@@ -130,16 +134,21 @@ impl<I: Socket, P: ProtocolProvider> Socket for WrappedCaptureSocket<I, P> {
     /// A result containing received data or an error.
     fn receive(&mut self, size: Option<usize>) -> crate::GDResult<Vec<u8>> {
         let data = self.inner.receive(size)?;
-        let info = CapturePacket {
-            direction: Direction::Receive,
-            protocol: P::protocol(),
-            remote_address: &self.remote_address,
-            local_address: &self.local_addr().unwrap(),
-        };
+        self.capture_received(&data);
+
+        Ok(data)
+    }
 
-        if let Some(writer) = CAPTURE_WRITER.lock().unwrap().as_mut() {
-            writer.write(&info, &data)?;
-        }
+    /// Receives the available data from the socket and captures the packet.
+    ///
+    /// # Arguments
+    /// * `size` - Optional size of data to receive.
+    ///
+    /// # Returns
+    /// A result containing received data or an error.
+    fn receive_available(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> {
+        let data = self.inner.receive_available(size)?;
+        self.capture_received(&data);
 
         Ok(data)
     }
@@ -168,6 +177,14 @@ impl<I: Socket, P: ProtocolProvider> Socket for WrappedCaptureSocket<I, P> {
     /// The remote port number.
     fn port(&self) -> u16 { self.inner.port() }
 
+    /// Returns the latency of the last exchange of the wrapped socket.
+    ///
+    /// Delegates the operation to the inner socket implementation.
+    ///
+    /// # Returns
+    /// The latency of the last exchange.
+    fn latency(&self) -> Option<std::time::Duration> { self.inner.latency() }
+
     /// Returns the local SocketAddr of the wrapped socket.
     ///
     /// Delegates the operation to the inner socket implementation.
@@ -177,6 +194,31 @@ impl<I: Socket, P: ProtocolProvider> Socket for WrappedCaptureSocket<I, P> {
     fn local_addr(&self) -> std::io::Result<SocketAddr> { self.inner.local_addr() }
 }
 
+impl<I: Socket, P: ProtocolProvider> WrappedCaptureSocket<I, P> {
+    /// Captures received data if a (scoped or global) capture writer is set.
+    fn capture_received(&self, data: &[u8]) {
+        let info = CapturePacket {
+            direction: Direction::Receive,
+            protocol: P::protocol(),
+            remote_address: &self.remote_address,
+            local_address: &self.local_addr().unwrap(),
+            generated: false,
+        };
+
+        capture(|writer| writer.write(&info, data));
+    }
+}
+
+impl<I: Socket + Send, P: ProtocolProvider + Send> Transport for WrappedCaptureSocket<I, P> {
+    fn send(&mut self, data: &[u8]) -> GDResult<()> { Socket::send(self, data) }
+
+    fn receive(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> { Socket::receive(self, size) }
+
+    fn receive_available(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> { Socket::receive_available(self, size) }
+
+    fn latency(&self) -> Option<std::time::Duration> { Socket::latency(self) }
+}
+
 // this seems a bad way to do this, but its safe
 impl<I: Socket, P: ProtocolProvider> Drop for WrappedCaptureSocket<I, P> {
     fn drop(&mut self) {
@@ -188,12 +230,11 @@ impl<I: Socket, P: ProtocolProvider> Drop for WrappedCaptureSocket<I, P> {
             local_address: &self
                 .local_addr()
                 .unwrap_or_else(|_| SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED), 0)),
+            generated: false,
         };
 
         // If a capture writer is set, close the connection and capture the packet.
-        if let Some(writer) = CAPTURE_WRITER.lock().unwrap().as_mut() {
-            let _ = writer.close_connection(&info);
-        }
+        capture(|writer| writer.close_connection(&info));
     }
 }
 
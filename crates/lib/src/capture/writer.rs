@@ -1,10 +1,14 @@
-use std::{io::Write, sync::Mutex};
+use std::{
+    cell::RefCell,
+    io::Write,
+    sync::{Arc, Mutex, PoisonError},
+};
 
 use super::{
     packet::{CapturePacket, Protocol},
     pcap::Pcap,
 };
-use crate::GDResult;
+use crate::{trace, GDResult};
 use lazy_static::lazy_static;
 
 lazy_static! {
@@ -14,10 +18,45 @@ lazy_static! {
     pub(crate) static ref CAPTURE_WRITER: Mutex<Option<Box<dyn Writer + Send + Sync>>> = Mutex::new(None);
 }
 
+thread_local! {
+    /// The writer of the current thread's capture scope, which takes precedence
+    /// over the global one, see [with_writer](super::with_writer).
+    pub(crate) static SCOPED_WRITER: RefCell<Option<Arc<Mutex<dyn Writer + Send>>>> = const { RefCell::new(None) };
+}
+
+/// Call `write` with the writer the packets of the current thread are
+/// captured to, if any.
+pub(crate) fn with_current_writer(write: impl FnOnce(&mut dyn Writer) -> GDResult<()>) -> GDResult<()> {
+    if let Some(writer) = SCOPED_WRITER.with_borrow(Clone::clone) {
+        return write(&mut *writer.lock().unwrap_or_else(PoisonError::into_inner));
+    }
+
+    match CAPTURE_WRITER
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_mut()
+    {
+        Some(writer) => write(writer.as_mut()),
+        None => Ok(()),
+    }
+}
+
+/// Capture to the current writer, if any. Capturing is a side concern of the
+/// query, so failing to capture doesn't fail it.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn capture(write: impl FnOnce(&mut dyn Writer) -> GDResult<()>) {
+    if let Err(error) = with_current_writer(write) {
+        trace::event!(WARN, kind = ?error.kind, "couldn't capture a packet");
+    }
+}
+
 /// Trait defining the functionality for a writer that handles network packet
 /// captures. This trait includes methods for writing packet data, handling new
 /// connections, and closing connections.
-pub(crate) trait Writer {
+///
+/// [Pcap] writes the packets as a PCAPNG capture, implement this to capture
+/// them some other way.
+pub trait Writer {
     /// Writes a given packet's data to an underlying storage or stream.
     ///
     /// # Arguments
@@ -37,7 +76,7 @@ pub(crate) trait Writer {
     /// # Returns
     /// A `GDResult` indicating the success or failure of handling the new
     /// connection.
-    fn new_connect(&mut self, packet: &CapturePacket) -> GDResult<()>;
+    fn new_connect(&mut self, _packet: &CapturePacket) -> GDResult<()> { Ok(()) }
 
     /// Closes a connection, handling any necessary cleanup or finalization.
     ///
@@ -48,23 +87,19 @@ pub(crate) trait Writer {
     /// # Returns
     /// A `GDResult` indicating the success or failure of the connection closure
     /// operation.
-    fn close_connection(&mut self, packet: &CapturePacket) -> GDResult<()>;
+    fn close_connection(&mut self, _packet: &CapturePacket) -> GDResult<()> { Ok(()) }
 }
 
 /// Implementation of the `Writer` trait for the `Pcap` struct.
 /// This implementation enables writing, connection handling, and closure
 /// specific to PCAP (Packet Capture) format.
 impl<W: Write> Writer for Pcap<W> {
-    fn write(&mut self, info: &CapturePacket, data: &[u8]) -> GDResult<()> {
-        self.write_transport_packet(info, data);
-
-        Ok(())
-    }
+    fn write(&mut self, info: &CapturePacket, data: &[u8]) -> GDResult<()> { self.write_transport_packet(info, data) }
 
     fn new_connect(&mut self, packet: &CapturePacket) -> GDResult<()> {
         match packet.protocol {
             Protocol::Tcp => {
-                self.write_tcp_handshake(packet);
+                self.write_tcp_handshake(packet)?;
             }
             Protocol::Udp => {}
         }
@@ -77,7 +112,7 @@ impl<W: Write> Writer for Pcap<W> {
     fn close_connection(&mut self, packet: &CapturePacket) -> GDResult<()> {
         match packet.protocol {
             Protocol::Tcp => {
-                self.send_tcp_fin(packet);
+                self.send_tcp_fin(packet)?;
             }
             Protocol::Udp => {}
         }
@@ -0,0 +1,156 @@
+//! The overall deadline of the query being made, see
+//! [TimeoutSettings::with_total_timeout].
+//!
+//! Sockets clamp their timeouts to the time left until it and fail with
+//! [DeadlineExceeded] once it has passed, which (unlike timeouts) is not
+//! retried.
+
+#[cfg(feature = "games")]
+use crate::protocols::types::TimeoutSettings;
+use crate::GDErrorKind::{self, DeadlineExceeded};
+use crate::GDResult;
+
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+#[cfg(all(feature = "async", feature = "games"))]
+use std::future::Future;
+
+thread_local! {
+    static DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+#[cfg(feature = "async")]
+tokio::task_local! {
+    static TASK_DEADLINE: Option<Instant>;
+}
+
+/// The deadline of a query starting now, an already running one's deadline
+/// is kept if it is earlier.
+#[cfg(feature = "games")]
+fn starting_now(timeout_settings: &Option<TimeoutSettings>) -> Option<Instant> {
+    let deadline = TimeoutSettings::get_total_or_default(timeout_settings).map(|total| Instant::now() + total);
+
+    match (current(), deadline) {
+        (Some(current), Some(deadline)) => Some(current.min(deadline)),
+        (current, deadline) => current.or(deadline),
+    }
+}
+
+/// Restores the previous deadline of the thread when dropped.
+#[cfg(feature = "games")]
+struct DeadlineGuard {
+    previous: Option<Instant>,
+}
+
+#[cfg(feature = "games")]
+impl Drop for DeadlineGuard {
+    fn drop(&mut self) { DEADLINE.set(self.previous); }
+}
+
+/// Run a (sync) query within the total timeout of its settings.
+#[cfg(feature = "games")]
+pub(crate) fn scope<T>(timeout_settings: &Option<TimeoutSettings>, query: impl FnOnce() -> T) -> T {
+    let _guard = DeadlineGuard {
+        previous: DEADLINE.replace(starting_now(timeout_settings)),
+    };
+
+    query()
+}
+
+/// Async counterpart of [scope].
+#[cfg(all(feature = "async", feature = "games"))]
+pub(crate) async fn scope_async<T>(timeout_settings: &Option<TimeoutSettings>, query: impl Future<Output = T>) -> T {
+    TASK_DEADLINE
+        .scope(starting_now(timeout_settings), query)
+        .await
+}
+
+/// The deadline of the current query, if any.
+fn current() -> Option<Instant> {
+    #[cfg(feature = "async")]
+    if let Ok(deadline) = TASK_DEADLINE.try_with(|deadline| *deadline) {
+        return deadline;
+    }
+
+    DEADLINE.get()
+}
+
+/// Clamp an operation's timeout to the time left until the deadline, failing
+/// if it has passed already.
+pub(crate) fn clamp(timeout: Option<Duration>) -> GDResult<Option<Duration>> {
+    let Some(deadline) = current() else {
+        return Ok(timeout);
+    };
+
+    let left = deadline
+        .checked_duration_since(Instant::now())
+        .filter(|left| !left.is_zero())
+        .ok_or(DeadlineExceeded)?;
+
+    Ok(Some(timeout.map_or(left, |timeout| timeout.min(left))))
+}
+
+/// Whether the query's deadline (if any) leaves time to wait for `delay`.
+pub(crate) fn leaves_time_for(delay: Duration) -> bool {
+    current().is_none_or(|deadline| Instant::now() + delay < deadline)
+}
+
+/// The kind of error to fail with when an operation timed out:
+/// [DeadlineExceeded] if the deadline has passed, `kind` otherwise.
+pub(crate) fn timed_out(kind: GDErrorKind) -> GDErrorKind {
+    match current().is_some_and(|deadline| Instant::now() >= deadline) {
+        true => DeadlineExceeded,
+        false => kind,
+    }
+}
+
+#[cfg(all(test, feature = "games"))]
+mod tests {
+    use super::*;
+
+    fn timeout_settings(total: Duration) -> Option<TimeoutSettings> {
+        Some(
+            TimeoutSettings::default()
+                .with_total_timeout(total)
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn clamps_inside_scope_only() {
+        let timeout = Some(Duration::from_secs(4));
+        assert_eq!(clamp(timeout).unwrap(), timeout);
+
+        scope(&timeout_settings(Duration::from_secs(1)), || {
+            assert!(clamp(timeout).unwrap() <= Some(Duration::from_secs(1)));
+            assert!(clamp(None).unwrap().is_some());
+        });
+
+        assert_eq!(clamp(timeout).unwrap(), timeout);
+    }
+
+    #[test]
+    fn nested_scope_keeps_earlier_deadline() {
+        scope(&timeout_settings(Duration::from_millis(1)), || {
+            scope(&timeout_settings(Duration::from_secs(60)), || {
+                std::thread::sleep(Duration::from_millis(2));
+
+                assert_eq!(clamp(None).unwrap_err().kind, DeadlineExceeded);
+                assert_eq!(timed_out(GDErrorKind::PacketReceive), DeadlineExceeded);
+            });
+        });
+    }
+
+    #[test]
+    fn scope_restored_after_panic() {
+        let panicked = std::panic::catch_unwind(|| {
+            scope(&timeout_settings(Duration::from_millis(1)), || {
+                panic!("query panicked")
+            });
+        });
+
+        assert!(panicked.is_err());
+        assert_eq!(current(), None);
+    }
+}
@@ -0,0 +1,43 @@
+use crate::protocols::types::Protocol;
+use std::net::SocketAddr;
+
+/// The stage of a query an error happened at.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum QueryStage {
+    /// Creating (binding or connecting) the socket.
+    Connect,
+    /// The handshake giving the challenge of the following requests (GameSpy
+    /// 3), or the authentication they need (Epic).
+    Challenge,
+    /// The request of the server's information (Valve's info request, Unreal
+    /// 2's server info, GameSpy 3's data request...).
+    Info,
+    /// The request of the players list.
+    Players,
+    /// The request of the rules (Valve's rules, Unreal 2's mutators and
+    /// rules).
+    Rules,
+}
+
+/// Where an error happened, as far as it is known.
+///
+/// The protocols set the address and the stage (the generic query functions
+/// also set the protocol) and the retries set the attempt.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// The address of the queried server.
+    pub address: Option<SocketAddr>,
+    /// The protocol the server was queried with.
+    pub protocol: Option<Protocol>,
+    /// The stage of the query that failed.
+    pub stage: Option<QueryStage>,
+    /// The try of the request that failed, 0 being the first one (retries
+    /// start at 1).
+    pub attempt: Option<u32>,
+}
+
+impl ErrorContext {
+    /// Whether nothing is known.
+    pub fn is_empty(&self) -> bool { self == &Self::default() }
+}
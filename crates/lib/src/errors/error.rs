@@ -1,6 +1,8 @@
-use crate::GDErrorKind;
+use crate::protocols::types::Protocol;
+use crate::{ErrorContext, GDErrorKind, QueryStage};
 use std::error::Error;
 use std::fmt::Formatter;
+use std::net::SocketAddr;
 use std::{backtrace, fmt};
 
 pub(crate) type ErrorSource = Box<dyn Error + 'static + Send + Sync>;
@@ -32,10 +34,22 @@ pub(crate) type ErrorSource = Box<dyn Error + 'static + Send + Sync>;
 /// use gamedig::{GDError, GDErrorKind};
 /// let _: GDError = GDError::new(GDErrorKind::PacketBad, Some("Reason the packet was bad".into()));
 /// ```
+///
+/// Errors of queries also tell where they happened (see [ErrorContext]):
+///
+/// ```
+/// use gamedig::{GDError, GDErrorKind, QueryStage};
+/// let error: GDError = GDErrorKind::PacketReceive.into();
+/// let error = error.with_stage(QueryStage::Players).with_attempt(2);
+/// assert_eq!(error.stage(), Some(QueryStage::Players));
+/// assert!(error.is_timeout());
+/// ```
 pub struct GDError {
     pub kind: GDErrorKind,
     pub source: Option<ErrorSource>,
     pub backtrace: Option<backtrace::Backtrace>,
+    /// Where the error happened, if anything is known.
+    pub context: Option<Box<ErrorContext>>,
 }
 
 impl From<GDErrorKind> for GDError {
@@ -45,6 +59,7 @@ impl From<GDErrorKind> for GDError {
             kind: value,
             source: None,
             backtrace,
+            context: None,
         }
     }
 }
@@ -63,6 +78,9 @@ impl fmt::Debug for GDError {
         if let Some(source) = &self.source {
             writeln!(f, "  source={source:?}")?;
         }
+        if let Some(context) = &self.context {
+            writeln!(f, "  context={context:?}")?;
+        }
         if let Some(backtrace) = &self.backtrace {
             let bt = format!("{backtrace:#?}");
             writeln!(f, "  backtrace={}", bt.replace('\n', "\n  "))?;
@@ -84,6 +102,7 @@ impl GDError {
             kind,
             source,
             backtrace,
+            context: None,
         }
     }
 
@@ -91,6 +110,93 @@ impl GDError {
     pub fn from_error<E: Into<ErrorSource>>(kind: GDErrorKind, source: E) -> Self {
         Self::new(kind, Some(source.into()))
     }
+
+    /// Update the context with `update`, creating it if needed.
+    fn with_context(mut self, update: impl FnOnce(&mut ErrorContext)) -> Self {
+        update(self.context.get_or_insert_with(Default::default));
+        self
+    }
+
+    /// Set the address of the queried server, unless it is already set.
+    pub fn with_address(self, address: SocketAddr) -> Self {
+        self.with_context(|context| {
+            context.address.get_or_insert(address);
+        })
+    }
+
+    /// Set the protocol the server was queried with, unless it is already set.
+    pub fn with_protocol(self, protocol: Protocol) -> Self {
+        self.with_context(|context| {
+            context.protocol.get_or_insert(protocol);
+        })
+    }
+
+    /// Set the stage of the query that failed, unless it is already set (the
+    /// innermost stage being the most precise).
+    pub fn with_stage(self, stage: QueryStage) -> Self {
+        self.with_context(|context| {
+            context.stage.get_or_insert(stage);
+        })
+    }
+
+    /// Set the try of the request that failed, unless it is already set.
+    pub fn with_attempt(self, attempt: u32) -> Self {
+        self.with_context(|context| {
+            context.attempt.get_or_insert(attempt);
+        })
+    }
+
+    /// The address of the queried server, if known.
+    pub fn address(&self) -> Option<SocketAddr> { self.context.as_ref()?.address }
+
+    /// The protocol the server was queried with, if known.
+    pub fn protocol(&self) -> Option<&Protocol> { self.context.as_ref()?.protocol.as_ref() }
+
+    /// The stage of the query that failed, if known.
+    pub fn stage(&self) -> Option<QueryStage> { self.context.as_ref()?.stage }
+
+    /// The try of the request that failed (0 being the first one), if known.
+    pub fn attempt(&self) -> Option<u32> { self.context.as_ref()?.attempt }
+
+    /// Whether nothing was received in time: receiving a packet failed (which
+    /// is how timeouts surface) or the query's deadline passed.
+    ///
+    /// Failing to send (which includes HTTP error statuses) isn't a timeout.
+    pub const fn is_timeout(&self) -> bool {
+        matches!(
+            self.kind,
+            GDErrorKind::PacketReceive | GDErrorKind::DeadlineExceeded
+        )
+    }
+
+    /// Whether querying again may succeed: timeouts, and failures to bind,
+    /// connect or look the host up.
+    ///
+    /// This is broader than what [RetryPolicy](crate::RetryPolicy) retries by
+    /// default (timeouts only).
+    pub const fn is_retryable(&self) -> bool {
+        self.is_timeout()
+            || matches!(
+                self.kind,
+                GDErrorKind::SocketConnect | GDErrorKind::SocketBind | GDErrorKind::HostLookup
+            )
+    }
+
+    /// Whether the server answered, but not the way its protocol says it
+    /// should (bad, truncated or unparsable data).
+    pub const fn is_server_misbehaving(&self) -> bool {
+        matches!(
+            self.kind,
+            GDErrorKind::PacketOverflow
+                | GDErrorKind::PacketUnderflow
+                | GDErrorKind::PacketBad
+                | GDErrorKind::Decompress
+                | GDErrorKind::ProtocolFormat
+                | GDErrorKind::UnknownEnumCast
+                | GDErrorKind::JsonParse
+                | GDErrorKind::TypeParse
+        )
+    }
 }
 
 #[cfg(test)]
@@ -140,4 +246,44 @@ mod tests {
         assert_eq!(error_from_into.kind, GDErrorKind::InvalidInput);
         assert!(error_from_into.source.is_none());
     }
+
+    // Test that the innermost context is kept
+    #[test]
+    fn test_context() {
+        let address = "127.0.0.1:27015".parse().unwrap();
+
+        let error: GDError = GDErrorKind::PacketBad.into();
+        assert!(error.context.is_none());
+
+        let error = error
+            .with_stage(QueryStage::Challenge)
+            .with_attempt(1)
+            .with_stage(QueryStage::Info)
+            .with_address(address);
+        assert_eq!(error.address(), Some(address));
+        assert_eq!(error.protocol(), None);
+        assert_eq!(error.stage(), Some(QueryStage::Challenge));
+        assert_eq!(error.attempt(), Some(1));
+    }
+
+    // Test classifying errors by their kind
+    #[test]
+    fn test_classification() {
+        let timeout: GDError = GDErrorKind::PacketReceive.into();
+        assert!(timeout.is_timeout());
+        assert!(timeout.is_retryable());
+        assert!(!timeout.is_server_misbehaving());
+
+        let send: GDError = GDErrorKind::PacketSend.into();
+        assert!(!send.is_timeout());
+        assert!(!send.is_retryable());
+
+        let lookup: GDError = GDErrorKind::HostLookup.into();
+        assert!(!lookup.is_timeout());
+        assert!(lookup.is_retryable());
+
+        let bad: GDError = GDErrorKind::PacketUnderflow.into();
+        assert!(!bad.is_retryable());
+        assert!(bad.is_server_misbehaving());
+    }
 }
@@ -2,7 +2,8 @@ use crate::error::ErrorSource;
 use crate::GDError;
 
 /// All GameDig Error kinds.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum GDErrorKind {
     /// The received packet was bigger than the buffer size.
     PacketOverflow,
@@ -38,6 +39,10 @@ pub enum GDErrorKind {
     TypeParse,
     /// Couldn't find the host specified.
     HostLookup,
+    /// The query's overall deadline passed before it could complete.
+    DeadlineExceeded,
+    /// Couldn't set up or write a packet capture.
+    PacketCapture,
 }
 
 impl GDErrorKind {
@@ -1,5 +1,7 @@
 //! Every GameDig errors.
 
+/// Where an Error happened.
+pub mod context;
 /// The Error with backtrace.
 pub mod error;
 /// All defined Error kinds.
@@ -7,6 +9,7 @@ pub mod kind;
 /// `GDResult`, a shorthand of `Result<T, GDError>`.
 pub mod result;
 
+pub use context::*;
 pub use error::*;
 pub use kind::*;
 pub use result::*;
@@ -1,27 +1,30 @@
 use crate::protocols::valve::Engine;
 use crate::{
     protocols::valve::{self, game},
+    GDError,
     GDErrorKind::TypeParse,
     GDResult,
+    QueryStage,
 };
 use std::net::{IpAddr, SocketAddr};
 
 pub fn query(address: &IpAddr, port: Option<u16>) -> GDResult<game::Response> {
-    let mut valve_response = valve::query(
-        &SocketAddr::new(*address, port.unwrap_or(7780)),
-        Engine::new(489_940),
-        None,
-        None,
-    )?;
+    let address = SocketAddr::new(*address, port.unwrap_or(7780));
+    let mut valve_response = valve::query(&address, Engine::new(489_940), None, None)?;
+    let rules_error = |e: GDError| e.with_address(address).with_stage(QueryStage::Rules);
 
     if let Some(rules) = &mut valve_response.rules {
         if let Some(bat_max_players) = rules.get("bat_max_players_i") {
-            valve_response.info.players_maximum = bat_max_players.parse().map_err(|e| TypeParse.context(e))?;
+            valve_response.info.players_maximum = bat_max_players
+                .parse()
+                .map_err(|e| rules_error(TypeParse.context(e)))?;
             rules.remove("bat_max_players_i");
         }
 
         if let Some(bat_player_count) = rules.get("bat_player_count_s") {
-            valve_response.info.players_online = bat_player_count.parse().map_err(|e| TypeParse.context(e))?;
+            valve_response.info.players_online = bat_player_count
+                .parse()
+                .map_err(|e| rules_error(TypeParse.context(e)))?;
             rules.remove("bat_player_count_s");
         }
 
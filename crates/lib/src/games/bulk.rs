@@ -0,0 +1,277 @@
+//! Querying many servers at once.
+
+use std::net::SocketAddr;
+use std::sync::{mpsc, Arc, Mutex, PoisonError};
+use std::{thread, vec};
+
+use crate::games::query::query_with_timeout_and_extra_settings;
+use crate::games::types::Game;
+use crate::protocols::types::{CommonResponse, ExtraRequestSettings, TimeoutSettings};
+use crate::socket::UdpSocketReuse;
+use crate::GDResult;
+
+/// Default number of queries in flight at once.
+pub const DEFAULT_CONCURRENCY: usize = 64;
+
+/// A query to make many servers at once, with a bounded number of them in
+/// flight (and so a bounded number of open sockets) at any time.
+///
+/// Results are streamed back as soon as each query completes.
+///
+/// ```no_run
+/// use gamedig::{BulkQuery, GAMES};
+///
+/// let game = GAMES.get("teamfortress2").unwrap();
+/// let targets = vec![
+///     (game.clone(), "127.0.0.1:27015".parse().unwrap()),
+///     (game.clone(), "127.0.0.1:27016".parse().unwrap()),
+/// ];
+///
+/// for result in BulkQuery::new(targets).concurrency(16).run() {
+///     match result.result {
+///         Ok(response) => println!("{}: {:?}", result.address, response.as_json()),
+///         Err(error) => println!("{}: {}", result.address, error),
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct BulkQuery {
+    targets: Vec<(Game, SocketAddr)>,
+    concurrency: usize,
+    timeout_settings: Option<TimeoutSettings>,
+    extra_settings: Option<ExtraRequestSettings>,
+}
+
+/// The result of one of the queries of a [BulkQuery], tagged with its target.
+pub struct BulkResult {
+    /// The game the server was queried as.
+    pub game: Game,
+    /// The queried address.
+    pub address: SocketAddr,
+    /// The query's result.
+    pub result: GDResult<Box<dyn CommonResponse>>,
+}
+
+impl BulkQuery {
+    /// Create a bulk query of the targets, with the [default
+    /// concurrency](DEFAULT_CONCURRENCY) and the default timeout settings.
+    pub fn new<T: IntoIterator<Item = (Game, SocketAddr)>>(targets: T) -> Self {
+        Self {
+            targets: targets.into_iter().collect(),
+            concurrency: DEFAULT_CONCURRENCY,
+            timeout_settings: None,
+            extra_settings: None,
+        }
+    }
+
+    /// Set the maximum number of queries in flight at once (a value of 0 is
+    /// treated as 1).
+    pub const fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Set the timeout settings used for every query.
+    pub const fn timeout_settings(mut self, timeout_settings: TimeoutSettings) -> Self {
+        self.timeout_settings = Some(timeout_settings);
+        self
+    }
+
+    /// Set the extra request settings used for every query (instead of the
+    /// game's ones).
+    pub fn extra_settings(mut self, extra_settings: ExtraRequestSettings) -> Self {
+        self.extra_settings = Some(extra_settings);
+        self
+    }
+
+    /// Number of workers to use, never more than there are targets.
+    fn workers(&self) -> usize { self.concurrency.clamp(1, self.targets.len().max(1)) }
+
+    /// Start querying on a pool of worker threads, returning an iterator over
+    /// the results in the order they complete.
+    ///
+    /// Each worker reuses its UDP sockets and buffers between its queries.
+    /// Dropping the iterator stops the workers after their current query.
+    pub fn run(self) -> BulkResults {
+        let workers = self.workers();
+        let targets = Arc::new(Mutex::new(self.targets.into_iter()));
+        let (sender, receiver) = mpsc::channel();
+
+        for _ in 0 .. workers {
+            let targets = Arc::clone(&targets);
+            let sender = sender.clone();
+            let timeout_settings = self.timeout_settings;
+            let extra_settings = self.extra_settings.clone();
+
+            thread::spawn(move || {
+                let _reuse = UdpSocketReuse::enable();
+
+                while let Some((game, address)) = next_target(&targets) {
+                    let result = query_with_timeout_and_extra_settings(
+                        &game,
+                        &address.ip(),
+                        Some(address.port()),
+                        timeout_settings,
+                        extra_settings.clone(),
+                    );
+
+                    let result = BulkResult {
+                        game,
+                        address,
+                        result,
+                    };
+
+                    if sender.send(result).is_err() {
+                        // Nobody is listening anymore.
+                        break;
+                    }
+                }
+            });
+        }
+
+        BulkResults { receiver }
+    }
+
+    /// Async counterpart of [BulkQuery::run], querying on a set of tokio tasks.
+    ///
+    /// Must be called from within a tokio runtime.
+    #[cfg(feature = "async")]
+    pub fn run_async(self) -> tokio::sync::mpsc::Receiver<BulkResult> {
+        use crate::games::query::query_with_timeout_and_extra_settings_async;
+
+        let workers = self.workers();
+        let targets = Arc::new(Mutex::new(self.targets.into_iter()));
+        let (sender, receiver) = tokio::sync::mpsc::channel(workers);
+
+        for _ in 0 .. workers {
+            let targets = Arc::clone(&targets);
+            let sender = sender.clone();
+            let timeout_settings = self.timeout_settings;
+            let extra_settings = self.extra_settings.clone();
+
+            tokio::spawn(async move {
+                while let Some((game, address)) = next_target(&targets) {
+                    let result = query_with_timeout_and_extra_settings_async(
+                        &game,
+                        &address.ip(),
+                        Some(address.port()),
+                        timeout_settings,
+                        extra_settings.clone(),
+                    )
+                    .await;
+
+                    let result = BulkResult {
+                        game,
+                        address,
+                        result,
+                    };
+
+                    if sender.send(result).await.is_err() {
+                        // Nobody is listening anymore.
+                        break;
+                    }
+                }
+            });
+        }
+
+        receiver
+    }
+}
+
+fn next_target(targets: &Mutex<vec::IntoIter<(Game, SocketAddr)>>) -> Option<(Game, SocketAddr)> {
+    // Taking the next target can't panic, so the lock can't be poisoned halfway.
+    targets
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .next()
+}
+
+/// Iterator over the results of a running [BulkQuery], see [BulkQuery::run].
+pub struct BulkResults {
+    receiver: mpsc::Receiver<BulkResult>,
+}
+
+impl Iterator for BulkResults {
+    type Item = BulkResult;
+
+    fn next(&mut self) -> Option<Self::Item> { self.receiver.recv().ok() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::types::Protocol;
+    use crate::protocols::valve::Engine;
+    use std::time::Duration;
+
+    fn targets(count: usize) -> (Vec<std::net::UdpSocket>, Vec<(Game, SocketAddr)>) {
+        let game = Game {
+            name: "Test",
+            default_port: 27015,
+            protocol: Protocol::Valve(Engine::Source(None)),
+            request_settings: ExtraRequestSettings::default(),
+            query_ports: &[],
+        };
+
+        // Servers that never answer
+        let servers: Vec<_> = (0 .. count)
+            .map(|_| std::net::UdpSocket::bind("127.0.0.1:0").unwrap())
+            .collect();
+        let targets = servers
+            .iter()
+            .map(|server| (game.clone(), server.local_addr().unwrap()))
+            .collect();
+
+        (servers, targets)
+    }
+
+    fn timeout_settings() -> TimeoutSettings {
+        let timeout = Some(Duration::from_millis(50));
+        TimeoutSettings::new(timeout, timeout, timeout, 0).unwrap()
+    }
+
+    #[test]
+    fn every_target_gets_a_result() {
+        let (_servers, targets) = targets(5);
+
+        let mut addresses: Vec<_> = BulkQuery::new(targets.clone())
+            .concurrency(2)
+            .timeout_settings(timeout_settings())
+            .run()
+            .map(|result| {
+                assert!(result.result.is_err());
+                result.address
+            })
+            .collect();
+        addresses.sort();
+
+        let mut expected: Vec<_> = targets.into_iter().map(|(_, address)| address).collect();
+        expected.sort();
+
+        assert_eq!(addresses, expected);
+    }
+
+    #[test]
+    fn no_targets() {
+        assert_eq!(BulkQuery::new(Vec::new()).run().count(), 0);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn every_target_gets_a_result_async() {
+        let (_servers, targets) = targets(3);
+
+        let mut receiver = BulkQuery::new(targets)
+            .concurrency(2)
+            .timeout_settings(timeout_settings())
+            .run_async();
+
+        let mut count = 0;
+        while let Some(result) = receiver.recv().await {
+            assert!(result.result.is_err());
+            count += 1;
+        }
+
+        assert_eq!(count, 3);
+    }
+}
@@ -0,0 +1,599 @@
+//! Caching the results of queries.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::games::query::query_with_timeout_and_extra_settings;
+use crate::games::types::Game;
+use crate::protocols::types::{ExtraRequestSettings, GenericResponseOwned, TimeoutSettings};
+use crate::{GDError, GDResult};
+
+/// A cache of query results, for when the same servers are queried over and
+/// over (e.g. by the visitors of a web page).
+///
+/// Results are kept for a time to live, keyed by the game, the address and the
+/// settings of the query. Failures are cached too (for the [failure time to
+/// live](QueryCache::failure_ttl)), so an offline server isn't queried again
+/// on every request. Concurrent identical queries are coalesced: only one of
+/// them queries the server, the others wait for and share its result.
+///
+/// At most [max_entries](QueryCache::max_entries) results are kept, the least
+/// recently used ones are forgotten first, as are the expired ones whenever a
+/// query isn't cached.
+///
+/// Responses are cached whole (as [GenericResponseOwned], see
+/// [GenericResponseOwned::as_common] for their common fields), errors keep
+/// their kind and context but their source is turned into its message.
+///
+/// ```no_run
+/// use gamedig::{QueryCache, GAMES};
+/// use std::time::Duration;
+///
+/// let cache = QueryCache::new(Duration::from_secs(30)).failure_ttl(Duration::from_secs(5));
+/// let game = GAMES.get("teamfortress2").unwrap();
+///
+/// // Only the first one queries the server.
+/// let first = cache.query(game, &"127.0.0.1".parse().unwrap(), None);
+/// let second = cache.query(game, &"127.0.0.1".parse().unwrap(), None);
+/// ```
+#[derive(Debug)]
+pub struct QueryCache {
+    ttl: Duration,
+    failure_ttl: Duration,
+    max_entries: usize,
+    entries: Mutex<HashMap<Key, Entry>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    game: Game,
+    address: SocketAddr,
+    timeout_settings: Option<TimeoutSettings>,
+    extra_settings: Option<ExtraRequestSettings>,
+}
+
+type CachedResult = GDResult<GenericResponseOwned>;
+
+#[derive(Debug)]
+enum Entry {
+    Ready {
+        result: Box<CachedResult>,
+        expires: Instant,
+        last_used: Instant,
+    },
+    InFlight(Arc<Flight>),
+}
+
+/// A query in flight, that the identical queries wait for.
+#[derive(Debug, Default)]
+struct Flight {
+    state: Mutex<FlightState>,
+    settled: Condvar,
+    #[cfg(feature = "async")]
+    settled_async: tokio::sync::Notify,
+}
+
+#[derive(Debug, Default)]
+enum FlightState {
+    #[default]
+    Pending,
+    Done(Box<CachedResult>),
+    /// The query panicked or was cancelled, the waiters have to query again.
+    Abandoned,
+}
+
+enum Lookup<'a> {
+    Hit(CachedResult),
+    Wait(Arc<Flight>),
+    Lead(Leader<'a>),
+}
+
+impl QueryCache {
+    /// The default [maximum amount of cached results](QueryCache::max_entries).
+    pub const DEFAULT_MAX_ENTRIES: usize = 1024;
+
+    /// Create a cache keeping results (successful or not) for a time to live.
+    ///
+    /// A time to live of zero caches nothing, but still coalesces concurrent
+    /// identical queries.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            failure_ttl: ttl,
+            max_entries: Self::DEFAULT_MAX_ENTRIES,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Set the time to live of the failures (zero to not cache them).
+    pub const fn failure_ttl(mut self, failure_ttl: Duration) -> Self {
+        self.failure_ttl = failure_ttl;
+        self
+    }
+
+    /// Set the maximum amount of cached results, [DEFAULT_MAX_ENTRIES] by
+    /// default.
+    ///
+    /// [DEFAULT_MAX_ENTRIES]: QueryCache::DEFAULT_MAX_ENTRIES
+    pub const fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Query a server given a game definition, or get its cached result.
+    pub fn query(&self, game: &Game, address: &IpAddr, port: Option<u16>) -> CachedResult {
+        self.query_with_timeout_and_extra_settings(game, address, port, None, None)
+    }
+
+    /// Query a server given a game definition, timeout settings, and extra
+    /// settings (see [query_with_timeout_and_extra_settings]), or get its
+    /// cached result.
+    pub fn query_with_timeout_and_extra_settings(
+        &self,
+        game: &Game,
+        address: &IpAddr,
+        port: Option<u16>,
+        timeout_settings: Option<TimeoutSettings>,
+        extra_settings: Option<ExtraRequestSettings>,
+    ) -> CachedResult {
+        let key = Key::new(game, address, port, timeout_settings, &extra_settings);
+
+        loop {
+            let flight = match self.lookup(&key) {
+                Lookup::Hit(result) => return result,
+                Lookup::Wait(flight) => flight,
+                Lookup::Lead(leader) => {
+                    let result =
+                        query_with_timeout_and_extra_settings(game, address, port, timeout_settings, extra_settings)
+                            .map(GenericResponseOwned::from);
+                    return leader.finish(result);
+                }
+            };
+
+            if let Some(result) = flight.wait() {
+                return result;
+            }
+        }
+    }
+
+    /// Async counterpart of [QueryCache::query].
+    #[cfg(feature = "async")]
+    pub async fn query_async(&self, game: &Game, address: &IpAddr, port: Option<u16>) -> CachedResult {
+        self.query_with_timeout_and_extra_settings_async(game, address, port, None, None)
+            .await
+    }
+
+    /// Async counterpart of
+    /// [QueryCache::query_with_timeout_and_extra_settings].
+    #[cfg(feature = "async")]
+    pub async fn query_with_timeout_and_extra_settings_async(
+        &self,
+        game: &Game,
+        address: &IpAddr,
+        port: Option<u16>,
+        timeout_settings: Option<TimeoutSettings>,
+        extra_settings: Option<ExtraRequestSettings>,
+    ) -> CachedResult {
+        use crate::games::query::query_with_timeout_and_extra_settings_async;
+
+        let key = Key::new(game, address, port, timeout_settings, &extra_settings);
+
+        loop {
+            let flight = match self.lookup(&key) {
+                Lookup::Hit(result) => return result,
+                Lookup::Wait(flight) => flight,
+                Lookup::Lead(leader) => {
+                    let result = query_with_timeout_and_extra_settings_async(
+                        game,
+                        address,
+                        port,
+                        timeout_settings,
+                        extra_settings,
+                    )
+                    .await
+                    .map(GenericResponseOwned::from);
+                    return leader.finish(result);
+                }
+            };
+
+            if let Some(result) = flight.wait_async().await {
+                return result;
+            }
+        }
+    }
+
+    /// Forget all the cached results (the queries in flight still complete).
+    pub fn clear(&self) {
+        self.entries()
+            .retain(|_, entry| matches!(entry, Entry::InFlight(_)));
+    }
+
+    fn entries(&self) -> MutexGuard<'_, HashMap<Key, Entry>> {
+        // The map is never left halfway updated, so a poisoned lock is fine.
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Get the cached result of a query, the query in flight to wait for, or
+    /// become the one querying.
+    fn lookup(&self, key: &Key) -> Lookup<'_> {
+        let now = Instant::now();
+        let mut entries = self.entries();
+        match entries.get_mut(key) {
+            Some(Entry::Ready {
+                result,
+                expires,
+                last_used,
+            }) if *expires > now => {
+                *last_used = now;
+                return Lookup::Hit(copy(result));
+            }
+            Some(Entry::InFlight(flight)) => return Lookup::Wait(Arc::clone(flight)),
+            _ => {}
+        }
+
+        entries.retain(|_, entry| !matches!(entry, Entry::Ready { expires, .. } if *expires <= now));
+
+        let flight = Arc::new(Flight::default());
+        entries.insert(key.clone(), Entry::InFlight(Arc::clone(&flight)));
+
+        Lookup::Lead(Leader {
+            cache: self,
+            key: key.clone(),
+            flight,
+            finished: false,
+        })
+    }
+}
+
+impl Key {
+    fn new(
+        game: &Game,
+        address: &IpAddr,
+        port: Option<u16>,
+        timeout_settings: Option<TimeoutSettings>,
+        extra_settings: &Option<ExtraRequestSettings>,
+    ) -> Self {
+        Self {
+            game: game.clone(),
+            address: SocketAddr::new(*address, port.unwrap_or(game.default_port)),
+            timeout_settings,
+            extra_settings: extra_settings.clone(),
+        }
+    }
+}
+
+impl Flight {
+    fn settle(&self, state: FlightState) {
+        *self.state.lock().unwrap_or_else(PoisonError::into_inner) = state;
+        self.settled.notify_all();
+        #[cfg(feature = "async")]
+        self.settled_async.notify_waiters();
+    }
+
+    /// Wait for the query's result, `None` if it was abandoned.
+    fn wait(&self) -> Option<CachedResult> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        loop {
+            match &*state {
+                FlightState::Pending => {
+                    state = self
+                        .settled
+                        .wait(state)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+                FlightState::Done(result) => return Some(copy(result)),
+                FlightState::Abandoned => return None,
+            }
+        }
+    }
+
+    /// Async counterpart of [Flight::wait].
+    #[cfg(feature = "async")]
+    async fn wait_async(&self) -> Option<CachedResult> {
+        loop {
+            let settled = self.settled_async.notified();
+            tokio::pin!(settled);
+            // Register before looking at the state, to not miss a settlement in between.
+            settled.as_mut().enable();
+
+            match &*self.state.lock().unwrap_or_else(PoisonError::into_inner) {
+                FlightState::Pending => {}
+                FlightState::Done(result) => return Some(copy(result)),
+                FlightState::Abandoned => return None,
+            }
+
+            settled.await;
+        }
+    }
+}
+
+/// The one making a query that others may be waiting for, abandons the query
+/// if dropped before [finishing](Leader::finish) it.
+struct Leader<'a> {
+    cache: &'a QueryCache,
+    key: Key,
+    flight: Arc<Flight>,
+    finished: bool,
+}
+
+impl Leader<'_> {
+    /// Cache the query's result and hand it to the waiters.
+    fn finish(mut self, result: CachedResult) -> CachedResult {
+        let ttl = match result {
+            Ok(_) => self.cache.ttl,
+            Err(_) => self.cache.failure_ttl,
+        };
+
+        let now = Instant::now();
+        let mut entries = self.cache.entries();
+        if ttl.is_zero() || self.cache.max_entries == 0 {
+            entries.remove(&self.key);
+        } else {
+            let entry = Entry::Ready {
+                result: Box::new(copy(&result)),
+                expires: now + ttl,
+                last_used: now,
+            };
+            entries.insert(self.key.clone(), entry);
+            evict(&mut entries, self.cache.max_entries);
+        }
+        drop(entries);
+
+        self.flight
+            .settle(FlightState::Done(Box::new(copy(&result))));
+        self.finished = true;
+
+        result
+    }
+}
+
+impl Drop for Leader<'_> {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+
+        let mut entries = self.cache.entries();
+        if matches!(entries.get(&self.key), Some(Entry::InFlight(flight)) if Arc::ptr_eq(flight, &self.flight)) {
+            entries.remove(&self.key);
+        }
+        drop(entries);
+
+        self.flight.settle(FlightState::Abandoned);
+    }
+}
+
+/// Forget the least recently used results beyond `max_entries`.
+fn evict(entries: &mut HashMap<Key, Entry>, max_entries: usize) {
+    let ready = entries
+        .values()
+        .filter(|entry| matches!(entry, Entry::Ready { .. }))
+        .count();
+
+    for _ in max_entries .. ready {
+        let least_recently_used = entries
+            .iter()
+            .filter_map(|(key, entry)| {
+                match entry {
+                    Entry::Ready { last_used, .. } => Some((*last_used, key)),
+                    Entry::InFlight(_) => None,
+                }
+            })
+            .min_by_key(|(last_used, _)| *last_used)
+            .map(|(_, key)| key.clone());
+
+        if let Some(key) = least_recently_used {
+            entries.remove(&key);
+        }
+    }
+}
+
+/// Copy a cached result, the source of an error is kept as its message.
+fn copy(result: &CachedResult) -> CachedResult {
+    match result {
+        Ok(response) => Ok(response.clone()),
+        Err(error) => {
+            Err(GDError {
+                kind: error.kind.clone(),
+                source: error
+                    .source
+                    .as_ref()
+                    .map(|source| source.to_string().into()),
+                backtrace: None,
+                context: error.context.clone(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::quake::QuakeVersion;
+    use crate::protocols::types::Protocol;
+    use crate::GDErrorKind::PacketReceive;
+
+    use gamedig_fake_servers::{quake::Quake, Fake, FakeServer, Script};
+    use std::thread;
+
+    fn game() -> Game {
+        Game {
+            name: "Test",
+            default_port: 27500,
+            protocol: Protocol::Quake(QuakeVersion::One),
+            request_settings: ExtraRequestSettings::default(),
+            query_ports: &[],
+        }
+    }
+
+    /// A Quake 1 server answering (if it should) after a delay.
+    fn server(answers: bool, delay: Duration) -> FakeServer {
+        let script = if answers {
+            Quake::one().script().delay(delay)
+        } else {
+            Script::new()
+        };
+        FakeServer::udp(script).unwrap()
+    }
+
+    fn timeout_settings() -> Option<TimeoutSettings> {
+        let timeout = Some(Duration::from_millis(200));
+        Some(TimeoutSettings::new(timeout, timeout, timeout, 0).unwrap())
+    }
+
+    fn query(cache: &QueryCache, address: SocketAddr) -> CachedResult {
+        cache.query_with_timeout_and_extra_settings(
+            &game(),
+            &address.ip(),
+            Some(address.port()),
+            timeout_settings(),
+            None,
+        )
+    }
+
+    #[test]
+    fn caches_responses() {
+        let server = server(true, Duration::ZERO);
+        let address = server.address();
+        let cache = QueryCache::new(Duration::from_secs(60));
+
+        let first = query(&cache, address).unwrap();
+        let second = query(&cache, address).unwrap();
+
+        assert_eq!(first.as_common().name(), Some("Fake server"));
+        assert_eq!(first, second);
+        assert_eq!(server.requests().len(), 1);
+
+        cache.clear();
+        query(&cache, address).unwrap();
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let first = server(true, Duration::ZERO);
+        let second = server(true, Duration::ZERO);
+        let cache = QueryCache::new(Duration::from_secs(60)).max_entries(1);
+
+        query(&cache, first.address()).unwrap();
+        query(&cache, first.address()).unwrap();
+        assert_eq!(first.requests().len(), 1);
+
+        // Only the second server's response is kept.
+        query(&cache, second.address()).unwrap();
+        query(&cache, first.address()).unwrap();
+        query(&cache, first.address()).unwrap();
+        assert_eq!(first.requests().len(), 2);
+        assert_eq!(second.requests().len(), 1);
+        assert_eq!(cache.entries().len(), 1);
+    }
+
+    #[test]
+    fn expired_responses_are_queried_again() {
+        let server = server(true, Duration::ZERO);
+        let address = server.address();
+        let cache = QueryCache::new(Duration::ZERO);
+
+        query(&cache, address).unwrap();
+        query(&cache, address).unwrap();
+
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn caches_failures() {
+        let server = server(false, Duration::ZERO);
+        let address = server.address();
+        let cache = QueryCache::new(Duration::from_secs(60));
+
+        let first = query(&cache, address).err().unwrap();
+        let started = Instant::now();
+        let second = query(&cache, address).err().unwrap();
+
+        assert_eq!(first.kind, PacketReceive);
+        assert_eq!(second.kind, PacketReceive);
+        assert_eq!(second.context, first.context);
+        assert!(started.elapsed() < Duration::from_millis(100));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn failures_are_not_cached_without_failure_ttl() {
+        let server = server(false, Duration::ZERO);
+        let address = server.address();
+        let cache = QueryCache::new(Duration::from_secs(60)).failure_ttl(Duration::ZERO);
+
+        assert!(query(&cache, address).is_err());
+        assert!(query(&cache, address).is_err());
+
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn coalesces_concurrent_queries() {
+        let server = server(true, Duration::from_millis(100));
+        let address = server.address();
+        let cache = QueryCache::new(Duration::ZERO);
+
+        thread::scope(|scope| {
+            let queries: Vec<_> = (0 .. 4)
+                .map(|_| scope.spawn(|| query(&cache, address)))
+                .collect();
+
+            for query in queries {
+                assert!(query.join().unwrap().is_ok());
+            }
+        });
+
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn coalesces_concurrent_queries_async() {
+        let server = server(true, Duration::from_millis(100));
+        let address = server.address();
+        let cache = QueryCache::new(Duration::ZERO);
+        let (game, ip) = (game(), address.ip());
+
+        let query = || {
+            cache.query_with_timeout_and_extra_settings_async(
+                &game,
+                &ip,
+                Some(address.port()),
+                timeout_settings(),
+                None,
+            )
+        };
+        let (first, second, third) = tokio::join!(query(), query(), query());
+
+        assert!(first.is_ok() && second.is_ok() && third.is_ok());
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn cancelled_query_is_abandoned() {
+        let server = server(true, Duration::from_millis(100));
+        let address = server.address();
+        let cache = QueryCache::new(Duration::from_secs(60));
+
+        let (game, ip) = (game(), address.ip());
+        let leader = cache.query_async(&game, &ip, Some(address.port()));
+        let waiter = cache.query_async(&game, &ip, Some(address.port()));
+        tokio::pin!(waiter);
+
+        // The leader is dropped while the waiter waits for it, so the waiter
+        // queries again.
+        tokio::select! {
+            biased;
+            _ = leader => panic!("the leader should be cancelled"),
+            _ = &mut waiter => panic!("the waiter can't finish before the leader"),
+            () = tokio::time::sleep(Duration::from_millis(20)) => {}
+        }
+        assert!(waiter.await.is_ok());
+        assert_eq!(server.requests().len(), 2);
+    }
+}
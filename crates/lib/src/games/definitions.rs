@@ -2,7 +2,7 @@
 
 use crate::games::minecraft::types::{LegacyGroup, Server};
 use crate::protocols::{gamespy::GameSpyVersion, quake::QuakeVersion, valve::Engine, Protocol};
-use crate::Game;
+use crate::{Game, QueryPort};
 
 use crate::protocols::types::{GatherToggle, ProprietaryProtocol};
 use crate::protocols::valve::GatheringSettings;
@@ -18,12 +18,33 @@ macro_rules! game {
         )
     };
 
+    ($name: literal, $default_port: expr, $protocol: expr, query_ports: $query_ports: expr) => {
+        game!(
+            $name,
+            $default_port,
+            $protocol,
+            GatheringSettings::default().into_extra(),
+            query_ports: $query_ports
+        )
+    };
+
     ($name: literal, $default_port: expr, $protocol: expr, $extra_request_settings: expr) => {
+        game!(
+            $name,
+            $default_port,
+            $protocol,
+            $extra_request_settings,
+            query_ports: &[]
+        )
+    };
+
+    ($name: literal, $default_port: expr, $protocol: expr, $extra_request_settings: expr, query_ports: $query_ports: expr) => {
         Game {
             name: $name,
             default_port: $default_port,
             protocol: $protocol,
             request_settings: $extra_request_settings,
+            query_ports: $query_ports,
         }
     };
 }
@@ -47,8 +68,8 @@ pub static GAMES: Map<&'static str, Game> = phf_map! {
     "abioticfactor" => game!("Abiotic Factor", 27015, Protocol::Valve(Engine::new(427_410))),
     "alienswarm" => game!("Alien Swarm", 27015, Protocol::Valve(Engine::new(630))),
     "aoc" => game!("Age of Chivalry", 27015, Protocol::Valve(Engine::new(17510))),
-    "a2oa" => game!("ARMA 2: Operation Arrowhead", 2304, Protocol::Valve(Engine::new(33930))),
-    "arma3" => game!("ARMA 3", 2303, Protocol::Valve(Engine::new(107_410))),
+    "a2oa" => game!("ARMA 2: Operation Arrowhead", 2304, Protocol::Valve(Engine::new(33930)), query_ports: &[QueryPort::Offset(2)]),
+    "arma3" => game!("ARMA 3", 2303, Protocol::Valve(Engine::new(107_410)), query_ports: &[QueryPort::Offset(1)]),
     "ase" => game!("ARK: Survival Evolved", 27015, Protocol::Valve(Engine::new(346_110))),
     "asrd" => game!("Alien Swarm: Reactive Drop", 2304, Protocol::Valve(Engine::new(563_560))),
     "armareforger" => game!("Arma Reforger", 17777, Protocol::Valve(Engine::new(1_874_880)), GatheringSettings {
@@ -124,7 +145,7 @@ pub static GAMES: Map<&'static str, Game> = phf_map! {
     "sof2" => game!("Soldier of Fortune 2", 20100, Protocol::Quake(QuakeVersion::Three)),
     "soulmask" => game!("Soulmask", 27015, Protocol::Valve(Engine::new(2_646_460))),
     "serioussam" => game!("Serious Sam", 25601, Protocol::Gamespy(GameSpyVersion::One)),
-    "squad" => game!("Squad", 27165, Protocol::Valve(Engine::new(393_380))),
+    "squad" => game!("Squad", 27165, Protocol::Valve(Engine::new(393_380)), query_ports: &[QueryPort::Fixed(27165), QueryPort::Offset(19378)]),
     "starbound" => game!("Starbound", 21025, Protocol::Valve(Engine::new(211_820)), GatheringSettings {
         players: GatherToggle::Enforce,
         rules: GatherToggle::Enforce,
@@ -135,7 +156,7 @@ pub static GAMES: Map<&'static str, Game> = phf_map! {
     "teamfortress2" => game!("Team Fortress 2", 27015, Protocol::Valve(Engine::new(440))),
     "tfc" => game!("Team Fortress Classic", 27015, Protocol::Valve(Engine::new_gold_src(false))),
     "theship" => game!("The Ship", 27015, Protocol::PROPRIETARY(ProprietaryProtocol::TheShip)),
-    "unturned" => game!("Unturned", 27015, Protocol::Valve(Engine::new(304_930))),
+    "unturned" => game!("Unturned", 27015, Protocol::Valve(Engine::new(304_930)), query_ports: &[QueryPort::Offset(1)]),
     "unrealtournament" => game!("Unreal Tournament", 7778, Protocol::Gamespy(GameSpyVersion::One)),
     "valheim" => game!("Valheim", 2457, Protocol::Valve(Engine::new(892_970)), GatheringSettings {
         players: GatherToggle::Enforce,
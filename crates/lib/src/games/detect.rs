@@ -0,0 +1,446 @@
+//! Finding out what is running on an address, or where a game answers
+//! queries.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::thread;
+
+use crate::games::minecraft::{LegacyGroup, Server};
+use crate::games::query::query_with_timeout_and_extra_settings;
+use crate::games::types::Game;
+use crate::protocols::gamespy::GameSpyVersion;
+use crate::protocols::quake::QuakeVersion;
+use crate::protocols::types::GenericResponse;
+use crate::protocols::types::{CommonResponse, ProprietaryProtocol, Protocol, TimeoutSettings};
+use crate::protocols::valve::Engine;
+use crate::transport;
+use crate::GDErrorKind::{AutoQuery, BadGame, PacketReceive};
+use crate::GDResult;
+
+#[cfg(feature = "game_defs")]
+use crate::games::GAMES;
+
+/// The protocols probed by [detect], in the order detections are reported.
+pub const PROBES: &[Protocol] = &[
+    Protocol::Valve(Engine::Source(None)),
+    Protocol::Gamespy(GameSpyVersion::One),
+    Protocol::Gamespy(GameSpyVersion::Two),
+    Protocol::Gamespy(GameSpyVersion::Three),
+    Protocol::Quake(QuakeVersion::One),
+    Protocol::Quake(QuakeVersion::Two),
+    Protocol::Quake(QuakeVersion::Three),
+    Protocol::Unreal2,
+    Protocol::PROPRIETARY(ProprietaryProtocol::Minecraft(Some(Server::Java))),
+    Protocol::PROPRIETARY(ProprietaryProtocol::Minecraft(Some(Server::Bedrock))),
+    Protocol::PROPRIETARY(ProprietaryProtocol::Minecraft(Some(Server::Legacy(
+        LegacyGroup::V1_6,
+    )))),
+    Protocol::PROPRIETARY(ProprietaryProtocol::Minecraft(Some(Server::Legacy(
+        LegacyGroup::V1_4,
+    )))),
+    Protocol::PROPRIETARY(ProprietaryProtocol::Minecraft(Some(Server::Legacy(
+        LegacyGroup::VB1_8,
+    )))),
+    Protocol::PROPRIETARY(ProprietaryProtocol::Savage2),
+    Protocol::PROPRIETARY(ProprietaryProtocol::Mindustry),
+    Protocol::PROPRIETARY(ProprietaryProtocol::FFOW),
+    Protocol::PROPRIETARY(ProprietaryProtocol::JC2M),
+];
+
+/// A protocol that answered on the probed address.
+pub struct Detection {
+    /// The protocol that answered.
+    pub protocol: Protocol,
+    /// The identifier (in [GAMES]) of the game definition that best matches
+    /// the answer: for Valve the one of the returned app id, for other
+    /// protocols the one using the protocol if it is the only one.
+    #[cfg(feature = "game_defs")]
+    pub game_id: Option<&'static str>,
+    /// The protocol's response.
+    pub response: Box<dyn CommonResponse>,
+}
+
+#[cfg(feature = "game_defs")]
+impl Detection {
+    /// The game definition that best matches the answer, see
+    /// [Detection::game_id].
+    pub fn game(&self) -> Option<&'static Game> { self.game_id.and_then(|id| GAMES.get(id)) }
+}
+
+/// Probe all the [PROBES] protocols on an address at once, returning the ones
+/// that answered (in the order of [PROBES]).
+///
+/// Fails with [AutoQuery] if none of them did.
+///
+/// ```no_run
+/// use gamedig::games::detect;
+///
+/// let address = "127.0.0.1:27015".parse().unwrap();
+/// for detection in detect(&address).unwrap() {
+///     println!("{:?} ({:?})", detection.protocol, detection.game_id);
+/// }
+/// ```
+pub fn detect(address: &SocketAddr) -> GDResult<Vec<Detection>> { detect_with_timeout(address, None) }
+
+/// Probe all the [PROBES] protocols on an address at once given timeout
+/// settings, see [detect].
+///
+/// Each probe runs on its own thread, using the transport factory of the
+/// calling thread.
+pub fn detect_with_timeout(
+    address: &SocketAddr,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<Vec<Detection>> {
+    let responses = in_parallel(PROBES, |protocol| {
+        query_with_timeout_and_extra_settings(
+            &probe_game(protocol, address),
+            &address.ip(),
+            Some(address.port()),
+            timeout_settings,
+            None,
+        )
+        .ok()
+    });
+
+    detections(
+        address,
+        responses.into_iter().map(Option::flatten).collect(),
+    )
+}
+
+/// Async counterpart of [detect].
+#[cfg(feature = "async")]
+pub async fn detect_async(address: &SocketAddr) -> GDResult<Vec<Detection>> {
+    detect_with_timeout_async(address, None).await
+}
+
+/// Async counterpart of [detect_with_timeout], each probe runs in its own
+/// tokio task.
+///
+/// Must be called from within a tokio runtime.
+#[cfg(feature = "async")]
+pub async fn detect_with_timeout_async(
+    address: &SocketAddr,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<Vec<Detection>> {
+    use crate::games::query::query_with_timeout_and_extra_settings_async;
+
+    let mut probes = tokio::task::JoinSet::new();
+    for (index, protocol) in PROBES.iter().enumerate() {
+        let game = probe_game(protocol, address);
+        let address = *address;
+
+        probes.spawn(async move {
+            let response = query_with_timeout_and_extra_settings_async(
+                &game,
+                &address.ip(),
+                Some(address.port()),
+                timeout_settings,
+                None,
+            )
+            .await;
+
+            (index, response.ok())
+        });
+    }
+
+    let mut responses: Vec<_> = PROBES.iter().map(|_| None).collect();
+    while let Some(probe) = probes.join_next().await {
+        if let Ok((index, response)) = probe {
+            responses[index] = response;
+        }
+    }
+
+    detections(address, responses)
+}
+
+/// Run `probe` on every item at once, each on its own thread using the
+/// transport factory of the calling thread, returning the results in the
+/// order of the items (`None` for the probes that panicked).
+fn in_parallel<T: Sync, R: Send>(items: &[T], probe: impl Fn(&T) -> R + Sync) -> Vec<Option<R>> {
+    let factory = transport::current_factory();
+
+    thread::scope(|scope| {
+        let probes: Vec<_> = items
+            .iter()
+            .map(|item| {
+                let factory = Arc::clone(&factory);
+                let probe = &probe;
+                scope.spawn(move || transport::with_factory(factory, || probe(item)))
+            })
+            .collect();
+
+        probes.into_iter().map(|probe| probe.join().ok()).collect()
+    })
+}
+
+/// A definition to query `address` with `protocol` (and its default request
+/// settings).
+fn probe_game(protocol: &Protocol, address: &SocketAddr) -> Game {
+    Game {
+        name: "",
+        default_port: address.port(),
+        protocol: protocol.clone(),
+        request_settings: Default::default(),
+        query_ports: &[],
+    }
+}
+
+/// The detections of the probes' responses (in the order of [PROBES]).
+fn detections(
+    #[cfg_attr(not(feature = "game_defs"), allow(unused_variables))] address: &SocketAddr,
+    responses: Vec<Option<Box<dyn CommonResponse>>>,
+) -> GDResult<Vec<Detection>> {
+    let detections: Vec<_> = PROBES
+        .iter()
+        .zip(responses)
+        .filter_map(|(protocol, response)| {
+            let response = response?;
+
+            Some(Detection {
+                #[cfg(feature = "game_defs")]
+                game_id: best_game(protocol, response.as_ref(), address.port()),
+                protocol: protocol.clone(),
+                response,
+            })
+        })
+        .collect();
+
+    match detections.is_empty() {
+        true => Err(AutoQuery.context("No protocol answered")),
+        false => Ok(detections),
+    }
+}
+
+/// The identifier of the game definition that best matches a probe's answer.
+#[cfg(feature = "game_defs")]
+fn best_game(protocol: &Protocol, response: &dyn CommonResponse, port: u16) -> Option<&'static str> {
+    if let GenericResponse::Valve(response) = response.as_original() {
+        return best_valve_game(response.info.appid, port);
+    }
+
+    let mut games = GAMES
+        .entries()
+        .filter(|(_, game)| game.protocol == *protocol);
+    match (games.next(), games.next()) {
+        (Some((id, _)), None) => Some(id),
+        _ => None,
+    }
+}
+
+/// The identifier of the Valve game definition of an app id, preferring the
+/// ones whose main app id it is, then the ones whose default port is the
+/// queried one.
+#[cfg(feature = "game_defs")]
+fn best_valve_game(appid: u32, port: u16) -> Option<&'static str> {
+    GAMES
+        .entries()
+        .filter_map(|(id, game)| {
+            let Protocol::Valve(Engine::Source(Some((main, dedicated)))) = game.protocol else {
+                return None;
+            };
+
+            let is_main = main == appid;
+            (is_main || dedicated == Some(appid)).then_some((!is_main, game.default_port != port, *id))
+        })
+        .min()
+        .map(|(_, _, id)| id)
+}
+
+/// A query port found by [discover_query_port].
+pub struct DiscoveredQueryPort {
+    /// The port the game answered queries on.
+    pub port: u16,
+    /// The game's response on it.
+    pub response: Box<dyn CommonResponse>,
+}
+
+/// Find the port a game answers queries on given the address players connect
+/// to, probing all of its [candidates](Game::query_port_candidates) at once.
+///
+/// The first candidate (in order) answering as the game is returned, for Valve
+/// games the answer's app id has to be one of the [Engine]'s.
+/// Fails with [BadGame] if the candidates that answered did so as other games,
+/// otherwise with the error of the first candidate.
+///
+/// ```no_run
+/// use gamedig::games::{discover_query_port, GAMES};
+///
+/// let game = GAMES.get("arma3").unwrap();
+/// let address = "127.0.0.1:2302".parse().unwrap();
+/// let discovered = discover_query_port(game, &address, None).unwrap();
+/// println!("Queries are answered on port {}", discovered.port);
+/// ```
+pub fn discover_query_port(
+    game: &Game,
+    address: &SocketAddr,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<DiscoveredQueryPort> {
+    let candidates = game.query_port_candidates(address.port());
+    let results = in_parallel(&candidates, |port| {
+        query_with_timeout_and_extra_settings(game, &address.ip(), Some(*port), timeout_settings, None)
+    });
+
+    discovered(game, &candidates, results)
+}
+
+/// Async counterpart of [discover_query_port], each candidate is probed in its
+/// own tokio task.
+///
+/// Must be called from within a tokio runtime.
+#[cfg(feature = "async")]
+pub async fn discover_query_port_async(
+    game: &Game,
+    address: &SocketAddr,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<DiscoveredQueryPort> {
+    use crate::games::query::query_with_timeout_and_extra_settings_async;
+
+    let candidates = game.query_port_candidates(address.port());
+
+    let mut probes = tokio::task::JoinSet::new();
+    for (index, port) in candidates.iter().copied().enumerate() {
+        let game = game.clone();
+        let ip = address.ip();
+
+        probes.spawn(async move {
+            let response =
+                query_with_timeout_and_extra_settings_async(&game, &ip, Some(port), timeout_settings, None).await;
+
+            (index, response)
+        });
+    }
+
+    let mut results: Vec<_> = candidates.iter().map(|_| None).collect();
+    while let Some(probe) = probes.join_next().await {
+        if let Ok((index, result)) = probe {
+            results[index] = Some(result);
+        }
+    }
+
+    discovered(game, &candidates, results)
+}
+
+/// The query port found given the results of probing the candidates (`None`
+/// for the probes that didn't complete).
+fn discovered(
+    game: &Game,
+    candidates: &[u16],
+    results: Vec<Option<GDResult<Box<dyn CommonResponse>>>>,
+) -> GDResult<DiscoveredQueryPort> {
+    let mut first_error = None;
+    let mut other_game = None;
+
+    for (port, result) in candidates.iter().copied().zip(results) {
+        match result.unwrap_or_else(|| Err(PacketReceive.context("The probe didn't complete"))) {
+            Ok(response) if answers_as(game, response.as_ref()) => return Ok(DiscoveredQueryPort { port, response }),
+            Ok(_) => _ = other_game.get_or_insert(port),
+            Err(error) if error.kind == BadGame => _ = other_game.get_or_insert(port),
+            Err(error) => _ = first_error.get_or_insert(error),
+        }
+    }
+
+    match (other_game, first_error) {
+        (Some(port), _) => Err(BadGame.context(format!("Port {port} answered as another game"))),
+        (None, Some(error)) => Err(error),
+        (None, None) => Err(PacketReceive.context("No query port candidates")),
+    }
+}
+
+/// Whether a response is the one of `game`.
+fn answers_as(game: &Game, response: &dyn CommonResponse) -> bool {
+    match (&game.protocol, response.as_original()) {
+        (Protocol::Valve(Engine::Source(Some((main, dedicated)))), GenericResponse::Valve(response)) => {
+            response.info.appid == *main || Some(response.info.appid) == *dedicated
+        }
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::types::QueryPort;
+    use gamedig_fake_servers::{quake::Quake, FakeServer};
+    use std::net::UdpSocket;
+    use std::time::Duration;
+
+    fn timeout_settings() -> Option<TimeoutSettings> {
+        let timeout = Some(Duration::from_millis(300));
+        Some(TimeoutSettings::new(timeout, timeout, timeout, 0).unwrap())
+    }
+
+    #[test]
+    fn detects_answering_protocol() {
+        let server = FakeServer::start(&Quake::one()).unwrap();
+        let address = server.address();
+        let detections = detect_with_timeout(&address, timeout_settings()).unwrap();
+
+        assert_eq!(detections.len(), 1);
+        assert_eq!(detections[0].protocol, Protocol::Quake(QuakeVersion::One));
+        assert_eq!(detections[0].response.name(), Some("Fake server"));
+    }
+
+    #[test]
+    fn nothing_answering_fails() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = socket.local_addr().unwrap();
+
+        let error = detect_with_timeout(&address, timeout_settings())
+            .err()
+            .unwrap();
+        assert_eq!(error.kind, AutoQuery);
+    }
+
+    #[test]
+    fn discovers_query_port() {
+        let server = FakeServer::start(&Quake::one()).unwrap();
+        let query_address = server.address();
+
+        // Nothing answers on the connect port
+        let connect = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let connect_address = connect.local_addr().unwrap();
+        let offset = i32::from(query_address.port()) - i32::from(connect_address.port());
+
+        let game = Game {
+            name: "Test",
+            default_port: 26000,
+            protocol: Protocol::Quake(QuakeVersion::One),
+            request_settings: Default::default(),
+            query_ports: Box::leak(Box::new([QueryPort::Offset(offset)])),
+        };
+
+        let discovered = discover_query_port(&game, &connect_address, timeout_settings()).unwrap();
+        assert_eq!(discovered.port, query_address.port());
+        assert_eq!(discovered.response.name(), Some("Fake server"));
+    }
+
+    #[test]
+    fn other_games_answering_is_bad_game() {
+        let game = probe_game(&Protocol::Unreal2, &"127.0.0.1:7778".parse().unwrap());
+        let results = vec![Some(Err(PacketReceive.into())), Some(Err(BadGame.into()))];
+
+        let error = discovered(&game, &[7778, 7779], results).err().unwrap();
+        assert_eq!(error.kind, BadGame);
+    }
+
+    #[cfg(feature = "game_defs")]
+    #[test]
+    fn valve_appid_picks_game() {
+        assert_eq!(best_valve_game(440, 27015), Some("teamfortress2"));
+        assert_eq!(best_valve_game(u32::MAX, 27015), None);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn detects_answering_protocol_async() {
+        let server = FakeServer::start(&Quake::one()).unwrap();
+        let address = server.address();
+        let detections = detect_with_timeout_async(&address, timeout_settings())
+            .await
+            .unwrap();
+
+        assert_eq!(detections.len(), 1);
+        assert_eq!(detections[0].protocol, Protocol::Quake(QuakeVersion::One));
+    }
+}
@@ -1,6 +1,8 @@
 use crate::eco::{EcoRequestSettings, Response, Root};
+#[cfg(feature = "async")]
+use crate::http::AsyncHttpClient;
 use crate::http::HttpClient;
-use crate::{GDResult, TimeoutSettings};
+use crate::{GDErrorKind::ProtocolFormat, GDResult, QueryStage, TimeoutSettings};
 use std::net::{IpAddr, SocketAddr};
 
 /// Query an eco server.
@@ -24,14 +26,43 @@ pub fn query_with_timeout_and_extra_settings(
     timeout_settings: &Option<TimeoutSettings>,
     extra_settings: Option<EcoRequestSettings>,
 ) -> GDResult<Response> {
-    let address = &SocketAddr::new(*address, port.unwrap_or(3001));
-    let mut client = HttpClient::new(
-        address,
+    let address = SocketAddr::new(*address, port.unwrap_or(3001));
+    HttpClient::new(
+        &address,
         timeout_settings,
         extra_settings.unwrap_or_default().into(),
-    )?;
+    )
+    .and_then(|mut client| client.get_json::<Root>("/frontpage", None))
+    .map(Response::from)
+    .map_err(|e| e.with_address(address).with_stage(QueryStage::Info))
+}
+
+/// Query an eco server asynchronously.
+#[cfg(feature = "async")]
+pub async fn query_with_timeout_and_extra_settings_async(
+    address: &IpAddr,
+    port: Option<u16>,
+    timeout_settings: &Option<TimeoutSettings>,
+    extra_settings: Option<EcoRequestSettings>,
+) -> GDResult<Response> {
+    let address = SocketAddr::new(*address, port.unwrap_or(3001));
+    async {
+        let mut client = AsyncHttpClient::new(
+            &address,
+            timeout_settings,
+            extra_settings.unwrap_or_default().into(),
+        )?;
 
-    let response = client.get_json::<Root>("/frontpage", None)?;
+        client.get_json::<Root>("/frontpage", None).await
+    }
+    .await
+    .map(Response::from)
+    .map_err(|e| e.with_address(address).with_stage(QueryStage::Info))
+}
 
-    Ok(response.into())
+/// Parse the response of a front page request, as received.
+pub fn parse(data: &[u8]) -> GDResult<Response> {
+    serde_json::from_slice::<Root>(data)
+        .map(Response::from)
+        .map_err(|e| ProtocolFormat.context(e))
 }
@@ -1,8 +1,12 @@
 use crate::buffer::{Buffer, Utf8Decoder};
 use crate::games::ffow::types::Response;
 use crate::protocols::types::TimeoutSettings;
-use crate::protocols::valve::{Engine, Environment, Server, ValveProtocol};
-use crate::GDResult;
+#[cfg(feature = "async")]
+use crate::protocols::valve::protocol::AsyncValveProtocol;
+use crate::protocols::valve::{Engine, Environment, Packet, Server, ValveProtocol};
+#[cfg(feature = "async")]
+use crate::GDError;
+use crate::{GDErrorKind::PacketBad, GDResult, QueryStage};
 use byteorder::LittleEndian;
 use std::net::{IpAddr, SocketAddr};
 
@@ -13,10 +17,12 @@ pub fn query_with_timeout(
     port: Option<u16>,
     timeout_settings: Option<TimeoutSettings>,
 ) -> GDResult<Response> {
-    let mut client = ValveProtocol::new(
-        &SocketAddr::new(*address, port.unwrap_or(5478)),
-        timeout_settings,
-    )?;
+    let address = SocketAddr::new(*address, port.unwrap_or(5478));
+    get_response(&address, timeout_settings).map_err(|e| e.with_address(address).with_stage(QueryStage::Info))
+}
+
+fn get_response(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Response> {
+    let mut client = ValveProtocol::new(address, timeout_settings)?;
     let data = client.get_request_data(
         &Engine::GoldSrc(true),
         0,
@@ -24,7 +30,53 @@ pub fn query_with_timeout(
         String::from("LSQ").into_bytes(),
     )?;
 
-    let mut buffer = Buffer::<LittleEndian>::new(&data);
+    Ok(Response {
+        latency: client.latency(),
+        ..parse_response(&data)?
+    })
+}
+
+/// Async counterpart of [query_with_timeout].
+#[cfg(feature = "async")]
+pub async fn query_with_timeout_async(
+    address: &IpAddr,
+    port: Option<u16>,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<Response> {
+    let address = SocketAddr::new(*address, port.unwrap_or(5478));
+    async {
+        let mut client = AsyncValveProtocol::new(&address, timeout_settings).await?;
+        let data = client
+            .get_request_data(
+                &Engine::GoldSrc(true),
+                0,
+                0x46,
+                String::from("LSQ").into_bytes(),
+            )
+            .await?;
+
+        Ok(Response {
+            latency: client.latency(),
+            ..parse_response(&data)?
+        })
+    }
+    .await
+    .map_err(|e: GDError| e.with_address(address).with_stage(QueryStage::Info))
+}
+
+/// Parse a response packet, as received (reassembled if it was split).
+pub fn parse(data: &[u8]) -> GDResult<Response> {
+    let packet = Packet::new_from_bufferer(&mut Buffer::<LittleEndian>::new(data))?;
+    if packet.header != u32::MAX {
+        return Err(PacketBad.context("Expected a whole packet (a split one has to be reassembled)"));
+    }
+
+    parse_response(&packet.payload)
+}
+
+/// Parse the payload of a response.
+fn parse_response(data: &[u8]) -> GDResult<Response> {
+    let mut buffer = Buffer::<LittleEndian>::new(data);
 
     let protocol_version = buffer.read::<u8>()?;
     let name = buffer.read_string::<Utf8Decoder>(None)?;
@@ -62,5 +114,6 @@ pub fn query_with_timeout(
         round,
         rounds_maximum,
         time_left,
+        latency: None,
     })
 }
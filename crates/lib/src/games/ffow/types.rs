@@ -3,6 +3,7 @@ use crate::protocols::valve::{Environment, Server};
 use crate::protocols::GenericResponse;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 /// The query response.
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -40,6 +41,8 @@ pub struct Response {
     pub rounds_maximum: u8,
     /// Time left for the current round in seconds.
     pub time_left: u16,
+    /// Round-trip time of the info request.
+    pub latency: Option<Duration>,
 }
 
 impl CommonResponse for Response {
@@ -53,4 +56,6 @@ impl CommonResponse for Response {
     fn has_password(&self) -> Option<bool> { Some(self.has_password) }
     fn players_maximum(&self) -> u32 { self.players_maximum.into() }
     fn players_online(&self) -> u32 { self.players_online.into() }
+
+    fn latency(&self) -> Option<Duration> { self.latency }
 }
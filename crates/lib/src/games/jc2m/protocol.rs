@@ -1,13 +1,23 @@
 use crate::buffer::{Buffer, Utf8Decoder};
 use crate::jc2m::{Player, Response};
 use crate::protocols::gamespy::common::has_password;
-use crate::protocols::gamespy::three::{data_to_map, GameSpy3};
+#[cfg(feature = "async")]
+use crate::protocols::gamespy::three::protocol::AsyncGameSpy3;
+use crate::protocols::gamespy::three::{data_packets, data_to_map, GameSpy3, GameSpy3RequestSettings};
 use crate::protocols::types::TimeoutSettings;
+#[cfg(feature = "async")]
+use crate::GDError;
 use crate::GDErrorKind::{PacketBad, TypeParse};
-use crate::GDResult;
+use crate::{GDResult, QueryStage};
 use byteorder::BigEndian;
 use std::net::{IpAddr, SocketAddr};
 
+/// The server answers with a single packet, to a request with its own payload.
+const REQUEST_SETTINGS: GameSpy3RequestSettings = GameSpy3RequestSettings {
+    payload: [0xFF, 0xFF, 0xFF, 0x02],
+    single_packets: true,
+};
+
 fn parse_players_and_teams(packet: &[u8]) -> GDResult<Vec<Player>> {
     let mut buf = Buffer::<BigEndian>::new(packet);
 
@@ -32,14 +42,50 @@ pub fn query_with_timeout(
     port: Option<u16>,
     timeout_settings: Option<TimeoutSettings>,
 ) -> GDResult<Response> {
-    let mut client = GameSpy3::new_custom(
-        &SocketAddr::new(*address, port.unwrap_or(7777)),
-        timeout_settings,
-        [0xFF, 0xFF, 0xFF, 0x02],
-        true,
-    )?;
+    let address = SocketAddr::new(*address, port.unwrap_or(7777));
+    get_response(&address, timeout_settings).map_err(|e| e.with_address(address).with_stage(QueryStage::Info))
+}
 
+fn get_response(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Response> {
+    let mut client = GameSpy3::new_custom(address, timeout_settings, REQUEST_SETTINGS)?;
     let packets = client.get_server_packets()?;
+
+    Ok(Response {
+        latency: client.latency(),
+        ..parse_response(&packets)?
+    })
+}
+
+/// Async counterpart of [query_with_timeout].
+#[cfg(feature = "async")]
+pub async fn query_with_timeout_async(
+    address: &IpAddr,
+    port: Option<u16>,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<Response> {
+    let address = SocketAddr::new(*address, port.unwrap_or(7777));
+    async {
+        let mut client = AsyncGameSpy3::new_custom(&address, timeout_settings, REQUEST_SETTINGS).await?;
+        let packets = client.get_server_packets().await?;
+
+        Ok(Response {
+            latency: client.latency(),
+            ..parse_response(&packets)?
+        })
+    }
+    .await
+    .map_err(|e: GDError| e.with_address(address).with_stage(QueryStage::Info))
+}
+
+/// Parse the packets of a response, as received.
+///
+/// Unlike queries, the session id of the packets isn't checked, so that
+/// responses to any client can be parsed.
+pub fn parse<P: AsRef<[u8]>>(packets: &[P]) -> GDResult<Response> {
+    parse_response(&data_packets(packets, REQUEST_SETTINGS.single_packets)?)
+}
+
+fn parse_response(packets: &[Vec<u8>]) -> GDResult<Response> {
     let data = packets
         .first()
         .ok_or_else(|| PacketBad.context("First packet missing"))?;
@@ -71,5 +117,6 @@ pub fn query_with_timeout(
         players,
         players_maximum,
         players_online,
+        latency: None,
     })
 }
@@ -2,6 +2,7 @@ use crate::protocols::types::{CommonPlayer, CommonResponse, GenericPlayer};
 use crate::protocols::GenericResponse;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -27,6 +28,7 @@ pub struct Response {
     pub players: Vec<Player>,
     pub players_maximum: u32,
     pub players_online: u32,
+    pub latency: Option<Duration>,
 }
 
 impl CommonResponse for Response {
@@ -47,4 +49,6 @@ impl CommonResponse for Response {
                 .collect(),
         )
     }
+
+    fn latency(&self) -> Option<Duration> { self.latency }
 }
@@ -4,7 +4,7 @@
 
 use std::{net::IpAddr, net::SocketAddr};
 
-use crate::{GDResult, TimeoutSettings};
+use crate::{GDResult, QueryStage, TimeoutSettings};
 
 use self::types::ServerData;
 
@@ -22,4 +22,19 @@ pub fn query(ip: &IpAddr, port: Option<u16>, timeout_settings: &Option<TimeoutSe
     let address = SocketAddr::new(*ip, port.unwrap_or(DEFAULT_PORT));
 
     protocol::query_with_retries(&address, timeout_settings)
+        .map_err(|e| e.with_address(address).with_stage(QueryStage::Info))
+}
+
+/// Async counterpart of [query].
+#[cfg(feature = "async")]
+pub async fn query_async(
+    ip: &IpAddr,
+    port: Option<u16>,
+    timeout_settings: &Option<TimeoutSettings>,
+) -> GDResult<ServerData> {
+    let address = SocketAddr::new(*ip, port.unwrap_or(DEFAULT_PORT));
+
+    protocol::query_with_retries_async(&address, timeout_settings)
+        .await
+        .map_err(|e| e.with_address(address).with_stage(QueryStage::Info))
 }
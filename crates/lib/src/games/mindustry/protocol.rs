@@ -2,12 +2,16 @@ use std::net::SocketAddr;
 
 use crate::{
     buffer::{self, Buffer},
+    retry,
     socket::{Socket, UdpSocket},
-    utils,
+    trace,
     GDResult,
     TimeoutSettings,
 };
 
+#[cfg(feature = "async")]
+use crate::socket::{AsyncSocket, AsyncUdpSocket};
+
 use super::types::ServerData;
 
 /// Mindustry max datagram packet size.
@@ -16,7 +20,12 @@ pub const MAX_BUFFER_SIZE: usize = 500;
 /// Send a ping packet.
 ///
 /// [Reference](https://github.com/Anuken/Mindustry/blob/a2e5fbdedb2fc1c8d3c157bf344d10ad6d321442/core/src/mindustry/net/ArcNetProvider.java#L248)
-pub(crate) fn send_ping(socket: &mut UdpSocket) -> GDResult<()> { socket.send(&[-2i8 as u8, 1i8 as u8]) }
+const PING_REQUEST: [u8; 2] = [-2i8 as u8, 1i8 as u8];
+
+pub(crate) fn send_ping(socket: &mut UdpSocket) -> GDResult<()> {
+    trace::event!(DEBUG, "Mindustry ping");
+    socket.send(&PING_REQUEST)
+}
 
 /// Parse server data.
 ///
@@ -35,6 +44,7 @@ pub fn parse_server_data<B: byteorder::ByteOrder, D: buffer::StringDecoder>(
         player_limit: buffer.read()?,
         description: buffer.read_string::<D>(None)?,
         mode_name: buffer.read_string::<D>(None).ok(),
+        latency: None,
     })
 }
 
@@ -45,7 +55,16 @@ pub fn query(address: &SocketAddr, timeout_settings: &Option<TimeoutSettings>) -
     send_ping(&mut socket)?;
 
     let socket_data = socket.receive(Some(MAX_BUFFER_SIZE))?;
-    let mut buffer = Buffer::new(&socket_data);
+
+    Ok(ServerData {
+        latency: socket.latency(),
+        ..parse(&socket_data)?
+    })
+}
+
+/// Parse a ping response, as received.
+pub fn parse(data: &[u8]) -> GDResult<ServerData> {
+    let mut buffer = Buffer::new(data);
 
     parse_server_data::<byteorder::BigEndian, buffer::Utf8LengthPrefixedDecoder>(&mut buffer)
 }
@@ -54,5 +73,40 @@ pub fn query(address: &SocketAddr, timeout_settings: &Option<TimeoutSettings>) -
 pub fn query_with_retries(address: &SocketAddr, timeout_settings: &Option<TimeoutSettings>) -> GDResult<ServerData> {
     let retries = TimeoutSettings::get_retries_or_default(timeout_settings);
 
-    utils::retry_on_timeout(retries, || query(address, timeout_settings))
+    retry::retry(
+        retries,
+        TimeoutSettings::get_retry_policy_or_default(timeout_settings),
+        || query(address, timeout_settings),
+    )
+}
+
+/// Async counterpart of [query].
+#[cfg(feature = "async")]
+pub async fn query_async(address: &SocketAddr, timeout_settings: &Option<TimeoutSettings>) -> GDResult<ServerData> {
+    let mut socket = AsyncUdpSocket::new(address, timeout_settings).await?;
+
+    trace::event!(DEBUG, "Mindustry ping");
+    socket.send(&PING_REQUEST).await?;
+
+    let socket_data = socket.receive(Some(MAX_BUFFER_SIZE)).await?;
+
+    Ok(ServerData {
+        latency: socket.latency(),
+        ..parse(&socket_data)?
+    })
+}
+
+/// Async counterpart of [query_with_retries].
+#[cfg(feature = "async")]
+pub async fn query_with_retries_async(
+    address: &SocketAddr,
+    timeout_settings: &Option<TimeoutSettings>,
+) -> GDResult<ServerData> {
+    let retries = TimeoutSettings::get_retries_or_default(timeout_settings);
+
+    retry::retry_async!(
+        retries,
+        TimeoutSettings::get_retry_policy_or_default(timeout_settings),
+        query_async(address, timeout_settings).await
+    )
 }
@@ -4,6 +4,7 @@ use crate::{
 };
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 /// Mindustry sever data
 ///
@@ -21,6 +22,7 @@ pub struct ServerData {
     pub player_limit: i32,
     pub description: String,
     pub mode_name: Option<String>,
+    pub latency: Option<Duration>,
 }
 
 /// Mindustry game mode
@@ -73,6 +75,8 @@ impl CommonResponse for ServerData {
 
     fn map(&self) -> Option<&str> { Some(&self.map) }
     fn description(&self) -> Option<&str> { Some(&self.description) }
+
+    fn latency(&self) -> Option<Duration> { self.latency }
 }
 
 #[cfg(test)]
@@ -94,6 +98,7 @@ mod test {
             player_limit: 20,
             description: String::from("description"),
             mode_name: Some(String::from("campaign")),
+            latency: None,
         };
 
         let common: &dyn CommonResponse = &data;
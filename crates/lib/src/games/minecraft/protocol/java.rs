@@ -1,22 +1,30 @@
 use crate::{
     buffer::Buffer,
     games::minecraft::{as_string, as_varint, get_string, get_varint, JavaResponse, Player, RequestSettings, Server},
-    protocols::types::TimeoutSettings,
+    machine::{drive, timed_out, Event, Machine, Step},
+    protocols::types::{RetryPolicy, TimeoutSettings},
+    retry::retry,
     socket::{Socket, TcpSocket},
-    utils::retry_on_timeout,
+    trace,
     GDErrorKind::{JsonParse, PacketBad},
     GDResult,
 };
+#[cfg(feature = "async")]
+use crate::{
+    machine::drive_async,
+    retry::retry_async,
+    socket::{AsyncSocket, AsyncTcpSocket},
+};
 
 use byteorder::LittleEndian;
 use serde_json::Value;
 use std::net::SocketAddr;
-use std::time::{SystemTime, UNIX_EPOCH};
 
-pub struct Java {
+pub(crate) struct Java {
     socket: TcpSocket,
     request_settings: RequestSettings,
     retry_count: usize,
+    retry_policy: RetryPolicy,
 }
 
 impl Java {
@@ -28,151 +36,313 @@ impl Java {
         let socket = TcpSocket::new(address, &timeout_settings)?;
 
         let retry_count = TimeoutSettings::get_retries_or_default(&timeout_settings);
+        let retry_policy = TimeoutSettings::get_retry_policy_or_default(&timeout_settings);
         Ok(Self {
             socket,
             request_settings: request_settings.unwrap_or_default(),
             retry_count,
+            retry_policy,
         })
     }
 
-    fn send(&mut self, data: Vec<u8>) -> GDResult<()> {
-        self.socket
-            .send(&[as_varint(data.len() as i32), data].concat())
+    /// Send minecraft status and ping requests and parse the response, its
+    /// latency being the ping's round-trip.
+    /// This function will retry fetch on timeouts.
+    fn get_info(&mut self) -> GDResult<JavaResponse> {
+        let mut machine = StatusMachine::new(&self.request_settings, self.socket.port())?;
+        let response = retry(self.retry_count, self.retry_policy, || {
+            drive(&mut self.socket, &mut machine, None)
+        })?;
+
+        Ok(JavaResponse {
+            latency: self.socket.latency(),
+            ..response
+        })
     }
 
-    fn receive(&mut self) -> GDResult<Vec<u8>> {
-        let data = &self.socket.receive(None)?;
-        let mut buffer = Buffer::<LittleEndian>::new(data);
+    pub fn query(
+        address: &SocketAddr,
+        timeout_settings: Option<TimeoutSettings>,
+        request_settings: Option<RequestSettings>,
+    ) -> GDResult<JavaResponse> {
+        Self::new(address, timeout_settings, request_settings)?.get_info()
+    }
 
-        let _packet_length = get_varint(&mut buffer)? as usize;
-        // this declared 'packet length' from within the packet might be wrong (?), not
-        // checking with it...
+    /// Async counterpart of [Java::query].
+    #[cfg(feature = "async")]
+    pub async fn query_async(
+        address: &SocketAddr,
+        timeout_settings: Option<TimeoutSettings>,
+        request_settings: Option<RequestSettings>,
+    ) -> GDResult<JavaResponse> {
+        let mut socket = AsyncTcpSocket::new(address, &timeout_settings).await?;
+        let mut machine = StatusMachine::new(&request_settings.unwrap_or_default(), socket.port())?;
 
-        Ok(buffer.remaining_bytes().to_vec())
-    }
+        let response = retry_async!(
+            TimeoutSettings::get_retries_or_default(&timeout_settings),
+            TimeoutSettings::get_retry_policy_or_default(&timeout_settings),
+            drive_async(&mut socket, &mut machine, None).await
+        )?;
 
-    fn send_handshake(&mut self) -> GDResult<()> {
-        let handshake_payload = [
-            &[
-                // Packet ID (0)
-                0x00,
-            ], // Protocol Version (-1 to determine version)
-            as_varint(self.request_settings.protocol_version).as_slice(),
-            // Server address (can be anything)
-            as_string(&self.request_settings.hostname)?.as_slice(),
-            // Server port (can be anything)
-            &self.socket.port().to_le_bytes(),
-            &[
-                // Next state (1 for status)
-                0x01,
-            ],
-        ]
-        .concat();
+        Ok(JavaResponse {
+            latency: socket.latency(),
+            ..response
+        })
+    }
+}
 
-        self.send(handshake_payload)?;
+/// The exchange of a status query: the handshake and status requests, the
+/// status response, and then the ping request and its pong, whose round-trip
+/// is the query's latency.
+///
+/// Servers that don't answer the ping (timing out or closing the connection)
+/// still give the status response.
+pub struct StatusMachine {
+    handshake: Vec<u8>,
+    /// What was received of the status response (or of the pong) so far.
+    received: Vec<u8>,
+    /// The status response, once received, while waiting for the pong.
+    response: Option<JavaResponse>,
+}
 
-        Ok(())
+impl StatusMachine {
+    /// `port` is the server's, which the handshake holds.
+    pub fn new(request_settings: &RequestSettings, port: u16) -> GDResult<Self> {
+        Ok(Self {
+            handshake: handshake_payload(request_settings, port)?,
+            received: Vec::new(),
+            response: None,
+        })
     }
 
-    fn send_status_request(&mut self) -> GDResult<()> {
-        self.send(
-            [0x00] // Packet ID (0)
-                .to_vec(),
-        )?;
+    /// Wait for the whole pong, after the status response.
+    fn handle_pong(&mut self, response: JavaResponse, event: Event<'_>) -> Step<JavaResponse> {
+        match event {
+            Event::Received(data) if !data.is_empty() => {
+                self.received.extend_from_slice(data);
+                if is_partial(&self.received) {
+                    self.response = Some(response);
+                    return Step::Receive;
+                }
 
-        Ok(())
+                trace::event!(DEBUG, "Minecraft Java pong");
+                Step::Done(response)
+            }
+            _ => {
+                trace::event!(DEBUG, "Minecraft Java ping not answered");
+                Step::Done(response)
+            }
+        }
     }
+}
+
+impl Machine for StatusMachine {
+    type Output = JavaResponse;
+
+    // The server waits for the ping before closing the connection.
+    const STREAMED: bool = true;
 
-    fn send_ping_request(&mut self) -> GDResult<()> {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis() as i64;
+    fn start(&mut self) -> Vec<Vec<u8>> {
+        self.received.clear();
+        self.response = None;
+        trace::event!(DEBUG, "Minecraft Java handshake and status requests");
+        vec![
+            frame(self.handshake.clone()),
+            frame(STATUS_REQUEST.to_vec()),
+        ]
+    }
 
-        let mut payload = [0x01].to_vec(); // Packet ID (1)
-        payload.extend_from_slice(&timestamp.to_be_bytes()); // Timestamp (long, 8 bytes)
+    fn handle(&mut self, event: Event<'_>) -> GDResult<Step<JavaResponse>> {
+        if let Some(response) = self.response.take() {
+            return Ok(self.handle_pong(response, event));
+        }
 
-        self.send(payload)?;
+        let Event::Received(data) = event else {
+            return Err(timed_out());
+        };
+        self.received.extend_from_slice(data);
 
-        Ok(())
+        // The declared length isn't trusted, a response is whole once it
+        // parses, it can only be partial if it is shorter than declared.
+        match parse_status(&self.received) {
+            Ok(response) => {
+                trace::event!(DEBUG, "Minecraft Java ping request");
+                self.received.clear();
+                self.response = Some(response);
+                Ok(Step::Send(vec![frame(PING_REQUEST.to_vec())]))
+            }
+            Err(_) if !data.is_empty() && is_partial(&self.received) => {
+                trace::event!(
+                    TRACE,
+                    received = self.received.len(),
+                    "Minecraft Java partial status response"
+                );
+                Ok(Step::Receive)
+            }
+            Err(e) => Err(e),
+        }
     }
+}
 
-    /// Send minecraft ping request and parse the response.
-    /// This function will retry fetch on timeouts.
-    fn get_info(&mut self) -> GDResult<JavaResponse> {
-        retry_on_timeout(self.retry_count, move || self.get_info_impl())
+/// Status request packet, its only content is the Packet ID (0).
+const STATUS_REQUEST: [u8; 1] = [0x00];
+
+/// Prefix a packet with its length.
+fn frame(data: Vec<u8>) -> Vec<u8> { [as_varint(data.len() as i32), data].concat() }
+
+/// Remove the length prefix of a received packet.
+fn strip_packet_length(data: &[u8]) -> GDResult<Vec<u8>> {
+    let mut buffer = Buffer::<LittleEndian>::new(data);
+
+    let _packet_length = get_varint(&mut buffer)? as usize;
+    // this declared 'packet length' from within the packet might be wrong (?), not
+    // checking with it...
+
+    Ok(buffer.remaining_bytes().to_vec())
+}
+
+/// Whether a framed packet is shorter than its declared length, or too short
+/// to declare one.
+fn is_partial(data: &[u8]) -> bool {
+    let mut buffer = Buffer::<LittleEndian>::new(data);
+
+    get_varint(&mut buffer).map_or(true, |length| buffer.remaining_length() < length as usize)
+}
+
+fn handshake_payload(request_settings: &RequestSettings, port: u16) -> GDResult<Vec<u8>> {
+    Ok([
+        &[
+            // Packet ID (0)
+            0x00,
+        ], // Protocol Version (-1 to determine version)
+        as_varint(request_settings.protocol_version).as_slice(),
+        // Server address (can be anything)
+        as_string(&request_settings.hostname)?.as_slice(),
+        // Server port (can be anything)
+        &port.to_le_bytes(),
+        &[
+            // Next state (1 for status)
+            0x01,
+        ],
+    ]
+    .concat())
+}
+
+/// Ping request packet, the server echoes its payload back. Any payload does,
+/// a fixed one (rather than a timestamp) keeps the requests replayable.
+const PING_REQUEST: [u8; 9] = [
+    0x01, // Packet ID (1)
+    0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, // Payload (long, 8 bytes)
+];
+
+/// Parse a status response packet (without its length).
+fn parse_response(socket_data: &[u8]) -> GDResult<JavaResponse> {
+    let mut buffer = Buffer::<LittleEndian>::new(socket_data);
+
+    if get_varint(&mut buffer)? != 0 {
+        // first var int is the packet id
+        return Err(PacketBad.context("Expected 0"));
     }
 
-    /// Send minecraft ping request and parse the response (without retry
-    /// logic).
-    fn get_info_impl(&mut self) -> GDResult<JavaResponse> {
-        self.send_handshake()?;
-        self.send_status_request()?;
-        self.send_ping_request()?;
+    parse_status_json(&get_string(&mut buffer)?)
+}
+
+/// Parse a (framed) status response packet, as received.
+pub fn parse_status(data: &[u8]) -> GDResult<JavaResponse> { parse_response(&strip_packet_length(data)?) }
 
-        let socket_data = self.receive()?;
-        let mut buffer = Buffer::<LittleEndian>::new(&socket_data);
+/// Parse the JSON of a status response.
+pub fn parse_status_json(json: &str) -> GDResult<JavaResponse> {
+    let value_response: Value = serde_json::from_str(json).map_err(|e| JsonParse.context(e))?;
 
-        if get_varint(&mut buffer)? != 0 {
-            // first var int is the packet id
-            return Err(PacketBad.context("Expected 0"));
+    let game_version = value_response["version"]["name"]
+        .as_str()
+        .ok_or(PacketBad)?
+        .to_string();
+    let protocol_version = value_response["version"]["protocol"]
+        .as_i64()
+        .ok_or(PacketBad)? as i32;
+
+    let max_players = value_response["players"]["max"].as_u64().ok_or(PacketBad)? as u32;
+    let online_players = value_response["players"]["online"]
+        .as_u64()
+        .ok_or(PacketBad)? as u32;
+    let players: Option<Vec<Player>> = match value_response["players"]["sample"].is_null() {
+        true => None,
+        false => {
+            Some({
+                let players_values = value_response["players"]["sample"]
+                    .as_array()
+                    .ok_or(PacketBad)?;
+
+                let mut players = Vec::with_capacity(players_values.len());
+                for player in players_values {
+                    players.push(Player {
+                        name: player["name"].as_str().ok_or(PacketBad)?.to_string(),
+                        id: player["id"].as_str().ok_or(PacketBad)?.to_string(),
+                    });
+                }
+
+                players
+            })
         }
+    };
+
+    Ok(JavaResponse {
+        game_version,
+        protocol_version,
+        players_maximum: max_players,
+        players_online: online_players,
+        players,
+        description: value_response["description"].to_string(),
+        favicon: value_response["favicon"].as_str().map(str::to_string),
+        previews_chat: value_response["previewsChat"].as_bool(),
+        enforces_secure_chat: value_response["enforcesSecureChat"].as_bool(),
+        server_type: Server::Java,
+        latency: None,
+    })
+}
 
-        let json_response = get_string(&mut buffer)?;
-        let value_response: Value = serde_json::from_str(&json_response).map_err(|e| JsonParse.context(e))?;
-
-        let game_version = value_response["version"]["name"]
-            .as_str()
-            .ok_or(PacketBad)?
-            .to_string();
-        let protocol_version = value_response["version"]["protocol"]
-            .as_i64()
-            .ok_or(PacketBad)? as i32;
-
-        let max_players = value_response["players"]["max"].as_u64().ok_or(PacketBad)? as u32;
-        let online_players = value_response["players"]["online"]
-            .as_u64()
-            .ok_or(PacketBad)? as u32;
-        let players: Option<Vec<Player>> = match value_response["players"]["sample"].is_null() {
-            true => None,
-            false => {
-                Some({
-                    let players_values = value_response["players"]["sample"]
-                        .as_array()
-                        .ok_or(PacketBad)?;
-
-                    let mut players = Vec::with_capacity(players_values.len());
-                    for player in players_values {
-                        players.push(Player {
-                            name: player["name"].as_str().ok_or(PacketBad)?.to_string(),
-                            id: player["id"].as_str().ok_or(PacketBad)?.to_string(),
-                        });
-                    }
-
-                    players
-                })
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responder::minecraft::encode_java_status;
+
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn latency_is_the_ping_round_trip() {
+        let response = parse_status_json(r#"{"version":{"name":"1.20.4","protocol":765},"players":{"max":20,"online":0},"description":"A Minecraft Server"}"#).unwrap();
+        let status = encode_java_status(&response);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 1024];
+
+            // Slow to give its status, quick to answer the ping.
+            let mut request = Vec::new();
+            while !request.ends_with(&frame(STATUS_REQUEST.to_vec())) {
+                let size = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[.. size]);
             }
-        };
+            thread::sleep(Duration::from_millis(500));
+            stream.write_all(&status).unwrap();
 
-        Ok(JavaResponse {
-            game_version,
-            protocol_version,
-            players_maximum: max_players,
-            players_online: online_players,
-            players,
-            description: value_response["description"].to_string(),
-            favicon: value_response["favicon"].as_str().map(str::to_string),
-            previews_chat: value_response["previewsChat"].as_bool(),
-            enforces_secure_chat: value_response["enforcesSecureChat"].as_bool(),
-            server_type: Server::Java,
-        })
-    }
+            let size = stream.read(&mut buf).unwrap();
+            assert_eq!(buf[.. size], frame(PING_REQUEST.to_vec()));
+            stream.write_all(&buf[.. size]).unwrap();
+        });
 
-    pub fn query(
-        address: &SocketAddr,
-        timeout_settings: Option<TimeoutSettings>,
-        request_settings: Option<RequestSettings>,
-    ) -> GDResult<JavaResponse> {
-        Self::new(address, timeout_settings, request_settings)?.get_info()
+        let queried = Java::query(&address, None, None).unwrap();
+        server.join().unwrap();
+
+        assert_eq!(queried.game_version, response.game_version);
+        assert!(queried
+            .latency
+            .is_some_and(|latency| latency < Duration::from_millis(500)));
     }
 }
@@ -4,17 +4,25 @@ use crate::minecraft::protocol::legacy_v1_6::LegacyV1_6;
 use crate::{
     buffer::{Buffer, Utf16Decoder},
     games::minecraft::{JavaResponse, LegacyGroup, Server},
-    protocols::types::TimeoutSettings,
+    protocols::types::{RetryPolicy, TimeoutSettings},
+    retry::retry,
     socket::{Socket, TcpSocket},
-    utils::{error_by_expected_size, retry_on_timeout},
+    trace,
+    utils::error_by_expected_size,
     GDErrorKind::{PacketBad, ProtocolFormat},
     GDResult,
 };
+#[cfg(feature = "async")]
+use crate::{
+    retry::retry_async,
+    socket::{AsyncSocket, AsyncTcpSocket},
+};
 use std::net::SocketAddr;
 
-pub struct LegacyV1_4 {
+pub(crate) struct LegacyV1_4 {
     socket: TcpSocket,
     retry_count: usize,
+    retry_policy: RetryPolicy,
 }
 
 impl LegacyV1_4 {
@@ -22,62 +30,106 @@ impl LegacyV1_4 {
         let socket = TcpSocket::new(address, &timeout_settings)?;
 
         let retry_count = TimeoutSettings::get_retries_or_default(&timeout_settings);
+        let retry_policy = TimeoutSettings::get_retry_policy_or_default(&timeout_settings);
         Ok(Self {
             socket,
             retry_count,
+            retry_policy,
         })
     }
 
-    fn send_initial_request(&mut self) -> GDResult<()> { self.socket.send(&[0xFE, 0x01]) }
+    fn send_initial_request(&mut self) -> GDResult<()> {
+        trace::event!(DEBUG, "Minecraft legacy status request");
+        self.socket.send(&INITIAL_REQUEST)
+    }
 
     /// Send info request and parse response.
     /// This function will retry fetch on timeouts.
     fn get_info(&mut self) -> GDResult<JavaResponse> {
-        retry_on_timeout(self.retry_count, move || self.get_info_impl())
+        retry(self.retry_count, self.retry_policy, move || {
+            self.get_info_impl()
+        })
     }
 
     /// Send info request and parse response (without retry logic).
     fn get_info_impl(&mut self) -> GDResult<JavaResponse> {
         self.send_initial_request()?;
 
-        let data = self.socket.receive(None)?;
-        let mut buffer = Buffer::<BigEndian>::new(&data);
+        let mut response = parse(&self.socket.receive(None)?)?;
+        response.latency = self.socket.latency();
 
-        if buffer.read::<u8>()? != 0xFF {
-            return Err(ProtocolFormat.context("Expected 0xFF"));
-        }
-
-        let length = buffer.read::<u16>()? * 2;
-        error_by_expected_size((length + 3) as usize, data.len())?;
+        Ok(response)
+    }
 
-        if LegacyV1_6::is_protocol(&mut buffer)? {
-            return LegacyV1_6::get_response(&mut buffer);
-        }
+    pub fn query(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<JavaResponse> {
+        Self::new(address, timeout_settings)?.get_info()
+    }
 
-        let packet_string = buffer.read_string::<Utf16Decoder<BigEndian>>(None)?;
+    /// Async counterpart of [LegacyV1_4::query].
+    #[cfg(feature = "async")]
+    pub async fn query_async(
+        address: &SocketAddr,
+        timeout_settings: Option<TimeoutSettings>,
+    ) -> GDResult<JavaResponse> {
+        let mut socket = AsyncTcpSocket::new(address, &timeout_settings).await?;
+
+        retry_async!(
+            TimeoutSettings::get_retries_or_default(&timeout_settings),
+            TimeoutSettings::get_retry_policy_or_default(&timeout_settings),
+            async {
+                trace::event!(DEBUG, "Minecraft legacy status request");
+                socket.send(&INITIAL_REQUEST).await?;
+                let data = socket.receive(None).await?;
+
+                parse(&data).map(|response| {
+                    JavaResponse {
+                        latency: socket.latency(),
+                        ..response
+                    }
+                })
+            }
+            .await
+        )
+    }
+}
 
-        let split: Vec<&str> = packet_string.split('§').collect();
-        error_by_expected_size(3, split.len())?;
+const INITIAL_REQUEST: [u8; 2] = [0xFE, 0x01];
 
-        let description = split[0].to_string();
-        let online_players = split[1].parse().map_err(|e| PacketBad.context(e))?;
-        let max_players = split[2].parse().map_err(|e| PacketBad.context(e))?;
+/// Parse an info response, as received.
+pub fn parse(data: &[u8]) -> GDResult<JavaResponse> {
+    let mut buffer = Buffer::<BigEndian>::new(data);
 
-        Ok(JavaResponse {
-            game_version: "1.4+".to_string(),
-            protocol_version: -1,
-            players_maximum: max_players,
-            players_online: online_players,
-            players: None,
-            description,
-            favicon: None,
-            previews_chat: None,
-            enforces_secure_chat: None,
-            server_type: Server::Legacy(LegacyGroup::V1_4),
-        })
+    if buffer.read::<u8>()? != 0xFF {
+        return Err(ProtocolFormat.context("Expected 0xFF"));
     }
 
-    pub fn query(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<JavaResponse> {
-        Self::new(address, timeout_settings)?.get_info()
+    let length = buffer.read::<u16>()? * 2;
+    error_by_expected_size((length + 3) as usize, data.len())?;
+
+    if LegacyV1_6::is_protocol(&mut buffer)? {
+        return LegacyV1_6::get_response(&mut buffer);
     }
+
+    let packet_string = buffer.read_string::<Utf16Decoder<BigEndian>>(None)?;
+
+    let split: Vec<&str> = packet_string.split('§').collect();
+    error_by_expected_size(3, split.len())?;
+
+    let description = split[0].to_string();
+    let online_players = split[1].parse().map_err(|e| PacketBad.context(e))?;
+    let max_players = split[2].parse().map_err(|e| PacketBad.context(e))?;
+
+    Ok(JavaResponse {
+        game_version: "1.4+".to_string(),
+        protocol_version: -1,
+        players_maximum: max_players,
+        players_online: online_players,
+        players: None,
+        description,
+        favicon: None,
+        previews_chat: None,
+        enforces_secure_chat: None,
+        server_type: Server::Legacy(LegacyGroup::V1_4),
+        latency: None,
+    })
 }
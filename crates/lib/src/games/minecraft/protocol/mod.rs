@@ -12,17 +12,49 @@ use crate::{
         JavaResponse,
         LegacyGroup,
     },
-    protocols::types::TimeoutSettings,
+    protocols::types::{ProprietaryProtocol, Protocol, TimeoutSettings},
+    report,
+    trace,
+    GDError,
     GDErrorKind::AutoQuery,
     GDResult,
+    QueryStage,
 };
 use std::net::SocketAddr;
 
-mod bedrock;
-mod java;
-mod legacy_v1_4;
-mod legacy_v1_6;
-mod legacy_vb1_8;
+#[cfg(feature = "async")]
+use crate::trace::Instrument;
+
+/// Bedrock's unconnected ping.
+pub mod bedrock;
+/// Java's status, from 1.7 onwards.
+pub mod java;
+/// The legacy status of 1.4 to 1.5.
+pub mod legacy_v1_4;
+/// The legacy status of 1.6.
+pub mod legacy_v1_6;
+/// The legacy status of Beta 1.8 to 1.3.
+pub mod legacy_vb1_8;
+
+/// The response of a server type tried by an auto-query, if it answered.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+fn attempted<T>(server: &str, result: GDResult<T>) -> Option<T> {
+    result
+        .inspect_err(|e| trace::event!(DEBUG, server, kind = ?e.kind, "Minecraft server type didn't answer"))
+        .ok()
+}
+
+/// Record which server type answered an auto-query.
+fn answered(response: JavaResponse) -> GDResult<JavaResponse> {
+    trace::event!(DEBUG, server_type = ?response.server_type, "Minecraft server type answered");
+    report::record(|report| {
+        report.protocol = Some(Protocol::PROPRIETARY(ProprietaryProtocol::Minecraft(Some(
+            response.server_type,
+        ))));
+    });
+
+    Ok(response)
+}
 
 /// Queries a Minecraft server with all the protocol variants one by one (Java
 /// -> Bedrock -> Legacy (1.6 -> 1.4 -> Beta 1.8)).
@@ -31,19 +63,22 @@ pub fn query(
     timeout_settings: Option<TimeoutSettings>,
     request_settings: Option<RequestSettings>,
 ) -> GDResult<JavaResponse> {
-    if let Ok(response) = query_java(address, timeout_settings, request_settings) {
-        return Ok(response);
+    if let Some(response) = attempted(
+        "java",
+        query_java(address, timeout_settings, request_settings),
+    ) {
+        return answered(response);
     }
 
-    if let Ok(response) = query_bedrock(address, timeout_settings) {
-        return Ok(JavaResponse::from_bedrock_response(response));
+    if let Some(response) = attempted("bedrock", query_bedrock(address, timeout_settings)) {
+        return answered(JavaResponse::from_bedrock_response(response));
     }
 
-    if let Ok(response) = query_legacy(address, timeout_settings) {
-        return Ok(response);
+    if let Some(response) = attempted("legacy", query_legacy(address, timeout_settings)) {
+        return answered(response);
     }
 
-    Err(AutoQuery.into())
+    Err(GDError::from(AutoQuery).with_address(*address))
 }
 
 /// Query a Java Server.
@@ -52,24 +87,35 @@ pub fn query_java(
     timeout_settings: Option<TimeoutSettings>,
     request_settings: Option<RequestSettings>,
 ) -> GDResult<JavaResponse> {
-    Java::query(address, timeout_settings, request_settings)
+    trace::span!("minecraft", %address, server = "java")
+        .in_scope(|| Java::query(address, timeout_settings, request_settings))
+        .map_err(|e| e.with_address(*address).with_stage(QueryStage::Info))
 }
 
 /// Query a (Java) Legacy Server (1.6 -> 1.4 -> Beta 1.8).
 pub fn query_legacy(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<JavaResponse> {
-    if let Ok(response) = query_legacy_specific(LegacyGroup::V1_6, address, timeout_settings) {
-        return Ok(response);
+    if let Some(response) = attempted(
+        "legacy 1.6",
+        query_legacy_specific(LegacyGroup::V1_6, address, timeout_settings),
+    ) {
+        return answered(response);
     }
 
-    if let Ok(response) = query_legacy_specific(LegacyGroup::V1_4, address, timeout_settings) {
-        return Ok(response);
+    if let Some(response) = attempted(
+        "legacy 1.4",
+        query_legacy_specific(LegacyGroup::V1_4, address, timeout_settings),
+    ) {
+        return answered(response);
     }
 
-    if let Ok(response) = query_legacy_specific(LegacyGroup::VB1_8, address, timeout_settings) {
-        return Ok(response);
+    if let Some(response) = attempted(
+        "legacy b1.8",
+        query_legacy_specific(LegacyGroup::VB1_8, address, timeout_settings),
+    ) {
+        return answered(response);
     }
 
-    Err(AutoQuery.into())
+    Err(GDError::from(AutoQuery).with_address(*address))
 }
 
 /// Query a specific (Java) Legacy Server.
@@ -78,14 +124,117 @@ pub fn query_legacy_specific(
     address: &SocketAddr,
     timeout_settings: Option<TimeoutSettings>,
 ) -> GDResult<JavaResponse> {
-    match group {
-        LegacyGroup::V1_6 => LegacyV1_6::query(address, timeout_settings),
-        LegacyGroup::V1_4 => LegacyV1_4::query(address, timeout_settings),
-        LegacyGroup::VB1_8 => LegacyVB1_8::query(address, timeout_settings),
-    }
+    trace::span!("minecraft", %address, server = "legacy", ?group)
+        .in_scope(|| {
+            match group {
+                LegacyGroup::V1_6 => LegacyV1_6::query(address, timeout_settings),
+                LegacyGroup::V1_4 => LegacyV1_4::query(address, timeout_settings),
+                LegacyGroup::VB1_8 => LegacyVB1_8::query(address, timeout_settings),
+            }
+        })
+        .map_err(|e| e.with_address(*address).with_stage(QueryStage::Info))
 }
 
 /// Query a Bedrock Server.
 pub fn query_bedrock(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<BedrockResponse> {
-    Bedrock::query(address, timeout_settings)
+    trace::span!("minecraft", %address, server = "bedrock")
+        .in_scope(|| Bedrock::query(address, timeout_settings))
+        .map_err(|e| e.with_address(*address).with_stage(QueryStage::Info))
+}
+
+/// Async counterpart of [query].
+#[cfg(feature = "async")]
+pub async fn query_async(
+    address: &SocketAddr,
+    timeout_settings: Option<TimeoutSettings>,
+    request_settings: Option<RequestSettings>,
+) -> GDResult<JavaResponse> {
+    if let Some(response) = attempted(
+        "java",
+        query_java_async(address, timeout_settings, request_settings).await,
+    ) {
+        return answered(response);
+    }
+
+    if let Some(response) = attempted(
+        "bedrock",
+        query_bedrock_async(address, timeout_settings).await,
+    ) {
+        return answered(JavaResponse::from_bedrock_response(response));
+    }
+
+    if let Some(response) = attempted(
+        "legacy",
+        query_legacy_async(address, timeout_settings).await,
+    ) {
+        return answered(response);
+    }
+
+    Err(GDError::from(AutoQuery).with_address(*address))
+}
+
+/// Async counterpart of [query_java].
+#[cfg(feature = "async")]
+pub async fn query_java_async(
+    address: &SocketAddr,
+    timeout_settings: Option<TimeoutSettings>,
+    request_settings: Option<RequestSettings>,
+) -> GDResult<JavaResponse> {
+    Java::query_async(address, timeout_settings, request_settings)
+        .instrument(trace::span!("minecraft", %address, server = "java"))
+        .await
+        .map_err(|e| e.with_address(*address).with_stage(QueryStage::Info))
+}
+
+/// Async counterpart of [query_legacy].
+#[cfg(feature = "async")]
+pub async fn query_legacy_async(
+    address: &SocketAddr,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<JavaResponse> {
+    for (server, group) in [
+        ("legacy 1.6", LegacyGroup::V1_6),
+        ("legacy 1.4", LegacyGroup::V1_4),
+        ("legacy b1.8", LegacyGroup::VB1_8),
+    ] {
+        if let Some(response) = attempted(
+            server,
+            query_legacy_specific_async(group, address, timeout_settings).await,
+        ) {
+            return answered(response);
+        }
+    }
+
+    Err(GDError::from(AutoQuery).with_address(*address))
+}
+
+/// Async counterpart of [query_legacy_specific].
+#[cfg(feature = "async")]
+pub async fn query_legacy_specific_async(
+    group: LegacyGroup,
+    address: &SocketAddr,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<JavaResponse> {
+    async {
+        match group {
+            LegacyGroup::V1_6 => LegacyV1_6::query_async(address, timeout_settings).await,
+            LegacyGroup::V1_4 => LegacyV1_4::query_async(address, timeout_settings).await,
+            LegacyGroup::VB1_8 => LegacyVB1_8::query_async(address, timeout_settings).await,
+        }
+    }
+    .instrument(trace::span!("minecraft", %address, server = "legacy", ?group))
+    .await
+    .map_err(|e| e.with_address(*address).with_stage(QueryStage::Info))
+}
+
+/// Async counterpart of [query_bedrock].
+#[cfg(feature = "async")]
+pub async fn query_bedrock_async(
+    address: &SocketAddr,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<BedrockResponse> {
+    Bedrock::query_async(address, timeout_settings)
+        .instrument(trace::span!("minecraft", %address, server = "bedrock"))
+        .await
+        .map_err(|e| e.with_address(*address).with_stage(QueryStage::Info))
 }
@@ -15,6 +15,7 @@ use crate::{
 use byteorder::ByteOrder;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 /// The type of Minecraft Server you want to query.
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -62,6 +63,33 @@ pub enum VersionedResponse<'a> {
     Java(&'a JavaResponse),
 }
 
+/// Owned [VersionedResponse], which can be deserialized.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionedResponseOwned {
+    Bedrock(BedrockResponse),
+    Java(JavaResponse),
+}
+
+impl VersionedResponseOwned {
+    /// Get the response as a [CommonResponse].
+    pub fn as_common(&self) -> &dyn CommonResponse {
+        match self {
+            Self::Bedrock(response) => response,
+            Self::Java(response) => response,
+        }
+    }
+}
+
+impl From<VersionedResponse<'_>> for VersionedResponseOwned {
+    fn from(response: VersionedResponse<'_>) -> Self {
+        match response {
+            VersionedResponse::Bedrock(response) => Self::Bedrock(response.clone()),
+            VersionedResponse::Java(response) => Self::Java(response.clone()),
+        }
+    }
+}
+
 /// A Java query response.
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -87,6 +115,9 @@ pub struct JavaResponse {
     pub enforces_secure_chat: Option<bool>,
     /// Tell's the server type.
     pub server_type: Server,
+    /// Round-trip time of the status request (or of the ping request, for
+    /// Java Edition 1.7+).
+    pub latency: Option<Duration>,
 }
 
 /// Java-only additional request settings.
@@ -146,6 +177,8 @@ impl CommonResponse for JavaResponse {
             .as_ref()
             .map(|players| players.iter().map(|p| p as &dyn CommonPlayer).collect())
     }
+
+    fn latency(&self) -> Option<Duration> { self.latency }
 }
 
 /// A Bedrock Edition query response.
@@ -172,6 +205,8 @@ pub struct BedrockResponse {
     pub game_mode: Option<GameMode>,
     /// Tells the server type.
     pub server_type: Server,
+    /// Round-trip time of the unconnected ping.
+    pub latency: Option<Duration>,
 }
 
 impl CommonResponse for BedrockResponse {
@@ -182,6 +217,7 @@ impl CommonResponse for BedrockResponse {
     fn game_version(&self) -> Option<&str> { Some(&self.version_name) }
     fn players_maximum(&self) -> u32 { self.players_maximum }
     fn players_online(&self) -> u32 { self.players_online }
+    fn latency(&self) -> Option<Duration> { self.latency }
 }
 
 impl JavaResponse {
@@ -197,6 +233,7 @@ impl JavaResponse {
             previews_chat: None,
             enforces_secure_chat: None,
             server_type: Server::Bedrock,
+            latency: response.latency,
         }
     }
 }
@@ -223,6 +260,16 @@ impl GameMode {
             _ => Err(UnknownEnumCast.context(format!("Unknown gamemode {value:?}"))),
         }
     }
+
+    pub(crate) const fn to_bedrock(self) -> &'static str {
+        match self {
+            Self::Survival => "Survival",
+            Self::Creative => "Creative",
+            Self::Hardcore => "Hardcore",
+            Self::Spectator => "Spectator",
+            Self::Adventure => "Adventure",
+        }
+    }
 }
 
 pub(crate) fn get_varint<B: ByteOrder>(buffer: &mut Buffer<B>) -> GDResult<i32> {
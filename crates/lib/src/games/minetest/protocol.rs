@@ -1,6 +1,9 @@
 use crate::minetest::Response;
-use crate::{minetest_master_server, GDErrorKind, GDResult, TimeoutSettings};
-use std::net::IpAddr;
+use crate::{minetest_master_server, GDErrorKind, GDResult, QueryStage, TimeoutSettings};
+use std::net::{IpAddr, SocketAddr};
+
+/// The port servers listen on by default.
+const DEFAULT_PORT: u16 = 30000;
 
 pub fn query(address: &IpAddr, port: Option<u16>) -> GDResult<Response> { query_with_timeout(address, port, &None) }
 
@@ -9,10 +12,46 @@ pub fn query_with_timeout(
     port: Option<u16>,
     timeout_settings: &Option<TimeoutSettings>,
 ) -> GDResult<Response> {
+    minetest_master_server::query(timeout_settings.unwrap_or_default())
+        .and_then(|servers| find_server(servers, address, port))
+        .map_err(|e| {
+            e.with_address(server_address(address, port))
+                .with_stage(QueryStage::Info)
+        })
+}
+
+/// Async counterpart of [query_with_timeout].
+#[cfg(feature = "async")]
+pub async fn query_with_timeout_async(
+    address: &IpAddr,
+    port: Option<u16>,
+    timeout_settings: &Option<TimeoutSettings>,
+) -> GDResult<Response> {
+    minetest_master_server::query_async(timeout_settings.unwrap_or_default())
+        .await
+        .and_then(|servers| find_server(servers, address, port))
+        .map_err(|e| {
+            e.with_address(server_address(address, port))
+                .with_stage(QueryStage::Info)
+        })
+}
+
+/// Parse the master server's list, as received, to the server at `address`
+/// and `port`.
+pub fn parse(data: &[u8], address: &IpAddr, port: Option<u16>) -> GDResult<Response> {
+    let servers = serde_json::from_slice(data).map_err(|e| GDErrorKind::ProtocolFormat.context(e))?;
+    find_server(servers, address, port)
+}
+
+/// The address of the server, as listed by the master server.
+fn server_address(address: &IpAddr, port: Option<u16>) -> SocketAddr {
+    SocketAddr::new(*address, port.unwrap_or(DEFAULT_PORT))
+}
+
+fn find_server(servers: minetest_master_server::Response, address: &IpAddr, port: Option<u16>) -> GDResult<Response> {
     let address = address.to_string();
-    let port = port.unwrap_or(30000);
+    let port = port.unwrap_or(DEFAULT_PORT);
 
-    let servers = minetest_master_server::query(timeout_settings.unwrap_or_default())?;
     for server in servers.list {
         if server.ip == address && server.port == port {
             return Ok(server.into());
@@ -17,8 +17,9 @@ pub use quake::*;
 pub use unreal2::*;
 pub use valve::*;
 
+// Not a glob, Minetest's query functions would clash with the generic ones.
 #[cfg(all(feature = "tls", feature = "serde", feature = "services"))]
-pub use minetest::*;
+pub use minetest::{Player, Response};
 
 /// Battalion 1944
 pub mod battalion1944;
@@ -43,6 +44,20 @@ pub use types::*;
 pub mod query;
 pub use query::*;
 
+pub mod bulk;
+pub use bulk::{BulkQuery, BulkResult, BulkResults};
+
+pub mod cache;
+pub use cache::QueryCache;
+
+pub mod monitor;
+pub use monitor::{Monitor, MonitorEvent, MonitorEvents};
+
+pub mod detect;
+pub use detect::{detect, detect_with_timeout, discover_query_port, Detection, DiscoveredQueryPort};
+#[cfg(feature = "async")]
+pub use detect::{detect_async, detect_with_timeout_async, discover_query_port_async};
+
 #[cfg(feature = "game_defs")]
 mod definitions;
 
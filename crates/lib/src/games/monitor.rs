@@ -0,0 +1,505 @@
+//! Watching servers for changes.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::games::query::query_with_timeout_and_extra_settings;
+use crate::games::types::Game;
+use crate::protocols::types::{CommonPlayerOwned, CommonResponseOwned, ExtraRequestSettings, TimeoutSettings};
+use crate::{GDError, GDResult};
+
+/// Default time between two queries of a monitored server.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Default number of consecutive failed queries after which a monitored server
+/// is considered offline.
+pub const DEFAULT_OFFLINE_AFTER: usize = 2;
+
+/// A server to query on an interval, emitting the changes between its
+/// successive responses as [events](MonitorEvent).
+///
+/// The first answer emits [CameOnline](MonitorEvent::CameOnline) (with the
+/// whole response), the next ones emit what changed since the previous one.
+/// The server goes [offline](MonitorEvent::WentOffline) after a [number of
+/// consecutive failed queries](Monitor::offline_after), so a single lost
+/// packet doesn't make it go offline and come back online.
+///
+/// ```no_run
+/// use gamedig::{Monitor, MonitorEvent, GAMES};
+/// use std::time::Duration;
+///
+/// let game = GAMES.get("teamfortress2").unwrap();
+/// let monitor = Monitor::new(game.clone(), "127.0.0.1:27015".parse().unwrap()).interval(Duration::from_secs(30));
+///
+/// for event in monitor.run() {
+///     match event {
+///         MonitorEvent::PlayerJoined(player) => println!("{} joined", player.name),
+///         MonitorEvent::WentOffline(error) => println!("offline: {:?}", error.kind),
+///         event => println!("{event:?}"),
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Monitor {
+    game: Game,
+    address: SocketAddr,
+    interval: Duration,
+    offline_after: usize,
+    timeout_settings: Option<TimeoutSettings>,
+    extra_settings: Option<ExtraRequestSettings>,
+}
+
+/// A change of a monitored server.
+#[derive(Debug, PartialEq)]
+pub enum MonitorEvent {
+    /// The server answered (for the first time, or after being offline).
+    CameOnline(CommonResponseOwned),
+    /// The server's queries failed [enough times in a
+    /// row](Monitor::offline_after), with the last error (for the first time,
+    /// or after it answered).
+    WentOffline(GDError),
+    /// A player is in the response, that wasn't in the previous one.
+    PlayerJoined(CommonPlayerOwned),
+    /// A player of the previous response isn't in the response anymore.
+    PlayerLeft(CommonPlayerOwned),
+    /// The map changed.
+    MapChanged {
+        from: Option<String>,
+        to: Option<String>,
+    },
+    /// The number of players online changed.
+    PlayerCountChanged { from: u32, to: u32 },
+    /// The server got (or lost) a password.
+    PasswordChanged {
+        from: Option<bool>,
+        to: Option<bool>,
+    },
+}
+
+impl Monitor {
+    /// Create a monitor of a server, querying it every [default
+    /// interval](DEFAULT_INTERVAL) with the default timeout settings.
+    pub const fn new(game: Game, address: SocketAddr) -> Self {
+        Self {
+            game,
+            address,
+            interval: DEFAULT_INTERVAL,
+            offline_after: DEFAULT_OFFLINE_AFTER,
+            timeout_settings: None,
+            extra_settings: None,
+        }
+    }
+
+    /// Set the time between the starts of two queries.
+    pub const fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set the number of consecutive failed queries after which the server is
+    /// considered offline (a value of 0 is treated as 1), the [default
+    /// one](DEFAULT_OFFLINE_AFTER) tolerates a single failure.
+    pub const fn offline_after(mut self, failures: usize) -> Self {
+        self.offline_after = failures;
+        self
+    }
+
+    /// Set the timeout settings used for every query.
+    pub const fn timeout_settings(mut self, timeout_settings: TimeoutSettings) -> Self {
+        self.timeout_settings = Some(timeout_settings);
+        self
+    }
+
+    /// Set the extra request settings used for every query (instead of the
+    /// game's ones).
+    pub fn extra_settings(mut self, extra_settings: ExtraRequestSettings) -> Self {
+        self.extra_settings = Some(extra_settings);
+        self
+    }
+
+    fn query(&self) -> GDResult<CommonResponseOwned> {
+        query_with_timeout_and_extra_settings(
+            &self.game,
+            &self.address.ip(),
+            Some(self.address.port()),
+            self.timeout_settings,
+            self.extra_settings.clone(),
+        )
+        .map(CommonResponseOwned::from)
+    }
+
+    /// Start monitoring on a thread, returning an iterator over the events as
+    /// they happen.
+    ///
+    /// Dropping the iterator stops the monitoring (after the current query).
+    pub fn run(self) -> MonitorEvents {
+        let (sender, receiver) = mpsc::channel();
+        let (stop, stopped) = mpsc::channel::<()>();
+
+        thread::spawn(move || {
+            let mut state = State::new(self.offline_after);
+            loop {
+                let started = Instant::now();
+                for event in state.update(self.query()) {
+                    if sender.send(event).is_err() {
+                        // Nobody is listening anymore.
+                        return;
+                    }
+                }
+
+                // Wakes up early once the iterator (and so the stop sender) is dropped.
+                match stopped.recv_timeout(self.interval.saturating_sub(started.elapsed())) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    _ => return,
+                }
+            }
+        });
+
+        MonitorEvents {
+            receiver,
+            _stop: stop,
+        }
+    }
+
+    /// Async counterpart of [Monitor::run], monitoring on a tokio task.
+    ///
+    /// Must be called from within a tokio runtime. Dropping the receiver stops
+    /// the monitoring (after the current query or wait between two queries).
+    #[cfg(feature = "async")]
+    pub fn run_async(self) -> tokio::sync::mpsc::Receiver<MonitorEvent> {
+        use crate::games::query::query_with_timeout_and_extra_settings_async;
+
+        let (sender, receiver) = tokio::sync::mpsc::channel(16);
+
+        tokio::spawn(async move {
+            let mut state = State::new(self.offline_after);
+            loop {
+                let started = tokio::time::Instant::now();
+                let result = query_with_timeout_and_extra_settings_async(
+                    &self.game,
+                    &self.address.ip(),
+                    Some(self.address.port()),
+                    self.timeout_settings,
+                    self.extra_settings.clone(),
+                )
+                .await
+                .map(CommonResponseOwned::from);
+
+                for event in state.update(result) {
+                    if sender.send(event).await.is_err() {
+                        // Nobody is listening anymore.
+                        return;
+                    }
+                }
+
+                tokio::time::sleep_until(started + self.interval).await;
+                if sender.is_closed() {
+                    return;
+                }
+            }
+        });
+
+        receiver
+    }
+}
+
+/// Iterator over the events of a running [Monitor], see [Monitor::run].
+pub struct MonitorEvents {
+    receiver: mpsc::Receiver<MonitorEvent>,
+    /// Dropped with the iterator, to stop the monitoring thread.
+    _stop: mpsc::Sender<()>,
+}
+
+impl Iterator for MonitorEvents {
+    type Item = MonitorEvent;
+
+    fn next(&mut self) -> Option<Self::Item> { self.receiver.recv().ok() }
+}
+
+/// What is known about the server from its previous queries.
+#[derive(Debug)]
+struct State {
+    status: Status,
+    /// Consecutive failed queries.
+    failures: usize,
+    offline_after: usize,
+}
+
+#[derive(Debug, Default)]
+enum Status {
+    #[default]
+    Unknown,
+    /// With the last response.
+    Online(CommonResponseOwned),
+    Offline,
+}
+
+impl State {
+    const fn new(offline_after: usize) -> Self {
+        Self {
+            status: Status::Unknown,
+            failures: 0,
+            offline_after: if offline_after == 0 { 1 } else { offline_after },
+        }
+    }
+
+    /// Move to the result of a new query, returning the changes.
+    fn update(&mut self, result: GDResult<CommonResponseOwned>) -> Vec<MonitorEvent> {
+        match result {
+            Ok(response) => {
+                self.failures = 0;
+                match std::mem::replace(&mut self.status, Status::Online(response.clone())) {
+                    Status::Online(previous) => changes(&previous, &response),
+                    _ => vec![MonitorEvent::CameOnline(response)],
+                }
+            }
+            Err(error) => {
+                self.failures += 1;
+                if matches!(self.status, Status::Offline) || self.failures < self.offline_after {
+                    return Vec::new();
+                }
+
+                self.status = Status::Offline;
+                vec![MonitorEvent::WentOffline(error)]
+            }
+        }
+    }
+}
+
+/// The changes between two responses of a server.
+fn changes(previous: &CommonResponseOwned, current: &CommonResponseOwned) -> Vec<MonitorEvent> {
+    let mut events = Vec::new();
+
+    if previous.map != current.map {
+        events.push(MonitorEvent::MapChanged {
+            from: previous.map.clone(),
+            to: current.map.clone(),
+        });
+    }
+
+    if previous.has_password != current.has_password {
+        events.push(MonitorEvent::PasswordChanged {
+            from: previous.has_password,
+            to: current.has_password,
+        });
+    }
+
+    // Players can only be compared if both responses have them.
+    if let (Some(previous), Some(current)) = (&previous.players, &current.players) {
+        events.extend(missing(previous, current).map(MonitorEvent::PlayerLeft));
+        events.extend(missing(current, previous).map(MonitorEvent::PlayerJoined));
+    }
+
+    if previous.players_online != current.players_online {
+        events.push(MonitorEvent::PlayerCountChanged {
+            from: previous.players_online,
+            to: current.players_online,
+        });
+    }
+
+    events
+}
+
+/// The players of `players` that aren't in `others`, by name (players with the
+/// same name are counted).
+fn missing<'a>(
+    players: &'a [CommonPlayerOwned],
+    others: &'a [CommonPlayerOwned],
+) -> impl Iterator<Item = CommonPlayerOwned> + 'a {
+    let mut others_by_name: HashMap<&str, usize> = HashMap::new();
+    for other in others {
+        *others_by_name.entry(other.name.as_str()).or_default() += 1;
+    }
+
+    players.iter().filter_map(move |player| {
+        match others_by_name.get_mut(player.name.as_str()) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                None
+            }
+            _ => Some(player.clone()),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::quake::QuakeVersion;
+    use crate::protocols::types::Protocol;
+    use crate::GDErrorKind::PacketReceive;
+
+    use gamedig_fake_servers::{quake::Quake, Fake, FakeServer, Script};
+
+    fn player(name: &str) -> CommonPlayerOwned {
+        CommonPlayerOwned {
+            name: name.to_string(),
+            score: None,
+        }
+    }
+
+    fn response(map: &str, players: &[&str]) -> CommonResponseOwned {
+        CommonResponseOwned {
+            map: Some(map.to_string()),
+            players_online: players.len() as u32,
+            players: Some(players.iter().map(|name| player(name)).collect()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn first_result_is_online_or_offline() {
+        let mut state = State::new(1);
+        assert_eq!(
+            state.update(Ok(response("de_dust2", &["a"]))),
+            [MonitorEvent::CameOnline(response("de_dust2", &["a"]))]
+        );
+
+        let mut state = State::new(1);
+        assert_eq!(
+            state.update(Err(PacketReceive.into())),
+            [MonitorEvent::WentOffline(PacketReceive.into())]
+        );
+    }
+
+    #[test]
+    fn going_offline_and_back() {
+        let mut state = State::new(2);
+        state.update(Ok(response("de_dust2", &[])));
+
+        assert!(state.update(Err(PacketReceive.into())).is_empty());
+        assert_eq!(
+            state.update(Err(PacketReceive.into())),
+            [MonitorEvent::WentOffline(PacketReceive.into())]
+        );
+        assert!(state.update(Err(PacketReceive.into())).is_empty());
+        assert_eq!(
+            state.update(Ok(response("de_inferno", &[]))),
+            [MonitorEvent::CameOnline(response("de_inferno", &[]))]
+        );
+    }
+
+    #[test]
+    fn transient_failure_emits_nothing() {
+        let mut state = State::new(DEFAULT_OFFLINE_AFTER);
+        state.update(Ok(response("de_dust2", &["a"])));
+
+        assert!(state.update(Err(PacketReceive.into())).is_empty());
+        assert_eq!(
+            state.update(Ok(response("de_inferno", &["a"]))),
+            [MonitorEvent::MapChanged {
+                from: Some("de_dust2".to_string()),
+                to: Some("de_inferno".to_string()),
+            }]
+        );
+
+        // The failures have to be consecutive
+        assert!(state.update(Err(PacketReceive.into())).is_empty());
+        assert!(state.update(Ok(response("de_inferno", &["a"]))).is_empty());
+        assert!(state.update(Err(PacketReceive.into())).is_empty());
+    }
+
+    #[test]
+    fn diffs_responses() {
+        let mut state = State::new(1);
+        state.update(Ok(response("de_dust2", &["a", "b", "b"])));
+
+        assert!(state
+            .update(Ok(response("de_dust2", &["b", "a", "b"])))
+            .is_empty());
+
+        let mut changed = response("de_inferno", &["b", "c", "d"]);
+        changed.has_password = Some(true);
+        assert_eq!(
+            state.update(Ok(changed)),
+            [
+                MonitorEvent::MapChanged {
+                    from: Some("de_dust2".to_string()),
+                    to: Some("de_inferno".to_string()),
+                },
+                MonitorEvent::PasswordChanged {
+                    from: None,
+                    to: Some(true),
+                },
+                MonitorEvent::PlayerLeft(player("a")),
+                MonitorEvent::PlayerLeft(player("b")),
+                MonitorEvent::PlayerJoined(player("c")),
+                MonitorEvent::PlayerJoined(player("d")),
+            ]
+        );
+    }
+
+    #[test]
+    fn counts_without_players() {
+        let mut state = State::new(1);
+        let mut previous = response("de_dust2", &["a"]);
+        previous.players = None;
+        state.update(Ok(previous));
+
+        let mut current = response("de_dust2", &["a", "b"]);
+        current.players = None;
+        assert_eq!(
+            state.update(Ok(current)),
+            [MonitorEvent::PlayerCountChanged { from: 1, to: 2 }]
+        );
+    }
+
+    /// A Quake 1 server answering with the maps, then not answering.
+    fn server(maps: &[&str]) -> (FakeServer, Monitor) {
+        let script = maps.iter().fold(Script::new(), |script, map| {
+            script.then(Quake::one().value("map", map).script().times(1))
+        });
+        let server = FakeServer::udp(script).unwrap();
+        let address = server.address();
+
+        let game = Game {
+            name: "Test",
+            default_port: 27500,
+            protocol: Protocol::Quake(QuakeVersion::One),
+            request_settings: ExtraRequestSettings::default(),
+            query_ports: &[],
+        };
+        let timeout = Some(Duration::from_millis(50));
+        let monitor = Monitor::new(game, address)
+            .interval(Duration::from_millis(10))
+            .timeout_settings(TimeoutSettings::new(timeout, timeout, timeout, 0).unwrap());
+
+        (server, monitor)
+    }
+
+    fn expected() -> Vec<MonitorEvent> {
+        vec![
+            MonitorEvent::MapChanged {
+                from: Some("e1m1".to_string()),
+                to: Some("e1m2".to_string()),
+            },
+            MonitorEvent::WentOffline(PacketReceive.into()),
+        ]
+    }
+
+    #[test]
+    fn monitors_server() {
+        let (_server, monitor) = server(&["e1m1", "e1m2"]);
+
+        let mut events = monitor.run();
+        assert!(matches!(events.next(), Some(MonitorEvent::CameOnline(_))));
+        assert_eq!(events.take(2).collect::<Vec<_>>(), expected());
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn monitors_server_async() {
+        let (_server, monitor) = server(&["e1m1", "e1m2"]);
+
+        let mut events = monitor.run_async();
+        assert!(matches!(
+            events.recv().await,
+            Some(MonitorEvent::CameOnline(_))
+        ));
+        assert_eq!(events.recv().await, expected().into_iter().next());
+        assert_eq!(events.recv().await, expected().into_iter().nth(1));
+    }
+}
@@ -6,11 +6,15 @@ use std::net::{IpAddr, SocketAddr};
 use crate::games::minetest;
 use crate::games::types::Game;
 use crate::games::{eco, ffow, jc2m, mindustry, minecraft, savage2, theship};
+use crate::metrics;
 use crate::protocols;
 use crate::protocols::gamespy::GameSpyVersion;
 use crate::protocols::quake::QuakeVersion;
 use crate::protocols::types::{CommonResponse, ExtraRequestSettings, ProprietaryProtocol, Protocol, TimeoutSettings};
-use crate::GDResult;
+use crate::{deadline, trace, GDError, GDResult};
+
+#[cfg(feature = "async")]
+use crate::trace::Instrument;
 
 /// Make a query given a game definition
 #[inline]
@@ -30,12 +34,55 @@ pub fn query_with_timeout(
 }
 
 /// Make a query given a game definition, timeout settings, and extra settings
+///
+/// The query is bound by the settings' [total
+/// timeout](TimeoutSettings::with_total_timeout), if any. Its errors carry the
+/// address and the protocol of the query. It is observed by the installed
+/// [metrics observer](metrics::set_observer), if any.
 pub fn query_with_timeout_and_extra_settings(
     game: &Game,
     address: &IpAddr,
     port: Option<u16>,
     timeout_settings: Option<TimeoutSettings>,
     extra_settings: Option<ExtraRequestSettings>,
+) -> GDResult<Box<dyn CommonResponse>> {
+    let socket_addr = SocketAddr::new(*address, port.unwrap_or(game.default_port));
+    trace::span!("query", game = game.name, address = %socket_addr, protocol = ?game.protocol).in_scope(|| {
+        metrics::observe(game, socket_addr, || {
+            failed(
+                deadline::scope(&timeout_settings, || {
+                    dispatch(game, address, port, timeout_settings, extra_settings)
+                })
+                .map_err(|e| with_query_context(e, game, socket_addr)),
+            )
+        })
+    })
+}
+
+/// Emit an event with the error of a failed query.
+#[cfg(feature = "tracing")]
+fn failed<T>(result: GDResult<T>) -> GDResult<T> {
+    result.inspect_err(|e| {
+        tracing::debug!(kind = ?e.kind, stage = ?e.stage(), attempt = ?e.attempt(), "query failed");
+    })
+}
+
+#[cfg(not(feature = "tracing"))]
+const fn failed<T>(result: GDResult<T>) -> GDResult<T> { result }
+
+/// Add the address and the protocol of a query to its error.
+fn with_query_context(error: GDError, game: &Game, address: SocketAddr) -> GDError {
+    error
+        .with_address(address)
+        .with_protocol(game.protocol.clone())
+}
+
+fn dispatch(
+    game: &Game,
+    address: &IpAddr,
+    port: Option<u16>,
+    timeout_settings: Option<TimeoutSettings>,
+    extra_settings: Option<ExtraRequestSettings>,
 ) -> GDResult<Box<dyn CommonResponse>> {
     let socket_addr = SocketAddr::new(*address, port.unwrap_or(game.default_port));
     Ok(match &game.protocol {
@@ -135,3 +182,248 @@ pub fn query_with_timeout_and_extra_settings(
         }
     })
 }
+
+/// Async counterpart of [query].
+#[cfg(feature = "async")]
+#[inline]
+pub async fn query_async(game: &Game, address: &IpAddr, port: Option<u16>) -> GDResult<Box<dyn CommonResponse>> {
+    query_with_timeout_and_extra_settings_async(game, address, port, None, None).await
+}
+
+/// Async counterpart of [query_with_timeout].
+#[cfg(feature = "async")]
+#[inline]
+pub async fn query_with_timeout_async(
+    game: &Game,
+    address: &IpAddr,
+    port: Option<u16>,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<Box<dyn CommonResponse>> {
+    query_with_timeout_and_extra_settings_async(game, address, port, timeout_settings, None).await
+}
+
+/// Async counterpart of [query_with_timeout_and_extra_settings].
+#[cfg(feature = "async")]
+pub async fn query_with_timeout_and_extra_settings_async(
+    game: &Game,
+    address: &IpAddr,
+    port: Option<u16>,
+    timeout_settings: Option<TimeoutSettings>,
+    extra_settings: Option<ExtraRequestSettings>,
+) -> GDResult<Box<dyn CommonResponse>> {
+    let socket_addr = SocketAddr::new(*address, port.unwrap_or(game.default_port));
+    metrics::observe_async(game, socket_addr, async {
+        failed(
+            deadline::scope_async(
+                &timeout_settings,
+                dispatch_async(game, address, port, timeout_settings, extra_settings),
+            )
+            .await
+            .map_err(|e| with_query_context(e, game, socket_addr)),
+        )
+    })
+    .instrument(trace::span!("query", game = game.name, address = %socket_addr, protocol = ?game.protocol))
+    .await
+}
+
+#[cfg(feature = "async")]
+async fn dispatch_async(
+    game: &Game,
+    address: &IpAddr,
+    port: Option<u16>,
+    timeout_settings: Option<TimeoutSettings>,
+    extra_settings: Option<ExtraRequestSettings>,
+) -> GDResult<Box<dyn CommonResponse>> {
+    let socket_addr = SocketAddr::new(*address, port.unwrap_or(game.default_port));
+    Ok(match &game.protocol {
+        Protocol::Valve(engine) => {
+            protocols::valve::query_async(
+                &socket_addr,
+                *engine,
+                extra_settings
+                    .or_else(|| Option::from(game.request_settings.clone()))
+                    .map(ExtraRequestSettings::into),
+                timeout_settings,
+            )
+            .await
+            .map(Box::new)?
+        }
+        #[cfg(feature = "tls")]
+        Protocol::Epic(credentials) => {
+            protocols::epic::query_with_timeout_async(credentials.clone(), &socket_addr, timeout_settings)
+                .await
+                .map(Box::new)?
+        }
+        Protocol::Gamespy(version) => {
+            match version {
+                GameSpyVersion::One => {
+                    protocols::gamespy::one::query_async(&socket_addr, timeout_settings)
+                        .await
+                        .map(Box::new)?
+                }
+                GameSpyVersion::Two => {
+                    protocols::gamespy::two::query_async(&socket_addr, timeout_settings)
+                        .await
+                        .map(Box::new)?
+                }
+                GameSpyVersion::Three => {
+                    protocols::gamespy::three::query_async(&socket_addr, timeout_settings)
+                        .await
+                        .map(Box::new)?
+                }
+            }
+        }
+        Protocol::Quake(version) => {
+            match version {
+                QuakeVersion::One => {
+                    protocols::quake::one::query_async(&socket_addr, timeout_settings)
+                        .await
+                        .map(Box::new)?
+                }
+                QuakeVersion::Two => {
+                    protocols::quake::two::query_async(&socket_addr, timeout_settings)
+                        .await
+                        .map(Box::new)?
+                }
+                QuakeVersion::Three => {
+                    protocols::quake::three::query_async(&socket_addr, timeout_settings)
+                        .await
+                        .map(Box::new)?
+                }
+            }
+        }
+        Protocol::Unreal2 => {
+            protocols::unreal2::query_async(
+                &socket_addr,
+                &extra_settings
+                    .map(ExtraRequestSettings::into)
+                    .unwrap_or_default(),
+                timeout_settings,
+            )
+            .await
+            .map(Box::new)?
+        }
+        Protocol::PROPRIETARY(protocol) => {
+            match protocol {
+                ProprietaryProtocol::Savage2 => {
+                    savage2::query_with_timeout_async(address, port, timeout_settings)
+                        .await
+                        .map(Box::new)?
+                }
+                ProprietaryProtocol::TheShip => {
+                    theship::query_with_timeout_async(address, port, timeout_settings)
+                        .await
+                        .map(Box::new)?
+                }
+                ProprietaryProtocol::FFOW => {
+                    ffow::query_with_timeout_async(address, port, timeout_settings)
+                        .await
+                        .map(Box::new)?
+                }
+                ProprietaryProtocol::JC2M => {
+                    jc2m::query_with_timeout_async(address, port, timeout_settings)
+                        .await
+                        .map(Box::new)?
+                }
+                ProprietaryProtocol::Mindustry => {
+                    mindustry::query_async(address, port, &timeout_settings)
+                        .await
+                        .map(Box::new)?
+                }
+                ProprietaryProtocol::Minecraft(version) => {
+                    match version {
+                        Some(minecraft::Server::Java) => {
+                            minecraft::protocol::query_java_async(
+                                &socket_addr,
+                                timeout_settings,
+                                extra_settings.map(ExtraRequestSettings::into),
+                            )
+                            .await
+                            .map(Box::new)?
+                        }
+                        Some(minecraft::Server::Bedrock) => {
+                            minecraft::protocol::query_bedrock_async(&socket_addr, timeout_settings)
+                                .await
+                                .map(Box::new)?
+                        }
+                        Some(minecraft::Server::Legacy(group)) => {
+                            minecraft::protocol::query_legacy_specific_async(*group, &socket_addr, timeout_settings)
+                                .await
+                                .map(Box::new)?
+                        }
+                        None => {
+                            minecraft::protocol::query_async(
+                                &socket_addr,
+                                timeout_settings,
+                                extra_settings.map(ExtraRequestSettings::into),
+                            )
+                            .await
+                            .map(Box::new)?
+                        }
+                    }
+                }
+                ProprietaryProtocol::Eco => {
+                    eco::query_with_timeout_and_extra_settings_async(
+                        address,
+                        port,
+                        &timeout_settings,
+                        extra_settings.map(ExtraRequestSettings::into),
+                    )
+                    .await
+                    .map(Box::new)?
+                }
+                #[cfg(all(feature = "services", feature = "tls", feature = "serde"))]
+                ProprietaryProtocol::Minetest => {
+                    minetest::query_with_timeout_async(address, port, &timeout_settings)
+                        .await
+                        .map(Box::new)?
+                }
+            }
+        }
+    })
+}
+
+#[cfg(all(test, feature = "game_defs"))]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[cfg(feature = "async")]
+    fn assert_send<T: Send>(_: &T) {}
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn query_async_is_send() {
+        let game = crate::GAMES.get("teamfortress2").unwrap();
+        let address = IpAddr::V4(std::net::Ipv4Addr::LOCALHOST);
+
+        let query = query_async(game, &address, None);
+        assert_send(&query);
+    }
+
+    #[test]
+    fn total_timeout_bounds_the_query() {
+        // A server that never answers
+        let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let game = crate::GAMES.get("teamfortress2").unwrap();
+
+        let timeout = Some(Duration::from_secs(4));
+        let timeout_settings = TimeoutSettings::new(timeout, timeout, timeout, 5)
+            .unwrap()
+            .with_total_timeout(Duration::from_millis(100))
+            .unwrap();
+
+        let started = Instant::now();
+        let error = query_with_timeout(
+            game,
+            &server.local_addr().unwrap().ip(),
+            Some(server.local_addr().unwrap().port()),
+            Some(timeout_settings),
+        )
+        .err()
+        .unwrap();
+
+        assert_eq!(error.kind, crate::GDErrorKind::DeadlineExceeded);
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
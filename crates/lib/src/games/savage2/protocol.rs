@@ -1,8 +1,12 @@
 use crate::buffer::{Buffer, Utf8Decoder};
 use crate::games::savage2::types::Response;
 use crate::protocols::types::TimeoutSettings;
+#[cfg(feature = "async")]
+use crate::socket::{AsyncSocket, AsyncUdpSocket};
 use crate::socket::{Socket, UdpSocket};
-use crate::GDResult;
+#[cfg(feature = "async")]
+use crate::GDError;
+use crate::{trace, GDResult, QueryStage};
 use byteorder::LittleEndian;
 use std::net::{IpAddr, SocketAddr};
 
@@ -13,11 +17,48 @@ pub fn query_with_timeout(
     port: Option<u16>,
     timeout_settings: Option<TimeoutSettings>,
 ) -> GDResult<Response> {
-    let addr = &SocketAddr::new(*address, port.unwrap_or(11235));
-    let mut socket = UdpSocket::new(addr, &timeout_settings)?;
+    let address = SocketAddr::new(*address, port.unwrap_or(11235));
+    get_response(&address, &timeout_settings).map_err(|e| e.with_address(address).with_stage(QueryStage::Info))
+}
+
+fn get_response(address: &SocketAddr, timeout_settings: &Option<TimeoutSettings>) -> GDResult<Response> {
+    let mut socket = UdpSocket::new(address, timeout_settings)?;
+    trace::event!(DEBUG, "Savage 2 request");
     socket.send(&[0x01])?;
     let data = socket.receive(None)?;
-    let mut buffer = Buffer::<LittleEndian>::new(&data);
+
+    Ok(Response {
+        latency: socket.latency(),
+        ..parse(&data)?
+    })
+}
+
+/// Async counterpart of [query_with_timeout].
+#[cfg(feature = "async")]
+pub async fn query_with_timeout_async(
+    address: &IpAddr,
+    port: Option<u16>,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<Response> {
+    let address = SocketAddr::new(*address, port.unwrap_or(11235));
+    async {
+        let mut socket = AsyncUdpSocket::new(&address, &timeout_settings).await?;
+        trace::event!(DEBUG, "Savage 2 request");
+        socket.send(&[0x01]).await?;
+        let data = socket.receive(None).await?;
+
+        Ok(Response {
+            latency: socket.latency(),
+            ..parse(&data)?
+        })
+    }
+    .await
+    .map_err(|e: GDError| e.with_address(address).with_stage(QueryStage::Info))
+}
+
+/// Parse a response packet, as received.
+pub fn parse(data: &[u8]) -> GDResult<Response> {
+    let mut buffer = Buffer::<LittleEndian>::new(data);
 
     buffer.move_cursor(12)?;
 
@@ -33,5 +74,6 @@ pub fn query_with_timeout(
         game_mode: buffer.read_string::<Utf8Decoder>(None)?,
         protocol_version: buffer.read_string::<Utf8Decoder>(None)?,
         level_minimum: buffer.read::<u8>()?,
+        latency: None,
     })
 }
@@ -2,6 +2,7 @@ use crate::protocols::types::CommonResponse;
 use crate::protocols::GenericResponse;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -17,6 +18,7 @@ pub struct Response {
     pub game_mode: String,
     pub protocol_version: String,
     pub level_minimum: u8,
+    pub latency: Option<Duration>,
 }
 
 impl CommonResponse for Response {
@@ -27,4 +29,6 @@ impl CommonResponse for Response {
     fn map(&self) -> Option<&str> { Some(&self.map) }
     fn players_maximum(&self) -> u32 { self.players_maximum.into() }
     fn players_online(&self) -> u32 { self.players_online.into() }
+
+    fn latency(&self) -> Option<Duration> { self.latency }
 }
@@ -12,12 +12,23 @@ pub fn query_with_timeout(
     port: Option<u16>,
     timeout_settings: Option<TimeoutSettings>,
 ) -> GDResult<Response> {
-    let valve_response = valve::query(
-        &SocketAddr::new(*address, port.unwrap_or(27015)),
-        Engine::new(2400),
-        None,
-        timeout_settings,
-    )?;
+    let address = SocketAddr::new(*address, port.unwrap_or(27015));
+    let valve_response = valve::query(&address, Engine::new(2400), None, timeout_settings)?;
 
-    Response::new_from_valve_response(valve_response)
+    // The response is made of the info, players and rules: its errors are
+    // only known to be about this address.
+    Response::new_from_valve_response(valve_response).map_err(|e| e.with_address(address))
+}
+
+/// Async counterpart of [query_with_timeout].
+#[cfg(feature = "async")]
+pub async fn query_with_timeout_async(
+    address: &IpAddr,
+    port: Option<u16>,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<Response> {
+    let address = SocketAddr::new(*address, port.unwrap_or(27015));
+    let valve_response = valve::query_async(&address, Engine::new(2400), None, timeout_settings).await?;
+
+    Response::new_from_valve_response(valve_response).map_err(|e| e.with_address(address))
 }
@@ -1,4 +1,4 @@
-use crate::protocols::types::{CommonPlayer, CommonResponse, GenericPlayer};
+use crate::protocols::types::{CommonPlayer, CommonResponse, Diagnostic, GenericPlayer};
 use crate::protocols::valve::{get_optional_extracted_data, Server, ServerPlayer};
 use crate::protocols::{valve, GenericResponse};
 use crate::GDErrorKind::PacketBad;
@@ -7,6 +7,7 @@ use std::collections::HashMap;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
@@ -61,6 +62,11 @@ pub struct Response {
     pub mode: u8,
     pub witnesses: u8,
     pub duration: u8,
+    pub latency: Option<Duration>,
+    /// The players and rules requests that failed, if they were only
+    /// attempted.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 impl CommonResponse for Response {
@@ -82,6 +88,10 @@ impl CommonResponse for Response {
                 .collect(),
         )
     }
+
+    fn latency(&self) -> Option<Duration> { self.latency }
+
+    fn diagnostics(&self) -> &[Diagnostic] { &self.diagnostics }
 }
 
 impl Response {
@@ -117,6 +127,8 @@ impl Response {
             mode: the_unwrapped_ship.mode,
             witnesses: the_unwrapped_ship.witnesses,
             duration: the_unwrapped_ship.duration,
+            latency: response.latency,
+            diagnostics: response.diagnostics,
         })
     }
 }
@@ -7,7 +7,7 @@ use serde::{Deserialize, Serialize};
 
 /// Definition of a game
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Game {
     /// Full name of the game
     pub name: &'static str,
@@ -17,4 +17,80 @@ pub struct Game {
     pub protocol: Protocol,
     /// Request settings.
     pub request_settings: ExtraRequestSettings,
+    /// The ports the game is known to answer queries on given its connect
+    /// port, for games whose query port differs from it (see
+    /// [discover_query_port](crate::games::discover_query_port)).
+    ///
+    /// Not deserialized, deserialized games have none.
+    #[cfg_attr(feature = "serde", serde(skip_deserializing))]
+    pub query_ports: &'static [QueryPort],
+}
+
+impl Game {
+    /// The ports to look for the game's query port on given the port players
+    /// connect to: the known [query ports](Game::query_ports), the connect
+    /// port itself and the game's default port.
+    pub fn query_port_candidates(&self, connect_port: u16) -> Vec<u16> {
+        let mut candidates = Vec::with_capacity(self.query_ports.len() + 2);
+
+        let ports = self
+            .query_ports
+            .iter()
+            .filter_map(|query_port| query_port.resolve(connect_port));
+        for port in ports.chain([connect_port, self.default_port]) {
+            if !candidates.contains(&port) {
+                candidates.push(port);
+            }
+        }
+
+        candidates
+    }
+}
+
+/// A port a game answers queries on, relative to the port players connect to.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum QueryPort {
+    /// The connect port plus an offset.
+    Offset(i32),
+    /// A fixed port, whatever the connect port is.
+    Fixed(u16),
+}
+
+impl QueryPort {
+    /// The query port given the connect port, `None` if the offset goes out of
+    /// the port range.
+    pub fn resolve(&self, connect_port: u16) -> Option<u16> {
+        match self {
+            Self::Offset(offset) => u16::try_from(i32::from(connect_port) + offset).ok(),
+            Self::Fixed(port) => Some(*port),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::valve::Engine;
+
+    #[test]
+    fn query_port_candidates() {
+        let game = Game {
+            name: "Test",
+            default_port: 2303,
+            protocol: Protocol::Valve(Engine::Source(None)),
+            request_settings: ExtraRequestSettings::default(),
+            query_ports: &[
+                QueryPort::Offset(1),
+                QueryPort::Fixed(27016),
+                QueryPort::Offset(-3000),
+            ],
+        };
+
+        assert_eq!(game.query_port_candidates(2302), vec![2303, 27016, 2302]);
+        assert_eq!(
+            game.query_port_candidates(65535),
+            vec![27016, 62535, 65535, 2303]
+        );
+    }
 }
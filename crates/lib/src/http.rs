@@ -8,13 +8,16 @@
 #![allow(dead_code)]
 
 use crate::GDErrorKind::{HostLookup, InvalidInput, PacketReceive, PacketSend, ProtocolFormat};
-use crate::{GDResult, TimeoutSettings};
+use crate::{deadline, trace, GDResult, TimeoutSettings};
 
 use std::io::Read;
 use std::net::{SocketAddr, SocketAddrV4, SocketAddrV6, ToSocketAddrs};
 
 use ureq::{Agent, AgentBuilder, Request};
-use url::{Host, Url};
+use url::{form_urlencoded, Host, Url};
+
+#[cfg(feature = "packet_capture")]
+use crate::capture::http::{response_head, HttpCapture};
 
 use serde::{de::DeserializeOwned, Serialize};
 
@@ -34,6 +37,8 @@ const MAX_RESPONSE_LENGTH: usize = 1024 * 1024 * 1024;
 pub struct HttpClient {
     client: Agent,
     address: Url,
+    /// The address requests connect to.
+    remote_address: SocketAddr,
     headers: Vec<(String, String)>,
 }
 
@@ -146,6 +151,11 @@ impl HttpClient {
             client_builder = client_builder.timeout_connect(connect_timeout);
         }
 
+        // Requests must not outlive the query's deadline
+        if let Some(time_left) = deadline::clamp(None)? {
+            client_builder = client_builder.timeout(time_left);
+        }
+
         // Every request sent from this client will connect to the address set
         {
             let address = *address;
@@ -161,26 +171,14 @@ impl HttpClient {
 
         let client = client_builder.build();
 
-        let host = http_settings
-            .hostname
-            .map(S::into)
-            .unwrap_or_else(|| address.ip().to_string());
+        let remote_address = *address;
+        let (address, headers) = client_url_and_headers(address, http_settings)?;
 
         Ok(Self {
             client,
-            // TODO: Use Url from_parts if it gets added
-            address: Url::parse(&format!(
-                "{}//{}:{}",
-                http_settings.protocol.as_str(),
-                host,
-                address.port()
-            ))
-            .map_err(|e| InvalidInput.context(e))?,
-            headers: http_settings
-                .headers
-                .into_iter()
-                .map(|(k, v)| (k.into(), v.into()))
-                .collect(),
+            address,
+            remote_address,
+            headers,
         })
     }
 
@@ -199,13 +197,7 @@ impl HttpClient {
         U::Error: std::error::Error + Send + Sync + 'static,
     {
         let url: Url = url.try_into().map_err(|e| InvalidInput.context(e))?;
-
-        let host = url
-            .host()
-            .ok_or_else(|| InvalidInput.context("URL used to create a HTTPClient must have a host"))?;
-        let port = url
-            .port_or_known_default()
-            .ok_or_else(|| InvalidInput.context("URL used to create HttpClient must have a port"))?;
+        let (host, port) = url_host_and_port(&url)?;
 
         let address = match host {
             Host::Ipv4(ip) => SocketAddr::V4(SocketAddrV4::new(ip, port)),
@@ -219,21 +211,13 @@ impl HttpClient {
             }
         };
 
-        let http_settings = HttpSettings {
-            hostname: url.host_str(),
-            protocol: match url.scheme() {
-                #[cfg(feature = "tls")]
-                "https" => HttpProtocol::Https,
-                _ => HttpProtocol::Http,
-            },
-            headers: headers.unwrap_or_default(),
-        };
-
-        Self::new(&address, timeout_settings, http_settings)
+        Self::new(&address, timeout_settings, url_http_settings(&url, headers))
     }
 
     /// Send a HTTP GET request and return the response data as a buffer.
-    pub fn get(&mut self, path: &str, headers: HttpHeaders) -> GDResult<Vec<u8>> { self.request("GET", path, headers) }
+    pub fn get(&mut self, path: &str, headers: HttpHeaders) -> GDResult<Vec<u8>> {
+        self.request("GET", path, headers, None)
+    }
 
     /// Send a HTTP GET request and parse the JSON resonse.
     pub fn get_json<T: DeserializeOwned>(&mut self, path: &str, headers: HttpHeaders) -> GDResult<T> {
@@ -280,49 +264,89 @@ impl HttpClient {
         request
     }
 
-    /// Internal request method, makes a request with an arbitrary HTTP method.
-    #[inline]
-    fn request(&mut self, method: &str, path: &str, headers: HttpHeaders) -> GDResult<Vec<u8>> {
+    /// Internal request method, makes a request with an arbitrary HTTP method
+    /// and an optional body (with its content type).
+    fn request(
+        &mut self,
+        method: &str,
+        path: &str,
+        headers: HttpHeaders,
+        body: Option<(&str, Vec<u8>)>,
+    ) -> GDResult<Vec<u8>> {
         // Append the path to the pre-parsed URL and create a request object.
         self.address.set_path(path);
-        let request = self.make_request(method, headers);
-
-        // Send the request.
-        let http_response = request.call().map_err(|e| PacketSend.context(e))?;
-
-        let length = if let Some(length) = http_response.header("Content-Length") {
-            length
-                .parse::<usize>()
-                .map_err(|e| ProtocolFormat.context(e))?
-                .min(MAX_RESPONSE_LENGTH)
-        } else {
-            5012 // Sensible default allocation
+        let mut request = self.make_request(method, headers);
+
+        let body = match body {
+            Some((content_type, body)) => {
+                request = request.set("Content-Type", content_type);
+                Some(body)
+            }
+            None => None,
         };
 
-        let mut buffer: Vec<u8> = Vec::with_capacity(length);
+        #[cfg(feature = "packet_capture")]
+        let capture = HttpCapture::new(
+            &self.remote_address,
+            &self.address,
+            &request,
+            body.as_deref().unwrap_or_default(),
+        );
 
-        let _ = http_response
-            .into_reader()
-            .take(MAX_RESPONSE_LENGTH as u64)
-            .read_to_end(&mut buffer)
-            .map_err(|e| PacketReceive.context(e))?;
+        trace::event!(
+            DEBUG,
+            method,
+            url = %self.address,
+            bytes = body.as_ref().map_or(0, Vec::len),
+            "HTTP request"
+        );
 
-        Ok(buffer)
+        // Send the request, error statuses' responses are read as well so that
+        // they get captured.
+        let result = match &body {
+            Some(body) => request.send_bytes(body),
+            None => request.call(),
+        };
+        let (http_response, status_error) = match result {
+            Ok(http_response) => (http_response, None),
+            Err(ureq::Error::Status(status, http_response)) => {
+                let error = format!("{}: status code {status}", http_response.get_url());
+                (http_response, Some(error))
+            }
+            Err(e) => {
+                #[cfg(feature = "packet_capture")]
+                capture.record(None, &[]);
+
+                return Err(PacketSend.context(e));
+            }
+        };
+
+        #[cfg(feature = "packet_capture")]
+        let response_head = response_head(&http_response);
+
+        trace::event!(DEBUG, status = http_response.status(), "HTTP response");
+        let data = read_body(http_response);
+        trace::event!(
+            TRACE,
+            bytes = data.as_ref().map_or(0, Vec::len),
+            "HTTP received"
+        );
+
+        #[cfg(feature = "packet_capture")]
+        capture.record(Some(response_head), data.as_deref().unwrap_or_default());
+
+        match status_error {
+            Some(error) => Err(PacketSend.context(error)),
+            None => data,
+        }
     }
 
     /// Send a HTTP request without any data and parse the JSON response.
     #[inline]
     fn request_json<T: DeserializeOwned>(&mut self, method: &str, path: &str, headers: HttpHeaders) -> GDResult<T> {
-        // Append the path to the pre-parsed URL and create a request object.
-        self.address.set_path(path);
-        let request = self.make_request(method, headers);
+        let data = self.request(method, path, headers, None)?;
 
-        // Send the request and parse the response as JSON.
-        request
-            .call()
-            .map_err(|e| PacketSend.context(e))?
-            .into_json::<T>()
-            .map_err(|e| ProtocolFormat.context(e))
+        serde_json::from_slice(&data).map_err(|e| ProtocolFormat.context(e))
     }
 
     /// Send a HTTP request with JSON data and parse the JSON response.
@@ -334,14 +358,10 @@ impl HttpClient {
         headers: HttpHeaders,
         data: S,
     ) -> GDResult<T> {
-        self.address.set_path(path);
-        let request = self.make_request(method, headers);
+        let body = serde_json::to_vec(&data).map_err(|e| InvalidInput.context(e))?;
+        let data = self.request(method, path, headers, Some(("application/json", body)))?;
 
-        request
-            .send_json(data)
-            .map_err(|e| PacketSend.context(e))?
-            .into_json::<T>()
-            .map_err(|e| ProtocolFormat.context(e))
+        serde_json::from_slice(&data).map_err(|e| ProtocolFormat.context(e))
     }
 
     /// Send a HTTP request with FORM data and parse the JSON response.
@@ -353,14 +373,281 @@ impl HttpClient {
         headers: HttpHeaders,
         data: &[(&str, &str)],
     ) -> GDResult<T> {
+        let body = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(data)
+            .finish();
+        let data = self.request(
+            method,
+            path,
+            headers,
+            Some(("application/x-www-form-urlencoded", body.into_bytes())),
+        )?;
+
+        serde_json::from_slice(&data).map_err(|e| ProtocolFormat.context(e))
+    }
+}
+
+/// Read the body of a response.
+fn read_body(http_response: ureq::Response) -> GDResult<Vec<u8>> {
+    let length = if let Some(length) = http_response.header("Content-Length") {
+        length
+            .parse::<usize>()
+            .map_err(|e| ProtocolFormat.context(e))?
+            .min(MAX_RESPONSE_LENGTH)
+    } else {
+        5012 // Sensible default allocation
+    };
+
+    let mut buffer: Vec<u8> = Vec::with_capacity(length);
+
+    let _ = http_response
+        .into_reader()
+        .take(MAX_RESPONSE_LENGTH as u64)
+        .read_to_end(&mut buffer)
+        .map_err(|e| PacketReceive.context(e))?;
+
+    Ok(buffer)
+}
+
+/// Build the base URL and the owned default headers of a client connecting to
+/// address.
+fn client_url_and_headers<S: Into<String>>(
+    address: &SocketAddr,
+    http_settings: HttpSettings<S>,
+) -> GDResult<(Url, Vec<(String, String)>)> {
+    let host = http_settings
+        .hostname
+        .map(S::into)
+        .unwrap_or_else(|| address.ip().to_string());
+
+    // TODO: Use Url from_parts if it gets added
+    let url = Url::parse(&format!(
+        "{}//{}:{}",
+        http_settings.protocol.as_str(),
+        host,
+        address.port()
+    ))
+    .map_err(|e| InvalidInput.context(e))?;
+
+    let headers = http_settings
+        .headers
+        .into_iter()
+        .map(|(k, v)| (k.into(), v.into()))
+        .collect();
+
+    Ok((url, headers))
+}
+
+/// Get the host and port of a URL used to create a client.
+fn url_host_and_port(url: &Url) -> GDResult<(Host<&str>, u16)> {
+    let host = url
+        .host()
+        .ok_or_else(|| InvalidInput.context("URL used to create a HTTPClient must have a host"))?;
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| InvalidInput.context("URL used to create HttpClient must have a port"))?;
+
+    Ok((host, port))
+}
+
+/// Get the settings of a client created from a URL.
+fn url_http_settings<'a>(url: &'a Url, headers: Option<Vec<(&'a str, &'a str)>>) -> HttpSettings<&'a str> {
+    HttpSettings {
+        hostname: url.host_str(),
+        protocol: match url.scheme() {
+            #[cfg(feature = "tls")]
+            "https" => HttpProtocol::Https,
+            _ => HttpProtocol::Http,
+        },
+        headers: headers.unwrap_or_default(),
+    }
+}
+
+/// Async HTTP request client, the async counterpart of [HttpClient].
+#[cfg(feature = "async")]
+pub struct AsyncHttpClient {
+    client: reqwest::Client,
+    address: Url,
+    headers: Vec<(String, String)>,
+}
+
+#[cfg(feature = "async")]
+impl AsyncHttpClient {
+    /// Creates a new AsyncHttpClient that can be used to send requests, see
+    /// [HttpClient::new].
+    pub fn new<S: Into<String>>(
+        address: &SocketAddr,
+        timeout_settings: &Option<TimeoutSettings>,
+        http_settings: HttpSettings<S>,
+    ) -> GDResult<Self> {
+        let (url, headers) = client_url_and_headers(address, http_settings)?;
+
+        // Set a friendly user-agent string
+        let mut client_builder = reqwest::Client::builder().user_agent(concat!(
+            env!("CARGO_PKG_NAME"),
+            "/",
+            env!("CARGO_PKG_VERSION")
+        ));
+
+        // Set timeout settings
+        let (read_timeout, _) = TimeoutSettings::get_read_and_write_or_defaults(timeout_settings);
+
+        if let Some(read_timeout) = read_timeout {
+            client_builder = client_builder.read_timeout(read_timeout);
+        }
+
+        if let Some(connect_timeout) = TimeoutSettings::get_connect_or_default(timeout_settings) {
+            client_builder = client_builder.connect_timeout(connect_timeout);
+        }
+
+        // Requests must not outlive the query's deadline
+        if let Some(time_left) = deadline::clamp(None)? {
+            client_builder = client_builder.timeout(time_left);
+        }
+
+        // Every request sent from this client will connect to the address set
+        if let Some(Host::Domain(domain)) = url.host() {
+            client_builder = client_builder.resolve(domain, *address);
+        }
+
+        let client = client_builder
+            .build()
+            .map_err(|e| InvalidInput.context(e))?;
+
+        Ok(Self {
+            client,
+            address: url,
+            headers,
+        })
+    }
+
+    /// Create a new async HTTP client from a pre-existing URL, see
+    /// [HttpClient::from_url].
+    pub async fn from_url<U: TryInto<Url>>(
+        url: U,
+        timeout_settings: &Option<TimeoutSettings>,
+        headers: Option<Vec<(&str, &str)>>,
+    ) -> GDResult<Self>
+    where
+        U::Error: std::error::Error + Send + Sync + 'static,
+    {
+        let url: Url = url.try_into().map_err(|e| InvalidInput.context(e))?;
+        let (host, port) = url_host_and_port(&url)?;
+
+        let address = match host {
+            Host::Ipv4(ip) => SocketAddr::V4(SocketAddrV4::new(ip, port)),
+            Host::Ipv6(ip) => SocketAddr::V6(SocketAddrV6::new(ip, port, 0, 0)),
+            Host::Domain(domain) => {
+                tokio::net::lookup_host((domain, port))
+                    .await
+                    .map_err(|e| HostLookup.context(e))?
+                    .next()
+                    .ok_or_else(|| HostLookup.context("No socket addresses found for host"))?
+            }
+        };
+
+        Self::new(&address, timeout_settings, url_http_settings(&url, headers))
+    }
+
+    /// Send a HTTP GET request and return the response data as a buffer.
+    pub async fn get(&mut self, path: &str, headers: HttpHeaders<'_>) -> GDResult<Vec<u8>> {
+        let request = self.make_request(reqwest::Method::GET, path, headers);
+
+        Self::send(request).await
+    }
+
+    /// Send a HTTP GET request and parse the JSON resonse.
+    pub async fn get_json<T: DeserializeOwned>(&mut self, path: &str, headers: HttpHeaders<'_>) -> GDResult<T> {
+        let data = self.get(path, headers).await?;
+
+        serde_json::from_slice(&data).map_err(|e| ProtocolFormat.context(e))
+    }
+
+    /// Send a HTTP Post request with JSON data and parse a JSON response.
+    pub async fn post_json<T: DeserializeOwned, S: Serialize>(
+        &mut self,
+        path: &str,
+        headers: HttpHeaders<'_>,
+        data: S,
+    ) -> GDResult<T> {
+        let request = self
+            .make_request(reqwest::Method::POST, path, headers)
+            .json(&data);
+
+        let data = Self::send(request).await?;
+        serde_json::from_slice(&data).map_err(|e| ProtocolFormat.context(e))
+    }
+
+    /// Send a HTTP Post request with FORM data and parse a JSON response.
+    pub async fn post_json_with_form<T: DeserializeOwned>(
+        &mut self,
+        path: &str,
+        headers: HttpHeaders<'_>,
+        data: &[(&str, &str)],
+    ) -> GDResult<T> {
+        let request = self
+            .make_request(reqwest::Method::POST, path, headers)
+            .form(data);
+
+        let data = Self::send(request).await?;
+        serde_json::from_slice(&data).map_err(|e| ProtocolFormat.context(e))
+    }
+
+    fn make_request(&mut self, method: reqwest::Method, path: &str, headers: HttpHeaders) -> reqwest::RequestBuilder {
+        // Append the path to the pre-parsed URL and create a request object.
         self.address.set_path(path);
-        let request = self.make_request(method, headers);
+        trace::event!(DEBUG, %method, url = %self.address, "HTTP request");
+        let mut request = self.client.request(method, self.address.clone());
+
+        // Set the request headers.
+        for (key, value) in self.headers.iter() {
+            request = request.header(key, value);
+        }
+
+        if let Some(headers) = headers {
+            for (key, value) in headers {
+                request = request.header(*key, *value);
+            }
+        }
 
         request
-            .send_form(data)
-            .map_err(|e| PacketSend.context(e))?
-            .into_json::<T>()
-            .map_err(|e| ProtocolFormat.context(e))
+    }
+
+    /// Send a request and read its response body.
+    async fn send(request: reqwest::RequestBuilder) -> GDResult<Vec<u8>> {
+        let mut http_response = request
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| PacketSend.context(e))?;
+        trace::event!(
+            DEBUG,
+            status = http_response.status().as_u16(),
+            "HTTP response"
+        );
+
+        let length = http_response
+            .content_length()
+            .map_or(5012, |length| length as usize) // Sensible default allocation
+            .min(MAX_RESPONSE_LENGTH);
+
+        let mut buffer: Vec<u8> = Vec::with_capacity(length);
+
+        while let Some(chunk) = http_response
+            .chunk()
+            .await
+            .map_err(|e| PacketReceive.context(e))?
+        {
+            let remaining = MAX_RESPONSE_LENGTH - buffer.len();
+            buffer.extend_from_slice(&chunk[.. chunk.len().min(remaining)]);
+
+            if buffer.len() == MAX_RESPONSE_LENGTH {
+                break;
+            }
+        }
+
+        trace::event!(TRACE, bytes = buffer.len(), "HTTP received");
+        Ok(buffer)
     }
 }
 
@@ -412,6 +699,159 @@ mod tests {
         );
     }
 
+    /// Answer a single HTTP request with a JSON body, returns the request.
+    fn serve_json(listener: std::net::TcpListener, status: &'static str) -> std::thread::JoinHandle<String> {
+        use std::io::Write;
+
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+
+            // Read the head, then as much of the body as it announces.
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            let body_end = loop {
+                let size = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[.. size]);
+
+                let text = String::from_utf8_lossy(&request).to_lowercase();
+                if let Some(head_end) = text.find("\r\n\r\n") {
+                    let length = text[.. head_end]
+                        .lines()
+                        .find_map(|line| line.strip_prefix("content-length: "))
+                        .map_or(0, |length| length.trim().parse().unwrap());
+                    break head_end + 4 + length;
+                }
+            };
+            while request.len() < body_end {
+                let size = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[.. size]);
+            }
+
+            let body = r#"{"players":7}"#;
+            let response = format!(
+                "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: \
+                 close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+
+            String::from_utf8(request).unwrap()
+        })
+    }
+
+    #[test]
+    fn http_post_json() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = serve_json(listener, "200 OK");
+
+        let mut client = HttpClient::new(&address, &None, HttpSettings::<&str>::default()).unwrap();
+        let response: serde_json::Value = client
+            .post_json("/info", None, serde_json::json!({"full": true}))
+            .unwrap();
+        assert_eq!(response["players"], 7);
+
+        let request = server.join().unwrap();
+        assert!(request.starts_with("POST /info HTTP/1.1"));
+        assert!(request.ends_with(r#"{"full":true}"#));
+    }
+
+    #[test]
+    fn http_error_status() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = serve_json(listener, "503 Service Unavailable");
+
+        let mut client = HttpClient::new(&address, &None, HttpSettings::<&str>::default()).unwrap();
+        let error = client.get("/info", None).unwrap_err();
+        assert_eq!(error.kind, crate::GDErrorKind::PacketSend);
+
+        server.join().unwrap();
+    }
+
+    #[cfg(feature = "packet_capture")]
+    #[test]
+    fn http_captured() {
+        use crate::capture::{self, CapturePacket, Direction, Writer};
+        use std::sync::{Arc, Mutex};
+
+        /// Keeps the data received.
+        #[derive(Default)]
+        struct Received(Vec<u8>);
+
+        impl Writer for Received {
+            fn write(&mut self, packet: &CapturePacket, data: &[u8]) -> GDResult<()> {
+                if packet.direction == Direction::Receive {
+                    self.0.extend_from_slice(data);
+                }
+
+                Ok(())
+            }
+        }
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = serve_json(listener, "503 Service Unavailable");
+
+        let received = Arc::new(Mutex::new(Received::default()));
+        let mut client = HttpClient::new(&address, &None, HttpSettings::<&str>::default()).unwrap();
+        assert!(capture::with_writer(received.clone(), || client.get("/info", None)).is_err());
+        server.join().unwrap();
+
+        let received = String::from_utf8(received.lock().unwrap().0.clone()).unwrap();
+        assert!(received.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        assert!(received.ends_with("\r\n\r\n{\"players\":7}"));
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn async_http_client_new() {
+        const ADDRESS: SocketAddr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 8000));
+
+        let settings = HttpSettings::default()
+            .hostname("github.com")
+            .header("Authorization", "UUDDLRLRBA");
+
+        let client = AsyncHttpClient::new(&ADDRESS, &None, settings).unwrap();
+
+        assert_eq!(client.address.as_str(), "http://github.com:8000/");
+        assert_eq!(
+            client.headers,
+            vec![(String::from("Authorization"), String::from("UUDDLRLRBA")),]
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn async_http_get_json() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).await.unwrap();
+
+            let body = r#"{"players":7}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: \
+                 close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let mut client = AsyncHttpClient::new(&address, &None, HttpSettings::<&str>::default()).unwrap();
+        let response: serde_json::Value = client.get_json("/info", None).await.unwrap();
+
+        assert_eq!(response["players"], 7);
+        server.await.unwrap();
+    }
+
     #[cfg(feature = "tls")]
     #[test]
     #[ignore = "HTTP requests won't work without internet"]
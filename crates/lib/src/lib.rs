@@ -35,18 +35,30 @@
 //! `game_defs` - include game definitions for programmatic access (enabled by
 //! default). <br>
 //! `clap` - enable clap derivations for gamedig settings types. <br>
-//! `tls` - enable TLS support for the HTTP client.
+//! `tls` - enable TLS support for the HTTP client. <br>
+//! `async` - enable async (tokio based) query functions, e.g.
+//! `query_async`. <br>
+//! `tracing` - emit spans of the queries and events of the packets sent and
+//! received through `tracing`.
 
 pub mod errors;
 #[cfg(feature = "games")]
 pub mod games;
+pub mod machine;
+pub mod metrics;
 pub mod protocols;
+pub mod report;
+pub mod responder;
 #[cfg(feature = "services")]
 pub mod services;
+pub mod transport;
 
 mod buffer;
+mod deadline;
 mod http;
+mod retry;
 mod socket;
+mod trace;
 mod utils;
 
 #[cfg(feature = "packet_capture")]
@@ -62,4 +74,4 @@ pub use query::*;
 pub use services::*;
 
 // Re-export types needed to call games::query::query in the root
-pub use protocols::types::{ExtraRequestSettings, TimeoutSettings};
+pub use protocols::types::{ExtraRequestSettings, NetworkSettings, RetryPolicy, TimeoutSettings};
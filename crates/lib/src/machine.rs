@@ -0,0 +1,150 @@
+//! Sans-IO state machines of the protocols' exchanges, to drive queries from
+//! any event loop.
+//!
+//! A [Machine] doesn't touch the network: it gives the packets to send, is
+//! told what was received (or that nothing was in time) and eventually gives
+//! its output. The query functions of this crate drive the same machines over
+//! their (blocking or async) sockets.
+//!
+//! ```no_run
+//! use gamedig::machine::{Event, Machine, Step};
+//! use gamedig::protocols::valve::{self, Engine, RequestMachine};
+//! use std::net::UdpSocket;
+//!
+//! let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+//! socket.connect("127.0.0.1:27015").unwrap();
+//!
+//! let engine = Engine::new(440);
+//! let mut machine = RequestMachine::info(engine);
+//! let mut packets = machine.start();
+//! let response = loop {
+//!     for packet in packets {
+//!         socket.send(&packet).unwrap();
+//!     }
+//!
+//!     let mut buf = [0; 6144];
+//!     let size = socket.recv(&mut buf).unwrap();
+//!     packets = match machine.handle(Event::Received(&buf[.. size])).unwrap() {
+//!         Step::Send(packets) => packets,
+//!         Step::Receive => Vec::new(),
+//!         Step::Done(response) => break response,
+//!     };
+//! };
+//!
+//! println!("{:#?}", valve::parse_info(&engine, &response));
+//! ```
+
+use crate::GDErrorKind::PacketReceive;
+use crate::{GDError, GDResult};
+
+#[cfg(feature = "async")]
+use crate::socket::AsyncSocket;
+use crate::socket::Socket;
+
+/// Something that happened to the connection of a [Machine].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event<'a> {
+    /// Data was received: a datagram for UDP, whatever was read for TCP.
+    Received(&'a [u8]),
+    /// Nothing was received in time.
+    TimedOut,
+}
+
+/// What a [Machine] needs next.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step<T> {
+    /// Send these packets (or stream chunks), in order, then wait for an
+    /// event.
+    Send(Vec<Vec<u8>>),
+    /// Wait for an event without sending anything.
+    Receive,
+    /// The exchange is over.
+    Done(T),
+}
+
+/// A protocol exchange, as a state machine.
+///
+/// Once done or failed, a machine has to be started again, which is also how
+/// an exchange is retried.
+pub trait Machine {
+    type Output;
+
+    /// Whether TCP data is handed to the machine as it is read, rather than
+    /// once the server stops sending, for exchanges where the server waits for
+    /// more requests before closing the connection.
+    const STREAMED: bool = false;
+
+    /// (Re)start the exchange, returns the packets to send first.
+    fn start(&mut self) -> Vec<Vec<u8>>;
+
+    /// Handle an event, returns what is needed next.
+    fn handle(&mut self, event: Event<'_>) -> GDResult<Step<Self::Output>>;
+}
+
+/// The error of an exchange that timed out.
+pub(crate) fn timed_out() -> GDError { PacketReceive.context("Timed out waiting for a response") }
+
+/// Hand a machine what a socket received, or [Event::TimedOut] if the socket
+/// timed out, whose error is kept if the machine gives up.
+fn handle_received<M: Machine>(machine: &mut M, received: GDResult<Vec<u8>>) -> GDResult<Step<M::Output>> {
+    match received {
+        Ok(data) => machine.handle(Event::Received(&data)),
+        Err(error) if error.kind == PacketReceive => machine.handle(Event::TimedOut).map_err(|_| error),
+        Err(error) => Err(error),
+    }
+}
+
+/// Start a machine and run it over a socket until it is done, receiving at
+/// most `receive_size` bytes at a time.
+///
+/// A socket's receive timeouts are [Event::TimedOut]s, other errors (such as
+/// the deadline passing) end the exchange.
+pub(crate) fn drive<M: Machine>(
+    socket: &mut impl Socket,
+    machine: &mut M,
+    receive_size: Option<usize>,
+) -> GDResult<M::Output> {
+    let mut packets = machine.start();
+    loop {
+        for packet in packets {
+            socket.send(&packet)?;
+        }
+
+        let received = match M::STREAMED {
+            true => socket.receive_available(receive_size),
+            false => socket.receive(receive_size),
+        };
+
+        packets = match handle_received(machine, received)? {
+            Step::Send(packets) => packets,
+            Step::Receive => Vec::new(),
+            Step::Done(output) => return Ok(output),
+        };
+    }
+}
+
+/// Async counterpart of [drive].
+#[cfg(feature = "async")]
+pub(crate) async fn drive_async<M: Machine>(
+    socket: &mut impl AsyncSocket,
+    machine: &mut M,
+    receive_size: Option<usize>,
+) -> GDResult<M::Output> {
+    let mut packets = machine.start();
+    loop {
+        for packet in packets {
+            socket.send(&packet).await?;
+        }
+
+        let received = match M::STREAMED {
+            true => socket.receive_available(receive_size).await,
+            false => socket.receive(receive_size).await,
+        };
+
+        packets = match handle_received(machine, received)? {
+            Step::Send(packets) => packets,
+            Step::Receive => Vec::new(),
+            Step::Done(output) => return Ok(output),
+        };
+    }
+}
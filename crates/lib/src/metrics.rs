@@ -0,0 +1,336 @@
+//! Hooks for exporting metrics of the queries.
+//!
+//! Install an [Observer] with [set_observer] to be called for every packet
+//! sent and received, every retry and every query made through
+//! [query](crate::games::query::query) (or its variants), e.g. to count them
+//! in a metrics exporter without wrapping every call:
+//!
+//! ```
+//! use gamedig::metrics::{self, Observer, QueryInfo, QueryOutcome};
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use std::sync::Arc;
+//!
+//! #[derive(Default)]
+//! struct Failures(AtomicUsize);
+//!
+//! impl Observer for Failures {
+//!     fn query_finished(&self, _query: &QueryInfo, outcome: &QueryOutcome) {
+//!         if outcome.error.is_some() {
+//!             self.0.fetch_add(1, Ordering::Relaxed);
+//!         }
+//!     }
+//! }
+//!
+//! metrics::set_observer(Arc::new(Failures::default()));
+//! ```
+
+use crate::protocols::types::Protocol;
+use crate::report::QueryReport;
+use crate::GDErrorKind;
+
+use std::cell::RefCell;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+
+#[cfg(feature = "games")]
+use crate::{games::Game, report, GDResult};
+#[cfg(all(feature = "games", feature = "async"))]
+use std::future::Future;
+#[cfg(feature = "games")]
+use std::time::Instant;
+
+/// Called by the queries as they go, all the methods do nothing by default.
+///
+/// The packet and retry methods are called for every query, the query methods
+/// only for the queries made through [query](crate::games::query::query) (or
+/// its variants). The packet and retry methods are given the query they belong
+/// to when it is one of these, `None` otherwise (e.g. when calling a protocol
+/// directly).
+///
+/// They are called from the querying thread (or task), so they should be
+/// quick, and they must not install or remove an observer.
+pub trait Observer: Send + Sync {
+    /// A query started.
+    fn query_started(&self, _query: &QueryInfo) {}
+    /// A query finished, successfully or not.
+    fn query_finished(&self, _query: &QueryInfo, _outcome: &QueryOutcome) {}
+    /// A packet (or TCP write) of some bytes was sent.
+    fn packet_sent(&self, _query: Option<&QueryInfo>, _bytes: usize) {}
+    /// A packet (or TCP read) of some bytes was received.
+    fn packet_received(&self, _query: Option<&QueryInfo>, _bytes: usize) {}
+    /// A request is retried after failing with an error.
+    fn retried(&self, _query: Option<&QueryInfo>, _kind: &GDErrorKind) {}
+}
+
+/// What is being queried.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryInfo<'a> {
+    /// Name of the game.
+    pub game: &'a str,
+    /// Protocol of the game.
+    pub protocol: &'a Protocol,
+    /// Address of the server.
+    pub address: SocketAddr,
+}
+
+/// How a query went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryOutcome<'a> {
+    /// Time from the start to the end of the query.
+    pub duration: Duration,
+    /// The kind of error the query failed with, `None` if it succeeded.
+    pub error: Option<&'a GDErrorKind>,
+    /// Retries, packets and bytes of the query.
+    pub report: &'a QueryReport,
+}
+
+static OBSERVER: RwLock<Option<Arc<dyn Observer>>> = RwLock::new(None);
+/// Whether an observer is installed, checked first so that packets aren't
+/// slowed down by the lock when there is none.
+static OBSERVING: AtomicBool = AtomicBool::new(false);
+
+/// The query made through [query](crate::games::query::query) (or its
+/// variants) that is running.
+#[derive(Debug, Clone)]
+struct CurrentQuery {
+    game: &'static str,
+    protocol: Protocol,
+    address: SocketAddr,
+}
+
+impl CurrentQuery {
+    const fn info(&self) -> QueryInfo<'_> {
+        QueryInfo {
+            game: self.game,
+            protocol: &self.protocol,
+            address: self.address,
+        }
+    }
+}
+
+thread_local! {
+    static QUERY: RefCell<Option<CurrentQuery>> = const { RefCell::new(None) };
+}
+
+#[cfg(feature = "async")]
+tokio::task_local! {
+    static TASK_QUERY: CurrentQuery;
+}
+
+/// Install the observer of all the queries, replacing the previous one (which
+/// is returned).
+pub fn set_observer(observer: Arc<dyn Observer>) -> Option<Arc<dyn Observer>> {
+    let mut installed = OBSERVER.write().unwrap_or_else(PoisonError::into_inner);
+    OBSERVING.store(true, Ordering::Release);
+    installed.replace(observer)
+}
+
+/// Remove the observer of the queries, returning it.
+pub fn remove_observer() -> Option<Arc<dyn Observer>> {
+    let mut installed = OBSERVER.write().unwrap_or_else(PoisonError::into_inner);
+    OBSERVING.store(false, Ordering::Release);
+    installed.take()
+}
+
+#[cfg(feature = "games")]
+fn observer() -> Option<Arc<dyn Observer>> {
+    if !OBSERVING.load(Ordering::Acquire) {
+        return None;
+    }
+
+    OBSERVER
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// Call the observer (if any) with the current query (if any).
+fn notify(call: impl FnOnce(&dyn Observer, Option<&QueryInfo>)) {
+    if !OBSERVING.load(Ordering::Acquire) {
+        return;
+    }
+
+    let installed = OBSERVER.read().unwrap_or_else(PoisonError::into_inner);
+    let Some(observer) = installed.as_deref() else {
+        return;
+    };
+
+    #[cfg(feature = "async")]
+    if TASK_QUERY.try_with(|_| ()).is_ok() {
+        return TASK_QUERY.with(|query| call(observer, Some(&query.info())));
+    }
+
+    QUERY.with(|query| {
+        call(
+            observer,
+            query.borrow().as_ref().map(CurrentQuery::info).as_ref(),
+        )
+    });
+}
+
+pub(crate) fn sent(bytes: usize) { notify(|observer, query| observer.packet_sent(query, bytes)); }
+
+pub(crate) fn received(bytes: usize) { notify(|observer, query| observer.packet_received(query, bytes)); }
+
+pub(crate) fn retried(kind: &GDErrorKind) { notify(|observer, query| observer.retried(query, kind)); }
+
+/// Run a query of a game, telling the observer (if any) when it starts and
+/// finishes.
+///
+/// The query's report is also recorded in the report being collected, if any.
+#[cfg(feature = "games")]
+pub(crate) fn observe<T>(game: &Game, address: SocketAddr, run: impl FnOnce() -> GDResult<T>) -> GDResult<T> {
+    let Some(observer) = observer() else {
+        return run();
+    };
+
+    let current = current_query(game, address);
+    let query = current.info();
+    observer.query_started(&query);
+    let started = Instant::now();
+
+    let previous = QUERY.with(|query| query.replace(Some(current.clone())));
+    let (result, query_report) = report::collect(run);
+    QUERY.with(|query| query.replace(previous));
+
+    finish(&*observer, &query, started, &result, &query_report);
+
+    result
+}
+
+/// Async counterpart of [observe].
+#[cfg(all(feature = "games", feature = "async"))]
+pub(crate) async fn observe_async<T>(
+    game: &Game,
+    address: SocketAddr,
+    run: impl Future<Output = GDResult<T>>,
+) -> GDResult<T> {
+    let Some(observer) = observer() else {
+        return run.await;
+    };
+
+    let current = current_query(game, address);
+    let query = current.info();
+    observer.query_started(&query);
+    let started = Instant::now();
+
+    let (result, query_report) = TASK_QUERY
+        .scope(current.clone(), report::collect_async(run))
+        .await;
+
+    finish(&*observer, &query, started, &result, &query_report);
+
+    result
+}
+
+#[cfg(feature = "games")]
+fn current_query(game: &Game, address: SocketAddr) -> CurrentQuery {
+    CurrentQuery {
+        game: game.name,
+        protocol: game.protocol.clone(),
+        address,
+    }
+}
+
+#[cfg(feature = "games")]
+fn finish<T>(
+    observer: &dyn Observer,
+    query: &QueryInfo,
+    started: Instant,
+    result: &GDResult<T>,
+    query_report: &QueryReport,
+) {
+    report::merge(query_report);
+
+    let outcome = QueryOutcome {
+        duration: started.elapsed(),
+        error: result.as_ref().err().map(|e| &e.kind),
+        report: query_report,
+    };
+    observer.query_finished(query, &outcome);
+}
+
+#[cfg(all(test, feature = "game_defs"))]
+mod tests {
+    use super::*;
+    use crate::GDErrorKind::PacketReceive;
+
+    use gamedig_fake_servers::{quake::Quake, Fake, FakeServer};
+    use std::sync::Mutex;
+
+    /// Records the finished queries to an address (and the packets they
+    /// sent), other tests may run queries (to other addresses) while the
+    /// observer is installed.
+    struct Recorder {
+        address: SocketAddr,
+        finished: Mutex<Vec<(String, Option<GDErrorKind>, QueryReport)>>,
+        sent: Mutex<Vec<String>>,
+    }
+
+    impl Observer for Recorder {
+        fn packet_sent(&self, query: Option<&QueryInfo>, _bytes: usize) {
+            if let Some(query) = query.filter(|query| query.address == self.address) {
+                self.sent.lock().unwrap().push(query.game.to_string());
+            }
+        }
+
+        fn query_finished(&self, query: &QueryInfo, outcome: &QueryOutcome) {
+            if query.address == self.address {
+                self.finished.lock().unwrap().push((
+                    query.game.to_string(),
+                    outcome.error.cloned(),
+                    outcome.report.clone(),
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn observes_queries() {
+        // Don't answer the second query
+        let server = FakeServer::udp(Quake::one().script().times(1)).unwrap();
+        let address = server.address();
+
+        let recorder = Arc::new(Recorder {
+            address,
+            finished: Mutex::new(Vec::new()),
+            sent: Mutex::new(Vec::new()),
+        });
+        set_observer(recorder.clone());
+
+        let game = crate::GAMES.get("quake1").unwrap();
+        let timeout = Some(Duration::from_millis(100));
+        let timeout_settings = crate::TimeoutSettings::new(timeout, timeout, timeout, 0).unwrap();
+        let ((first, second), outer) = report::collect(|| {
+            (
+                crate::games::query::query(game, &address.ip(), Some(address.port())),
+                crate::games::query::query_with_timeout(
+                    game,
+                    &address.ip(),
+                    Some(address.port()),
+                    Some(timeout_settings),
+                ),
+            )
+        });
+        remove_observer();
+
+        assert!(first.is_ok());
+        assert_eq!(second.err().unwrap().kind, PacketReceive);
+
+        let finished = recorder.finished.lock().unwrap();
+        assert_eq!(finished.len(), 2);
+        assert_eq!(finished[0].0, "Quake 1");
+        assert_eq!(finished[0].1, None);
+        assert_eq!(finished[0].2.packets_sent, 1);
+        assert_eq!(finished[0].2.packets_received, 1);
+        assert_eq!(finished[1].1, Some(PacketReceive));
+        assert_eq!(finished[1].2.packets_received, 0);
+        assert_eq!(*recorder.sent.lock().unwrap(), ["Quake 1", "Quake 1"]);
+
+        // The reports of the queries are still recorded in the outer one
+        assert_eq!(outer.packets_sent, 2);
+        assert_eq!(outer.packets_received, 1);
+    }
+}
@@ -1,7 +1,11 @@
+#[cfg(feature = "async")]
+use crate::http::AsyncHttpClient;
 use crate::http::HttpClient;
 use crate::protocols::epic::Response;
-use crate::GDErrorKind::{JsonParse, PacketBad};
-use crate::{GDResult, TimeoutSettings};
+#[cfg(feature = "async")]
+use crate::trace::Instrument;
+use crate::GDErrorKind::{JsonParse, PacketBad, ProtocolFormat};
+use crate::{trace, GDResult, QueryStage, TimeoutSettings};
 use base64::prelude::BASE64_STANDARD;
 use base64::Engine;
 use serde::Deserialize;
@@ -13,7 +17,7 @@ use std::net::SocketAddr;
 const EPIC_API_ENDPOINT: &str = "https://api.epicgames.dev";
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Credentials {
     #[cfg_attr(feature = "serde", serde(skip_deserializing, skip_serializing))]
     pub deployment: &'static str,
@@ -72,16 +76,17 @@ impl EpicProtocol {
             ("deployment_id", self.credentials.deployment),
         ];
 
-        let auth_format = format!("{}:{}", self.credentials.id, self.credentials.secret);
-        let auth_base = BASE64_STANDARD.encode(auth_format);
-        let auth = format!("Basic {}", auth_base.as_str());
-        let authorization = auth.as_str();
-
+        let auth = client_authorization(&self.credentials);
         let headers = [
-            ("Authorization", authorization),
+            ("Authorization", auth.as_str()),
             ("Content-Type", "application/x-www-form-urlencoded"),
         ];
 
+        trace::event!(
+            DEBUG,
+            deployment = self.credentials.deployment,
+            "Epic client authentication"
+        );
         let response =
             self.client
                 .post_json_with_form::<ClientTokenResponse>("/auth/v1/oauth/token", Some(&headers), &body)?;
@@ -89,20 +94,19 @@ impl EpicProtocol {
     }
 
     pub fn query_raw(&mut self, address: &SocketAddr) -> GDResult<Value> {
-        let port = address.port();
-        let address = address.ip().to_string();
+        self.get_session(address)
+            .map_err(|e| e.with_address(*address).with_stage(QueryStage::Info))
+    }
 
-        let body = format!(
-            "{{\"criteria\":[{{\"key\":\"attributes.ADDRESS_s\",\"op\":\"EQUAL\",\"value\":\"{}\"}}]}}",
-            address
-        );
-        let body = serde_json::from_str::<Value>(body.as_str()).map_err(|e| JsonParse.context(e))?;
+    /// Authenticate and find the server's session.
+    fn get_session(&mut self, address: &SocketAddr) -> GDResult<Value> {
+        let body = filter_body(address)?;
 
-        let token = if self.credentials.auth_by_external {
-            self.auth_by_external()?
-        } else {
-            self.auth_by_client()?
-        };
+        let token = match self.credentials.auth_by_external {
+            true => self.auth_by_external(),
+            false => self.auth_by_client(),
+        }
+        .map_err(|e| e.with_stage(QueryStage::Challenge))?;
         let authorization = format!("Bearer {}", token);
         let headers = [
             ("Content-Type", "application/json"),
@@ -111,73 +115,209 @@ impl EpicProtocol {
         ];
 
         let url = format!("/matchmaking/v1/{}/filter", self.credentials.deployment);
+        trace::event!(DEBUG, "Epic sessions request");
         let response: QueryResponse = self.client.post_json(url.as_str(), Some(&headers), body)?;
 
-        if let Value::Array(sessions) = response.sessions {
-            if sessions.is_empty() {
-                return Err(PacketBad.context("No servers provided."));
-            }
+        find_session(response, address)
+    }
 
-            for session in sessions.into_iter() {
-                let attributes = session
-                    .get("attributes")
-                    .ok_or(PacketBad.context("Expected attributes field missing in sessions."))?;
-
-                let address_match = attributes
-                    .get("ADDRESSBOUND_s")
-                    .and_then(Value::as_str)
-                    .map_or(false, |v| v == address || v == format!("0.0.0.0:{}", port))
-                    || attributes
-                        .get("GAMESERVER_PORT_1")
-                        .and_then(Value::as_u64)
-                        .map_or(false, |v| v == port as u64);
-
-                if address_match {
-                    return Ok(session);
-                }
-            }
+    pub fn query(&mut self, address: &SocketAddr) -> GDResult<Response> {
+        parse_session(self.query_raw(address)?).map_err(|e| e.with_address(*address).with_stage(QueryStage::Info))
+    }
+}
+
+pub fn query(credentials: Credentials, address: &SocketAddr) -> GDResult<Response> {
+    query_with_timeout(credentials, address, None)
+}
+
+pub fn query_with_timeout(
+    credentials: Credentials,
+    address: &SocketAddr,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<Response> {
+    trace::span!("epic", %address).in_scope(|| {
+        let mut client = EpicProtocol::new(credentials, timeout_settings.unwrap_or_default())?;
+        client.query(address)
+    })
+}
+
+/// Parse the response of a sessions request, as received, to the session of
+/// the server at `address`.
+pub fn parse(data: &[u8], address: &SocketAddr) -> GDResult<Response> {
+    let response = serde_json::from_slice(data).map_err(|e| ProtocolFormat.context(e))?;
+    parse_session(find_session(response, address)?)
+}
+
+/// The basic authorization header value for the client credentials.
+fn client_authorization(credentials: &Credentials) -> String {
+    let auth_format = format!("{}:{}", credentials.id, credentials.secret);
+    let auth_base = BASE64_STANDARD.encode(auth_format);
+
+    format!("Basic {}", auth_base.as_str())
+}
 
-            return Err(
-                PacketBad.context("Servers were provided but the specified one couldn't be found amongst them.")
-            );
+/// The matchmaking filter for the sessions bound to the address.
+fn filter_body(address: &SocketAddr) -> GDResult<Value> {
+    let body = format!(
+        "{{\"criteria\":[{{\"key\":\"attributes.ADDRESS_s\",\"op\":\"EQUAL\",\"value\":\"{}\"}}]}}",
+        address.ip()
+    );
+
+    serde_json::from_str::<Value>(body.as_str()).map_err(|e| JsonParse.context(e))
+}
+
+/// Find the session of the server at address amongst the ones provided.
+fn find_session(response: QueryResponse, address: &SocketAddr) -> GDResult<Value> {
+    let port = address.port();
+    let address = address.ip().to_string();
+
+    if let Value::Array(sessions) = response.sessions {
+        if sessions.is_empty() {
+            return Err(PacketBad.context("No servers provided."));
         }
 
-        Err(PacketBad.context("Expected session field to be an array."))
+        for session in sessions.into_iter() {
+            let attributes = session
+                .get("attributes")
+                .ok_or(PacketBad.context("Expected attributes field missing in sessions."))?;
+
+            let address_match = attributes
+                .get("ADDRESSBOUND_s")
+                .and_then(Value::as_str)
+                .map_or(false, |v| v == address || v == format!("0.0.0.0:{}", port))
+                || attributes
+                    .get("GAMESERVER_PORT_1")
+                    .and_then(Value::as_u64)
+                    .map_or(false, |v| v == port as u64);
+
+            if address_match {
+                return Ok(session);
+            }
+        }
+
+        return Err(PacketBad.context("Servers were provided but the specified one couldn't be found amongst them."));
     }
 
-    pub fn query(&mut self, address: &SocketAddr) -> GDResult<Response> {
-        let value = self.query_raw(address)?;
-
-        let build_version = extract_optional_field!(value, ["attributes", "BUILDID_s"], Value::as_str);
-        let minor_version = extract_optional_field!(value, ["attributes", "MINORBUILDID_s"], Value::as_str);
-
-        let game_version = match (build_version, minor_version) {
-            (Some(b), Some(m)) => Some(format!("{b}.{m}")),
-            _ => None,
-        };
-
-        Ok(Response {
-            name: extract_field!(value, ["attributes", "CUSTOMSERVERNAME_s"], Value::as_str).to_string(),
-            map: extract_field!(value, ["attributes", "MAPNAME_s"], Value::as_str).to_string(),
-            has_password: extract_field!(value, ["attributes", "SERVERPASSWORD_b"], Value::as_bool),
-            players_online: extract_field!(value, ["totalPlayers"], Value::as_u64) as u32,
-            players_maxmimum: extract_field!(value, ["settings", "maxPublicPlayers"], Value::as_u64) as u32,
-            players: vec![],
-            game_version,
-            raw: value,
+    Err(PacketBad.context("Expected session field to be an array."))
+}
+
+/// Parse a matched session.
+fn parse_session(value: Value) -> GDResult<Response> {
+    let build_version = extract_optional_field!(value, ["attributes", "BUILDID_s"], Value::as_str);
+    let minor_version = extract_optional_field!(value, ["attributes", "MINORBUILDID_s"], Value::as_str);
+
+    let game_version = match (build_version, minor_version) {
+        (Some(b), Some(m)) => Some(format!("{b}.{m}")),
+        _ => None,
+    };
+
+    Ok(Response {
+        name: extract_field!(value, ["attributes", "CUSTOMSERVERNAME_s"], Value::as_str).to_string(),
+        map: extract_field!(value, ["attributes", "MAPNAME_s"], Value::as_str).to_string(),
+        has_password: extract_field!(value, ["attributes", "SERVERPASSWORD_b"], Value::as_bool),
+        players_online: extract_field!(value, ["totalPlayers"], Value::as_u64) as u32,
+        players_maxmimum: extract_field!(value, ["settings", "maxPublicPlayers"], Value::as_u64) as u32,
+        players: vec![],
+        game_version,
+        raw: value,
+    })
+}
+
+#[cfg(feature = "async")]
+pub struct AsyncEpicProtocol {
+    client: AsyncHttpClient,
+    credentials: Credentials,
+}
+
+#[cfg(feature = "async")]
+impl AsyncEpicProtocol {
+    pub async fn new(credentials: Credentials, timeout_settings: TimeoutSettings) -> GDResult<Self> {
+        Ok(Self {
+            client: AsyncHttpClient::from_url(EPIC_API_ENDPOINT, &Some(timeout_settings), None).await?,
+            credentials,
         })
     }
+
+    pub async fn auth_by_external(&self) -> GDResult<String> { Ok(String::new()) }
+
+    pub async fn auth_by_client(&mut self) -> GDResult<String> {
+        let body = [
+            ("grant_type", "client_credentials"),
+            ("deployment_id", self.credentials.deployment),
+        ];
+
+        let auth = client_authorization(&self.credentials);
+        let headers = [
+            ("Authorization", auth.as_str()),
+            ("Content-Type", "application/x-www-form-urlencoded"),
+        ];
+
+        trace::event!(
+            DEBUG,
+            deployment = self.credentials.deployment,
+            "Epic client authentication"
+        );
+        let response = self
+            .client
+            .post_json_with_form::<ClientTokenResponse>("/auth/v1/oauth/token", Some(&headers), &body)
+            .await?;
+        Ok(response.access_token)
+    }
+
+    pub async fn query_raw(&mut self, address: &SocketAddr) -> GDResult<Value> {
+        self.get_session(address)
+            .await
+            .map_err(|e| e.with_address(*address).with_stage(QueryStage::Info))
+    }
+
+    /// Authenticate and find the server's session.
+    async fn get_session(&mut self, address: &SocketAddr) -> GDResult<Value> {
+        let body = filter_body(address)?;
+
+        let token = match self.credentials.auth_by_external {
+            true => self.auth_by_external().await,
+            false => self.auth_by_client().await,
+        }
+        .map_err(|e| e.with_stage(QueryStage::Challenge))?;
+        let authorization = format!("Bearer {}", token);
+        let headers = [
+            ("Content-Type", "application/json"),
+            ("Accept", "application/json"),
+            ("Authorization", authorization.as_str()),
+        ];
+
+        let url = format!("/matchmaking/v1/{}/filter", self.credentials.deployment);
+        trace::event!(DEBUG, "Epic sessions request");
+        let response: QueryResponse = self
+            .client
+            .post_json(url.as_str(), Some(&headers), body)
+            .await?;
+
+        find_session(response, address)
+    }
+
+    pub async fn query(&mut self, address: &SocketAddr) -> GDResult<Response> {
+        parse_session(self.query_raw(address).await?).map_err(|e| e.with_address(*address).with_stage(QueryStage::Info))
+    }
 }
 
-pub fn query(credentials: Credentials, address: &SocketAddr) -> GDResult<Response> {
-    query_with_timeout(credentials, address, None)
+/// Async counterpart of [query].
+#[cfg(feature = "async")]
+pub async fn query_async(credentials: Credentials, address: &SocketAddr) -> GDResult<Response> {
+    query_with_timeout_async(credentials, address, None).await
 }
 
-pub fn query_with_timeout(
+/// Async counterpart of [query_with_timeout].
+#[cfg(feature = "async")]
+pub async fn query_with_timeout_async(
     credentials: Credentials,
     address: &SocketAddr,
     timeout_settings: Option<TimeoutSettings>,
 ) -> GDResult<Response> {
-    let mut client = EpicProtocol::new(credentials, timeout_settings.unwrap_or_default())?;
-    client.query(address)
+    async {
+        let mut client = AsyncEpicProtocol::new(credentials, timeout_settings.unwrap_or_default()).await?;
+        client.query(address).await
+    }
+    .instrument(trace::span!("epic", %address))
+    .await
 }
@@ -1,3 +1,4 @@
+use crate::protocols::types::CommonResponse;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
@@ -9,7 +10,7 @@ pub use protocols::*;
 
 /// Versions of the gamespy protocol
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum GameSpyVersion {
     One,
     Two,
@@ -25,6 +26,36 @@ pub enum VersionedResponse<'a> {
     Three(&'a three::Response),
 }
 
+/// Owned [VersionedResponse], which can be deserialized.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionedResponseOwned {
+    One(one::Response),
+    Two(two::Response),
+    Three(three::Response),
+}
+
+impl VersionedResponseOwned {
+    /// Get the response as a [CommonResponse].
+    pub fn as_common(&self) -> &dyn CommonResponse {
+        match self {
+            Self::One(response) => response,
+            Self::Two(response) => response,
+            Self::Three(response) => response,
+        }
+    }
+}
+
+impl From<VersionedResponse<'_>> for VersionedResponseOwned {
+    fn from(response: VersionedResponse<'_>) -> Self {
+        match response {
+            VersionedResponse::One(response) => Self::One(response.clone()),
+            VersionedResponse::Two(response) => Self::Two(response.clone()),
+            VersionedResponse::Three(response) => Self::Three(response.clone()),
+        }
+    }
+}
+
 /// Versioned player type
 #[cfg_attr(feature = "serde", derive(Serialize))]
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -85,6 +116,19 @@ macro_rules! game_query_fn {
                 None,
             )
         }
+
+        /// Async counterpart of [query].
+        #[cfg(feature = "async")]
+        pub async fn query_async(
+            address: &std::net::IpAddr,
+            port: Option<u16>,
+        ) -> crate::GDResult<crate::protocols::gamespy::$gamespy_ver::Response> {
+            crate::protocols::gamespy::$gamespy_ver::query_async(
+                &std::net::SocketAddr::new(*address, port.unwrap_or($default_port)),
+                None,
+            )
+            .await
+        }
     };
 }
 
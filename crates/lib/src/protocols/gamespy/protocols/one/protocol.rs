@@ -4,7 +4,8 @@ use crate::buffer::Utf8Decoder;
 use crate::protocols::gamespy::common::has_password;
 use crate::GDErrorKind::TypeParse;
 
-use crate::utils::retry_on_timeout;
+use crate::machine::{drive, timed_out, Event, Machine, Step};
+use crate::retry::retry;
 use crate::{
     buffer::Buffer,
     protocols::{
@@ -12,38 +13,108 @@ use crate::{
         types::TimeoutSettings,
     },
     socket::{Socket, UdpSocket},
+    trace,
     GDErrorKind,
     GDResult,
+    QueryStage,
+};
+#[cfg(feature = "async")]
+use crate::{
+    machine::drive_async,
+    retry::retry_async,
+    socket::{AsyncSocket, AsyncUdpSocket},
+    trace::Instrument,
 };
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::time::Duration;
+
+/// The request asking for the whole server status.
+const STATUS_REQUEST: &[u8] = b"\\status\\xserverquery";
 
-/// Send status request, and parse response into HashMap.
+/// Send status request, and parse response into HashMap, alongside the
+/// request's round-trip time.
 /// This function will retry fetch on timeouts.
 fn get_server_values(
     address: &SocketAddr,
     timeout_settings: &Option<TimeoutSettings>,
-) -> GDResult<HashMap<String, String>> {
-    let mut socket = UdpSocket::new(address, timeout_settings)?;
-    retry_on_timeout(
-        TimeoutSettings::get_retries_or_default(timeout_settings),
-        move || get_server_values_impl(&mut socket),
-    )
+) -> GDResult<(HashMap<String, String>, Option<Duration>)> {
+    trace::span!("gamespy1", %address).in_scope(|| {
+        let mut socket = UdpSocket::new(address, timeout_settings)?;
+        let server_values = retry(
+            TimeoutSettings::get_retries_or_default(timeout_settings),
+            TimeoutSettings::get_retry_policy_or_default(timeout_settings),
+            || drive(&mut socket, &mut ValuesMachine::default(), None),
+        )?;
+
+        Ok((server_values, socket.latency()))
+    })
 }
 
-/// Send status request, and parse response into HashMap (without retry logic).
-fn get_server_values_impl(socket: &mut UdpSocket) -> GDResult<HashMap<String, String>> {
-    socket.send(b"\\status\\xserverquery")?;
+/// The exchange of a status query: the request and its (possibly multiple)
+/// response packets, whose server values are the output.
+#[derive(Default)]
+struct ValuesMachine {
+    collector: ServerValuesCollector,
+}
+
+impl Machine for ValuesMachine {
+    type Output = HashMap<String, String>;
+
+    fn start(&mut self) -> Vec<Vec<u8>> {
+        self.collector = ServerValuesCollector::default();
+        trace::event!(DEBUG, "GameSpy 1 status request");
+        vec![STATUS_REQUEST.to_vec()]
+    }
+
+    fn handle(&mut self, event: Event<'_>) -> GDResult<Step<HashMap<String, String>>> {
+        let Event::Received(data) = event else {
+            return Err(timed_out());
+        };
 
-    let mut received_query_id: Option<usize> = None;
-    let mut parts: Vec<usize> = Vec::new();
-    let mut is_finished = false;
+        Ok(match self.collector.add_packet(data)? {
+            true => Step::Done(std::mem::take(&mut self.collector.server_values)),
+            false => Step::Receive,
+        })
+    }
+}
+
+/// The exchange of a [query], as a state machine.
+#[derive(Default)]
+pub struct QueryMachine {
+    values: ValuesMachine,
+}
+
+impl QueryMachine {
+    pub fn new() -> Self { Self::default() }
+}
+
+impl Machine for QueryMachine {
+    type Output = Response;
+
+    fn start(&mut self) -> Vec<Vec<u8>> { self.values.start() }
+
+    fn handle(&mut self, event: Event<'_>) -> GDResult<Step<Response>> {
+        Ok(match self.values.handle(event)? {
+            Step::Send(packets) => Step::Send(packets),
+            Step::Receive => Step::Receive,
+            Step::Done(server_values) => Step::Done(parse_response(server_values)?),
+        })
+    }
+}
 
-    let mut server_values = HashMap::new();
+/// Gathers the server values from the (possibly multiple) response packets.
+#[derive(Default)]
+struct ServerValuesCollector {
+    received_query_id: Option<usize>,
+    parts: Vec<usize>,
+    server_values: HashMap<String, String>,
+}
 
-    while !is_finished {
-        let data = socket.receive(None)?;
-        let mut bufferer = Buffer::<LittleEndian>::new(&data);
+impl ServerValuesCollector {
+    /// Add a received packet, returns whether it was the final one.
+    fn add_packet(&mut self, data: &[u8]) -> GDResult<bool> {
+        let mut bufferer = Buffer::<LittleEndian>::new(data);
 
         let mut as_string = bufferer.read_string::<Utf8Decoder>(None)?;
         as_string.remove(0);
@@ -57,14 +128,14 @@ fn get_server_values_impl(socket: &mut UdpSocket) -> GDResult<HashMap<String, St
                 .get(position + 1)
                 .map_or_else(String::new, Clone::clone);
 
-            server_values.insert(key, value);
+            self.server_values.insert(key, value);
         }
 
-        is_finished = server_values.remove("final").is_some();
+        let is_finished = self.server_values.remove("final").is_some();
 
-        let query_data = server_values.get("queryid");
+        let query_data = self.server_values.get("queryid");
 
-        let mut part = parts.len(); // if the part number isn't provided, it's value is the parts length
+        let mut part = self.parts.len(); // if the part number isn't provided, it's value is the parts length
         let mut query_id = None;
         if let Some(qid) = query_data {
             let split: Vec<&str> = qid.split('.').collect();
@@ -78,21 +149,28 @@ fn get_server_values_impl(socket: &mut UdpSocket) -> GDResult<HashMap<String, St
             };
         }
 
-        server_values.remove("queryid");
+        self.server_values.remove("queryid");
 
-        if received_query_id.is_some() && received_query_id != query_id {
+        if self.received_query_id.is_some() && self.received_query_id != query_id {
             return Err(GDErrorKind::PacketBad.into()); // wrong query id!
         }
 
-        received_query_id = query_id;
+        self.received_query_id = query_id;
 
-        match parts.contains(&part) {
+        match self.parts.contains(&part) {
             true => Err(GDErrorKind::PacketBad)?,
-            false => parts.push(part),
+            false => self.parts.push(part),
         }
+        trace::event!(
+            DEBUG,
+            ?query_id,
+            part,
+            is_finished,
+            "GameSpy 1 status packet received"
+        );
+
+        Ok(is_finished)
     }
-
-    Ok(server_values)
 }
 
 fn extract_players(server_vars: &mut HashMap<String, String>, players_maximum: u32) -> GDResult<Vec<Player>> {
@@ -189,14 +267,36 @@ pub fn query_vars(
     timeout_settings: Option<TimeoutSettings>,
 ) -> GDResult<HashMap<String, String>> {
     get_server_values(address, &timeout_settings)
+        .map(|(server_values, _)| server_values)
+        .map_err(|e| e.with_address(*address).with_stage(QueryStage::Info))
 }
 
 /// Query a server by providing the address, the port and timeout settings.
 /// Providing None to the timeout settings results in using the default values.
 /// (TimeoutSettings::[default](TimeoutSettings::default)).
 pub fn query(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Response> {
-    let mut server_vars = query_vars(address, timeout_settings)?;
+    get_server_values(address, &timeout_settings)
+        .and_then(|(server_values, latency)| {
+            Ok(Response {
+                latency,
+                ..parse_response(server_values)?
+            })
+        })
+        .map_err(|e| e.with_address(*address).with_stage(QueryStage::Info))
+}
 
+/// Parse the packets of a status response, as received (in any order).
+pub fn parse<P: AsRef<[u8]>>(packets: &[P]) -> GDResult<Response> {
+    let mut collector = ServerValuesCollector::default();
+    for packet in packets {
+        collector.add_packet(packet.as_ref())?;
+    }
+
+    parse_response(collector.server_values)
+}
+
+/// Build a response out of the server's values.
+fn parse_response(mut server_vars: HashMap<String, String>) -> GDResult<Response> {
     let players_maximum: u32 = server_vars
         .remove("maxplayers")
         .ok_or(GDErrorKind::PacketBad)?
@@ -239,5 +339,52 @@ pub fn query(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) ->
             .parse()
             .map_err(|e| TypeParse.context(e))?,
         unused_entries: server_vars,
+        latency: None,
     })
 }
+
+/// Async counterpart of [get_server_values].
+#[cfg(feature = "async")]
+async fn get_server_values_async(
+    address: &SocketAddr,
+    timeout_settings: &Option<TimeoutSettings>,
+) -> GDResult<(HashMap<String, String>, Option<Duration>)> {
+    async {
+        let mut socket = AsyncUdpSocket::new(address, timeout_settings).await?;
+        let server_values = retry_async!(
+            TimeoutSettings::get_retries_or_default(timeout_settings),
+            TimeoutSettings::get_retry_policy_or_default(timeout_settings),
+            drive_async(&mut socket, &mut ValuesMachine::default(), None).await
+        )?;
+
+        Ok((server_values, socket.latency()))
+    }
+    .instrument(trace::span!("gamespy1", %address))
+    .await
+}
+
+/// Async counterpart of [query_vars].
+#[cfg(feature = "async")]
+pub async fn query_vars_async(
+    address: &SocketAddr,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<HashMap<String, String>> {
+    get_server_values_async(address, &timeout_settings)
+        .await
+        .map(|(server_values, _)| server_values)
+        .map_err(|e| e.with_address(*address).with_stage(QueryStage::Info))
+}
+
+/// Async counterpart of [query].
+#[cfg(feature = "async")]
+pub async fn query_async(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Response> {
+    get_server_values_async(address, &timeout_settings)
+        .await
+        .and_then(|(server_values, latency)| {
+            Ok(Response {
+                latency,
+                ..parse_response(server_values)?
+            })
+        })
+        .map_err(|e| e.with_address(*address).with_stage(QueryStage::Info))
+}
@@ -6,6 +6,7 @@ use serde::{Deserialize, Serialize};
 use crate::protocols::gamespy::{VersionedPlayer, VersionedResponse};
 use crate::protocols::types::{CommonPlayer, CommonResponse, GenericPlayer};
 use crate::protocols::GenericResponse;
+use std::time::Duration;
 
 /// A player’s details.
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -49,6 +50,7 @@ pub struct Response {
     pub players: Vec<Player>,
     pub tournament: bool,
     pub unused_entries: HashMap<String, String>,
+    pub latency: Option<Duration>,
 }
 
 impl CommonResponse for Response {
@@ -70,4 +72,6 @@ impl CommonResponse for Response {
                 .collect(),
         )
     }
+
+    fn latency(&self) -> Option<Duration> { self.latency }
 }
@@ -1,15 +1,24 @@
 use byteorder::{BigEndian, LittleEndian};
 
 use crate::buffer::{Buffer, Utf8Decoder};
+use crate::machine::{drive, timed_out, Event, Machine, Step};
 use crate::protocols::gamespy::common::has_password;
-use crate::protocols::gamespy::three::{Player, Response, Team};
-use crate::protocols::types::TimeoutSettings;
+use crate::protocols::gamespy::three::{GameSpy3RequestSettings, Player, Response, Team};
+use crate::protocols::types::{RetryPolicy, TimeoutSettings};
+use crate::retry::retry;
 use crate::socket::{Socket, UdpSocket};
-use crate::utils::retry_on_timeout;
 use crate::GDErrorKind::{PacketBad, TypeParse};
-use crate::{GDErrorKind, GDResult};
+#[cfg(feature = "async")]
+use crate::{
+    machine::drive_async,
+    retry::retry_async,
+    socket::{AsyncSocket, AsyncUdpSocket},
+    trace::Instrument,
+};
+use crate::{trace, GDError, GDErrorKind, GDResult, QueryStage};
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::time::Duration;
 
 const THIS_SESSION_ID: u32 = 1;
 
@@ -42,147 +51,261 @@ impl RequestPacket {
 
 pub(crate) struct GameSpy3 {
     socket: UdpSocket,
-    payload: [u8; 4],
-    single_packets: bool,
+    settings: GameSpy3RequestSettings,
     retry_count: usize,
+    retry_policy: RetryPolicy,
 }
 
+/// The receive size of every packet of the exchange, which the small
+/// handshake response fits in as well as the data ones.
 const PACKET_SIZE: usize = 2048;
-const DEFAULT_PAYLOAD: [u8; 4] = [0xFF, 0xFF, 0xFF, 0x01];
 
 impl GameSpy3 {
     fn new(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Self> {
-        let socket = UdpSocket::new(address, &timeout_settings)?;
-        let retry_count = TimeoutSettings::get_retries_or_default(&timeout_settings);
-
-        Ok(Self {
-            socket,
-            payload: DEFAULT_PAYLOAD,
-            single_packets: false,
-            retry_count,
-        })
+        Self::new_custom(address, timeout_settings, GameSpy3RequestSettings::new())
     }
 
     pub(crate) fn new_custom(
         address: &SocketAddr,
         timeout_settings: Option<TimeoutSettings>,
-        payload: [u8; 4],
-        single_packets: bool,
+        settings: GameSpy3RequestSettings,
     ) -> GDResult<Self> {
         let socket = UdpSocket::new(address, &timeout_settings)?;
         let retry_count = TimeoutSettings::get_retries_or_default(&timeout_settings);
+        let retry_policy = TimeoutSettings::get_retry_policy_or_default(&timeout_settings);
 
         Ok(Self {
             socket,
-            payload,
-            single_packets,
+            settings,
             retry_count,
+            retry_policy,
         })
     }
 
-    fn receive(&mut self, size: Option<usize>, kind: u8) -> GDResult<Vec<u8>> {
-        let received = self.socket.receive(size.or(Some(PACKET_SIZE)))?;
-        let mut buf = Buffer::<BigEndian>::new(&received);
+    /// Round-trip time of the data request.
+    pub(crate) fn latency(&self) -> Option<Duration> { self.socket.latency() }
 
-        if buf.read::<u8>()? != kind {
-            return Err(PacketBad.context("Kind of packet did not match"));
+    /// Fetch packets from server and store in buffer.
+    /// This function will retry fetch on timeouts.
+    pub(crate) fn get_server_packets(&mut self) -> GDResult<Vec<Vec<u8>>> {
+        let mut machine = PacketsMachine::new(self.settings);
+        retry(self.retry_count, self.retry_policy, || {
+            drive(&mut self.socket, &mut machine, Some(PACKET_SIZE))
+        })
+        .map_err(|e| e.with_stage(machine.query_stage()))
+    }
+}
+
+/// Where a [PacketsMachine] is at.
+enum Stage {
+    Handshake,
+    Data(PacketsCollector),
+}
+
+/// The exchange of a query: the handshake (which gives the challenge, if
+/// any), then the data request and its (possibly split) response, whose
+/// packets' data is the output.
+pub(crate) struct PacketsMachine {
+    settings: GameSpy3RequestSettings,
+    stage: Stage,
+}
+
+impl PacketsMachine {
+    pub(crate) const fn new(settings: GameSpy3RequestSettings) -> Self {
+        Self {
+            settings,
+            stage: Stage::Handshake,
         }
+    }
 
-        if buf.read::<u32>()? != THIS_SESSION_ID {
-            return Err(PacketBad.context("Session ID did not match"));
+    /// The stage of the query the exchange is at.
+    const fn query_stage(&self) -> QueryStage {
+        match self.stage {
+            Stage::Handshake => QueryStage::Challenge,
+            Stage::Data(_) => QueryStage::Info,
         }
+    }
+}
+
+impl Machine for PacketsMachine {
+    type Output = Vec<Vec<u8>>;
 
-        Ok(buf.remaining_bytes().to_vec())
+    fn start(&mut self) -> Vec<Vec<u8>> {
+        self.stage = Stage::Handshake;
+        trace::event!(DEBUG, "GameSpy 3 handshake request");
+        vec![handshake_request()]
     }
 
-    fn make_initial_handshake(&mut self) -> GDResult<Option<i32>> {
-        self.socket.send(
-            &RequestPacket {
-                header: 65277,
-                kind: 9,
-                session_id: THIS_SESSION_ID,
-                challenge: None,
-                payload: None,
+    fn handle(&mut self, event: Event<'_>) -> GDResult<Step<Vec<Vec<u8>>>> {
+        let Event::Received(received) = event else {
+            return Err(timed_out());
+        };
+
+        match &mut self.stage {
+            Stage::Handshake => {
+                let challenge = parse_challenge(&strip_response_header(received, 9)?)?;
+                self.stage = Stage::Data(PacketsCollector::new(self.settings.single_packets));
+                trace::event!(
+                    DEBUG,
+                    has_challenge = challenge.is_some(),
+                    "GameSpy 3 data request"
+                );
+
+                Ok(Step::Send(vec![data_request(
+                    challenge,
+                    self.settings.payload,
+                )]))
             }
-            .to_bytes(),
-        )?;
+            Stage::Data(collector) => {
+                Ok(
+                    match collector.add_packet(&strip_response_header(received, 0)?)? {
+                        Some(packets) => Step::Done(packets),
+                        None => Step::Receive,
+                    },
+                )
+            }
+        }
+    }
+}
+
+/// The exchange of a [query], as a state machine.
+pub struct QueryMachine {
+    packets: PacketsMachine,
+}
+
+impl QueryMachine {
+    pub const fn new(settings: GameSpy3RequestSettings) -> Self {
+        Self {
+            packets: PacketsMachine::new(settings),
+        }
+    }
+}
+
+impl Default for QueryMachine {
+    fn default() -> Self { Self::new(GameSpy3RequestSettings::new()) }
+}
 
-        let data = self.receive(Some(16), 9)?;
-        let mut buf = Buffer::<LittleEndian>::new(&data);
+impl Machine for QueryMachine {
+    type Output = Response;
 
-        let challenge_as_string = buf.read_string::<Utf8Decoder>(None)?;
-        let challenge = challenge_as_string
-            .parse()
-            .map_err(|e| TypeParse.context(e))?;
+    fn start(&mut self) -> Vec<Vec<u8>> { self.packets.start() }
 
-        Ok(match challenge == 0 {
-            true => None,
-            false => Some(challenge),
+    fn handle(&mut self, event: Event<'_>) -> GDResult<Step<Response>> {
+        Ok(match self.packets.handle(event)? {
+            Step::Send(packets) => Step::Send(packets),
+            Step::Receive => Step::Receive,
+            Step::Done(packets) => Step::Done(parse_response(packets)?),
         })
     }
+}
 
-    fn send_data_request(&mut self, challenge: Option<i32>) -> GDResult<()> {
-        self.socket.send(
-            &RequestPacket {
-                header: 65277,
-                kind: 0,
-                session_id: THIS_SESSION_ID,
-                challenge,
-                payload: Some(self.payload),
-            }
-            .to_bytes(),
-        )
+/// The handshake request, asking for a challenge.
+fn handshake_request() -> Vec<u8> {
+    RequestPacket {
+        header: 65277,
+        kind: 9,
+        session_id: THIS_SESSION_ID,
+        challenge: None,
+        payload: None,
     }
+    .to_bytes()
+}
 
-    /// Fetch packets from server and store in buffer.
-    /// This function will retry fetch on timeouts.
-    pub(crate) fn get_server_packets(&mut self) -> GDResult<Vec<Vec<u8>>> {
-        retry_on_timeout(self.retry_count, move || self.get_server_packets_impl())
+/// The request asking for the server's data.
+fn data_request(challenge: Option<i32>, payload: [u8; 4]) -> Vec<u8> {
+    RequestPacket {
+        header: 65277,
+        kind: 0,
+        session_id: THIS_SESSION_ID,
+        challenge,
+        payload: Some(payload),
     }
+    .to_bytes()
+}
 
-    /// Fetch packets from server and store in buffer (without retry logic).
-    fn get_server_packets_impl(&mut self) -> GDResult<Vec<Vec<u8>>> {
-        let challenge = self.make_initial_handshake()?;
-        self.send_data_request(challenge)?;
+/// Validate the kind and the session id of a received packet, returning its
+/// remaining data.
+fn strip_response_header(received: &[u8], kind: u8) -> GDResult<Vec<u8>> {
+    let mut buf = Buffer::<BigEndian>::new(received);
 
-        let mut values: Vec<Vec<u8>> = Vec::new();
+    if buf.read::<u8>()? != kind {
+        return Err(PacketBad.context("Kind of packet did not match"));
+    }
 
-        let mut reached_expected_packets_size = false;
+    if buf.read::<u32>()? != THIS_SESSION_ID {
+        return Err(PacketBad.context("Session ID did not match"));
+    }
 
-        while !reached_expected_packets_size {
-            let received_data = self.receive(None, 0)?;
-            let mut buf = Buffer::<BigEndian>::new(&received_data);
+    Ok(buf.remaining_bytes().to_vec())
+}
 
-            if self.single_packets {
-                buf.move_cursor(11)?;
-                return Ok(vec![buf.remaining_bytes().to_vec()]);
-            }
+/// Parse the challenge out of a handshake response, a challenge of 0 means
+/// there is none.
+fn parse_challenge(data: &[u8]) -> GDResult<Option<i32>> {
+    let mut buf = Buffer::<LittleEndian>::new(data);
 
-            if buf.read_string::<Utf8Decoder>(None)? != "splitnum" {
-                return Err(PacketBad.context("Expected string \"splitnum\""));
-            }
+    let challenge_as_string = buf.read_string::<Utf8Decoder>(None)?;
+    let challenge = challenge_as_string
+        .parse()
+        .map_err(|e| TypeParse.context(e))?;
 
-            let id = buf.read::<u8>()?;
-            let is_last = (id & 0x80) > 0;
-            let packet_id = (id & 0x7f) as usize;
-            buf.move_cursor(1)?; // unknown byte regarding packet no.
+    Ok(match challenge == 0 {
+        true => None,
+        false => Some(challenge),
+    })
+}
 
-            if is_last && packet_id + 1 != values.len() {
-                reached_expected_packets_size = true;
-            }
+/// Gathers the (possibly split) packets of a data response.
+struct PacketsCollector {
+    single_packets: bool,
+    values: Vec<Vec<u8>>,
+}
 
-            while values.len() <= packet_id {
-                values.push(Vec::new());
-            }
+impl PacketsCollector {
+    const fn new(single_packets: bool) -> Self {
+        Self {
+            single_packets,
+            values: Vec::new(),
+        }
+    }
+
+    /// Add a received packet (without its header), returns all of the packets
+    /// once the last one has been received.
+    fn add_packet(&mut self, received_data: &[u8]) -> GDResult<Option<Vec<Vec<u8>>>> {
+        let mut buf = Buffer::<BigEndian>::new(received_data);
+
+        if self.single_packets {
+            buf.move_cursor(11)?;
+            return Ok(Some(vec![buf.remaining_bytes().to_vec()]));
+        }
+
+        if buf.read_string::<Utf8Decoder>(None)? != "splitnum" {
+            return Err(PacketBad.context("Expected string \"splitnum\""));
+        }
+
+        let id = buf.read::<u8>()?;
+        let is_last = (id & 0x80) > 0;
+        let packet_id = (id & 0x7f) as usize;
+        buf.move_cursor(1)?; // unknown byte regarding packet no.
+
+        let reached_expected_packets_size = is_last && packet_id + 1 != self.values.len();
+        trace::event!(DEBUG, packet_id, is_last, "GameSpy 3 split packet received");
 
-            values[packet_id] = buf.remaining_bytes().to_vec();
+        while self.values.len() <= packet_id {
+            self.values.push(Vec::new());
         }
 
-        if values.iter().any(Vec::is_empty) {
+        self.values[packet_id] = buf.remaining_bytes().to_vec();
+
+        if !reached_expected_packets_size {
+            return Ok(None);
+        }
+
+        if self.values.iter().any(Vec::is_empty) {
             return Err(PacketBad.context("One (or more) packets is empty"));
         }
 
-        Ok(values)
+        Ok(Some(std::mem::take(&mut self.values)))
     }
 }
 
@@ -210,12 +333,19 @@ pub fn query_vars(
     address: &SocketAddr,
     timeout_settings: Option<TimeoutSettings>,
 ) -> GDResult<HashMap<String, String>> {
-    let mut client = GameSpy3::new(address, timeout_settings)?;
-    let packets = client.get_server_packets()?;
+    trace::span!("gamespy3", %address)
+        .in_scope(|| {
+            let mut client = GameSpy3::new(address, timeout_settings)?;
+            packets_to_vars(&client.get_server_packets()?)
+        })
+        .map_err(|e| e.with_address(*address).with_stage(QueryStage::Info))
+}
 
+/// Merge the server's values of every packet.
+fn packets_to_vars(packets: &[Vec<u8>]) -> GDResult<HashMap<String, String>> {
     let mut vars = HashMap::new();
 
-    for packet in &packets {
+    for packet in packets {
         let (key_values, _remaining_data) = data_to_map(packet)?;
         vars.extend(key_values);
     }
@@ -235,7 +365,8 @@ fn parse_players_and_teams(packets: Vec<Vec<u8>>) -> GDResult<(Vec<Player>, Vec<
                 continue;
             }
 
-            buf.move_cursor(1)?;
+            // The byte was the first one of the field's name.
+            buf.move_cursor(-1)?;
 
             let field = buf.read_string::<Utf8Decoder>(None)?;
             if field.is_empty() {
@@ -351,9 +482,49 @@ fn parse_players_and_teams(packets: Vec<Vec<u8>>) -> GDResult<(Vec<Player>, Vec<
 /// Providing None to the timeout settings results in using the default values.
 /// (TimeoutSettings::[default](TimeoutSettings::default)).
 pub fn query(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Response> {
-    let mut client = GameSpy3::new(address, timeout_settings)?;
-    let packets = client.get_server_packets()?;
+    trace::span!("gamespy3", %address)
+        .in_scope(|| {
+            let mut client = GameSpy3::new(address, timeout_settings)?;
+            let packets = client.get_server_packets()?;
+
+            Ok(Response {
+                latency: client.latency(),
+                ..parse_response(packets)?
+            })
+        })
+        .map_err(|e: GDError| e.with_address(*address).with_stage(QueryStage::Info))
+}
+
+/// Parse the (possibly split) packets of a data response, as received (the
+/// one flagged as the last coming last).
+///
+/// Unlike queries, the session id of the packets isn't checked, so that
+/// responses to any client can be parsed.
+pub fn parse<P: AsRef<[u8]>>(packets: &[P]) -> GDResult<Response> { parse_response(data_packets(packets, false)?) }
+
+/// The data of the packets of a response, as received (the one flagged as the
+/// last coming last).
+pub(crate) fn data_packets<P: AsRef<[u8]>>(packets: &[P], single_packets: bool) -> GDResult<Vec<Vec<u8>>> {
+    let mut collector = PacketsCollector::new(single_packets);
+    let mut data_packets = None;
+
+    for packet in packets {
+        let mut buf = Buffer::<BigEndian>::new(packet.as_ref());
+        if buf.read::<u8>()? != 0 {
+            return Err(PacketBad.context("Kind of packet did not match"));
+        }
+
+        // The session id.
+        buf.move_cursor(4)?;
 
+        data_packets = collector.add_packet(buf.remaining_bytes())?;
+    }
+
+    data_packets.ok_or_else(|| PacketBad.context("Missing packets"))
+}
+
+/// Build a response out of the data packets.
+fn parse_response(packets: Vec<Vec<u8>>) -> GDResult<Response> {
     let (mut server_vars, remaining_data) = data_to_map(packets.first().ok_or(GDErrorKind::PacketBad)?)?;
 
     let mut remaining_data_packets = vec![remaining_data];
@@ -406,5 +577,87 @@ pub fn query(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) ->
             .parse()
             .map_err(|e| TypeParse.context(e))?,
         unused_entries: server_vars,
+        latency: None,
     })
 }
+
+/// Async counterpart of [GameSpy3].
+#[cfg(feature = "async")]
+pub(crate) struct AsyncGameSpy3 {
+    socket: AsyncUdpSocket,
+    settings: GameSpy3RequestSettings,
+    retry_count: usize,
+    retry_policy: RetryPolicy,
+}
+
+#[cfg(feature = "async")]
+impl AsyncGameSpy3 {
+    async fn new(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Self> {
+        Self::new_custom(address, timeout_settings, GameSpy3RequestSettings::new()).await
+    }
+
+    pub(crate) async fn new_custom(
+        address: &SocketAddr,
+        timeout_settings: Option<TimeoutSettings>,
+        settings: GameSpy3RequestSettings,
+    ) -> GDResult<Self> {
+        let socket = AsyncUdpSocket::new(address, &timeout_settings).await?;
+        let retry_count = TimeoutSettings::get_retries_or_default(&timeout_settings);
+        let retry_policy = TimeoutSettings::get_retry_policy_or_default(&timeout_settings);
+
+        Ok(Self {
+            socket,
+            settings,
+            retry_count,
+            retry_policy,
+        })
+    }
+
+    /// Round-trip time of the data request.
+    pub(crate) fn latency(&self) -> Option<Duration> { self.socket.latency() }
+
+    /// Fetch packets from server and store in buffer.
+    /// This function will retry fetch on timeouts.
+    pub(crate) async fn get_server_packets(&mut self) -> GDResult<Vec<Vec<u8>>> {
+        let mut machine = PacketsMachine::new(self.settings);
+        let packets: GDResult<_> = retry_async!(
+            self.retry_count,
+            self.retry_policy,
+            drive_async(&mut self.socket, &mut machine, Some(PACKET_SIZE)).await
+        );
+
+        packets.map_err(|e| e.with_stage(machine.query_stage()))
+    }
+}
+
+/// Async counterpart of [query_vars].
+#[cfg(feature = "async")]
+pub async fn query_vars_async(
+    address: &SocketAddr,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<HashMap<String, String>> {
+    async {
+        let mut client = AsyncGameSpy3::new(address, timeout_settings).await?;
+        packets_to_vars(&client.get_server_packets().await?)
+    }
+    .instrument(trace::span!("gamespy3", %address))
+    .await
+    .map_err(|e| e.with_address(*address).with_stage(QueryStage::Info))
+}
+
+/// Async counterpart of [query].
+#[cfg(feature = "async")]
+pub async fn query_async(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Response> {
+    async {
+        let mut client = AsyncGameSpy3::new(address, timeout_settings).await?;
+        let packets = client.get_server_packets().await?;
+
+        Ok(Response {
+            latency: client.latency(),
+            ..parse_response(packets)?
+        })
+    }
+    .instrument(trace::span!("gamespy3", %address))
+    .await
+    .map_err(|e: GDError| e.with_address(*address).with_stage(QueryStage::Info))
+}
@@ -5,6 +5,7 @@ use std::collections::HashMap;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 /// A player’s details.
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -51,6 +52,7 @@ pub struct Response {
     pub teams: Vec<Team>,
     pub tournament: bool,
     pub unused_entries: HashMap<String, String>,
+    pub latency: Option<Duration>,
 }
 
 impl CommonResponse for Response {
@@ -72,4 +74,35 @@ impl CommonResponse for Response {
                 .collect(),
         )
     }
+
+    fn latency(&self) -> Option<Duration> { self.latency }
+}
+
+/// The payload of the data request the queries send by default.
+pub const DEFAULT_PAYLOAD: [u8; 4] = [0xFF, 0xFF, 0xFF, 0x01];
+
+/// How a query asks for the server's data, as some games deviate from the
+/// protocol.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GameSpy3RequestSettings {
+    /// The payload of the data request, [DEFAULT_PAYLOAD] by default.
+    pub payload: [u8; 4],
+    /// Whether the server answers with a single, unnumbered packet instead
+    /// of split ones, `false` by default.
+    pub single_packets: bool,
+}
+
+impl GameSpy3RequestSettings {
+    /// The settings of the protocol itself.
+    pub const fn new() -> Self {
+        Self {
+            payload: DEFAULT_PAYLOAD,
+            single_packets: false,
+        }
+    }
+}
+
+impl Default for GameSpy3RequestSettings {
+    fn default() -> Self { Self::new() }
 }
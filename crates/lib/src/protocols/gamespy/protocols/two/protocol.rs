@@ -1,10 +1,16 @@
 use crate::buffer::{Buffer, Utf8Decoder};
 use crate::protocols::gamespy::two::{Player, Response, Team};
-use crate::protocols::types::TimeoutSettings;
+use crate::protocols::types::{RetryPolicy, TimeoutSettings};
+use crate::retry::retry;
 use crate::socket::{Socket, UdpSocket};
-use crate::utils::retry_on_timeout;
 use crate::GDErrorKind::{PacketBad, TypeParse};
-use crate::{GDErrorKind, GDResult};
+#[cfg(feature = "async")]
+use crate::{
+    retry::retry_async,
+    socket::{AsyncSocket, AsyncUdpSocket},
+    trace::Instrument,
+};
+use crate::{trace, GDError, GDErrorKind, GDResult, QueryStage};
 use byteorder::BigEndian;
 use std::collections::HashMap;
 use std::net::SocketAddr;
@@ -12,6 +18,7 @@ use std::net::SocketAddr;
 struct GameSpy2 {
     socket: UdpSocket,
     retry_count: usize,
+    retry_policy: RetryPolicy,
 }
 
 macro_rules! table_extract {
@@ -81,37 +88,49 @@ impl GameSpy2 {
     fn new(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Self> {
         let socket = UdpSocket::new(address, &timeout_settings)?;
         let retry_count = TimeoutSettings::get_retries_or_default(&timeout_settings);
+        let retry_policy = TimeoutSettings::get_retry_policy_or_default(&timeout_settings);
 
         Ok(Self {
             socket,
             retry_count,
+            retry_policy,
         })
     }
 
     /// Send fetch request to server and store result in buffer.
     /// This function will retry fetch on timeouts.
     fn request_data(&mut self) -> GDResult<(Vec<u8>, usize)> {
-        retry_on_timeout(self.retry_count, move || self.request_data_impl())
+        retry(self.retry_count, self.retry_policy, move || {
+            self.request_data_impl()
+        })
     }
 
     /// Send fetch request to server and store result in buffer (without retry
     /// logic).
     fn request_data_impl(&mut self) -> GDResult<(Vec<u8>, usize)> {
-        self.socket
-            .send(&[0xFE, 0xFD, 0x00, 0x00, 0x00, 0x00, 0x01, 0xFF, 0xFF, 0xFF])?;
+        trace::event!(DEBUG, "GameSpy 2 request");
+        self.socket.send(&REQUEST)?;
 
         let received = self.socket.receive(None)?;
+        let buf_index = check_response_header(&received)?;
 
-        let mut buf = Buffer::<BigEndian>::new(&received);
-        if buf.read::<u8>()? != 0 || buf.read::<u32>()? != 1 {
-            return Err(PacketBad.into());
-        }
-
-        let buf_index = buf.current_position();
         Ok((received, buf_index))
     }
 }
 
+/// The request asking for the server's values, players and teams.
+const REQUEST: [u8; 10] = [0xFE, 0xFD, 0x00, 0x00, 0x00, 0x00, 0x01, 0xFF, 0xFF, 0xFF];
+
+/// Validate the header of a response, returning where its data starts.
+fn check_response_header(received: &[u8]) -> GDResult<usize> {
+    let mut buf = Buffer::<BigEndian>::new(received);
+    if buf.read::<u8>()? != 0 || buf.read::<u32>()? != 1 {
+        return Err(PacketBad.into());
+    }
+
+    Ok(buf.current_position())
+}
+
 fn get_server_vars(bufferer: &mut Buffer<BigEndian>) -> GDResult<HashMap<String, String>> {
     let mut values = HashMap::new();
 
@@ -168,10 +187,38 @@ fn get_players(bufferer: &mut Buffer<BigEndian>) -> GDResult<Vec<Player>> {
 }
 
 pub fn query(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Response> {
-    let mut client = GameSpy2::new(address, timeout_settings)?;
-    let (data, buf_index) = client.request_data()?;
+    trace::span!("gamespy2", %address)
+        .in_scope(|| {
+            let mut client = GameSpy2::new(address, timeout_settings)?;
+            let (data, buf_index) = client.request_data()?;
+
+            Ok(Response {
+                latency: client.socket.latency(),
+                ..parse_response(&data, buf_index)?
+            })
+        })
+        .map_err(|e: GDError| e.with_address(*address).with_stage(QueryStage::Info))
+}
 
-    let mut buffer = Buffer::<BigEndian>::new(&data);
+/// Parse a response packet, as received.
+///
+/// Unlike queries, the session id of the response isn't checked, so that
+/// responses to any client can be parsed.
+pub fn parse(data: &[u8]) -> GDResult<Response> {
+    let mut buf = Buffer::<BigEndian>::new(data);
+    if buf.read::<u8>()? != 0 {
+        return Err(PacketBad.context("Kind of packet did not match"));
+    }
+
+    // The session id.
+    buf.move_cursor(4)?;
+
+    parse_response(data, buf.current_position())
+}
+
+/// Parse a response whose data starts at `buf_index`.
+fn parse_response(data: &[u8], buf_index: usize) -> GDResult<Response> {
+    let mut buffer = Buffer::<BigEndian>::new(data);
     buffer.move_cursor(buf_index as isize)?;
 
     let mut server_vars = get_server_vars(&mut buffer)?;
@@ -206,5 +253,32 @@ pub fn query(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) ->
         players_minimum,
         players,
         unused_entries: server_vars,
+        latency: None,
     })
 }
+
+/// Async counterpart of [query].
+#[cfg(feature = "async")]
+pub async fn query_async(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Response> {
+    async {
+        let mut socket = AsyncUdpSocket::new(address, &timeout_settings).await?;
+        let data = retry_async!(
+            TimeoutSettings::get_retries_or_default(&timeout_settings),
+            TimeoutSettings::get_retry_policy_or_default(&timeout_settings),
+            async {
+                trace::event!(DEBUG, "GameSpy 2 request");
+                socket.send(&REQUEST).await?;
+                socket.receive(None).await
+            }
+            .await
+        )?;
+
+        Ok(Response {
+            latency: socket.latency(),
+            ..parse_response(&data, check_response_header(&data)?)?
+        })
+    }
+    .instrument(trace::span!("gamespy2", %address))
+    .await
+    .map_err(|e: GDError| e.with_address(*address).with_stage(QueryStage::Info))
+}
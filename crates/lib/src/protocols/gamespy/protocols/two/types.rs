@@ -6,6 +6,7 @@ use crate::protocols::GenericResponse;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -42,6 +43,7 @@ pub struct Response {
     pub players_minimum: Option<u32>,
     pub players: Vec<Player>,
     pub unused_entries: HashMap<String, String>,
+    pub latency: Option<Duration>,
 }
 
 impl CommonResponse for Response {
@@ -61,4 +63,6 @@ impl CommonResponse for Response {
                 .collect(),
         )
     }
+
+    fn latency(&self) -> Option<Duration> { self.latency }
 }
@@ -3,13 +3,20 @@ use byteorder::LittleEndian;
 use crate::buffer::{Buffer, Utf8Decoder};
 use crate::protocols::quake::types::Response;
 use crate::protocols::types::TimeoutSettings;
+use crate::retry::retry;
 use crate::socket::{Socket, UdpSocket};
-use crate::utils::retry_on_timeout;
 use crate::GDErrorKind::{PacketBad, TypeParse};
-use crate::{GDErrorKind, GDResult};
+#[cfg(feature = "async")]
+use crate::{
+    retry::retry_async,
+    socket::{AsyncSocket, AsyncUdpSocket},
+    trace::Instrument,
+};
+use crate::{trace, GDError, GDErrorKind, GDResult, QueryStage};
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::slice::Iter;
+use std::time::Duration;
 
 pub trait QuakeClient {
     type Player;
@@ -19,32 +26,49 @@ pub trait QuakeClient {
     fn parse_player_string(data: Iter<&str>) -> GDResult<Self::Player>;
 }
 
-/// Send request and return result buffer.
+/// Send request and return result buffer, alongside the request's round-trip
+/// time.
 /// This function will retry fetch on timeouts.
 fn get_data<Client: QuakeClient>(
     address: &SocketAddr,
     timeout_settings: &Option<TimeoutSettings>,
-) -> GDResult<Vec<u8>> {
+) -> GDResult<(Vec<u8>, Option<Duration>)> {
     let mut socket = UdpSocket::new(address, timeout_settings)?;
-    retry_on_timeout(
+    let data = retry(
         TimeoutSettings::get_retries_or_default(timeout_settings),
-        move || get_data_impl::<Client>(&mut socket),
-    )
+        TimeoutSettings::get_retry_policy_or_default(timeout_settings),
+        || get_data_impl::<Client>(&mut socket),
+    )?;
+
+    Ok((data, socket.latency()))
 }
 
 /// Send request and return result buffer (without retry logic).
 fn get_data_impl<Client: QuakeClient>(socket: &mut UdpSocket) -> GDResult<Vec<u8>> {
-    socket.send(
-        &[
-            &[0xFF, 0xFF, 0xFF, 0xFF],
-            Client::get_send_header().as_bytes(),
-            &[0x00],
-        ]
-        .concat(),
-    )?;
+    trace::event!(
+        DEBUG,
+        header = Client::get_send_header(),
+        "Quake status request"
+    );
+    socket.send(&request::<Client>())?;
 
     let data = socket.receive(None)?;
-    let mut bufferer = Buffer::<LittleEndian>::new(&data);
+    strip_response_header::<Client>(&data)
+}
+
+/// The status request of a client.
+fn request<Client: QuakeClient>() -> Vec<u8> {
+    [
+        &[0xFF, 0xFF, 0xFF, 0xFF],
+        Client::get_send_header().as_bytes(),
+        &[0x00],
+    ]
+    .concat()
+}
+
+/// Validate the header of a response, returning the data that follows it.
+fn strip_response_header<Client: QuakeClient>(data: &[u8]) -> GDResult<Vec<u8>> {
+    let mut bufferer = Buffer::<LittleEndian>::new(data);
 
     if bufferer.read::<u32>()? != u32::MAX {
         return Err(PacketBad.context("Expected 4294967295"));
@@ -89,10 +113,8 @@ fn get_server_values(bufferer: &mut Buffer<LittleEndian>) -> GDResult<HashMap<St
 fn get_players<Client: QuakeClient>(bufferer: &mut Buffer<LittleEndian>) -> GDResult<Vec<Client::Player>> {
     let mut players: Vec<Client::Player> = Vec::new();
 
-    // this needs to be looked at again as theres no way to check if the buffer has
-    // a remaining null byte the original code was:
-    // while !bufferer.is_remaining_empty() && bufferer.remaining_data() != [0x00]
-    while !bufferer.remaining_length() == 0 {
+    // Some servers end the response with a null byte.
+    while bufferer.remaining_length() != 0 && bufferer.remaining_bytes() != [0x00] {
         let data = bufferer.read_string::<Utf8Decoder>(Some([0x0A]))?;
         let data_split = data.split(' ').collect::<Vec<&str>>();
         let data_iter = data_split.iter();
@@ -107,8 +129,26 @@ pub fn client_query<Client: QuakeClient>(
     address: &SocketAddr,
     timeout_settings: Option<TimeoutSettings>,
 ) -> GDResult<Response<Client::Player>> {
-    let data = get_data::<Client>(address, &timeout_settings)?;
-    let mut bufferer = Buffer::<LittleEndian>::new(&data);
+    trace::span!("quake", %address)
+        .in_scope(|| {
+            let (data, latency) = get_data::<Client>(address, &timeout_settings)?;
+
+            Ok(Response {
+                latency,
+                ..parse_response::<Client>(&data)?
+            })
+        })
+        .map_err(|e: GDError| e.with_address(*address).with_stage(QueryStage::Info))
+}
+
+/// Parse a response packet, as received.
+pub fn client_parse<Client: QuakeClient>(data: &[u8]) -> GDResult<Response<Client::Player>> {
+    parse_response::<Client>(&strip_response_header::<Client>(data)?)
+}
+
+/// Parse the data that follows the response header.
+fn parse_response<Client: QuakeClient>(data: &[u8]) -> GDResult<Response<Client::Player>> {
+    let mut bufferer = Buffer::<LittleEndian>::new(data);
 
     let mut server_vars = get_server_values(&mut bufferer)?;
     let players = get_players::<Client>(&mut bufferer)?;
@@ -134,9 +174,43 @@ pub fn client_query<Client: QuakeClient>(
             .remove("version")
             .or_else(|| server_vars.remove("*version")),
         unused_entries: server_vars,
+        latency: None,
     })
 }
 
+/// Async counterpart of [client_query].
+#[cfg(feature = "async")]
+pub async fn client_query_async<Client: QuakeClient>(
+    address: &SocketAddr,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<Response<Client::Player>> {
+    async {
+        let mut socket = AsyncUdpSocket::new(address, &timeout_settings).await?;
+        let data = retry_async!(
+            TimeoutSettings::get_retries_or_default(&timeout_settings),
+            TimeoutSettings::get_retry_policy_or_default(&timeout_settings),
+            async {
+                trace::event!(
+                    DEBUG,
+                    header = Client::get_send_header(),
+                    "Quake status request"
+                );
+                socket.send(&request::<Client>()).await?;
+                strip_response_header::<Client>(&socket.receive(None).await?)
+            }
+            .await
+        )?;
+
+        Ok(Response {
+            latency: socket.latency(),
+            ..parse_response::<Client>(&data)?
+        })
+    }
+    .instrument(trace::span!("quake", %address))
+    .await
+    .map_err(|e: GDError| e.with_address(*address).with_stage(QueryStage::Info))
+}
+
 pub fn remove_wrapping_quotes<'a>(string: &&'a str) -> &'a str {
     match string.starts_with('\"') && string.ends_with('\"') {
         false => string,
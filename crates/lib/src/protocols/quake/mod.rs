@@ -12,7 +12,7 @@ pub use types::*;
 mod client;
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum QuakeVersion {
     One,
     Two,
@@ -71,6 +71,19 @@ macro_rules! game_query_fn {
                 None,
             )
         }
+
+        /// Async counterpart of [query].
+        #[cfg(feature = "async")]
+        pub async fn query_async(
+            address: &std::net::IpAddr,
+            port: Option<u16>,
+        ) -> crate::GDResult<crate::protocols::quake::Response<$player_type>> {
+            crate::protocols::quake::$quake_ver::query_async(
+                &std::net::SocketAddr::new(*address, port.unwrap_or($default_port)),
+                None,
+            )
+            .await
+        }
     };
 }
 
@@ -1,4 +1,4 @@
-use crate::protocols::quake::client::{client_query, QuakeClient};
+use crate::protocols::quake::client::{client_parse, client_query, QuakeClient};
 use crate::protocols::quake::two::QuakeTwo;
 use crate::protocols::quake::Response;
 use crate::protocols::types::TimeoutSettings;
@@ -22,3 +22,15 @@ impl QuakeClient for QuakeThree {
 pub fn query(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Response<Player>> {
     client_query::<QuakeThree>(address, timeout_settings)
 }
+
+/// Parse a status response packet, as received.
+pub fn parse(data: &[u8]) -> GDResult<Response<Player>> { client_parse::<QuakeThree>(data) }
+
+/// Async counterpart of [query].
+#[cfg(feature = "async")]
+pub async fn query_async(
+    address: &SocketAddr,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<Response<Player>> {
+    crate::protocols::quake::client::client_query_async::<QuakeThree>(address, timeout_settings).await
+}
@@ -1,4 +1,4 @@
-use crate::protocols::quake::client::{client_query, remove_wrapping_quotes, QuakeClient};
+use crate::protocols::quake::client::{client_parse, client_query, remove_wrapping_quotes, QuakeClient};
 use crate::protocols::quake::one::QuakeOne;
 use crate::protocols::quake::Response;
 use crate::protocols::types::{CommonPlayer, GenericPlayer, TimeoutSettings};
@@ -65,3 +65,15 @@ impl QuakeClient for QuakeTwo {
 pub fn query(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Response<Player>> {
     client_query::<QuakeTwo>(address, timeout_settings)
 }
+
+/// Parse a status response packet, as received.
+pub fn parse(data: &[u8]) -> GDResult<Response<Player>> { client_parse::<QuakeTwo>(data) }
+
+/// Async counterpart of [query].
+#[cfg(feature = "async")]
+pub async fn query_async(
+    address: &SocketAddr,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<Response<Player>> {
+    crate::protocols::quake::client::client_query_async::<QuakeTwo>(address, timeout_settings).await
+}
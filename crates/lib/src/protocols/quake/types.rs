@@ -6,6 +6,7 @@ use crate::protocols::{
     types::{CommonPlayer, CommonResponse},
     GenericResponse,
 };
+use std::time::Duration;
 
 /// General server information's.
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -25,9 +26,11 @@ pub struct Response<P> {
     pub game_version: Option<String>,
     /// Other server entries that weren't used.
     pub unused_entries: HashMap<String, String>,
+    /// Round-trip time of the status request.
+    pub latency: Option<Duration>,
 }
 
-pub trait QuakePlayerType: Sized + CommonPlayer {
+pub trait QuakePlayerType: Sized + Send + Sync + CommonPlayer {
     fn version(response: &Response<Self>) -> VersionedResponse<'_>;
 }
 
@@ -48,6 +51,8 @@ impl<P: QuakePlayerType> CommonResponse for Response<P> {
                 .collect(),
         )
     }
+
+    fn latency(&self) -> Option<Duration> { self.latency }
 }
 
 /// Versioned response type
@@ -57,3 +62,30 @@ pub enum VersionedResponse<'a> {
     One(&'a Response<crate::protocols::quake::one::Player>),
     TwoAndThree(&'a Response<crate::protocols::quake::two::Player>),
 }
+
+/// Owned [VersionedResponse], which can be deserialized.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionedResponseOwned {
+    One(Response<crate::protocols::quake::one::Player>),
+    TwoAndThree(Response<crate::protocols::quake::two::Player>),
+}
+
+impl VersionedResponseOwned {
+    /// Get the response as a [CommonResponse].
+    pub fn as_common(&self) -> &dyn CommonResponse {
+        match self {
+            Self::One(response) => response,
+            Self::TwoAndThree(response) => response,
+        }
+    }
+}
+
+impl From<VersionedResponse<'_>> for VersionedResponseOwned {
+    fn from(response: VersionedResponse<'_>) -> Self {
+        match response {
+            VersionedResponse::One(response) => Self::One(response.clone()),
+            VersionedResponse::TwoAndThree(response) => Self::TwoAndThree(response.clone()),
+        }
+    }
+}
@@ -3,9 +3,12 @@ use crate::games::minecraft;
 #[cfg(feature = "tls")]
 use crate::protocols::epic;
 use crate::protocols::{gamespy, quake, unreal2, valve};
-use crate::GDErrorKind::InvalidInput;
-use crate::GDResult;
+use crate::GDErrorKind::{self, InvalidInput};
+use crate::{GDError, GDResult, QueryStage};
 
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 
 #[cfg(feature = "serde")]
@@ -29,7 +32,7 @@ pub enum ProprietaryProtocol {
 
 /// Enumeration of all valid protocol types
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Protocol {
     Gamespy(gamespy::GameSpyVersion),
     Quake(quake::QuakeVersion),
@@ -74,6 +77,120 @@ pub enum GenericResponse<'a> {
     Minetest(&'a crate::games::minetest::Response),
 }
 
+/// Owned [GenericResponse], which can be deserialized (from what either
+/// serializes to).
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum GenericResponseOwned {
+    GameSpy(gamespy::VersionedResponseOwned),
+    Quake(quake::VersionedResponseOwned),
+    Valve(valve::Response),
+    Unreal2(unreal2::Response),
+    #[cfg(feature = "tls")]
+    Epic(epic::Response),
+    #[cfg(feature = "games")]
+    Mindustry(crate::games::mindustry::types::ServerData),
+    #[cfg(feature = "games")]
+    Minecraft(minecraft::VersionedResponseOwned),
+    #[cfg(feature = "games")]
+    TheShip(crate::games::theship::Response),
+    #[cfg(feature = "games")]
+    FFOW(crate::games::ffow::Response),
+    #[cfg(feature = "games")]
+    JC2M(crate::games::jc2m::Response),
+    #[cfg(feature = "games")]
+    Savage2(crate::games::savage2::Response),
+    #[cfg(feature = "games")]
+    Eco(crate::games::eco::Response),
+    #[cfg(all(
+        feature = "services",
+        feature = "tls",
+        feature = "serde",
+        feature = "games"
+    ))]
+    Minetest(crate::games::minetest::Response),
+}
+
+impl GenericResponseOwned {
+    /// Get the response as a [CommonResponse].
+    pub fn as_common(&self) -> &dyn CommonResponse {
+        match self {
+            Self::GameSpy(response) => response.as_common(),
+            Self::Quake(response) => response.as_common(),
+            Self::Valve(response) => response,
+            Self::Unreal2(response) => response,
+            #[cfg(feature = "tls")]
+            Self::Epic(response) => response,
+            #[cfg(feature = "games")]
+            Self::Mindustry(response) => response,
+            #[cfg(feature = "games")]
+            Self::Minecraft(response) => response.as_common(),
+            #[cfg(feature = "games")]
+            Self::TheShip(response) => response,
+            #[cfg(feature = "games")]
+            Self::FFOW(response) => response,
+            #[cfg(feature = "games")]
+            Self::JC2M(response) => response,
+            #[cfg(feature = "games")]
+            Self::Savage2(response) => response,
+            #[cfg(feature = "games")]
+            Self::Eco(response) => response,
+            #[cfg(all(
+                feature = "services",
+                feature = "tls",
+                feature = "serde",
+                feature = "games"
+            ))]
+            Self::Minetest(response) => response,
+        }
+    }
+
+    /// Get the original response type.
+    pub fn as_original(&self) -> GenericResponse<'_> { self.as_common().as_original() }
+}
+
+impl From<GenericResponse<'_>> for GenericResponseOwned {
+    fn from(response: GenericResponse<'_>) -> Self {
+        match response {
+            GenericResponse::GameSpy(response) => Self::GameSpy(response.into()),
+            GenericResponse::Quake(response) => Self::Quake(response.into()),
+            GenericResponse::Valve(response) => Self::Valve(response.clone()),
+            GenericResponse::Unreal2(response) => Self::Unreal2(response.clone()),
+            #[cfg(feature = "tls")]
+            GenericResponse::Epic(response) => Self::Epic(response.clone()),
+            #[cfg(feature = "games")]
+            GenericResponse::Mindustry(response) => Self::Mindustry(response.clone()),
+            #[cfg(feature = "games")]
+            GenericResponse::Minecraft(response) => Self::Minecraft(response.into()),
+            #[cfg(feature = "games")]
+            GenericResponse::TheShip(response) => Self::TheShip(response.clone()),
+            #[cfg(feature = "games")]
+            GenericResponse::FFOW(response) => Self::FFOW(response.clone()),
+            #[cfg(feature = "games")]
+            GenericResponse::JC2M(response) => Self::JC2M(response.clone()),
+            #[cfg(feature = "games")]
+            GenericResponse::Savage2(response) => Self::Savage2(response.clone()),
+            #[cfg(feature = "games")]
+            GenericResponse::Eco(response) => Self::Eco(response.clone()),
+            #[cfg(all(
+                feature = "services",
+                feature = "tls",
+                feature = "serde",
+                feature = "games"
+            ))]
+            GenericResponse::Minetest(response) => Self::Minetest(response.clone()),
+        }
+    }
+}
+
+impl From<&dyn CommonResponse> for GenericResponseOwned {
+    fn from(response: &dyn CommonResponse) -> Self { response.as_original().into() }
+}
+
+impl From<Box<dyn CommonResponse>> for GenericResponseOwned {
+    fn from(response: Box<dyn CommonResponse>) -> Self { response.as_original().into() }
+}
+
 /// All player types
 #[cfg_attr(feature = "serde", derive(Serialize))]
 #[derive(Debug, Clone, PartialEq)]
@@ -102,7 +219,7 @@ pub enum GenericPlayer<'a> {
     Minetest(&'a crate::games::minetest::Player),
 }
 
-pub trait CommonResponse {
+pub trait CommonResponse: Send + Sync {
     /// Get the original response type
     fn as_original(&self) -> GenericResponse<'_>;
     /// Get a struct that can be stored as JSON (you don't need to override
@@ -121,6 +238,7 @@ pub trait CommonResponse {
             players: self
                 .players()
                 .map(|players| players.iter().map(|p| p.as_json()).collect()),
+            latency: self.latency(),
         }
     }
 
@@ -144,6 +262,10 @@ pub trait CommonResponse {
     fn has_password(&self) -> Option<bool> { None }
     /// Currently connected players
     fn players(&self) -> Option<Vec<&dyn CommonPlayer>> { None }
+    /// Round-trip time of the query's main request (e.g. the info request)
+    fn latency(&self) -> Option<Duration> { None }
+    /// Optional data that couldn't be gathered, and why
+    fn diagnostics(&self) -> &[Diagnostic] { &[] }
 }
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -159,6 +281,7 @@ pub struct CommonResponseJson<'a> {
     pub players_bots: Option<u32>,
     pub has_password: Option<bool>,
     pub players: Option<Vec<CommonPlayerJson<'a>>>,
+    pub latency: Option<Duration>,
 }
 
 pub trait CommonPlayer {
@@ -186,12 +309,114 @@ pub struct CommonPlayerJson<'a> {
     pub score: Option<i32>,
 }
 
+/// The current [CommonResponseOwned::schema_version], bumped whenever its
+/// serialized form changes in a way older readers can't handle.
+pub const COMMON_RESPONSE_SCHEMA_VERSION: u32 = 1;
+
+/// An owned [CommonResponseJson], which can outlive the response, be sent
+/// across threads and be deserialized.
+///
+/// Fields missing when deserializing take their default value, so stored
+/// responses can still be read once fields are added.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CommonResponseOwned {
+    /// The [COMMON_RESPONSE_SCHEMA_VERSION] this response was made with (0 if
+    /// it was stored without one).
+    pub schema_version: u32,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub game_mode: Option<String>,
+    pub game_version: Option<String>,
+    pub map: Option<String>,
+    pub players_maximum: u32,
+    pub players_online: u32,
+    pub players_bots: Option<u32>,
+    pub has_password: Option<bool>,
+    pub players: Option<Vec<CommonPlayerOwned>>,
+    pub latency: Option<Duration>,
+}
+
+impl From<CommonResponseJson<'_>> for CommonResponseOwned {
+    fn from(response: CommonResponseJson<'_>) -> Self {
+        Self {
+            schema_version: COMMON_RESPONSE_SCHEMA_VERSION,
+            name: response.name.map(str::to_string),
+            description: response.description.map(str::to_string),
+            game_mode: response.game_mode.map(str::to_string),
+            game_version: response.game_version.map(str::to_string),
+            map: response.map.map(str::to_string),
+            players_maximum: response.players_maximum,
+            players_online: response.players_online,
+            players_bots: response.players_bots,
+            has_password: response.has_password,
+            players: response
+                .players
+                .map(|players| players.into_iter().map(CommonPlayerOwned::from).collect()),
+            latency: response.latency,
+        }
+    }
+}
+
+impl From<&dyn CommonResponse> for CommonResponseOwned {
+    fn from(response: &dyn CommonResponse) -> Self { response.as_json().into() }
+}
+
+impl From<Box<dyn CommonResponse>> for CommonResponseOwned {
+    fn from(response: Box<dyn CommonResponse>) -> Self { response.as_json().into() }
+}
+
+/// An owned [CommonPlayerJson].
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CommonPlayerOwned {
+    pub name: String,
+    pub score: Option<i32>,
+}
+
+impl From<CommonPlayerJson<'_>> for CommonPlayerOwned {
+    fn from(player: CommonPlayerJson<'_>) -> Self {
+        Self {
+            name: player.name.to_string(),
+            score: player.score,
+        }
+    }
+}
+
+impl From<&dyn CommonPlayer> for CommonPlayerOwned {
+    fn from(player: &dyn CommonPlayer) -> Self { player.as_json().into() }
+}
+
 #[cfg(feature = "clap")]
 fn parse_duration_secs(value: &str) -> Result<Duration, std::num::ParseIntError> {
     let secs = value.parse()?;
     Ok(Duration::from_secs(secs))
 }
 
+#[cfg(feature = "clap")]
+fn parse_duration_millis(value: &str) -> Result<Duration, std::num::ParseIntError> {
+    let millis = value.parse()?;
+    Ok(Duration::from_millis(millis))
+}
+
+#[cfg(feature = "clap")]
+fn parse_port_range(value: &str) -> Result<(u16, u16), String> {
+    let (first, last) = value.split_once('-').unwrap_or((value, value));
+    let first = first.trim().parse().map_err(|e| format!("{e}"))?;
+    let last = last.trim().parse().map_err(|e| format!("{e}"))?;
+
+    match first <= last {
+        true => Ok((first, last)),
+        false => {
+            Err(String::from(
+                "The range's first port must not be after its last one",
+            ))
+        }
+    }
+}
+
 /// Timeout settings for socket operations
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "clap", derive(clap::Args))]
@@ -206,6 +431,15 @@ pub struct TimeoutSettings {
     /// Number of retries per request
     #[cfg_attr(feature = "clap", arg(long, default_value = "0"))]
     retries: usize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    #[cfg_attr(feature = "clap", arg(long = "total-timeout", value_parser = parse_duration_secs, help = "Deadline of the whole query (in seconds)"))]
+    total: Option<Duration>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    #[cfg_attr(feature = "clap", command(flatten))]
+    retry: RetryPolicy,
+    #[cfg_attr(feature = "serde", serde(default))]
+    #[cfg_attr(feature = "clap", command(flatten))]
+    network: NetworkSettings,
 }
 
 impl TimeoutSettings {
@@ -246,9 +480,43 @@ impl TimeoutSettings {
             write,
             connect,
             retries,
+            total: None,
+            retry: RetryPolicy::new(),
+            network: NetworkSettings::new(),
         })
     }
 
+    /// Set a deadline for the whole query (all of its requests and retries),
+    /// only enforced by the generic query functions (e.g.
+    /// [query_with_timeout_and_extra_settings](crate::games::query::query_with_timeout_and_extra_settings)).
+    ///
+    /// When it expires, the query returns the data gathered so far if the
+    /// missing parts were optional (e.g. players with
+    /// [GatherToggle::Try]) or fails with
+    /// GDErrorKind::[DeadlineExceeded](crate::GDErrorKind::DeadlineExceeded).
+    ///
+    /// Passing zero Duration throws GDErrorKind::[InvalidInput].
+    pub fn with_total_timeout(mut self, total: Duration) -> GDResult<Self> {
+        if total.is_zero() {
+            return Err(InvalidInput.context("Total duration must not be 0"));
+        }
+
+        self.total = Some(total);
+        Ok(self)
+    }
+
+    /// Set how failed requests are retried, see [RetryPolicy].
+    pub const fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Set the local network settings of the UDP sockets.
+    pub const fn with_network_settings(mut self, network: NetworkSettings) -> Self {
+        self.network = network;
+        self
+    }
+
     /// Get the read timeout.
     pub const fn get_read(&self) -> Option<Duration> { self.read }
 
@@ -261,6 +529,15 @@ impl TimeoutSettings {
     /// Get number of retries
     pub const fn get_retries(&self) -> usize { self.retries }
 
+    /// Get the total timeout.
+    pub const fn get_total(&self) -> Option<Duration> { self.total }
+
+    /// Get the retry policy.
+    pub const fn get_retry_policy(&self) -> RetryPolicy { self.retry }
+
+    /// Get the local network settings of the UDP sockets.
+    pub const fn get_network_settings(&self) -> NetworkSettings { self.network }
+
     /// Get the number of retries if there are timeout settings else fall back
     /// to the default
     pub const fn get_retries_or_default(timeout_settings: &Option<Self>) -> usize {
@@ -293,6 +570,33 @@ impl TimeoutSettings {
         }
     }
 
+    /// Get the total duration given timeout settings or get the default.
+    pub const fn get_total_or_default(timeout_settings: &Option<Self>) -> Option<Duration> {
+        if let Some(timeout_settings) = timeout_settings {
+            timeout_settings.get_total()
+        } else {
+            Self::const_default().get_total()
+        }
+    }
+
+    /// Get the retry policy given timeout settings or get the default.
+    pub const fn get_retry_policy_or_default(timeout_settings: &Option<Self>) -> RetryPolicy {
+        if let Some(timeout_settings) = timeout_settings {
+            timeout_settings.get_retry_policy()
+        } else {
+            Self::const_default().get_retry_policy()
+        }
+    }
+
+    /// Get the network settings given timeout settings or get the default.
+    pub const fn get_network_settings_or_default(timeout_settings: &Option<Self>) -> NetworkSettings {
+        if let Some(timeout_settings) = timeout_settings {
+            timeout_settings.get_network_settings()
+        } else {
+            Self::const_default().get_network_settings()
+        }
+    }
+
     /// Default values are 4 seconds for both read and write, no retries.
     pub const fn const_default() -> Self {
         Self {
@@ -300,6 +604,9 @@ impl TimeoutSettings {
             write: Some(Duration::from_secs(4)),
             connect: Some(Duration::from_secs(4)),
             retries: 0,
+            total: None,
+            retry: RetryPolicy::new(),
+            network: NetworkSettings::new(),
         }
     }
 }
@@ -309,6 +616,334 @@ impl Default for TimeoutSettings {
     fn default() -> Self { Self::const_default() }
 }
 
+/// Whether each kind of error is retried, see [RetryPolicy::retries_on].
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct RetryableKinds {
+    packet_overflow: bool,
+    packet_underflow: bool,
+    packet_bad: bool,
+    packet_send: bool,
+    packet_receive: bool,
+    decompress: bool,
+    socket_connect: bool,
+    socket_bind: bool,
+    invalid_input: bool,
+    bad_game: bool,
+    auto_query: bool,
+    protocol_format: bool,
+    unknown_enum_cast: bool,
+    json_parse: bool,
+    type_parse: bool,
+    host_lookup: bool,
+    deadline_exceeded: bool,
+    packet_capture: bool,
+}
+
+impl RetryableKinds {
+    /// Only timeouts and failures to send are retried.
+    const fn failed_exchanges() -> Self {
+        Self {
+            packet_overflow: false,
+            packet_underflow: false,
+            packet_bad: false,
+            packet_send: true,
+            packet_receive: true,
+            decompress: false,
+            socket_connect: false,
+            socket_bind: false,
+            invalid_input: false,
+            bad_game: false,
+            auto_query: false,
+            protocol_format: false,
+            unknown_enum_cast: false,
+            json_parse: false,
+            type_parse: false,
+            host_lookup: false,
+            deadline_exceeded: false,
+            packet_capture: false,
+        }
+    }
+
+    /// Whether `kind` is retried.
+    const fn get(mut self, kind: &GDErrorKind) -> bool { *self.flag(kind) }
+
+    /// The flag of `kind`.
+    const fn flag(&mut self, kind: &GDErrorKind) -> &mut bool {
+        match kind {
+            GDErrorKind::PacketOverflow => &mut self.packet_overflow,
+            GDErrorKind::PacketUnderflow => &mut self.packet_underflow,
+            GDErrorKind::PacketBad => &mut self.packet_bad,
+            GDErrorKind::PacketSend => &mut self.packet_send,
+            GDErrorKind::PacketReceive => &mut self.packet_receive,
+            GDErrorKind::Decompress => &mut self.decompress,
+            GDErrorKind::SocketConnect => &mut self.socket_connect,
+            GDErrorKind::SocketBind => &mut self.socket_bind,
+            GDErrorKind::InvalidInput => &mut self.invalid_input,
+            GDErrorKind::BadGame => &mut self.bad_game,
+            GDErrorKind::AutoQuery => &mut self.auto_query,
+            GDErrorKind::ProtocolFormat => &mut self.protocol_format,
+            GDErrorKind::UnknownEnumCast => &mut self.unknown_enum_cast,
+            GDErrorKind::JsonParse => &mut self.json_parse,
+            GDErrorKind::TypeParse => &mut self.type_parse,
+            GDErrorKind::HostLookup => &mut self.host_lookup,
+            GDErrorKind::DeadlineExceeded => &mut self.deadline_exceeded,
+            GDErrorKind::PacketCapture => &mut self.packet_capture,
+        }
+    }
+}
+
+impl Default for RetryableKinds {
+    fn default() -> Self { Self::failed_exchanges() }
+}
+
+/// The next fraction (in `[0, 1)`) of the backoff to wait when jittering,
+/// from a SplitMix64 sequence shared by the whole process so that concurrent
+/// queries get different delays.
+fn jitter_fraction() -> f64 {
+    const GOLDEN_GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+    static STATE: AtomicU64 = AtomicU64::new(0);
+
+    let mut z = STATE
+        .fetch_add(GOLDEN_GAMMA, Ordering::Relaxed)
+        .wrapping_add(GOLDEN_GAMMA);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+
+    // The top 53 bits, as many as an f64 holds exactly.
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// How failed requests are retried, carried by [TimeoutSettings] (which holds
+/// the number of retries).
+///
+/// By default requests are retried immediately, with the same read timeout,
+/// and only when they time out (GDErrorKind::[PacketReceive], see
+/// [GDError::is_timeout](crate::GDError::is_timeout)) or fail to be sent
+/// (GDErrorKind::[PacketSend], HTTP error statuses included).
+///
+/// ```
+/// use gamedig::protocols::types::RetryPolicy;
+/// use gamedig::TimeoutSettings;
+/// use std::time::Duration;
+///
+/// // Wait 250ms before the first retry, 500ms before the second, 1s before the
+/// // third... (randomized), and give each try 50% more time than the previous one.
+/// let retry = RetryPolicy::new()
+///     .with_backoff(Duration::from_millis(250), Some(Duration::from_secs(2)))
+///     .with_jitter(true)
+///     .with_read_timeout_growth(50);
+///
+/// let timeout_settings = TimeoutSettings::new(None, None, None, 3)
+///     .unwrap()
+///     .with_retry_policy(retry);
+/// ```
+///
+/// [PacketReceive]: GDErrorKind::PacketReceive
+/// [PacketSend]: GDErrorKind::PacketSend
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RetryPolicy {
+    #[cfg_attr(feature = "clap", arg(long = "retry-backoff", value_parser = parse_duration_millis, help = "Delay before the first retry, doubling with each retry (in milliseconds)"))]
+    backoff: Option<Duration>,
+    #[cfg_attr(feature = "clap", arg(long = "retry-max-backoff", value_parser = parse_duration_millis, help = "Maximum delay between retries (in milliseconds)"))]
+    max_backoff: Option<Duration>,
+    /// Randomize the delay between retries
+    #[cfg_attr(feature = "clap", arg(long = "retry-jitter"))]
+    jitter: bool,
+    /// Percentage by which the read timeout grows with each retry
+    #[cfg_attr(feature = "clap", arg(long, default_value = "0"))]
+    read_timeout_growth: u16,
+    #[cfg_attr(feature = "clap", arg(skip))]
+    retryable: RetryableKinds,
+}
+
+impl RetryPolicy {
+    /// Immediate retries of requests that timed out or failed to be sent.
+    pub const fn new() -> Self {
+        Self {
+            backoff: None,
+            max_backoff: None,
+            jitter: false,
+            read_timeout_growth: 0,
+            retryable: RetryableKinds::failed_exchanges(),
+        }
+    }
+
+    /// Wait `initial` before the first retry, doubling the wait with each
+    /// following retry, up to `max` (if any).
+    pub const fn with_backoff(mut self, initial: Duration, max: Option<Duration>) -> Self {
+        self.backoff = Some(initial);
+        self.max_backoff = max;
+        self
+    }
+
+    /// Wait a random duration between 0 and the backoff instead of the backoff
+    /// itself, so that queries sharing a network don't retry all at once.
+    pub const fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Grow the read timeout by `percent` with each retry (e.g. with 50 and a
+    /// 2 seconds read timeout, the retries get 3, then 4.5 seconds...).
+    pub const fn with_read_timeout_growth(mut self, percent: u16) -> Self {
+        self.read_timeout_growth = percent;
+        self
+    }
+
+    /// Also retry requests failing with `kind`.
+    ///
+    /// Note that retrying never goes past the deadline of
+    /// [TimeoutSettings::with_total_timeout].
+    pub const fn retry_on(mut self, kind: GDErrorKind) -> Self {
+        *self.retryable.flag(&kind) = true;
+        self
+    }
+
+    /// Don't retry requests failing with `kind`.
+    pub const fn dont_retry_on(mut self, kind: GDErrorKind) -> Self {
+        *self.retryable.flag(&kind) = false;
+        self
+    }
+
+    /// Get the delay before the first retry.
+    pub const fn get_backoff(&self) -> Option<Duration> { self.backoff }
+
+    /// Get the maximum delay between retries.
+    pub const fn get_max_backoff(&self) -> Option<Duration> { self.max_backoff }
+
+    /// Get whether the delay between retries is randomized.
+    pub const fn get_jitter(&self) -> bool { self.jitter }
+
+    /// Get the percentage the read timeout grows by with each retry.
+    pub const fn get_read_timeout_growth(&self) -> u16 { self.read_timeout_growth }
+
+    /// Whether requests failing with `kind` are retried.
+    pub const fn retries_on(&self, kind: &GDErrorKind) -> bool { self.retryable.get(kind) }
+
+    /// The delay before retry number `retry` (starting at 0).
+    pub(crate) fn delay(&self, retry: u32) -> Duration {
+        let Some(backoff) = self.backoff else {
+            return Duration::ZERO;
+        };
+
+        let delay = 2u32
+            .checked_pow(retry)
+            .and_then(|factor| backoff.checked_mul(factor))
+            .unwrap_or(Duration::MAX);
+        let delay = self.max_backoff.map_or(delay, |max| delay.min(max));
+
+        match self.jitter {
+            true => Duration::try_from_secs_f64(delay.as_secs_f64() * jitter_fraction()).unwrap_or(delay),
+            false => delay,
+        }
+    }
+
+    /// The factor to apply to the read timeout of attempt number `attempt`
+    /// (the first try being 0).
+    pub(crate) fn read_timeout_factor(&self, attempt: u32) -> f64 {
+        (1.0 + f64::from(self.read_timeout_growth) / 100.0).powi(attempt.try_into().unwrap_or(i32::MAX))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self { Self::new() }
+}
+
+/// Local network settings of the UDP sockets, carried by [TimeoutSettings].
+///
+/// By default sockets are bound to the unspecified address of the server's
+/// address family (`0.0.0.0` or `[::]`) and to a port picked by the OS.
+///
+/// They apply to UDP only: TCP (and HTTP) connections are always made from
+/// the address and port the OS picks.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NetworkSettings {
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, help = "Local address to send the UDP queries from")
+    )]
+    local_address: Option<IpAddr>,
+    #[cfg_attr(feature = "clap", arg(long, value_parser = parse_port_range, help = "Local port (or range, e.g. 27000-27100) to send the UDP queries from"))]
+    local_ports: Option<(u16, u16)>,
+}
+
+impl NetworkSettings {
+    /// Settings letting the OS pick the local address and port.
+    pub const fn new() -> Self {
+        Self {
+            local_address: None,
+            local_ports: None,
+        }
+    }
+
+    /// Bind to a specific local address, e.g. the one of a network interface.
+    ///
+    /// Its address family must match the queried servers' one.
+    pub const fn local_address(mut self, address: IpAddr) -> Self {
+        self.local_address = Some(address);
+        self
+    }
+
+    /// Bind to the first available port of a range.
+    ///
+    /// An empty range throws GDErrorKind::[InvalidInput].
+    pub fn local_port_range(mut self, ports: RangeInclusive<u16>) -> GDResult<Self> {
+        if ports.is_empty() {
+            return Err(InvalidInput.context("Local port range must not be empty"));
+        }
+
+        self.local_ports = Some((*ports.start(), *ports.end()));
+        Ok(self)
+    }
+
+    /// Get the local address.
+    pub const fn get_local_address(&self) -> Option<IpAddr> { self.local_address }
+
+    /// Get the local port range.
+    pub fn get_local_port_range(&self) -> Option<RangeInclusive<u16>> {
+        self.local_ports.map(|(first, last)| first ..= last)
+    }
+
+    /// The local addresses to try binding to (in order) to reach `remote`.
+    pub(crate) fn bind_addresses(&self, remote: &SocketAddr) -> GDResult<impl Iterator<Item = SocketAddr>> {
+        let ip = match (self.local_address, remote) {
+            (Some(local), _) if local.is_ipv4() != remote.is_ipv4() => {
+                return Err(InvalidInput.context(format!(
+                    "Local address {local} can't reach {remote}, their address families differ"
+                )));
+            }
+            (Some(local), _) => local,
+            (None, SocketAddr::V4(_)) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            (None, SocketAddr::V6(_)) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+
+        let (first, last) = self.local_ports.unwrap_or((0, 0));
+        Ok((first ..= last).map(move |port| SocketAddr::new(ip, port)))
+    }
+
+    /// Whether a socket bound to `local` honors these settings for `remote`.
+    pub(crate) fn accepts(&self, local: &SocketAddr, remote: &SocketAddr) -> bool {
+        let address_matches = match self.local_address {
+            Some(address) => local.ip() == address,
+            None => local.is_ipv4() == remote.is_ipv4(),
+        };
+
+        let port_matches = self
+            .get_local_port_range()
+            .is_none_or(|ports| ports.contains(&local.port()));
+
+        address_matches && port_matches
+    }
+}
+
 /// Generic extra request settings
 ///
 /// Fields of this struct may not be used depending on which protocol
@@ -391,6 +1026,29 @@ pub enum GatherToggle {
     Enforce,
 }
 
+/// A failure to gather optional data (with [GatherToggle::Try]), which
+/// didn't fail the query.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Diagnostic {
+    /// The stage of the query that failed.
+    pub stage: QueryStage,
+    /// The kind of the error.
+    pub kind: GDErrorKind,
+    /// The source of the error, as text.
+    pub source: Option<String>,
+}
+
+impl Diagnostic {
+    pub(crate) fn new(stage: QueryStage, error: &GDError) -> Self {
+        Self {
+            stage,
+            kind: error.kind.clone(),
+            source: error.source.as_ref().map(ToString::to_string),
+        }
+    }
+}
+
 impl ExtraRequestSettings {
     /// [Sets hostname](ExtraRequestSettings#structfield.hostname)
     pub fn set_hostname(mut self, hostname: String) -> Self {
@@ -490,4 +1148,95 @@ mod tests {
 
         let _: valve::GatheringSettings = settings.into();
     }
+
+    // Test that each kind of error is retried on its own
+    #[test]
+    fn test_retry_policy_kinds() {
+        let policy = RetryPolicy::new().retry_on(GDErrorKind::PacketCapture);
+
+        assert!(policy.retries_on(&GDErrorKind::PacketReceive));
+        assert!(policy.retries_on(&GDErrorKind::PacketCapture));
+        assert!(!policy.retries_on(&GDErrorKind::DeadlineExceeded));
+        assert!(!policy
+            .dont_retry_on(GDErrorKind::PacketReceive)
+            .retries_on(&GDErrorKind::PacketReceive));
+    }
+
+    // Test that jittered delays vary and stay within the backoff
+    #[test]
+    fn test_retry_policy_jitter() {
+        let backoff = Duration::from_secs(1);
+        let policy = RetryPolicy::new()
+            .with_backoff(backoff, None)
+            .with_jitter(true);
+
+        let delays: Vec<_> = (0 .. 8).map(|_| policy.delay(0)).collect();
+        assert!(delays.iter().all(|delay| *delay < backoff));
+        assert!(delays.windows(2).any(|pair| pair[0] != pair[1]));
+    }
+
+    fn quake_response() -> Box<dyn CommonResponse> {
+        Box::new(quake::Response {
+            name: "Test".to_string(),
+            map: "e1m1".to_string(),
+            players: vec![quake::one::Player {
+                id: 1,
+                score: 7,
+                time: 0,
+                ping: 30,
+                name: "Alice".to_string(),
+                skin: String::new(),
+                color_primary: 0,
+                color_secondary: 0,
+            }],
+            players_online: 1,
+            players_maximum: 8,
+            game_version: None,
+            unused_entries: Default::default(),
+            latency: Some(Duration::from_millis(20)),
+        })
+    }
+
+    // Test that a common response can be converted to an owned one
+    #[test]
+    fn test_common_response_owned() {
+        let owned = CommonResponseOwned::from(quake_response());
+
+        assert_eq!(
+            owned,
+            CommonResponseOwned {
+                schema_version: COMMON_RESPONSE_SCHEMA_VERSION,
+                name: Some("Test".to_string()),
+                map: Some("e1m1".to_string()),
+                players_maximum: 8,
+                players_online: 1,
+                players: Some(vec![CommonPlayerOwned {
+                    name: "Alice".to_string(),
+                    score: Some(7),
+                }]),
+                latency: Some(Duration::from_millis(20)),
+                ..Default::default()
+            }
+        );
+    }
+
+    // Test that an owned common response survives a JSON round trip, and that
+    // fields missing from stored responses are defaulted
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_common_response_owned_serde() {
+        let owned = CommonResponseOwned::from(quake_response());
+
+        let json = serde_json::to_string(&owned).unwrap();
+        assert_eq!(
+            serde_json::from_str::<CommonResponseOwned>(&json).unwrap(),
+            owned
+        );
+
+        let stored: CommonResponseOwned =
+            serde_json::from_str(r#"{"name":"Old","players_maximum":4,"players_online":2}"#).unwrap();
+        assert_eq!(stored.schema_version, 0);
+        assert_eq!(stored.name.as_deref(), Some("Old"));
+        assert_eq!(stored.players, None);
+    }
 }
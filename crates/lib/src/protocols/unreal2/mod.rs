@@ -51,6 +51,20 @@ macro_rules! game_query_fn {
                 None,
             )
         }
+
+        /// Async counterpart of [query].
+        #[cfg(feature = "async")]
+        pub async fn query_async(
+            address: &std::net::IpAddr,
+            port: Option<u16>,
+        ) -> crate::GDResult<crate::protocols::unreal2::Response> {
+            crate::protocols::unreal2::query_async(
+                &std::net::SocketAddr::new(*address, port.unwrap_or($default_port)),
+                &crate::protocols::unreal2::GatheringSettings::default(),
+                None,
+            )
+            .await
+        }
     };
 }
 
@@ -1,9 +1,18 @@
 use crate::buffer::{Buffer, StringDecoder};
 use crate::errors::GDErrorKind::PacketBad;
-use crate::protocols::types::TimeoutSettings;
+use crate::machine::{drive, timed_out, Event, Machine, Step};
+use crate::protocols::types::{RetryPolicy, TimeoutSettings};
+use crate::retry::retry;
 use crate::socket::{Socket, UdpSocket};
-use crate::utils::{maybe_gather, retry_on_timeout};
-use crate::GDResult;
+use crate::utils::maybe_gather;
+#[cfg(feature = "async")]
+use crate::{
+    machine::drive_async,
+    retry::retry_async,
+    socket::{AsyncSocket, AsyncUdpSocket},
+    trace::Instrument,
+};
+use crate::{trace, GDResult, QueryStage};
 
 use super::{GatheringSettings, MutatorsAndRules, PacketKind, Players, Response, ServerInfo};
 
@@ -28,6 +37,7 @@ const MAXIMUM_PLAYER_PREALLOCATION: usize = 50;
 pub(crate) struct Unreal2Protocol {
     socket: UdpSocket,
     retry_count: usize,
+    retry_policy: RetryPolicy,
 }
 
 impl Unreal2Protocol {
@@ -37,30 +47,15 @@ impl Unreal2Protocol {
             || TimeoutSettings::default().get_retries(),
             TimeoutSettings::get_retries,
         );
+        let retry_policy = TimeoutSettings::get_retry_policy_or_default(&timeout_settings);
 
         Ok(Self {
             socket,
             retry_count,
+            retry_policy,
         })
     }
 
-    /// Send a request packet and recieve the first response (with retries).
-    fn get_request_data(&mut self, packet_type: PacketKind) -> GDResult<Vec<u8>> {
-        retry_on_timeout(self.retry_count, move || {
-            self.get_request_data_impl(packet_type)
-        })
-    }
-
-    /// Send a request packet
-    fn get_request_data_impl(&mut self, packet_type: PacketKind) -> GDResult<Vec<u8>> {
-        let request = [0x79, 0, 0, 0, packet_type as u8];
-        self.socket.send(&request)?;
-
-        let data = self.socket.receive(Some(PACKET_SIZE))?;
-
-        Ok(data)
-    }
-
     /// Consume the header part of a response packet, validate that the packet
     /// type matches what is expected.
     fn consume_response_headers<B: ByteOrder>(
@@ -85,102 +80,58 @@ impl Unreal2Protocol {
 
     /// Send server info query.
     pub fn query_server_info(&mut self) -> GDResult<ServerInfo> {
-        let data = self.get_request_data(PacketKind::ServerInfo)?;
-        let mut buffer = Buffer::<LittleEndian>::new(&data);
-        // TODO: Maybe put consume headers in individual packet parse methods
-        Self::consume_response_headers(&mut buffer, PacketKind::ServerInfo)?;
-        ServerInfo::parse(&mut buffer)
+        retry(self.retry_count, self.retry_policy, || {
+            drive(&mut self.socket, &mut ServerInfoMachine, Some(PACKET_SIZE))
+        })
     }
 
     /// Send mutators and rules query.
     pub fn query_mutators_and_rules(&mut self) -> GDResult<MutatorsAndRules> {
-        // This is a required packet so we validate that we get at least one response.
-        // However there can be many packets in response to a single request so
-        // we greedily handle packets until we get a timeout (or any receive
-        // error).
-
-        let mut mutators_and_rules = MutatorsAndRules::default();
-        {
-            let data = self.get_request_data(PacketKind::MutatorsAndRules)?;
-            let mut buffer = Buffer::<LittleEndian>::new(&data);
-            // TODO: Maybe put consume headers in individual packet parse methods
-            Self::consume_response_headers(&mut buffer, PacketKind::MutatorsAndRules)?;
-            mutators_and_rules.parse(&mut buffer)?
-        };
-
-        // We could receive multiple packets in response
-        while let Ok(data) = self.socket.receive(Some(PACKET_SIZE)) {
-            let mut buffer = Buffer::<LittleEndian>::new(&data);
-
-            let r = Self::consume_response_headers(&mut buffer, PacketKind::MutatorsAndRules);
-            if r.is_err() {
-                println!("{r:?}");
-                break;
-            }
-
-            mutators_and_rules.parse(&mut buffer)?;
-        }
-
-        Ok(mutators_and_rules)
+        let mut machine = MutatorsAndRulesMachine::default();
+        retry(self.retry_count, self.retry_policy, || {
+            drive(&mut self.socket, &mut machine, Some(PACKET_SIZE))
+        })
     }
 
     /// Send players query.
     pub fn query_players(&mut self, server_info: Option<&ServerInfo>) -> GDResult<Players> {
-        // Pre-allocate the player arrays, but don't over allocate memory if the server
-        // specifies an insane number of players.
-        let num_players: Option<usize> = server_info.and_then(|i| i.num_players.try_into().ok());
+        let mut machine = PlayersMachine::new(server_info.and_then(|i| i.num_players.try_into().ok()));
+        let players = retry(self.retry_count, self.retry_policy, || {
+            drive(&mut self.socket, &mut machine, Some(PACKET_SIZE))
+        });
 
-        let mut players = Players::with_capacity(
-            num_players
-                .unwrap_or(DEFAULT_PLAYER_PREALLOCATION)
-                .min(MAXIMUM_PLAYER_PREALLOCATION),
-        );
-
-        // Fetch first players packet (with retries)
-        let mut players_data = self.get_request_data(PacketKind::Players);
         // Players are non required so if we don't get any responses we continue to
         // return
-        while let Ok(data) = players_data {
-            let mut buffer = Buffer::<LittleEndian>::new(&data);
-
-            Self::consume_response_headers(&mut buffer, PacketKind::Players)?;
-
-            players.parse(&mut buffer)?;
-
-            if let Some(num_players) = num_players {
-                if players.total_len() >= num_players {
-                    // If we have already received the amount of players specified in server info
-                    // then we don't need to wait for more player packets to time out.
-                    break;
-                }
-            }
-
-            // Receive next packet
-            players_data = self.socket.receive(Some(PACKET_SIZE));
+        match players {
+            Err(e) if e.is_timeout() => Ok(Players::default()),
+            players => players,
         }
-
-        Ok(players)
     }
 
     /// Make a full server query.
     pub fn query(&mut self, gather_settings: &GatheringSettings) -> GDResult<Response> {
         // Fetch the server info, this can only handle one response packet
-        let mut server_info = self.query_server_info()?;
+        let mut server_info = self
+            .query_server_info()
+            .map_err(|e| e.with_stage(QueryStage::Info))?;
+        let latency = self.socket.latency();
 
+        let mut diagnostics = Vec::new();
         let mutators_and_rules = maybe_gather!(
             gather_settings.mutators_and_rules,
-            self.query_mutators_and_rules()
+            self.query_mutators_and_rules(),
+            diagnostics,
+            QueryStage::Rules
         )
         .unwrap_or_default();
 
-        if let Some(password) = mutators_and_rules.rules.get("GamePassword") {
-            let string = password.concat().to_lowercase();
-            server_info.password = string == "true";
-        }
+        apply_password_rule(&mut server_info, &mutators_and_rules);
 
         let players = maybe_gather!(
             gather_settings.players,
-            self.query_players(Some(&server_info))
+            self.query_players(Some(&server_info)),
+            diagnostics,
+            QueryStage::Players
         )
         .unwrap_or_else(|| Players::with_capacity(0));
 
@@ -191,10 +142,191 @@ impl Unreal2Protocol {
             server_info,
             mutators_and_rules,
             players,
+            latency,
+            diagnostics,
         })
     }
 }
 
+/// The exchange of a server info request, whose response is a single packet.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ServerInfoMachine;
+
+impl Machine for ServerInfoMachine {
+    type Output = ServerInfo;
+
+    fn start(&mut self) -> Vec<Vec<u8>> { request_packets(PacketKind::ServerInfo) }
+
+    fn handle(&mut self, event: Event<'_>) -> GDResult<Step<ServerInfo>> {
+        match event {
+            Event::Received(data) => Ok(Step::Done(parse_server_info(data)?)),
+            Event::TimedOut => Err(timed_out()),
+        }
+    }
+}
+
+/// The exchange of a mutators and rules request, whose response packets are
+/// gathered until nothing (or another kind of packet) is received.
+#[derive(Debug, Clone, Default)]
+pub struct MutatorsAndRulesMachine {
+    mutators_and_rules: Option<MutatorsAndRules>,
+}
+
+impl Machine for MutatorsAndRulesMachine {
+    type Output = MutatorsAndRules;
+
+    fn start(&mut self) -> Vec<Vec<u8>> {
+        self.mutators_and_rules = None;
+        request_packets(PacketKind::MutatorsAndRules)
+    }
+
+    fn handle(&mut self, event: Event<'_>) -> GDResult<Step<MutatorsAndRules>> {
+        // At least one packet is required, the rest are optional.
+        let Event::Received(data) = event else {
+            return self
+                .mutators_and_rules
+                .take()
+                .map(Step::Done)
+                .ok_or_else(timed_out);
+        };
+
+        let mut buffer = Buffer::<LittleEndian>::new(data);
+        let headers = Unreal2Protocol::consume_response_headers(&mut buffer, PacketKind::MutatorsAndRules);
+        match &self.mutators_and_rules {
+            None => headers?,
+            Some(_) if headers.is_err() => {
+                trace::event!(DEBUG, result = ?headers, "Unreal2 mutators and rules ended by another packet");
+                return Ok(Step::Done(
+                    self.mutators_and_rules.take().unwrap_or_default(),
+                ));
+            }
+            Some(_) => trace::event!(TRACE, "Unreal2 mutators and rules packet received"),
+        }
+
+        self.mutators_and_rules
+            .get_or_insert_with(MutatorsAndRules::default)
+            .parse(&mut buffer)?;
+
+        Ok(Step::Receive)
+    }
+}
+
+/// The exchange of a players request, whose response packets are gathered
+/// until nothing is received or all of the expected players were.
+#[derive(Debug, Clone)]
+pub struct PlayersMachine {
+    expected: Option<usize>,
+    players: Option<Players>,
+}
+
+impl PlayersMachine {
+    /// `expected` is the amount of players (from the server info), if known.
+    pub const fn new(expected: Option<usize>) -> Self {
+        Self {
+            expected,
+            players: None,
+        }
+    }
+}
+
+impl Machine for PlayersMachine {
+    type Output = Players;
+
+    fn start(&mut self) -> Vec<Vec<u8>> {
+        self.players = None;
+        request_packets(PacketKind::Players)
+    }
+
+    fn handle(&mut self, event: Event<'_>) -> GDResult<Step<Players>> {
+        let Event::Received(data) = event else {
+            return self.players.take().map(Step::Done).ok_or_else(timed_out);
+        };
+
+        let mut buffer = Buffer::<LittleEndian>::new(data);
+        Unreal2Protocol::consume_response_headers(&mut buffer, PacketKind::Players)?;
+
+        // Pre-allocate the player arrays, but don't over allocate memory if the server
+        // specifies an insane number of players.
+        let capacity = self
+            .expected
+            .unwrap_or(DEFAULT_PLAYER_PREALLOCATION)
+            .min(MAXIMUM_PLAYER_PREALLOCATION);
+        let players = self
+            .players
+            .get_or_insert_with(|| Players::with_capacity(capacity));
+
+        players.parse(&mut buffer)?;
+        trace::event!(
+            TRACE,
+            players = players.total_len(),
+            "Unreal2 players packet received"
+        );
+
+        // If we have already received the amount of players specified in server info
+        // then we don't need to wait for more player packets to time out.
+        Ok(
+            match self
+                .expected
+                .is_some_and(|expected| players.total_len() >= expected)
+            {
+                true => Step::Done(std::mem::take(players)),
+                false => Step::Receive,
+            },
+        )
+    }
+}
+
+/// The packets of a request, to start an exchange with.
+fn request_packets(packet_type: PacketKind) -> Vec<Vec<u8>> {
+    trace::event!(DEBUG, ?packet_type, "Unreal2 request");
+    vec![request(packet_type).to_vec()]
+}
+
+/// Parse a server info response packet, as received.
+pub fn parse_server_info(data: &[u8]) -> GDResult<ServerInfo> {
+    let mut buffer = Buffer::<LittleEndian>::new(data);
+    Unreal2Protocol::consume_response_headers(&mut buffer, PacketKind::ServerInfo)?;
+    ServerInfo::parse(&mut buffer)
+}
+
+/// Parse the packets of a mutators and rules response, as received.
+pub fn parse_mutators_and_rules<P: AsRef<[u8]>>(packets: &[P]) -> GDResult<MutatorsAndRules> {
+    let mut mutators_and_rules = MutatorsAndRules::default();
+
+    for packet in packets {
+        let mut buffer = Buffer::<LittleEndian>::new(packet.as_ref());
+        Unreal2Protocol::consume_response_headers(&mut buffer, PacketKind::MutatorsAndRules)?;
+        mutators_and_rules.parse(&mut buffer)?;
+    }
+
+    Ok(mutators_and_rules)
+}
+
+/// Parse the packets of a players response, as received.
+pub fn parse_players<P: AsRef<[u8]>>(packets: &[P]) -> GDResult<Players> {
+    let mut players = Players::with_capacity(DEFAULT_PLAYER_PREALLOCATION);
+
+    for packet in packets {
+        let mut buffer = Buffer::<LittleEndian>::new(packet.as_ref());
+        Unreal2Protocol::consume_response_headers(&mut buffer, PacketKind::Players)?;
+        players.parse(&mut buffer)?;
+    }
+
+    Ok(players)
+}
+
+/// The request packet for a kind of data.
+const fn request(packet_type: PacketKind) -> [u8; 5] { [0x79, 0, 0, 0, packet_type as u8] }
+
+/// Use the password rule (if present) to tell whether the server has a
+/// password.
+fn apply_password_rule(server_info: &mut ServerInfo, mutators_and_rules: &MutatorsAndRules) {
+    if let Some(password) = mutators_and_rules.rules.get("GamePassword") {
+        let string = password.concat().to_lowercase();
+        server_info.password = string == "true";
+    }
+}
+
 /// Unreal 2 string decoder
 pub struct Unreal2StringDecoder;
 impl StringDecoder for Unreal2StringDecoder {
@@ -303,9 +435,123 @@ pub fn query(
     gather_settings: &GatheringSettings,
     timeout_settings: Option<TimeoutSettings>,
 ) -> GDResult<Response> {
-    let mut client = Unreal2Protocol::new(address, timeout_settings)?;
+    trace::span!("unreal2", %address)
+        .in_scope(|| {
+            let mut client = Unreal2Protocol::new(address, timeout_settings)?;
+
+            client.query(gather_settings)
+        })
+        .map_err(|e| e.with_address(*address))
+}
+
+/// Async counterpart of [Unreal2Protocol].
+#[cfg(feature = "async")]
+pub(crate) struct AsyncUnreal2Protocol {
+    socket: AsyncUdpSocket,
+    retry_count: usize,
+    retry_policy: RetryPolicy,
+}
+
+#[cfg(feature = "async")]
+impl AsyncUnreal2Protocol {
+    pub async fn new(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Self> {
+        let socket = AsyncUdpSocket::new(address, &timeout_settings).await?;
+        let retry_count = TimeoutSettings::get_retries_or_default(&timeout_settings);
+        let retry_policy = TimeoutSettings::get_retry_policy_or_default(&timeout_settings);
+
+        Ok(Self {
+            socket,
+            retry_count,
+            retry_policy,
+        })
+    }
+
+    /// Send server info query.
+    pub async fn query_server_info(&mut self) -> GDResult<ServerInfo> {
+        retry_async!(
+            self.retry_count,
+            self.retry_policy,
+            drive_async(&mut self.socket, &mut ServerInfoMachine, Some(PACKET_SIZE)).await
+        )
+    }
+
+    /// Send mutators and rules query.
+    pub async fn query_mutators_and_rules(&mut self) -> GDResult<MutatorsAndRules> {
+        let mut machine = MutatorsAndRulesMachine::default();
+        retry_async!(
+            self.retry_count,
+            self.retry_policy,
+            drive_async(&mut self.socket, &mut machine, Some(PACKET_SIZE)).await
+        )
+    }
+
+    /// Send players query.
+    pub async fn query_players(&mut self, server_info: Option<&ServerInfo>) -> GDResult<Players> {
+        let mut machine = PlayersMachine::new(server_info.and_then(|i| i.num_players.try_into().ok()));
+        let players = retry_async!(
+            self.retry_count,
+            self.retry_policy,
+            drive_async(&mut self.socket, &mut machine, Some(PACKET_SIZE)).await
+        );
+
+        match players {
+            Err(e) if e.is_timeout() => Ok(Players::default()),
+            players => players,
+        }
+    }
+
+    /// Make a full server query.
+    pub async fn query(&mut self, gather_settings: &GatheringSettings) -> GDResult<Response> {
+        let mut server_info = self
+            .query_server_info()
+            .await
+            .map_err(|e| e.with_stage(QueryStage::Info))?;
+        let latency = self.socket.latency();
+
+        let mut diagnostics = Vec::new();
+        let mutators_and_rules = maybe_gather!(
+            gather_settings.mutators_and_rules,
+            self.query_mutators_and_rules().await,
+            diagnostics,
+            QueryStage::Rules
+        )
+        .unwrap_or_default();
+
+        apply_password_rule(&mut server_info, &mutators_and_rules);
+
+        let players = maybe_gather!(
+            gather_settings.players,
+            self.query_players(Some(&server_info)).await,
+            diagnostics,
+            QueryStage::Players
+        )
+        .unwrap_or_else(|| Players::with_capacity(0));
+
+        Ok(Response {
+            server_info,
+            mutators_and_rules,
+            players,
+            latency,
+            diagnostics,
+        })
+    }
+}
+
+/// Async counterpart of [query].
+#[cfg(feature = "async")]
+pub async fn query_async(
+    address: &SocketAddr,
+    gather_settings: &GatheringSettings,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<Response> {
+    async {
+        let mut client = AsyncUnreal2Protocol::new(address, timeout_settings).await?;
 
-    client.query(gather_settings)
+        client.query(gather_settings).await
+    }
+    .instrument(trace::span!("unreal2", %address))
+    .await
+    .map_err(|e| e.with_address(*address))
 }
 
 // TODO: Add tests
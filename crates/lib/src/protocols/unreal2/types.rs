@@ -1,6 +1,13 @@
 use crate::buffer::Buffer;
 use crate::errors::GDErrorKind::PacketBad;
-use crate::protocols::types::{CommonPlayer, CommonResponse, ExtraRequestSettings, GatherToggle, GenericPlayer};
+use crate::protocols::types::{
+    CommonPlayer,
+    CommonResponse,
+    Diagnostic,
+    ExtraRequestSettings,
+    GatherToggle,
+    GenericPlayer,
+};
 use crate::protocols::GenericResponse;
 use crate::{GDError, GDResult};
 
@@ -9,6 +16,7 @@ use super::Unreal2StringDecoder;
 use std::collections::{HashMap, HashSet};
 
 use byteorder::ByteOrder;
+use std::time::Duration;
 
 /// Unreal 2 packet types.
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
@@ -177,6 +185,11 @@ pub struct Response {
     pub server_info: ServerInfo,
     pub mutators_and_rules: MutatorsAndRules,
     pub players: Players,
+    pub latency: Option<Duration>,
+    /// The mutators and rules and players requests that failed, if they were
+    /// only attempted.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 impl CommonResponse for Response {
@@ -203,6 +216,10 @@ impl CommonResponse for Response {
                 .collect(),
         )
     }
+
+    fn latency(&self) -> Option<Duration> { self.latency }
+
+    fn diagnostics(&self) -> &[Diagnostic] { &self.diagnostics }
 }
 
 /// What data to gather, purely used only with the query function.
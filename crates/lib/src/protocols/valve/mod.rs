@@ -74,6 +74,20 @@ macro_rules! game_query_fn {
 
             Ok(crate::protocols::valve::game::Response::new_from_valve_response(valve_response))
         }
+
+        /// Async counterpart of [query].
+        #[cfg(feature = "async")]
+        pub async fn query_async(address: &std::net::IpAddr, port: Option<u16>) -> crate::GDResult<crate::protocols::valve::game::Response> {
+            let valve_response = crate::protocols::valve::query_async(
+                &std::net::SocketAddr::new(*address, port.unwrap_or($default_port)),
+                $engine,
+                Some($gathering_settings),
+                None,
+            )
+            .await?;
+
+            Ok(crate::protocols::valve::game::Response::new_from_valve_response(valve_response))
+        }
     };
 }
 
@@ -1,7 +1,8 @@
 use crate::{
     buffer::Buffer,
+    machine::{drive, timed_out, Event, Machine, Step},
     protocols::{
-        types::TimeoutSettings,
+        types::{RetryPolicy, TimeoutSettings},
         valve::{
             types::{
                 Environment,
@@ -18,19 +19,32 @@ use crate::{
             ModData,
         },
     },
+    report,
+    retry::retry,
     socket::{Socket, UdpSocket},
-    utils::{maybe_gather, retry_on_timeout, u8_lower_upper},
-    GDErrorKind::{BadGame, Decompress, UnknownEnumCast},
+    trace,
+    utils::{maybe_gather, u8_lower_upper},
+    GDErrorKind::{BadGame, Decompress, PacketBad, UnknownEnumCast},
     GDResult,
+    QueryStage,
+};
+#[cfg(feature = "async")]
+use crate::{
+    machine::drive_async,
+    retry::retry_async,
+    socket::{AsyncSocket, AsyncUdpSocket},
+    trace::Instrument,
 };
 
-use bzip2_rs::decoder::Decoder;
+use bzip2_rs::DecoderReader;
 
 use crate::buffer::Utf8Decoder;
 use crate::protocols::valve::Packet;
 use byteorder::LittleEndian;
 use std::collections::HashMap;
+use std::io::Read;
 use std::net::SocketAddr;
+use std::time::Duration;
 
 #[derive(Debug)]
 #[allow(dead_code)] // remove this later on
@@ -64,8 +78,9 @@ impl SplitPacket {
                     true => 1248,
                 };
 
+                // Only the first packet holds the decompressed size and checksum.
                 let is_compressed = ((id >> 31) & 1u32) == 1u32;
-                let decompressed = match is_compressed {
+                let decompressed = match is_compressed && number == 0 {
                     false => None,
                     true => Some((buffer.read()?, buffer.read()?)),
                 };
@@ -87,19 +102,22 @@ impl SplitPacket {
 
     fn get_payload(&self) -> GDResult<Vec<u8>> {
         if let Some(decompressed) = self.decompressed {
-            let mut decoder = Decoder::new();
-            decoder
-                .write(&self.payload)
-                .map_err(|e| Decompress.context(e))?;
-
             let decompressed_size = decompressed.0 as usize;
 
-            let mut decompressed_payload = vec![0; decompressed_size];
+            let mut decompressed_payload = Vec::with_capacity(decompressed_size);
 
-            decoder
-                .read(&mut decompressed_payload)
+            DecoderReader::new(self.payload.as_slice())
+                .take(decompressed_size as u64 + 1)
+                .read_to_end(&mut decompressed_payload)
                 .map_err(|e| Decompress.context(e))?;
 
+            trace::event!(
+                DEBUG,
+                compressed = self.payload.len(),
+                decompressed = decompressed_payload.len(),
+                "Valve response decompressed"
+            );
+
             if decompressed_payload.len() != decompressed_size
                 || crc32fast::hash(&decompressed_payload) != decompressed.1
             {
@@ -115,314 +133,527 @@ impl SplitPacket {
             Ok(self.payload.clone())
         }
     }
-}
 
-pub(crate) struct ValveProtocol {
-    socket: UdpSocket,
-    retry_count: usize,
-}
+    /// Join the first packet of a split response with the rest of its chunks
+    /// and parse the resulting packet.
+    fn reassemble(mut self, mut chunk_packets: Vec<Self>) -> GDResult<Packet> {
+        report::record(|report| {
+            report.split_responses += 1;
+            report.compressed_responses += usize::from(self.decompressed.is_some());
+        });
+
+        trace::event!(
+            DEBUG,
+            id = self.id,
+            packets = chunk_packets.len() + 1,
+            compressed = self.decompressed.is_some(),
+            "Valve split response reassembled"
+        );
 
-static PACKET_SIZE: usize = 6144;
+        chunk_packets.sort_by_key(|a| a.number);
 
-impl ValveProtocol {
-    pub fn new(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Self> {
-        let socket = UdpSocket::new(address, &timeout_settings)?;
-        let retry_count = timeout_settings.as_ref().map_or_else(
-            || TimeoutSettings::default().get_retries(),
-            TimeoutSettings::get_retries,
-        );
+        for chunk_packet in chunk_packets {
+            self.payload.extend(chunk_packet.payload);
+        }
 
-        Ok(Self {
-            socket,
-            retry_count,
-        })
+        let payload = self.get_payload()?; // Creating a non-temporary value here
+        let mut new_packet_buffer = Buffer::<LittleEndian>::new(&payload); // Using the non-temporary value here
+        Packet::new_from_bufferer(&mut new_packet_buffer)
     }
+}
+
+/// The first datagram of a response, which is either the whole packet or the
+/// first part of a split one.
+enum FirstPacket {
+    Whole(Packet),
+    Split(SplitPacket),
+}
 
-    fn receive(&mut self, engine: &Engine, protocol: u8, buffer_size: usize) -> GDResult<Packet> {
-        let data = self.socket.receive(Some(buffer_size))?;
-        let mut buffer = Buffer::<LittleEndian>::new(&data);
+impl FirstPacket {
+    fn parse(engine: &Engine, protocol: u8, data: &[u8]) -> GDResult<Self> {
+        let mut buffer = Buffer::<LittleEndian>::new(data);
 
         let header: u8 = buffer.read()?;
         buffer.move_cursor(-1)?;
         if header == 0xFE {
             // the packet is split
-            let mut main_packet = SplitPacket::new(engine, protocol, &mut buffer)?;
-            let mut chunk_packets = Vec::with_capacity((main_packet.total - 1) as usize);
+            Ok(Self::Split(SplitPacket::new(
+                engine,
+                protocol,
+                &mut buffer,
+            )?))
+        } else {
+            Ok(Self::Whole(Packet::new_from_bufferer(&mut buffer)?))
+        }
+    }
+}
 
-            for _ in 1 .. main_packet.total {
-                let new_data = self.socket.receive(Some(buffer_size))?;
-                buffer = Buffer::<LittleEndian>::new(&new_data);
-                let chunk_packet = SplitPacket::new(engine, protocol, &mut buffer)?;
-                chunk_packets.push(chunk_packet);
-            }
+/// Build the packet that answers a challenge received while requesting `kind`.
+fn challenge_request(kind: u8, challenge: Vec<u8>) -> Vec<u8> {
+    const INFO: u8 = Request::Info as u8;
+    Packet::new(
+        kind,
+        match kind {
+            INFO => [Request::Info.get_default_payload(), challenge].concat(),
+            _ => challenge,
+        },
+    )
+    .to_bytes()
+}
+
+/// The exchange of a request: sending it (again, with the challenge the
+/// server answers with if it does) and receiving its (possibly split and
+/// compressed) response.
+///
+/// The output is the whole response packet, to parse with [parse_info],
+/// [parse_players] or [parse_rules].
+pub struct RequestMachine {
+    packets: PacketMachine,
+}
 
-            chunk_packets.sort_by(|a, b| a.number.cmp(&b.number));
+impl RequestMachine {
+    /// The info request.
+    pub fn info(engine: Engine) -> Self { Self::of_kind(engine, 0, Request::Info) }
 
-            for chunk_packet in chunk_packets {
-                main_packet.payload.extend(chunk_packet.payload);
-            }
+    /// The players request, `protocol` being the server's (from its info).
+    pub fn players(engine: Engine, protocol: u8) -> Self { Self::of_kind(engine, protocol, Request::Players) }
 
-            let payload = main_packet.get_payload()?; // Creating a non-temporary value here
-            let mut new_packet_buffer = Buffer::<LittleEndian>::new(&payload); // Using the non-temporary value here
-            Ok(Packet::new_from_bufferer(&mut new_packet_buffer)?)
-        } else {
-            Packet::new_from_bufferer(&mut buffer)
+    /// The rules request, `protocol` being the server's (from its info).
+    pub fn rules(engine: Engine, protocol: u8) -> Self { Self::of_kind(engine, protocol, Request::Rules) }
+
+    fn of_kind(engine: Engine, protocol: u8, kind: Request) -> Self {
+        Self {
+            packets: PacketMachine::new(engine, protocol, kind as u8, kind.get_default_payload()),
         }
     }
+}
 
-    pub fn get_kind_request_data(&mut self, engine: &Engine, protocol: u8, kind: Request) -> GDResult<Vec<u8>> {
-        let data = self.get_request_data(engine, protocol, kind as u8, kind.get_default_payload())?;
-        Ok(data)
-    }
+impl Machine for RequestMachine {
+    type Output = Vec<u8>;
 
-    /// Ask for a specific request only.
-    /// This function will retry fetch on timeouts.
-    pub fn get_request_data(&mut self, engine: &Engine, protocol: u8, kind: u8, payload: Vec<u8>) -> GDResult<Vec<u8>> {
-        retry_on_timeout(self.retry_count, || {
-            self.get_request_data_impl(engine, protocol, kind, payload.clone())
+    fn start(&mut self) -> Vec<Vec<u8>> { self.packets.start() }
+
+    fn handle(&mut self, event: Event<'_>) -> GDResult<Step<Vec<u8>>> {
+        Ok(match self.packets.handle(event)? {
+            Step::Send(packets) => Step::Send(packets),
+            Step::Receive => Step::Receive,
+            Step::Done(packet) => Step::Done(packet.to_bytes()),
         })
     }
+}
 
-    /// Ask for a specific request only (without retry logic).
-    fn get_request_data_impl(
-        &mut self,
-        engine: &Engine,
-        protocol: u8,
-        kind: u8,
-        payload: Vec<u8>,
-    ) -> GDResult<Vec<u8>> {
-        let request_initial_packet = Packet::new(kind, payload).to_bytes();
-        self.socket.send(&request_initial_packet)?;
-
-        let mut packet = self.receive(engine, protocol, PACKET_SIZE)?;
-        while packet.kind == 0x41 {
-            // 'A'
-            let challenge = packet.payload;
-
-            const INFO: u8 = Request::Info as u8;
-            let challenge_packet = Packet::new(
-                kind,
-                match kind {
-                    INFO => [Request::Info.get_default_payload(), challenge].concat(),
-                    _ => challenge,
-                },
-            )
-            .to_bytes();
+/// The exchange of a [RequestMachine], whose output is the response packet
+/// (which the queries only need the payload of).
+pub(crate) struct PacketMachine {
+    engine: Engine,
+    protocol: u8,
+    kind: u8,
+    payload: Vec<u8>,
+    /// The first packet of a split response and the chunks received so far.
+    split: Option<(SplitPacket, Vec<SplitPacket>)>,
+}
 
-            self.socket.send(&challenge_packet)?;
+impl PacketMachine {
+    pub(crate) const fn new(engine: Engine, protocol: u8, kind: u8, payload: Vec<u8>) -> Self {
+        Self {
+            engine,
+            protocol,
+            kind,
+            payload,
+            split: None,
+        }
+    }
 
-            packet = self.receive(engine, protocol, PACKET_SIZE)?;
+    /// Answer a challenge or finish with the response.
+    fn answered(&self, packet: Packet) -> Step<Packet> {
+        match packet.kind {
+            CHALLENGE_RESPONSE => {
+                trace::event!(
+                    DEBUG,
+                    request = self.kind,
+                    "Valve challenge received, requesting again"
+                );
+                Step::Send(vec![challenge_request(self.kind, packet.payload)])
+            }
+            _ => {
+                trace::event!(
+                    DEBUG,
+                    request = self.kind,
+                    kind = packet.kind,
+                    "Valve response received"
+                );
+                Step::Done(packet)
+            }
         }
+    }
+}
+
+impl Machine for PacketMachine {
+    type Output = Packet;
 
-        Ok(packet.payload)
+    fn start(&mut self) -> Vec<Vec<u8>> {
+        self.split = None;
+        trace::event!(DEBUG, request = self.kind, "Valve request");
+        vec![Packet::new(self.kind, self.payload.clone()).to_bytes()]
     }
 
-    fn get_goldsrc_server_info(buffer: &mut Buffer<LittleEndian>) -> GDResult<ServerInfo> {
-        let _header: u8 = buffer.read()?; // get the header (useless info)
-        let _address: String = buffer.read_string::<Utf8Decoder>(None)?; // get the server address (useless info)
-        let name = buffer.read_string::<Utf8Decoder>(None)?;
-        let map = buffer.read_string::<Utf8Decoder>(None)?;
-        let folder = buffer.read_string::<Utf8Decoder>(None)?;
-        let game_mode = buffer.read_string::<Utf8Decoder>(None)?;
-        let players = buffer.read()?;
-        let max_players = buffer.read()?;
-        let protocol = buffer.read()?;
-        let server_type = match buffer.read::<u8>()? {
-            68 => Server::Dedicated,    //'D'
-            76 => Server::NonDedicated, //'L'
-            80 => Server::TV,           //'P'
-            _ => Err(UnknownEnumCast)?,
-        };
-        let environment_type = match buffer.read::<u8>()? {
-            76 => Environment::Linux,   //'L'
-            87 => Environment::Windows, //'W'
-            _ => Err(UnknownEnumCast)?,
+    fn handle(&mut self, event: Event<'_>) -> GDResult<Step<Packet>> {
+        let Event::Received(data) = event else {
+            return Err(timed_out());
         };
-        let has_password = buffer.read::<u8>()? == 1;
-        let is_mod = buffer.read::<u8>()? == 1;
-        let mod_data = match is_mod {
-            false => None,
-            true => {
-                Some(ModData {
-                    link: buffer.read_string::<Utf8Decoder>(None)?,
-                    download_link: buffer.read_string::<Utf8Decoder>(None)?,
-                    version: buffer.read()?,
-                    size: buffer.read()?,
-                    multiplayer_only: buffer.read::<u8>()? == 1,
-                    has_own_dll: buffer.read::<u8>()? == 1,
-                })
+
+        let (main_packet, chunk_packets) = match self.split.take() {
+            None => {
+                match FirstPacket::parse(&self.engine, self.protocol, data)? {
+                    FirstPacket::Whole(packet) => return Ok(self.answered(packet)),
+                    FirstPacket::Split(main_packet) => {
+                        trace::event!(
+                            DEBUG,
+                            id = main_packet.id,
+                            total = main_packet.total,
+                            "Valve split response started"
+                        );
+                        (main_packet, Vec::new())
+                    }
+                }
+            }
+            Some((main_packet, mut chunk_packets)) => {
+                let mut buffer = Buffer::<LittleEndian>::new(data);
+                let chunk_packet = SplitPacket::new(&self.engine, self.protocol, &mut buffer)?;
+                trace::event!(
+                    TRACE,
+                    id = chunk_packet.id,
+                    number = chunk_packet.number,
+                    "Valve split packet received"
+                );
+                chunk_packets.push(chunk_packet);
+                (main_packet, chunk_packets)
             }
         };
-        let vac_secured = buffer.read::<u8>()? == 1;
-        let bots = buffer.read::<u8>()?;
-
-        Ok(ServerInfo {
-            protocol_version: protocol,
-            name,
-            map,
-            folder,
-            game_mode,
-            appid: 0, // not present in the obsolete response
-            players_online: players,
-            players_maximum: max_players,
-            players_bots: bots,
-            server_type,
-            environment_type,
-            has_password,
-            vac_secured,
-            the_ship: None,
-            game_version: String::new(), // a version field only for the mod
-            extra_data: None,
-            is_mod,
-            mod_data,
-        })
+
+        if chunk_packets.len() + 1 < main_packet.total as usize {
+            self.split = Some((main_packet, chunk_packets));
+            return Ok(Step::Receive);
+        }
+
+        Ok(self.answered(main_packet.reassemble(chunk_packets)?))
     }
+}
 
-    /// Get the server information's.
-    fn get_server_info(&mut self, engine: &Engine) -> GDResult<ServerInfo> {
-        let data = self.get_kind_request_data(engine, 0, Request::Info)?;
-        let mut buffer = Buffer::<LittleEndian>::new(&data);
+pub(crate) struct ValveProtocol {
+    socket: UdpSocket,
+    retry_count: usize,
+    retry_policy: RetryPolicy,
+}
 
-        if let Engine::GoldSrc(force) = engine {
-            if *force {
-                return Self::get_goldsrc_server_info(&mut buffer);
-            }
-        }
+static PACKET_SIZE: usize = 6144;
 
-        let protocol = buffer.read()?;
-        let name = buffer.read_string::<Utf8Decoder>(None)?;
-        let map = buffer.read_string::<Utf8Decoder>(None)?;
-        let folder = buffer.read_string::<Utf8Decoder>(None)?;
-        let game_mode = buffer.read_string::<Utf8Decoder>(None)?;
-        let mut appid = buffer.read::<u16>()? as u32;
-        let players = buffer.read()?;
-        let max_players = buffer.read()?;
-        let bots = buffer.read()?;
-        let server_type = Server::from_gldsrc(buffer.read()?)?;
-        let environment_type = Environment::from_gldsrc(buffer.read()?)?;
-        let has_password = buffer.read::<u8>()? == 1;
-        let vac_secured = buffer.read::<u8>()? == 1;
-        let the_ship = match *engine == Engine::new(2400) {
-            false => None,
-            true => {
-                Some(TheShip {
-                    mode: buffer.read()?,
-                    witnesses: buffer.read()?,
-                    duration: buffer.read()?,
-                })
-            }
-        };
-        let game_version = buffer.read_string::<Utf8Decoder>(None)?;
-        let extra_data = match buffer.read::<u8>() {
-            Err(_) => None,
-            Ok(value) => {
-                Some(ExtraData {
-                    port: match (value & 0x80) > 0 {
-                        false => None,
-                        true => Some(buffer.read()?),
-                    },
-                    steam_id: match (value & 0x10) > 0 {
-                        false => None,
-                        true => Some(buffer.read()?),
-                    },
-                    tv_port: match (value & 0x40) > 0 {
-                        false => None,
-                        true => Some(buffer.read()?),
-                    },
-                    tv_name: match (value & 0x40) > 0 {
-                        false => None,
-                        true => Some(buffer.read_string::<Utf8Decoder>(None)?),
-                    },
-                    keywords: match (value & 0x20) > 0 {
-                        false => None,
-                        true => Some(buffer.read_string::<Utf8Decoder>(None)?),
-                    },
-                    game_id: match (value & 0x01) > 0 {
-                        false => None,
-                        true => {
-                            let gid = buffer.read()?;
-                            appid = (gid & ((1 << 24) - 1)) as u32;
-
-                            Some(gid)
-                        }
-                    },
-                })
-            }
-        };
+const CHALLENGE_RESPONSE: u8 = 0x41;
+const INFO_RESPONSE: u8 = 0x49;
+/// The kind of the obsolete GoldSrc info response.
+const GOLDSRC_INFO_RESPONSE: u8 = 0x6D;
+const PLAYERS_RESPONSE: u8 = 0x44;
+const RULES_RESPONSE: u8 = 0x45;
+
+impl ValveProtocol {
+    pub fn new(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Self> {
+        let socket = UdpSocket::new(address, &timeout_settings)?;
+        let retry_count = timeout_settings.as_ref().map_or_else(
+            || TimeoutSettings::default().get_retries(),
+            TimeoutSettings::get_retries,
+        );
+        let retry_policy = TimeoutSettings::get_retry_policy_or_default(&timeout_settings);
 
-        Ok(ServerInfo {
-            protocol_version: protocol,
-            name,
-            map,
-            folder,
-            game_mode,
-            appid,
-            players_online: players,
-            players_maximum: max_players,
-            players_bots: bots,
-            server_type,
-            environment_type,
-            has_password,
-            vac_secured,
-            the_ship,
-            game_version,
-            extra_data,
-            is_mod: false,
-            mod_data: None,
+        Ok(Self {
+            socket,
+            retry_count,
+            retry_policy,
         })
     }
 
+    pub fn get_kind_request_data(&mut self, engine: &Engine, protocol: u8, kind: Request) -> GDResult<Vec<u8>> {
+        let data = self.get_request_data(engine, protocol, kind as u8, kind.get_default_payload())?;
+        Ok(data)
+    }
+
+    /// Ask for a specific request only.
+    /// This function will retry fetch on timeouts.
+    pub fn get_request_data(&mut self, engine: &Engine, protocol: u8, kind: u8, payload: Vec<u8>) -> GDResult<Vec<u8>> {
+        let mut machine = PacketMachine::new(*engine, protocol, kind, payload);
+        let response = retry(self.retry_count, self.retry_policy, || {
+            drive(&mut self.socket, &mut machine, Some(PACKET_SIZE))
+        })?;
+
+        Ok(response.payload)
+    }
+
+    /// Round-trip time of the last exchange with the server.
+    pub(crate) fn latency(&self) -> Option<Duration> { self.socket.latency() }
+
+    /// Get the server information's.
+    fn get_server_info(&mut self, engine: &Engine) -> GDResult<ServerInfo> {
+        self.get_kind_request_data(engine, 0, Request::Info)
+            .and_then(|data| parse_server_info(engine, &data))
+            .map_err(|e| e.with_stage(QueryStage::Info))
+    }
+
     /// Get the server player's.
     fn get_server_players(&mut self, engine: &Engine, protocol: u8) -> GDResult<Vec<ServerPlayer>> {
-        let data = self.get_kind_request_data(engine, protocol, Request::Players)?;
-        let mut buffer = Buffer::<LittleEndian>::new(&data);
+        self.get_kind_request_data(engine, protocol, Request::Players)
+            .and_then(|data| parse_server_players(engine, &data))
+    }
 
-        let count = buffer.read::<u8>()? as usize;
-        let mut players: Vec<ServerPlayer> = Vec::with_capacity(count);
+    /// Get the server's rules.
+    fn get_server_rules(&mut self, engine: &Engine, protocol: u8) -> GDResult<HashMap<String, String>> {
+        self.get_kind_request_data(engine, protocol, Request::Rules)
+            .and_then(|data| parse_server_rules(engine, &data))
+    }
+}
 
-        for _ in 0 .. count {
-            buffer.move_cursor(1)?; // skip the index byte
+fn parse_goldsrc_server_info(buffer: &mut Buffer<LittleEndian>) -> GDResult<ServerInfo> {
+    let _header: u8 = buffer.read()?; // get the header (useless info)
+    let _address: String = buffer.read_string::<Utf8Decoder>(None)?; // get the server address (useless info)
+    let name = buffer.read_string::<Utf8Decoder>(None)?;
+    let map = buffer.read_string::<Utf8Decoder>(None)?;
+    let folder = buffer.read_string::<Utf8Decoder>(None)?;
+    let game_mode = buffer.read_string::<Utf8Decoder>(None)?;
+    let players = buffer.read()?;
+    let max_players = buffer.read()?;
+    let protocol = buffer.read()?;
+    let server_type = match buffer.read::<u8>()? {
+        68 => Server::Dedicated,    //'D'
+        76 => Server::NonDedicated, //'L'
+        80 => Server::TV,           //'P'
+        _ => Err(UnknownEnumCast)?,
+    };
+    let environment_type = match buffer.read::<u8>()? {
+        76 => Environment::Linux,   //'L'
+        87 => Environment::Windows, //'W'
+        _ => Err(UnknownEnumCast)?,
+    };
+    let has_password = buffer.read::<u8>()? == 1;
+    let is_mod = buffer.read::<u8>()? == 1;
+    let mod_data = match is_mod {
+        false => None,
+        true => {
+            Some(ModData {
+                link: buffer.read_string::<Utf8Decoder>(None)?,
+                download_link: buffer.read_string::<Utf8Decoder>(None)?,
+                version: buffer.read()?,
+                size: buffer.read()?,
+                multiplayer_only: buffer.read::<u8>()? == 1,
+                has_own_dll: buffer.read::<u8>()? == 1,
+            })
+        }
+    };
+    let vac_secured = buffer.read::<u8>()? == 1;
+    let bots = buffer.read::<u8>()?;
+
+    Ok(ServerInfo {
+        protocol_version: protocol,
+        name,
+        map,
+        folder,
+        game_mode,
+        appid: 0, // not present in the obsolete response
+        players_online: players,
+        players_maximum: max_players,
+        players_bots: bots,
+        server_type,
+        environment_type,
+        has_password,
+        vac_secured,
+        the_ship: None,
+        game_version: String::new(), // a version field only for the mod
+        extra_data: None,
+        is_mod,
+        mod_data,
+    })
+}
+
+/// Parse the server information's from an info response payload.
+fn parse_server_info(engine: &Engine, data: &[u8]) -> GDResult<ServerInfo> {
+    let mut buffer = Buffer::<LittleEndian>::new(data);
+
+    if let Engine::GoldSrc(force) = engine {
+        if *force {
+            return parse_goldsrc_server_info(&mut buffer);
+        }
+    }
 
-            players.push(ServerPlayer {
-                name: buffer.read_string::<Utf8Decoder>(None)?,
-                score: buffer.read()?,
+    let protocol = buffer.read()?;
+    let name = buffer.read_string::<Utf8Decoder>(None)?;
+    let map = buffer.read_string::<Utf8Decoder>(None)?;
+    let folder = buffer.read_string::<Utf8Decoder>(None)?;
+    let game_mode = buffer.read_string::<Utf8Decoder>(None)?;
+    let mut appid = buffer.read::<u16>()? as u32;
+    let players = buffer.read()?;
+    let max_players = buffer.read()?;
+    let bots = buffer.read()?;
+    let server_type = Server::from_gldsrc(buffer.read()?)?;
+    let environment_type = Environment::from_gldsrc(buffer.read()?)?;
+    let has_password = buffer.read::<u8>()? == 1;
+    let vac_secured = buffer.read::<u8>()? == 1;
+    let the_ship = match *engine == Engine::new(2400) {
+        false => None,
+        true => {
+            Some(TheShip {
+                mode: buffer.read()?,
+                witnesses: buffer.read()?,
                 duration: buffer.read()?,
-                deaths: match *engine == Engine::new(2400) {
+            })
+        }
+    };
+    let game_version = buffer.read_string::<Utf8Decoder>(None)?;
+    let extra_data = match buffer.read::<u8>() {
+        Err(_) => None,
+        Ok(value) => {
+            Some(ExtraData {
+                port: match (value & 0x80) > 0 {
+                    false => None,
+                    true => Some(buffer.read()?),
+                },
+                steam_id: match (value & 0x10) > 0 {
                     false => None,
                     true => Some(buffer.read()?),
                 },
-                money: match *engine == Engine::new(2400) {
+                tv_port: match (value & 0x40) > 0 {
                     false => None,
                     true => Some(buffer.read()?),
                 },
-            });
+                tv_name: match (value & 0x40) > 0 {
+                    false => None,
+                    true => Some(buffer.read_string::<Utf8Decoder>(None)?),
+                },
+                keywords: match (value & 0x20) > 0 {
+                    false => None,
+                    true => Some(buffer.read_string::<Utf8Decoder>(None)?),
+                },
+                game_id: match (value & 0x01) > 0 {
+                    false => None,
+                    true => {
+                        let gid = buffer.read()?;
+                        appid = (gid & ((1 << 24) - 1)) as u32;
+
+                        Some(gid)
+                    }
+                },
+            })
         }
+    };
+
+    Ok(ServerInfo {
+        protocol_version: protocol,
+        name,
+        map,
+        folder,
+        game_mode,
+        appid,
+        players_online: players,
+        players_maximum: max_players,
+        players_bots: bots,
+        server_type,
+        environment_type,
+        has_password,
+        vac_secured,
+        the_ship,
+        game_version,
+        extra_data,
+        is_mod: false,
+        mod_data: None,
+    })
+}
+
+/// Parse the server player's from a players response payload.
+fn parse_server_players(engine: &Engine, data: &[u8]) -> GDResult<Vec<ServerPlayer>> {
+    let mut buffer = Buffer::<LittleEndian>::new(data);
+
+    let count = buffer.read::<u8>()? as usize;
+    let mut players: Vec<ServerPlayer> = Vec::with_capacity(count);
 
-        Ok(players)
+    for _ in 0 .. count {
+        buffer.move_cursor(1)?; // skip the index byte
+
+        players.push(ServerPlayer {
+            name: buffer.read_string::<Utf8Decoder>(None)?,
+            score: buffer.read()?,
+            duration: buffer.read()?,
+            deaths: match *engine == Engine::new(2400) {
+                false => None,
+                true => Some(buffer.read()?),
+            },
+            money: match *engine == Engine::new(2400) {
+                false => None,
+                true => Some(buffer.read()?),
+            },
+        });
     }
 
-    /// Get the server's rules.
-    fn get_server_rules(&mut self, engine: &Engine, protocol: u8) -> GDResult<HashMap<String, String>> {
-        let data = self.get_kind_request_data(engine, protocol, Request::Rules)?;
-        let mut buffer = Buffer::<LittleEndian>::new(&data);
+    Ok(players)
+}
 
-        let count = buffer.read::<u16>()? as usize;
-        let mut rules: HashMap<String, String> = HashMap::with_capacity(count);
+/// Parse the server's rules from a rules response payload.
+fn parse_server_rules(engine: &Engine, data: &[u8]) -> GDResult<HashMap<String, String>> {
+    let mut buffer = Buffer::<LittleEndian>::new(data);
 
-        for _ in 0 .. count {
-            let name = buffer.read_string::<Utf8Decoder>(None)?;
-            let value = buffer.read_string::<Utf8Decoder>(None)?;
+    let count = buffer.read::<u16>()? as usize;
+    let mut rules: HashMap<String, String> = HashMap::with_capacity(count);
 
-            rules.insert(name, value);
-        }
+    for _ in 0 .. count {
+        let name = buffer.read_string::<Utf8Decoder>(None)?;
+        let value = buffer.read_string::<Utf8Decoder>(None)?;
 
-        if *engine == Engine::new(632_360) {
-            // ROR2
-            rules.remove("Test");
-        }
+        rules.insert(name, value);
+    }
+
+    if *engine == Engine::new(632_360) {
+        // ROR2
+        rules.remove("Test");
+    }
+
+    Ok(rules)
+}
+
+/// Validate the header of a whole response packet and that it is of one of
+/// the expected kinds.
+fn whole_packet(data: &[u8], kinds: &[u8]) -> GDResult<Packet> {
+    let packet = Packet::new_from_bufferer(&mut Buffer::<LittleEndian>::new(data))?;
 
-        Ok(rules)
+    if packet.header != u32::MAX {
+        return Err(PacketBad.context("Expected a whole packet (a split one has to be reassembled)"));
     }
+
+    if !kinds.contains(&packet.kind) {
+        return Err(PacketBad.context(format!("Unexpected packet kind {:#04x}", packet.kind)));
+    }
+
+    Ok(packet)
+}
+
+/// Parse an info response packet, as received (reassembled and decompressed
+/// if it was split), the obsolete GoldSrc response is told apart by its kind.
+pub fn parse_info(engine: &Engine, data: &[u8]) -> GDResult<ServerInfo> {
+    let packet = whole_packet(data, &[INFO_RESPONSE, GOLDSRC_INFO_RESPONSE])?;
+
+    let engine = match (packet.kind, engine) {
+        (GOLDSRC_INFO_RESPONSE, _) => &Engine::GoldSrc(true),
+        (_, Engine::GoldSrc(_)) => &Engine::GoldSrc(false),
+        (_, engine) => engine,
+    };
+
+    parse_server_info(engine, &packet.payload)
+}
+
+/// Parse a players response packet, as received (reassembled and
+/// decompressed if it was split).
+pub fn parse_players(engine: &Engine, data: &[u8]) -> GDResult<Vec<ServerPlayer>> {
+    parse_server_players(engine, &whole_packet(data, &[PLAYERS_RESPONSE])?.payload)
+}
+
+/// Parse a rules response packet, as received (reassembled and decompressed
+/// if it was split).
+pub fn parse_rules(engine: &Engine, data: &[u8]) -> GDResult<HashMap<String, String>> {
+    parse_server_rules(engine, &whole_packet(data, &[RULES_RESPONSE])?.payload)
 }
 
 /// Query a server by providing the address, the port, the app, gather and
@@ -437,20 +668,15 @@ pub fn query(
     timeout_settings: Option<TimeoutSettings>,
 ) -> GDResult<Response> {
     let response_gather_settings = gather_settings.unwrap_or_default();
-    get_response(address, engine, response_gather_settings, timeout_settings)
+    trace::span!("valve", %address, ?engine)
+        .in_scope(|| get_response(address, engine, response_gather_settings, timeout_settings))
+        .map_err(|e| e.with_address(*address))
 }
 
-fn get_response(
-    address: &SocketAddr,
-    engine: Engine,
-    gather_settings: GatheringSettings,
-    timeout_settings: Option<TimeoutSettings>,
-) -> GDResult<Response> {
-    let mut client = ValveProtocol::new(address, timeout_settings)?;
-
-    let info = client.get_server_info(&engine)?;
-
-    if let Engine::Source(Some(appids)) = &engine {
+/// Make sure the server answered with one of the app ids of the engine (if it
+/// has any and the gather settings ask for it to be checked).
+fn check_app_id(engine: &Engine, info: &ServerInfo, gather_settings: &GatheringSettings) -> GDResult<()> {
+    if let Engine::Source(Some(appids)) = engine {
         let mut is_specified_id = false;
 
         if appids.0 == info.appid {
@@ -466,17 +692,167 @@ fn get_response(
         }
     }
 
+    Ok(())
+}
+
+fn get_response(
+    address: &SocketAddr,
+    engine: Engine,
+    gather_settings: GatheringSettings,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<Response> {
+    let mut client = ValveProtocol::new(address, timeout_settings)?;
+
+    let info = client.get_server_info(&engine)?;
+    let latency = client.latency();
+    check_app_id(&engine, &info, &gather_settings)?;
+
+    let protocol = info.protocol_version;
+
+    let mut diagnostics = Vec::new();
+    let players = maybe_gather!(
+        gather_settings.players,
+        client.get_server_players(&engine, protocol),
+        diagnostics,
+        QueryStage::Players
+    );
+    let rules = maybe_gather!(
+        gather_settings.rules,
+        client.get_server_rules(&engine, protocol),
+        diagnostics,
+        QueryStage::Rules
+    );
+
+    Ok(Response {
+        info,
+        latency,
+        players,
+        rules,
+        diagnostics,
+    })
+}
+
+/// Async counterpart of [ValveProtocol].
+#[cfg(feature = "async")]
+pub(crate) struct AsyncValveProtocol {
+    socket: AsyncUdpSocket,
+    retry_count: usize,
+    retry_policy: RetryPolicy,
+}
+
+#[cfg(feature = "async")]
+impl AsyncValveProtocol {
+    pub async fn new(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Self> {
+        let socket = AsyncUdpSocket::new(address, &timeout_settings).await?;
+        let retry_count = TimeoutSettings::get_retries_or_default(&timeout_settings);
+        let retry_policy = TimeoutSettings::get_retry_policy_or_default(&timeout_settings);
+
+        Ok(Self {
+            socket,
+            retry_count,
+            retry_policy,
+        })
+    }
+
+    pub async fn get_kind_request_data(&mut self, engine: &Engine, protocol: u8, kind: Request) -> GDResult<Vec<u8>> {
+        self.get_request_data(engine, protocol, kind as u8, kind.get_default_payload())
+            .await
+    }
+
+    /// Ask for a specific request only.
+    /// This function will retry fetch on timeouts.
+    pub async fn get_request_data(
+        &mut self,
+        engine: &Engine,
+        protocol: u8,
+        kind: u8,
+        payload: Vec<u8>,
+    ) -> GDResult<Vec<u8>> {
+        let mut machine = PacketMachine::new(*engine, protocol, kind, payload);
+        let response = retry_async!(
+            self.retry_count,
+            self.retry_policy,
+            drive_async(&mut self.socket, &mut machine, Some(PACKET_SIZE)).await
+        )?;
+
+        Ok(response.payload)
+    }
+
+    /// Round-trip time of the last exchange with the server.
+    pub(crate) fn latency(&self) -> Option<Duration> { self.socket.latency() }
+
+    /// Get the server information's.
+    async fn get_server_info(&mut self, engine: &Engine) -> GDResult<ServerInfo> {
+        self.get_kind_request_data(engine, 0, Request::Info)
+            .await
+            .and_then(|data| parse_server_info(engine, &data))
+            .map_err(|e| e.with_stage(QueryStage::Info))
+    }
+
+    /// Get the server player's.
+    async fn get_server_players(&mut self, engine: &Engine, protocol: u8) -> GDResult<Vec<ServerPlayer>> {
+        self.get_kind_request_data(engine, protocol, Request::Players)
+            .await
+            .and_then(|data| parse_server_players(engine, &data))
+    }
+
+    /// Get the server's rules.
+    async fn get_server_rules(&mut self, engine: &Engine, protocol: u8) -> GDResult<HashMap<String, String>> {
+        self.get_kind_request_data(engine, protocol, Request::Rules)
+            .await
+            .and_then(|data| parse_server_rules(engine, &data))
+    }
+}
+
+/// Async counterpart of [query].
+#[cfg(feature = "async")]
+pub async fn query_async(
+    address: &SocketAddr,
+    engine: Engine,
+    gather_settings: Option<GatheringSettings>,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<Response> {
+    let gather_settings = gather_settings.unwrap_or_default();
+    get_response_async(address, engine, gather_settings, timeout_settings)
+        .instrument(trace::span!("valve", %address, ?engine))
+        .await
+        .map_err(|e| e.with_address(*address))
+}
+
+#[cfg(feature = "async")]
+async fn get_response_async(
+    address: &SocketAddr,
+    engine: Engine,
+    gather_settings: GatheringSettings,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<Response> {
+    let mut client = AsyncValveProtocol::new(address, timeout_settings).await?;
+
+    let info = client.get_server_info(&engine).await?;
+    let latency = client.latency();
+    check_app_id(&engine, &info, &gather_settings)?;
+
     let protocol = info.protocol_version;
 
+    let mut diagnostics = Vec::new();
+    let players = maybe_gather!(
+        gather_settings.players,
+        client.get_server_players(&engine, protocol).await,
+        diagnostics,
+        QueryStage::Players
+    );
+    let rules = maybe_gather!(
+        gather_settings.rules,
+        client.get_server_rules(&engine, protocol).await,
+        diagnostics,
+        QueryStage::Rules
+    );
+
     Ok(Response {
         info,
-        players: maybe_gather!(
-            gather_settings.players,
-            client.get_server_players(&engine, protocol)
-        ),
-        rules: maybe_gather!(
-            gather_settings.rules,
-            client.get_server_rules(&engine, protocol)
-        ),
+        latency,
+        players,
+        rules,
+        diagnostics,
     })
 }
@@ -1,6 +1,14 @@
 use std::collections::HashMap;
-
-use crate::protocols::types::{CommonPlayer, CommonResponse, ExtraRequestSettings, GatherToggle, GenericPlayer};
+use std::time::Duration;
+
+use crate::protocols::types::{
+    CommonPlayer,
+    CommonResponse,
+    Diagnostic,
+    ExtraRequestSettings,
+    GatherToggle,
+    GenericPlayer,
+};
 use crate::GDErrorKind::UnknownEnumCast;
 use crate::GDResult;
 use crate::{buffer::Buffer, protocols::GenericResponse};
@@ -26,6 +34,14 @@ impl Server {
             _ => Err(UnknownEnumCast)?,
         })
     }
+
+    pub(crate) const fn to_gldsrc(self) -> u8 {
+        match self {
+            Self::Dedicated => b'd',
+            Self::NonDedicated => b'l',
+            Self::TV => b'p',
+        }
+    }
 }
 
 /// The Operating System that the server is on.
@@ -46,6 +62,14 @@ impl Environment {
             _ => Err(UnknownEnumCast)?,
         })
     }
+
+    pub(crate) const fn to_gldsrc(self) -> u8 {
+        match self {
+            Self::Linux => b'l',
+            Self::Windows => b'w',
+            Self::Mac => b'm',
+        }
+    }
 }
 
 /// A query response.
@@ -55,6 +79,12 @@ pub struct Response {
     pub info: ServerInfo,
     pub players: Option<Vec<ServerPlayer>>,
     pub rules: Option<HashMap<String, String>>,
+    /// Round-trip time of the info request.
+    pub latency: Option<Duration>,
+    /// The players and rules requests that failed, if they were only
+    /// attempted.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 impl CommonResponse for Response {
@@ -74,6 +104,10 @@ impl CommonResponse for Response {
             .as_ref()
             .map(|p| p.iter().map(|p| p as &dyn CommonPlayer).collect())
     }
+
+    fn latency(&self) -> Option<Duration> { self.latency }
+
+    fn diagnostics(&self) -> &[Diagnostic] { &self.diagnostics }
 }
 
 /// General server information's.
@@ -332,6 +366,7 @@ pub mod game {
     use super::{Server, ServerPlayer};
     use crate::protocols::valve::types::get_optional_extracted_data;
     use std::collections::HashMap;
+    use std::time::Duration;
 
     #[cfg(feature = "serde")]
     use serde::{Deserialize, Serialize};
@@ -400,6 +435,8 @@ pub mod game {
         pub keywords: Option<String>,
         /// Server's rules.
         pub rules: HashMap<String, String>,
+        /// Round-trip time of the info request.
+        pub latency: Option<Duration>,
     }
 
     impl Response {
@@ -431,6 +468,7 @@ pub mod game {
                 tv_name,
                 keywords,
                 rules: response.rules.unwrap_or_default(),
+                latency: response.latency,
             }
         }
     }
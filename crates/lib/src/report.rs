@@ -0,0 +1,189 @@
+//! Reports about how a query's answer was obtained.
+//!
+//! Wrap any query in [collect] (or `collect_async`, with the `async` feature)
+//! to get a [QueryReport] alongside its result:
+//!
+//! ```no_run
+//! use gamedig::report;
+//! use gamedig::protocols::valve::{self, Engine};
+//!
+//! let address = "127.0.0.1:27015".parse().unwrap();
+//! let (result, report) = report::collect(|| valve::query(&address, Engine::Source(None), None, None));
+//!
+//! if report.retries > 0 {
+//!     println!("flaky server, needed {} retries", report.retries);
+//! }
+//! ```
+
+use crate::protocols::types::Protocol;
+
+use std::cell::RefCell;
+
+#[cfg(feature = "async")]
+use std::future::Future;
+
+/// Stats about how a query's answer was obtained.
+///
+/// Packets and bytes are counted at the socket level, so the HTTP based
+/// protocols (like Epic) don't count them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryReport {
+    /// Number of times a request was retried because of a timeout.
+    pub retries: usize,
+    /// Packets (or TCP writes) sent.
+    pub packets_sent: usize,
+    /// Packets (or TCP reads) received.
+    pub packets_received: usize,
+    /// Bytes sent.
+    pub bytes_sent: usize,
+    /// Bytes received.
+    pub bytes_received: usize,
+    /// Number of responses that were split in multiple packets (Valve).
+    pub split_responses: usize,
+    /// Number of responses that had a bzip2 compressed payload (Valve).
+    pub compressed_responses: usize,
+    /// The (sub-)protocol that answered, recorded by queries that try several
+    /// of them (e.g. the Minecraft auto-query).
+    pub protocol: Option<Protocol>,
+}
+
+thread_local! {
+    static REPORT: RefCell<Option<QueryReport>> = const { RefCell::new(None) };
+}
+
+#[cfg(feature = "async")]
+tokio::task_local! {
+    static TASK_REPORT: RefCell<QueryReport>;
+}
+
+/// Run a (sync) query, collecting a report of how its answer was obtained.
+///
+/// The report is returned even if the query failed.
+pub fn collect<T>(query: impl FnOnce() -> T) -> (T, QueryReport) {
+    let previous = REPORT.with(|report| report.replace(Some(QueryReport::default())));
+    let result = query();
+    let report = REPORT.with(|report| report.replace(previous));
+
+    (result, report.unwrap_or_default())
+}
+
+/// Async counterpart of [collect].
+#[cfg(feature = "async")]
+pub async fn collect_async<T>(query: impl Future<Output = T>) -> (T, QueryReport) {
+    TASK_REPORT
+        .scope(RefCell::new(QueryReport::default()), async {
+            let result = query.await;
+            (result, TASK_REPORT.with(RefCell::take))
+        })
+        .await
+}
+
+/// Update the report being collected, if any.
+pub(crate) fn record(update: impl FnOnce(&mut QueryReport)) {
+    #[cfg(feature = "async")]
+    if TASK_REPORT.try_with(|_| ()).is_ok() {
+        return TASK_REPORT.with(|report| update(&mut report.borrow_mut()));
+    }
+
+    REPORT.with(|report| {
+        if let Some(report) = report.borrow_mut().as_mut() {
+            update(report);
+        }
+    });
+}
+
+/// Add a report collected inside the current query to the report being
+/// collected, if any.
+#[cfg(feature = "games")]
+pub(crate) fn merge(inner: &QueryReport) {
+    record(|report| {
+        report.retries += inner.retries;
+        report.packets_sent += inner.packets_sent;
+        report.packets_received += inner.packets_received;
+        report.bytes_sent += inner.bytes_sent;
+        report.bytes_received += inner.bytes_received;
+        report.split_responses += inner.split_responses;
+        report.compressed_responses += inner.compressed_responses;
+        if inner.protocol.is_some() {
+            report.protocol.clone_from(&inner.protocol);
+        }
+    });
+}
+
+pub(crate) fn record_sent(bytes: usize) {
+    record(|report| {
+        report.packets_sent += 1;
+        report.bytes_sent += bytes;
+    });
+}
+
+pub(crate) fn record_received(bytes: usize) {
+    record(|report| {
+        report.packets_received += 1;
+        report.bytes_received += bytes;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::types::RetryPolicy;
+    use crate::retry::retry;
+    use crate::GDErrorKind::PacketReceive;
+
+    #[test]
+    fn collects_only_inside() {
+        record_sent(10);
+
+        let ((), report) = collect(|| {
+            record_sent(4);
+            record_received(6);
+        });
+
+        assert_eq!(report.packets_sent, 1);
+        assert_eq!(report.bytes_sent, 4);
+        assert_eq!(report.packets_received, 1);
+        assert_eq!(report.bytes_received, 6);
+    }
+
+    #[test]
+    fn counts_retries() {
+        let mut attempts = 0;
+        let (result, report) = collect(|| {
+            retry(5, RetryPolicy::new(), || {
+                attempts += 1;
+                match attempts {
+                    3 => Ok(()),
+                    _ => Err(PacketReceive.into()),
+                }
+            })
+        });
+
+        assert!(result.is_ok());
+        assert_eq!(report.retries, 2);
+    }
+
+    #[test]
+    fn nested_collects_are_separate() {
+        let ((), outer) = collect(|| {
+            record_sent(1);
+            let ((), inner) = collect(|| record_sent(2));
+            assert_eq!(inner.bytes_sent, 2);
+        });
+
+        assert_eq!(outer.bytes_sent, 1);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn collects_async() {
+        let ((), report) = collect_async(async {
+            tokio::task::yield_now().await;
+            record_received(3);
+        })
+        .await;
+
+        assert_eq!(report.packets_received, 1);
+        assert_eq!(report.bytes_received, 3);
+    }
+}
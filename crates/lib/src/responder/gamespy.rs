@@ -0,0 +1,291 @@
+//! GameSpy 3, with its challenge and split responses.
+
+use crate::protocols::gamespy::three::Response;
+use crate::responder::Responder;
+
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::net::SocketAddr;
+
+/// The size above which responses are split by default.
+pub const DEFAULT_PACKET_SIZE: usize = 1400;
+
+const HANDSHAKE: u8 = 0x09;
+const DATA: u8 = 0x00;
+
+/// The size of a data packet's header: the kind, the session id, the
+/// `splitnum` string, the packet's number and a byte of unknown use.
+const DATA_HEADER_SIZE: usize = 16;
+
+fn push_string(buf: &mut Vec<u8>, value: &str) {
+    buf.extend_from_slice(value.as_bytes());
+    buf.push(0);
+}
+
+/// Encode a field of the players (`player_`, `score_`...) or teams (`team_t`,
+/// `score_t`) lists.
+fn field<'a>(name: &str, values: impl Iterator<Item = &'a str>) -> Vec<u8> {
+    let mut data = Vec::new();
+    push_string(&mut data, name);
+    data.push(0); // The index of the first value.
+    for value in values {
+        push_string(&mut data, value);
+    }
+    data.push(0);
+    data
+}
+
+/// Encode a response into the data of its packets (without their headers),
+/// each one being at most `packet_size` long (unless a single value or field
+/// is longer).
+pub fn encode(response: &Response, packet_size: usize) -> Vec<Vec<u8>> {
+    let mut values = vec![
+        ("hostname", response.name.clone()),
+        ("mapname", response.map.clone()),
+        ("password", u8::from(response.has_password).to_string()),
+        ("gametype", response.game_mode.clone()),
+        ("gamever", response.game_version.clone()),
+        ("maxplayers", response.players_maximum.to_string()),
+        ("numplayers", response.players_online.to_string()),
+        ("tournament", response.tournament.to_string()),
+    ];
+    if let Some(players_minimum) = response.players_minimum {
+        values.push(("minplayers", players_minimum.to_string()));
+    }
+
+    let mut first = Vec::new();
+    for (key, value) in values
+        .iter()
+        .map(|(key, value)| (*key, value.as_str()))
+        .chain(
+            response
+                .unused_entries
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str())),
+        )
+    {
+        push_string(&mut first, key);
+        push_string(&mut first, value);
+    }
+    first.push(0);
+
+    let players = &response.players;
+    let teams = &response.teams;
+    let scores: Vec<String> = players.iter().map(|p| p.score.to_string()).collect();
+    let pings: Vec<String> = players.iter().map(|p| p.ping.to_string()).collect();
+    let player_teams: Vec<String> = players.iter().map(|p| p.team.to_string()).collect();
+    let deaths: Vec<String> = players.iter().map(|p| p.deaths.to_string()).collect();
+    let skills: Vec<String> = players.iter().map(|p| p.skill.to_string()).collect();
+    let team_scores: Vec<String> = teams.iter().map(|t| t.score.to_string()).collect();
+
+    let mut player_fields = vec![
+        field("player_", players.iter().map(|p| p.name.as_str())),
+        field("score_", scores.iter().map(String::as_str)),
+        field("ping_", pings.iter().map(String::as_str)),
+        field("team_", player_teams.iter().map(String::as_str)),
+        field("deaths_", deaths.iter().map(String::as_str)),
+        field("skill_", skills.iter().map(String::as_str)),
+    ];
+    let mut team_fields = vec![
+        field("team_t", teams.iter().map(|t| t.name.as_str())),
+        field("score_t", team_scores.iter().map(String::as_str)),
+    ];
+
+    // Each list starts with the number of its section.
+    player_fields[0].insert(0, 0x01);
+    team_fields[0].insert(0, 0x02);
+
+    // A field can't be split over packets, packets are filled with whole ones.
+    let mut packets = vec![first];
+    for field in player_fields.into_iter().chain(team_fields) {
+        match packets.last_mut() {
+            Some(packet) if packet.len() + field.len() <= packet_size => packet.extend(field),
+            _ => packets.push(field),
+        }
+    }
+
+    packets
+}
+
+/// Answers the handshake and data requests with a [Response].
+#[derive(Debug, Clone)]
+pub struct GameSpy3Responder {
+    pub response: Response,
+    /// The key of the clients' challenges, None if they are not required.
+    challenges: Option<RandomState>,
+    packet_size: usize,
+}
+
+impl GameSpy3Responder {
+    /// A responder that requires clients to send back the challenge given in
+    /// the handshake before answering them.
+    pub fn new(response: Response) -> Self {
+        Self {
+            response,
+            challenges: Some(RandomState::new()),
+            packet_size: DEFAULT_PACKET_SIZE,
+        }
+    }
+
+    /// Answer clients without giving them a challenge in the handshake.
+    ///
+    /// The challenge prevents address spoofing, without it the responder
+    /// answers small requests with big responses to any address, which can be
+    /// abused to flood it.
+    pub fn without_challenge(mut self) -> Self {
+        self.challenges = None;
+        self
+    }
+
+    /// Split the responses bigger than `size` bytes, [DEFAULT_PACKET_SIZE] by
+    /// default.
+    pub fn with_packet_size(mut self, size: usize) -> Self {
+        self.packet_size = size;
+        self
+    }
+
+    /// The challenge a client has to send, derived from its address so that
+    /// it doesn't have to be remembered.
+    fn challenge(&self, peer: &SocketAddr) -> Option<i32> {
+        let challenge = self.challenges.as_ref()?.hash_one(peer.ip()) as i32;
+
+        // Clients take 0 as no challenge, and parse it as a signed integer.
+        Some(challenge.checked_abs().unwrap_or(i32::MAX).max(1))
+    }
+}
+
+impl Responder for GameSpy3Responder {
+    fn respond(&mut self, request: &[u8], peer: &SocketAddr) -> Option<Vec<Vec<u8>>> {
+        let (kind, session_id, data) = match request {
+            [0xFE, 0xFD, kind, a, b, c, d, data @ ..] => (*kind, [*a, *b, *c, *d], data),
+            _ => return None,
+        };
+
+        let challenge = self.challenge(peer);
+
+        match kind {
+            HANDSHAKE => {
+                let mut reply = vec![HANDSHAKE];
+                reply.extend_from_slice(&session_id);
+                push_string(&mut reply, &challenge.unwrap_or(0).to_string());
+
+                Some(vec![reply])
+            }
+            DATA => {
+                if let Some(challenge) = challenge {
+                    if !data.starts_with(&challenge.to_be_bytes()) {
+                        return None;
+                    }
+                }
+
+                let packets = encode(
+                    &self.response,
+                    self.packet_size.saturating_sub(DATA_HEADER_SIZE),
+                );
+                let last = packets.len() - 1;
+
+                Some(
+                    packets
+                        .into_iter()
+                        .enumerate()
+                        .map(|(number, data)| {
+                            let mut packet = vec![DATA];
+                            packet.extend_from_slice(&session_id);
+                            push_string(&mut packet, "splitnum");
+                            packet.push(number as u8 | if number == last { 0x80 } else { 0x00 });
+                            packet.push(0x00);
+                            packet.extend(data);
+                            packet
+                        })
+                        .collect(),
+                )
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::protocols::gamespy::three::{self, Player, Team};
+    use crate::responder::UdpListener;
+
+    use std::collections::HashMap;
+    use std::net::Ipv4Addr;
+    use std::thread;
+
+    fn response() -> Response {
+        Response {
+            name: "Responder".to_string(),
+            map: "Mesa".to_string(),
+            has_password: true,
+            game_mode: "InstantAction".to_string(),
+            game_version: "1.5".to_string(),
+            players_maximum: 32,
+            players_online: 2,
+            players_minimum: Some(1),
+            players: ["Alice", "Bob"]
+                .into_iter()
+                .map(|name| {
+                    Player {
+                        name: name.to_string(),
+                        score: 7,
+                        ping: 30,
+                        team: 1,
+                        deaths: 2,
+                        skill: 100,
+                    }
+                })
+                .collect(),
+            teams: vec![Team {
+                name: "US".to_string(),
+                score: 9,
+            }],
+            tournament: false,
+            unused_entries: HashMap::from([("timelimit".to_string(), "20".to_string())]),
+            latency: None,
+        }
+    }
+
+    fn round_trip(responder: GameSpy3Responder) {
+        let mut listener = UdpListener::bind(&SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut served = responder.clone();
+        thread::spawn(move || listener.serve(&mut served));
+
+        let queried = three::query(&address, None).unwrap();
+
+        assert_eq!(
+            Response {
+                latency: None,
+                ..queried
+            },
+            responder.response
+        );
+    }
+
+    #[test]
+    fn answers() { round_trip(GameSpy3Responder::new(response())); }
+
+    #[test]
+    fn answers_without_challenge_and_split() {
+        round_trip(
+            GameSpy3Responder::new(response())
+                .without_challenge()
+                .with_packet_size(64),
+        );
+    }
+
+    #[test]
+    fn ignores_data_requests_without_challenge() {
+        let mut responder = GameSpy3Responder::new(response());
+        let peer = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 1);
+
+        let request = [
+            0xFE, 0xFD, DATA, 0, 0, 0, 1, 0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0x01,
+        ];
+        assert_eq!(responder.respond(&request, &peer), None);
+    }
+}
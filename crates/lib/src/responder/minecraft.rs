@@ -0,0 +1,283 @@
+//! Minecraft: the Java status (and ping) and the Bedrock unconnected ping.
+
+use crate::buffer::Buffer;
+use crate::games::minecraft::{as_varint, get_varint, BedrockResponse, JavaResponse};
+use crate::responder::Responder;
+
+use byteorder::LittleEndian;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+
+/// The magic of RakNet's offline messages.
+const MAGIC: [u8; 16] = [
+    0x00, 0xFF, 0xFF, 0x00, 0xFE, 0xFE, 0xFE, 0xFE, 0xFD, 0xFD, 0xFD, 0xFD, 0x12, 0x34, 0x56, 0x78,
+];
+
+const UNCONNECTED_PING: u8 = 0x01;
+const UNCONNECTED_PONG: u8 = 0x1C;
+
+/// Prefix a packet with its length.
+fn frame(data: Vec<u8>) -> Vec<u8> { [as_varint(data.len() as i32), data].concat() }
+
+/// Encode a (framed) status response.
+///
+/// The description is sent as is if it is JSON (as queries return it), as a
+/// string otherwise.
+pub fn encode_java_status(response: &JavaResponse) -> Vec<u8> {
+    let description =
+        serde_json::from_str(&response.description).unwrap_or_else(|_| Value::String(response.description.clone()));
+
+    let mut status = json!({
+        "version": {
+            "name": response.game_version,
+            "protocol": response.protocol_version,
+        },
+        "players": {
+            "max": response.players_maximum,
+            "online": response.players_online,
+        },
+        "description": description,
+    });
+
+    if let Some(players) = &response.players {
+        status["players"]["sample"] = players
+            .iter()
+            .map(|player| json!({ "name": player.name, "id": player.id }))
+            .collect();
+    }
+    if let Some(favicon) = &response.favicon {
+        status["favicon"] = json!(favicon);
+    }
+    if let Some(previews_chat) = response.previews_chat {
+        status["previewsChat"] = json!(previews_chat);
+    }
+    if let Some(enforces_secure_chat) = response.enforces_secure_chat {
+        status["enforcesSecureChat"] = json!(enforces_secure_chat);
+    }
+
+    let status = status.to_string();
+    let mut data = as_varint(0);
+    data.extend(as_varint(status.len() as i32));
+    data.extend(status.into_bytes());
+
+    frame(data)
+}
+
+/// Encode an unconnected pong, `nonce` being the one of the ping it answers.
+pub fn encode_bedrock_status(response: &BedrockResponse, nonce: [u8; 8]) -> Vec<u8> {
+    let mut values = vec![
+        response.edition.clone(),
+        response.name.clone(),
+        response.protocol_version.clone(),
+        response.version_name.clone(),
+        response.players_online.to_string(),
+        response.players_maximum.to_string(),
+    ];
+
+    // The optional values are positional, the missing ones are only left out
+    // at the end.
+    let optional = [
+        response.id.clone(),
+        response.map.clone(),
+        response.game_mode.map(|mode| mode.to_bedrock().to_string()),
+    ];
+    let present = optional
+        .iter()
+        .rposition(Option::is_some)
+        .map_or(0, |last| last + 1);
+    values.extend(
+        optional
+            .into_iter()
+            .take(present)
+            .map(Option::unwrap_or_default),
+    );
+
+    let status = values.join(";");
+    // Servers send their id as their GUID as well.
+    let guid: u64 = response
+        .id
+        .as_deref()
+        .and_then(|id| id.parse().ok())
+        .unwrap_or_default();
+
+    let mut packet = vec![UNCONNECTED_PONG];
+    packet.extend_from_slice(&nonce);
+    packet.extend_from_slice(&guid.to_be_bytes());
+    packet.extend_from_slice(&MAGIC);
+    packet.extend_from_slice(&(status.len().min(u16::MAX as usize) as u16).to_be_bytes());
+    packet.extend_from_slice(status.as_bytes());
+    packet
+}
+
+/// Answers status requests (over TCP) with a [JavaResponse], along with the
+/// ping if it was sent with them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JavaResponder {
+    pub response: JavaResponse,
+}
+
+impl JavaResponder {
+    pub const fn new(response: JavaResponse) -> Self { Self { response } }
+}
+
+impl Responder for JavaResponder {
+    fn respond(&mut self, request: &[u8], _peer: &SocketAddr) -> Option<Vec<Vec<u8>>> {
+        let mut buffer = Buffer::<LittleEndian>::new(request);
+
+        let mut status_requested = false;
+        let mut ping = None;
+        // Partial packets are read as errors, which waits for the rest.
+        while buffer.remaining_length() != 0 {
+            let length = get_varint(&mut buffer).ok()? as usize;
+            let packet = buffer.remaining_bytes().get(.. length)?.to_vec();
+            buffer.move_cursor(length as isize).ok()?;
+
+            match packet.as_slice() {
+                // The handshake has the same id, but more data.
+                [0x00] => status_requested = true,
+                [0x01, payload @ ..] if payload.len() == 8 => ping = Some(packet),
+                _ => {}
+            }
+        }
+
+        if !status_requested {
+            return None;
+        }
+
+        let mut replies = vec![encode_java_status(&self.response)];
+        if let Some(pong) = ping {
+            replies.push(frame(pong));
+        }
+
+        Some(replies)
+    }
+}
+
+/// Answers unconnected pings with a [BedrockResponse].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BedrockResponder {
+    pub response: BedrockResponse,
+}
+
+impl BedrockResponder {
+    pub const fn new(response: BedrockResponse) -> Self { Self { response } }
+}
+
+impl Responder for BedrockResponder {
+    fn respond(&mut self, request: &[u8], _peer: &SocketAddr) -> Option<Vec<Vec<u8>>> {
+        // The id, the nonce, the magic and the client's GUID.
+        if request.len() < 33 || request[0] != UNCONNECTED_PING || request[9 .. 25] != MAGIC {
+            return None;
+        }
+
+        let nonce = request[1 .. 9].try_into().ok()?;
+        Some(vec![encode_bedrock_status(&self.response, nonce)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::games::minecraft::{self, GameMode, Player, Server};
+    use crate::responder::{TcpListener, UdpListener};
+
+    use std::net::Ipv4Addr;
+    use std::thread;
+
+    fn localhost() -> SocketAddr { SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0) }
+
+    #[test]
+    fn java_round_trip() {
+        let response = JavaResponse {
+            game_version: "1.20.4".to_string(),
+            protocol_version: 765,
+            players_maximum: 20,
+            players_online: 1,
+            players: Some(vec![Player {
+                name: "Alice".to_string(),
+                id: "00000000-0000-0000-0000-000000000000".to_string(),
+            }]),
+            description: r#"{"text":"A Minecraft Server"}"#.to_string(),
+            favicon: None,
+            previews_chat: Some(false),
+            enforces_secure_chat: Some(true),
+            server_type: Server::Java,
+            latency: None,
+        };
+
+        let mut listener = TcpListener::bind(&localhost()).unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut responder = JavaResponder::new(response.clone());
+        thread::spawn(move || listener.serve(&mut responder));
+
+        let queried = minecraft::protocol::query_java(&address, None, None).unwrap();
+
+        assert_eq!(
+            JavaResponse {
+                latency: None,
+                ..queried
+            },
+            response
+        );
+    }
+
+    #[test]
+    fn java_waits_for_status_request() {
+        let mut responder = JavaResponder::new(JavaResponse {
+            game_version: String::new(),
+            protocol_version: 0,
+            players_maximum: 0,
+            players_online: 0,
+            players: None,
+            description: "Text".to_string(),
+            favicon: None,
+            previews_chat: None,
+            enforces_secure_chat: None,
+            server_type: Server::Java,
+            latency: None,
+        });
+
+        let handshake = frame(vec![0x00, 0x01, 0x00, 0x00, 0x00, 0x01]);
+        assert_eq!(responder.respond(&handshake, &localhost()), None);
+
+        let request = [handshake, frame(vec![0x00])].concat();
+        assert_eq!(
+            responder.respond(&request[.. request.len() - 1], &localhost()),
+            None
+        );
+        assert_eq!(responder.respond(&request, &localhost()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn bedrock_round_trip() {
+        let response = BedrockResponse {
+            edition: "MCPE".to_string(),
+            name: "Dedicated Server".to_string(),
+            version_name: "1.20.62".to_string(),
+            protocol_version: "649".to_string(),
+            players_maximum: 10,
+            players_online: 3,
+            id: Some("12345678901234567890".to_string()),
+            map: Some("Bedrock level".to_string()),
+            game_mode: Some(GameMode::Creative),
+            server_type: Server::Bedrock,
+            latency: None,
+        };
+
+        let mut listener = UdpListener::bind(&localhost()).unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut responder = BedrockResponder::new(response.clone());
+        thread::spawn(move || listener.serve(&mut responder));
+
+        let queried = minecraft::protocol::query_bedrock(&address, None).unwrap();
+
+        assert_eq!(
+            BedrockResponse {
+                latency: None,
+                ..queried
+            },
+            response
+        );
+    }
+}
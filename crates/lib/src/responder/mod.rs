@@ -0,0 +1,363 @@
+//! Answering queries, for game servers that have to speak the protocols this
+//! crate queries.
+//!
+//! The protocol modules encode responses (the same types that queries return)
+//! and implement [Responder] for them, taking care of the protocols'
+//! handshakes (such as challenges) and framing (such as split packets). A
+//! [UdpListener] or a [TcpListener] then answers the requests it receives
+//! with a responder.
+//!
+//! ```no_run
+//! use gamedig::protocols::valve::Response;
+//! use gamedig::responder::{valve::ValveResponder, UdpListener};
+//!
+//! # fn response() -> Response { unimplemented!() }
+//! let mut listener = UdpListener::bind(&"0.0.0.0:27015".parse().unwrap()).unwrap();
+//! listener.set_nonblocking(true).unwrap();
+//! let mut responder = ValveResponder::new(response());
+//!
+//! loop {
+//!     // Answer the pending requests...
+//!     while listener.answer(&mut responder).unwrap() {}
+//!
+//!     // ...then run a server tick, which keeps `responder.response` up to date.
+//! }
+//! ```
+
+use crate::GDErrorKind::{PacketReceive, SocketBind, SocketConnect};
+use crate::GDResult;
+
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub mod gamespy;
+#[cfg(feature = "games")]
+pub mod minecraft;
+pub mod valve;
+
+/// The size of the biggest datagram a [UdpListener] receives.
+const DATAGRAM_SIZE: usize = 65535;
+
+/// How long a [TcpListener] waits for a client to send (more of) its request
+/// or to close the connection once answered.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often a blocking [TcpListener] checks its connections.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Something that answers the requests of a protocol.
+pub trait Responder {
+    /// Answer a request from `peer`, returns the packets (or stream chunks) to
+    /// send back.
+    ///
+    /// For TCP, `request` is everything received on the connection so far.
+    /// Returning None ignores a UDP request and waits for more data on a TCP
+    /// connection.
+    fn respond(&mut self, request: &[u8], peer: &SocketAddr) -> Option<Vec<Vec<u8>>>;
+}
+
+/// A UDP socket that answers the requests it receives.
+pub struct UdpListener {
+    socket: std::net::UdpSocket,
+    /// Where requests are received, reused for all of them.
+    buf: Vec<u8>,
+}
+
+impl UdpListener {
+    pub fn bind(address: &SocketAddr) -> GDResult<Self> {
+        let socket = std::net::UdpSocket::bind(address).map_err(|e| SocketBind.context(e))?;
+
+        Ok(Self {
+            socket,
+            buf: vec![0; DATAGRAM_SIZE],
+        })
+    }
+
+    /// The address the listener is bound to.
+    pub fn local_addr(&self) -> GDResult<SocketAddr> { self.socket.local_addr().map_err(|e| SocketBind.context(e)) }
+
+    /// Make [answer](Self::answer) return immediately when no request is
+    /// pending instead of waiting for one.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> GDResult<()> {
+        self.socket
+            .set_nonblocking(nonblocking)
+            .map_err(|e| SocketBind.context(e))
+    }
+
+    /// Wait for a request and answer it, returns false if the listener is
+    /// nonblocking and no request was pending.
+    ///
+    /// Failing to send the replies only concerns the client that asked for
+    /// them, so it is not an error.
+    pub fn answer(&mut self, responder: &mut impl Responder) -> GDResult<bool> {
+        let (size, peer) = match self.socket.recv_from(&mut self.buf) {
+            Ok(received) => received,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+            Err(e) => return Err(PacketReceive.context(e)),
+        };
+
+        for reply in responder
+            .respond(&self.buf[.. size], &peer)
+            .unwrap_or_default()
+        {
+            if self.socket.send_to(&reply, peer).is_err() {
+                break;
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Answer requests until receiving fails.
+    pub fn serve(&mut self, responder: &mut impl Responder) -> GDResult<()> {
+        loop {
+            self.answer(responder)?;
+        }
+    }
+}
+
+/// A TCP listener that answers its connections: a connection gets a single
+/// answer and is then closed.
+///
+/// Connections are read without blocking, so that a slow (or idle) client
+/// doesn't hold up the others. Those that don't send a whole request in time
+/// are dropped.
+pub struct TcpListener {
+    listener: std::net::TcpListener,
+    nonblocking: bool,
+    connections: Vec<Connection>,
+}
+
+impl TcpListener {
+    pub fn bind(address: &SocketAddr) -> GDResult<Self> {
+        let listener = std::net::TcpListener::bind(address).map_err(|e| SocketBind.context(e))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| SocketBind.context(e))?;
+
+        Ok(Self {
+            listener,
+            nonblocking: false,
+            connections: Vec::new(),
+        })
+    }
+
+    /// The address the listener is bound to.
+    pub fn local_addr(&self) -> GDResult<SocketAddr> {
+        self.listener
+            .local_addr()
+            .map_err(|e| SocketBind.context(e))
+    }
+
+    /// Make [answer](Self::answer) return immediately when no connection can
+    /// be answered instead of waiting for one.
+    pub fn set_nonblocking(&mut self, nonblocking: bool) -> GDResult<()> {
+        self.nonblocking = nonblocking;
+        Ok(())
+    }
+
+    /// Accept the pending connections and answer those that sent a whole
+    /// request, waiting for one to do so unless the listener is nonblocking.
+    /// Returns whether a connection was answered.
+    ///
+    /// A failing connection only concerns its client, so it is not an error.
+    pub fn answer(&mut self, responder: &mut impl Responder) -> GDResult<bool> {
+        loop {
+            let answered = self.poll(responder)?;
+            if answered || self.nonblocking {
+                return Ok(answered);
+            }
+
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Answer connections until accepting one fails.
+    pub fn serve(&mut self, responder: &mut impl Responder) -> GDResult<()> {
+        loop {
+            self.answer(responder)?;
+        }
+    }
+
+    /// Accept the pending connections, read what their clients sent and
+    /// answer them if they can be.
+    fn poll(&mut self, responder: &mut impl Responder) -> GDResult<bool> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, peer)) => {
+                    // Accepted streams don't always inherit the listener's mode.
+                    if stream.set_nonblocking(true).is_ok() {
+                        self.connections.push(Connection {
+                            stream,
+                            peer,
+                            request: Vec::new(),
+                            answered: false,
+                            expires: Instant::now() + READ_TIMEOUT,
+                        });
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(SocketConnect.context(e)),
+            }
+        }
+
+        let now = Instant::now();
+        let mut answered = false;
+        self.connections.retain_mut(|connection| {
+            match connection.progress(responder) {
+                Ok(Progress::Answered) => {
+                    answered = true;
+                    true
+                }
+                Ok(Progress::Pending) => connection.expires > now,
+                Ok(Progress::Closed) | Err(_) => false,
+            }
+        });
+
+        Ok(answered)
+    }
+}
+
+/// A connection of a [TcpListener], until its client closes it.
+struct Connection {
+    stream: TcpStream,
+    peer: SocketAddr,
+    /// Everything received before answering.
+    request: Vec<u8>,
+    answered: bool,
+    /// When the connection is dropped if its client didn't send a whole
+    /// request or (once answered) didn't close it.
+    expires: Instant,
+}
+
+enum Progress {
+    Pending,
+    Answered,
+    Closed,
+}
+
+impl Connection {
+    /// Read what the client sent so far and answer it if it can be.
+    fn progress(&mut self, responder: &mut impl Responder) -> io::Result<Progress> {
+        let mut buf = [0; 4096];
+        loop {
+            let size = match self.stream.read(&mut buf) {
+                Ok(0) => return Ok(Progress::Closed),
+                Ok(size) => size,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(Progress::Pending),
+                Err(e) => return Err(e),
+            };
+
+            // Closing a socket with unread data resets the connection (which
+            // can drop the replies), so answered clients are read until they
+            // close it.
+            if self.answered {
+                continue;
+            }
+
+            self.request.extend_from_slice(&buf[.. size]);
+            if let Some(replies) = responder.respond(&self.request, &self.peer) {
+                // The replies are small enough to not wait for the client long.
+                self.stream.set_nonblocking(false)?;
+                self.stream.set_write_timeout(Some(READ_TIMEOUT))?;
+                for reply in replies {
+                    self.stream.write_all(&reply)?;
+                }
+                self.stream.shutdown(Shutdown::Write)?;
+                self.stream.set_nonblocking(true)?;
+
+                self.answered = true;
+                self.expires = Instant::now() + READ_TIMEOUT;
+                return Ok(Progress::Answered);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::{Ipv4Addr, UdpSocket};
+    use std::thread;
+
+    /// Answers requests with themselves reversed, ignoring (or waiting for the
+    /// rest of) the ones that end with an `x`.
+    struct Reverse;
+
+    impl Responder for Reverse {
+        fn respond(&mut self, request: &[u8], _peer: &SocketAddr) -> Option<Vec<Vec<u8>>> {
+            if request.last() == Some(&b'x') {
+                return None;
+            }
+
+            Some(vec![request.iter().rev().copied().collect()])
+        }
+    }
+
+    fn localhost() -> SocketAddr { SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0) }
+
+    #[test]
+    fn udp_answers() {
+        let mut listener = UdpListener::bind(&localhost()).unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let client = UdpSocket::bind(localhost()).unwrap();
+        client.send_to(b"abc", address).unwrap();
+        client.send_to(b"abx", address).unwrap();
+
+        assert!(listener.answer(&mut Reverse).unwrap());
+        assert!(listener.answer(&mut Reverse).unwrap());
+
+        let mut buf = [0; 16];
+        let size = client.recv(&mut buf).unwrap();
+        assert_eq!(&buf[.. size], b"cba");
+
+        listener.set_nonblocking(true).unwrap();
+        assert!(!listener.answer(&mut Reverse).unwrap());
+    }
+
+    #[test]
+    fn tcp_waits_for_whole_request() {
+        let mut listener = TcpListener::bind(&localhost()).unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(b"ax").unwrap();
+            thread::sleep(Duration::from_millis(50));
+            stream.write_all(b"b").unwrap();
+
+            let mut response = Vec::new();
+            stream.read_to_end(&mut response).unwrap();
+            response
+        });
+
+        assert!(listener.answer(&mut Reverse).unwrap());
+        assert_eq!(client.join().unwrap(), b"bxa");
+    }
+
+    #[test]
+    fn tcp_answers_while_waiting_for_another_client() {
+        let mut listener = TcpListener::bind(&localhost()).unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let mut idle = TcpStream::connect(address).unwrap();
+        idle.write_all(b"ax").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        while listener.connections.is_empty() {
+            assert!(!listener.answer(&mut Reverse).unwrap());
+        }
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(b"ab").unwrap();
+        listener.set_nonblocking(false).unwrap();
+        assert!(listener.answer(&mut Reverse).unwrap());
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        assert_eq!(response, b"ba");
+    }
+}
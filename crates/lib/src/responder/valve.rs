@@ -0,0 +1,402 @@
+//! Valve's [server queries](https://developer.valvesoftware.com/wiki/Server_queries),
+//! answered in the Source engine's format.
+
+use crate::protocols::valve::{ExtraData, Packet, Request, Response, ServerInfo, ServerPlayer};
+use crate::responder::Responder;
+use crate::GDErrorKind::InvalidInput;
+use crate::GDResult;
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::net::SocketAddr;
+
+/// The size above which responses are split by default.
+pub const DEFAULT_PACKET_SIZE: usize = 1400;
+
+const SPLIT_HEADER: [u8; 4] = [0xFE, 0xFF, 0xFF, 0xFF];
+/// The size of a split packet's header: the split header, the id, the total,
+/// the number and the size.
+const SPLIT_HEADER_SIZE: usize = 12;
+
+const INFO_PAYLOAD: &[u8] = b"Source Engine Query\0";
+
+const CHALLENGE: u8 = 0x41;
+const INFO_RESPONSE: u8 = 0x49;
+const PLAYERS_RESPONSE: u8 = 0x44;
+const RULES_RESPONSE: u8 = 0x45;
+
+/// The bits of a 64-bit GameID that hold the app id.
+const GAME_ID_APPID_MASK: u64 = (1 << 24) - 1;
+
+fn push_string(buf: &mut Vec<u8>, value: &str) {
+    buf.extend_from_slice(value.as_bytes());
+    buf.push(0);
+}
+
+/// Encode an info response.
+///
+/// App ids that don't fit in the response's 16 bits are sent in the GameID of
+/// the extra data (as servers do), those that don't fit in a GameID either are
+/// rejected.
+pub fn encode_info(info: &ServerInfo) -> GDResult<Vec<u8>> {
+    let mut extra_data = info.extra_data.clone();
+    if info.appid > u32::from(u16::MAX) {
+        if u64::from(info.appid) > GAME_ID_APPID_MASK {
+            return Err(InvalidInput.context(format!("AppId {} doesn't fit in a GameID", info.appid)));
+        }
+
+        let extra_data = extra_data.get_or_insert(ExtraData {
+            port: None,
+            steam_id: None,
+            tv_port: None,
+            tv_name: None,
+            keywords: None,
+            game_id: None,
+        });
+        let game_id = extra_data.game_id.unwrap_or_default() & !GAME_ID_APPID_MASK;
+        extra_data.game_id = Some(game_id | u64::from(info.appid));
+    }
+
+    let mut payload = vec![info.protocol_version];
+    push_string(&mut payload, &info.name);
+    push_string(&mut payload, &info.map);
+    push_string(&mut payload, &info.folder);
+    push_string(&mut payload, &info.game_mode);
+    // The lowest bits of bigger ids, which clients replace by the GameID's.
+    payload.extend_from_slice(&(info.appid as u16).to_le_bytes());
+    payload.extend_from_slice(&[
+        info.players_online,
+        info.players_maximum,
+        info.players_bots,
+        info.server_type.to_gldsrc(),
+        info.environment_type.to_gldsrc(),
+        u8::from(info.has_password),
+        u8::from(info.vac_secured),
+    ]);
+
+    if let Some(the_ship) = &info.the_ship {
+        payload.extend_from_slice(&[the_ship.mode, the_ship.witnesses, the_ship.duration]);
+    }
+
+    push_string(&mut payload, &info.game_version);
+
+    if let Some(extra_data) = &extra_data {
+        let has_tv = extra_data.tv_port.is_some() || extra_data.tv_name.is_some();
+
+        let mut flags = 0;
+        let mut data = Vec::new();
+        if let Some(port) = extra_data.port {
+            flags |= 0x80;
+            data.extend_from_slice(&port.to_le_bytes());
+        }
+        if let Some(steam_id) = extra_data.steam_id {
+            flags |= 0x10;
+            data.extend_from_slice(&steam_id.to_le_bytes());
+        }
+        if has_tv {
+            flags |= 0x40;
+            data.extend_from_slice(&extra_data.tv_port.unwrap_or_default().to_le_bytes());
+            push_string(&mut data, extra_data.tv_name.as_deref().unwrap_or_default());
+        }
+        if let Some(keywords) = &extra_data.keywords {
+            flags |= 0x20;
+            push_string(&mut data, keywords);
+        }
+        if let Some(game_id) = extra_data.game_id {
+            flags |= 0x01;
+            data.extend_from_slice(&game_id.to_le_bytes());
+        }
+
+        payload.push(flags);
+        payload.extend(data);
+    }
+
+    Ok(Packet::new(INFO_RESPONSE, payload).to_bytes())
+}
+
+/// Encode a players response, The Ship's deaths and money are sent when
+/// present.
+pub fn encode_players(players: &[ServerPlayer]) -> Vec<u8> {
+    let players = &players[.. players.len().min(u8::MAX as usize)];
+
+    let mut payload = vec![players.len() as u8];
+    for (index, player) in players.iter().enumerate() {
+        payload.push(index as u8);
+        push_string(&mut payload, &player.name);
+        payload.extend_from_slice(&player.score.to_le_bytes());
+        payload.extend_from_slice(&player.duration.to_le_bytes());
+
+        if let Some(deaths) = player.deaths {
+            payload.extend_from_slice(&deaths.to_le_bytes());
+        }
+        if let Some(money) = player.money {
+            payload.extend_from_slice(&money.to_le_bytes());
+        }
+    }
+
+    Packet::new(PLAYERS_RESPONSE, payload).to_bytes()
+}
+
+/// Encode a rules response.
+pub fn encode_rules(rules: &HashMap<String, String>) -> Vec<u8> {
+    let count = rules.len().min(u16::MAX as usize);
+
+    let mut payload = (count as u16).to_le_bytes().to_vec();
+    for (name, value) in rules.iter().take(count) {
+        push_string(&mut payload, name);
+        push_string(&mut payload, value);
+    }
+
+    Packet::new(RULES_RESPONSE, payload).to_bytes()
+}
+
+/// Answers the info, players and rules requests with a [Response].
+#[derive(Debug, Clone)]
+pub struct ValveResponder {
+    /// What is answered, missing players and rules are answered as empty.
+    pub response: Response,
+    /// The key of the clients' challenges, None if they are not required.
+    challenges: Option<RandomState>,
+    packet_size: usize,
+    split_id: u32,
+}
+
+impl ValveResponder {
+    /// A responder that requires clients to send back a challenge (given in a
+    /// `0x41` response) before answering them.
+    pub fn new(response: Response) -> Self {
+        Self {
+            response,
+            challenges: Some(RandomState::new()),
+            packet_size: DEFAULT_PACKET_SIZE,
+            split_id: 0,
+        }
+    }
+
+    /// Answer clients without asking them for a challenge first, as old
+    /// servers do.
+    ///
+    /// The challenge prevents address spoofing, without it the responder
+    /// answers small requests with big responses to any address, which can be
+    /// abused to flood it.
+    pub fn without_challenge(mut self) -> Self {
+        self.challenges = None;
+        self
+    }
+
+    /// Split the responses bigger than `size` bytes, [DEFAULT_PACKET_SIZE] by
+    /// default.
+    pub fn with_packet_size(mut self, size: usize) -> Self {
+        self.packet_size = size.max(SPLIT_HEADER_SIZE + 1);
+        self
+    }
+
+    /// The challenge a client has to send, derived from its address so that
+    /// it doesn't have to be remembered.
+    fn challenge(&self, peer: &SocketAddr) -> Option<[u8; 4]> {
+        let challenge = self.challenges.as_ref()?.hash_one(peer.ip()) as u32;
+
+        // All bits set is what clients send when they don't have a challenge.
+        Some(
+            match challenge {
+                u32::MAX => 0,
+                challenge => challenge,
+            }
+            .to_le_bytes(),
+        )
+    }
+
+    /// Split a response into packets that are at most the packet size.
+    fn split(&mut self, response: Vec<u8>) -> Vec<Vec<u8>> {
+        if response.len() <= self.packet_size {
+            return vec![response];
+        }
+
+        // The packets' total and number are single bytes.
+        let chunk_size = (self.packet_size - SPLIT_HEADER_SIZE).max(response.len().div_ceil(u8::MAX as usize));
+        let chunks: Vec<&[u8]> = response.chunks(chunk_size).collect();
+
+        // The highest bit of the id marks compressed responses.
+        self.split_id = (self.split_id + 1) & 0x7FFF_FFFF;
+
+        chunks
+            .iter()
+            .enumerate()
+            .map(|(number, chunk)| {
+                let mut packet = SPLIT_HEADER.to_vec();
+                packet.extend_from_slice(&self.split_id.to_le_bytes());
+                packet.extend_from_slice(&[chunks.len() as u8, number as u8]);
+                packet.extend_from_slice(&(self.packet_size.min(u16::MAX as usize) as u16).to_le_bytes());
+                packet.extend_from_slice(chunk);
+                packet
+            })
+            .collect()
+    }
+}
+
+impl Responder for ValveResponder {
+    fn respond(&mut self, request: &[u8], peer: &SocketAddr) -> Option<Vec<Vec<u8>>> {
+        let (kind, payload) = match request {
+            [0xFF, 0xFF, 0xFF, 0xFF, kind, payload @ ..] => (*kind, payload),
+            _ => return None,
+        };
+
+        let challenge = match kind {
+            k if k == Request::Info as u8 => payload.strip_prefix(INFO_PAYLOAD)?,
+            k if k == Request::Players as u8 || k == Request::Rules as u8 => payload,
+            _ => return None,
+        };
+
+        if let Some(expected) = self.challenge(peer) {
+            if challenge != expected {
+                return Some(vec![Packet::new(CHALLENGE, expected.to_vec()).to_bytes()]);
+            }
+        }
+
+        let response = match kind {
+            k if k == Request::Info as u8 => encode_info(&self.response.info).ok()?,
+            k if k == Request::Players as u8 => encode_players(self.response.players.as_deref().unwrap_or_default()),
+            _ => encode_rules(self.response.rules.as_ref().unwrap_or(&HashMap::new())),
+        };
+
+        Some(self.split(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::protocols::valve::{self, Engine, Environment, ExtraData, GatheringSettings, Server};
+    use crate::responder::UdpListener;
+
+    use std::net::Ipv4Addr;
+    use std::thread;
+
+    fn response() -> Response {
+        Response {
+            info: ServerInfo {
+                protocol_version: 17,
+                name: "Responder".to_string(),
+                map: "ctf_2fort".to_string(),
+                folder: "tf".to_string(),
+                game_mode: "Team Fortress".to_string(),
+                appid: 440,
+                players_online: 2,
+                players_maximum: 24,
+                players_bots: 0,
+                server_type: Server::Dedicated,
+                environment_type: Environment::Linux,
+                has_password: false,
+                vac_secured: true,
+                the_ship: None,
+                game_version: "8835751".to_string(),
+                extra_data: Some(ExtraData {
+                    port: Some(27015),
+                    steam_id: None,
+                    tv_port: None,
+                    tv_name: None,
+                    keywords: Some("payload,cp".to_string()),
+                    game_id: Some(440),
+                }),
+                is_mod: false,
+                mod_data: None,
+            },
+            players: Some(
+                ["Alice", "Bob"]
+                    .into_iter()
+                    .map(|name| {
+                        ServerPlayer {
+                            name: name.to_string(),
+                            score: 3,
+                            duration: 12.5,
+                            deaths: None,
+                            money: None,
+                        }
+                    })
+                    .collect(),
+            ),
+            rules: Some(HashMap::from([(
+                "mp_timelimit".to_string(),
+                "30".to_string(),
+            )])),
+            latency: None,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn round_trip(responder: ValveResponder) {
+        let mut listener = UdpListener::bind(&SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut served = responder.clone();
+        thread::spawn(move || listener.serve(&mut served));
+
+        let queried = valve::query(
+            &address,
+            Engine::new(440),
+            Some(GatheringSettings::default()),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Response {
+                latency: None,
+                ..queried
+            },
+            responder.response
+        );
+    }
+
+    #[test]
+    fn answers() { round_trip(ValveResponder::new(response())); }
+
+    #[test]
+    fn answers_without_challenge_and_split() {
+        round_trip(
+            ValveResponder::new(response())
+                .without_challenge()
+                .with_packet_size(32),
+        );
+    }
+
+    #[test]
+    fn answers_big_app_ids() {
+        let mut response = response();
+        response.info.appid = 252_490;
+        response.info.extra_data = None;
+
+        let info = encode_info(&response.info).unwrap();
+        let parsed = valve::parse_info(&Engine::new(252_490), &info).unwrap();
+        assert_eq!(parsed.appid, 252_490);
+        assert_eq!(parsed.extra_data.unwrap().game_id, Some(252_490));
+
+        response.info.appid = 1 << 24;
+        assert_eq!(encode_info(&response.info).unwrap_err().kind, InvalidInput);
+    }
+
+    #[test]
+    fn ignores_unknown_requests() {
+        let mut responder = ValveResponder::new(response()).without_challenge();
+        let peer = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 1);
+
+        assert_eq!(responder.respond(b"\xFF\xFF\xFF\xFFx", &peer), None);
+        assert_eq!(responder.respond(b"\xFF\xFF\xFF\xFFTGarbage", &peer), None);
+    }
+
+    #[test]
+    fn challenges_until_answered() {
+        let mut responder = ValveResponder::new(response());
+        let peer = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 1);
+
+        let replies = responder
+            .respond(b"\xFF\xFF\xFF\xFFU\xFF\xFF\xFF\xFF", &peer)
+            .unwrap();
+        assert_eq!(replies[0][4], CHALLENGE);
+
+        let request = [b"\xFF\xFF\xFF\xFFU".as_slice(), &replies[0][5 ..]].concat();
+        let replies = responder.respond(&request, &peer).unwrap();
+        assert_eq!(replies[0][4], PLAYERS_RESPONSE);
+    }
+}
@@ -0,0 +1,355 @@
+//! Retrying failed requests as the [RetryPolicy] of the query asks.
+//!
+//! The read timeout of each try is scaled by the policy's growth, sockets
+//! apply it through [read_timeout].
+
+use crate::protocols::types::RetryPolicy;
+use crate::{deadline, metrics, report, trace, GDResult};
+
+use std::cell::Cell;
+use std::time::Duration;
+
+#[cfg(feature = "async")]
+use std::future::Future;
+
+thread_local! {
+    static READ_TIMEOUT_FACTOR: Cell<f64> = const { Cell::new(1.0) };
+}
+
+#[cfg(feature = "async")]
+tokio::task_local! {
+    static TASK_READ_TIMEOUT_FACTOR: f64;
+}
+
+/// Restores the previous read timeout factor of the thread when dropped.
+struct FactorGuard {
+    previous: f64,
+}
+
+impl Drop for FactorGuard {
+    fn drop(&mut self) { READ_TIMEOUT_FACTOR.set(self.previous); }
+}
+
+/// Run a (sync) try with its read timeouts scaled by `factor`.
+fn scope<T>(factor: f64, attempt: impl FnOnce() -> T) -> T {
+    let _guard = FactorGuard {
+        previous: READ_TIMEOUT_FACTOR.replace(factor),
+    };
+
+    attempt()
+}
+
+/// Async counterpart of [scope].
+#[cfg(feature = "async")]
+pub(crate) async fn scope_async<T>(factor: f64, attempt: impl Future<Output = T>) -> T {
+    TASK_READ_TIMEOUT_FACTOR.scope(factor, attempt).await
+}
+
+/// The read timeout to use for the current try given the configured one.
+pub(crate) fn read_timeout(timeout: Option<Duration>) -> Option<Duration> {
+    #[cfg(feature = "async")]
+    let factor = TASK_READ_TIMEOUT_FACTOR
+        .try_with(|factor| *factor)
+        .unwrap_or_else(|_| READ_TIMEOUT_FACTOR.get());
+    #[cfg(not(feature = "async"))]
+    let factor = READ_TIMEOUT_FACTOR.get();
+
+    timeout.map(|timeout| Duration::try_from_secs_f64(timeout.as_secs_f64() * factor).unwrap_or(timeout))
+}
+
+/// The delay to wait before retry number `retry`, `None` if the query's
+/// deadline would pass before it.
+pub(crate) fn delay(policy: &RetryPolicy, retry: u32) -> Option<Duration> {
+    let delay = policy.delay(retry);
+    deadline::leaves_time_for(delay).then_some(delay)
+}
+
+/// Run `fetch` up to `retry_count+1` times while it fails with errors the
+/// policy retries, returning the first success or the last error (with its
+/// attempt).
+pub(crate) fn retry<T>(retry_count: usize, policy: RetryPolicy, mut fetch: impl FnMut() -> GDResult<T>) -> GDResult<T> {
+    let mut retry = 0;
+    loop {
+        let error = match scope(policy.read_timeout_factor(retry), &mut fetch) {
+            Ok(r) => return Ok(r),
+            Err(e) if (retry as usize) < retry_count && policy.retries_on(&e.kind) => e,
+            Err(e) => return Err(e.with_attempt(retry)),
+        };
+
+        let Some(delay) = delay(&policy, retry) else {
+            trace::event!(DEBUG, attempt = retry, kind = ?error.kind, "not retrying, the query's deadline would pass");
+            return Err(error.with_attempt(retry));
+        };
+        trace::event!(DEBUG, attempt = retry + 1, ?delay, kind = ?error.kind, "retrying");
+
+        if !delay.is_zero() {
+            std::thread::sleep(delay);
+        }
+
+        report::record(|report| report.retries += 1);
+        metrics::retried(&error.kind);
+        retry += 1;
+    }
+}
+
+/// Async counterpart of [retry], `fetch` should be an expression that awaits
+/// to a [crate::GDResult], it will be evaluated for every try.
+#[cfg(feature = "async")]
+macro_rules! retry_async {
+    ($retry_count: expr, $policy: expr, $fetch: expr) => {{
+        let retry_count: usize = $retry_count;
+        let policy: crate::protocols::types::RetryPolicy = $policy;
+        let mut retry: u32 = 0;
+        loop {
+            let error = match crate::retry::scope_async(policy.read_timeout_factor(retry), async { $fetch }).await {
+                Ok(r) => break Ok(r),
+                Err(e) if (retry as usize) < retry_count && policy.retries_on(&e.kind) => e,
+                Err(e) => break Err(e.with_attempt(retry)),
+            };
+
+            let Some(delay) = crate::retry::delay(&policy, retry) else {
+                crate::trace::event!(DEBUG, attempt = retry, kind = ?error.kind, "not retrying, the query's deadline would pass");
+                break Err(error.with_attempt(retry));
+            };
+            crate::trace::event!(DEBUG, attempt = retry + 1, ?delay, kind = ?error.kind, "retrying");
+
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+
+            crate::report::record(|report| report.retries += 1);
+            crate::metrics::retried(&error.kind);
+            retry += 1;
+        }
+    }};
+}
+
+#[cfg(feature = "async")]
+pub(crate) use retry_async;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "games")]
+    use crate::protocols::types::TimeoutSettings;
+    use crate::GDErrorKind::{PacketBad, PacketReceive, PacketSend};
+
+    use std::time::Instant;
+
+    #[test]
+    fn retry_success_on_first() {
+        let r = retry(0, RetryPolicy::new(), || Ok(()));
+        assert!(r.is_ok());
+    }
+
+    #[test]
+    fn retry_no_success() {
+        let r: GDResult<()> = retry(100, RetryPolicy::new(), || Err(PacketSend.context("test")));
+        assert!(r.is_err());
+        assert_eq!(r.unwrap_err().kind, PacketSend);
+    }
+
+    #[test]
+    fn retry_success_on_third() {
+        let mut i = 0u8;
+        let r = retry(2, RetryPolicy::new(), || {
+            i += 1;
+            if i < 3 {
+                Err(PacketReceive.context("test"))
+            } else {
+                Ok(())
+            }
+        });
+        assert!(r.is_ok());
+    }
+
+    #[test]
+    fn retry_success_on_third_but_less_retries() {
+        let mut i = 0u8;
+        let r = retry(1, RetryPolicy::new(), || {
+            i += 1;
+            if i < 3 {
+                Err(PacketReceive.context("test"))
+            } else {
+                Ok(())
+            }
+        });
+        assert!(r.is_err());
+        assert_eq!(r.unwrap_err().kind, PacketReceive);
+    }
+
+    #[test]
+    fn retry_with_non_timeout_error() {
+        let mut i = 0u8;
+        let r = retry(50, RetryPolicy::new(), || {
+            i += 1;
+            match i {
+                1 => Err(PacketReceive.context("test")),
+                2 => Err(PacketBad.context("test")),
+                _ => Ok(()),
+            }
+        });
+        assert!(r.is_err());
+        assert_eq!(r.unwrap_err().kind, PacketBad);
+    }
+
+    #[test]
+    fn retry_on_chosen_kinds() {
+        let policy = RetryPolicy::new()
+            .retry_on(PacketBad)
+            .dont_retry_on(PacketReceive);
+
+        let mut i = 0u8;
+        let r: GDResult<()> = retry(50, policy, || {
+            i += 1;
+            match i {
+                1 => Err(PacketBad.context("test")),
+                _ => Err(PacketReceive.context("test")),
+            }
+        });
+        assert_eq!(r.unwrap_err().kind, PacketReceive);
+        assert_eq!(i, 2);
+    }
+
+    #[test]
+    fn retry_sets_attempt() {
+        let r: GDResult<()> = retry(2, RetryPolicy::new(), || Err(PacketReceive.context("test")));
+        assert_eq!(r.unwrap_err().attempt(), Some(2));
+
+        let r: GDResult<()> = retry(2, RetryPolicy::new(), || Err(PacketBad.context("test")));
+        assert_eq!(r.unwrap_err().attempt(), Some(0));
+    }
+
+    #[test]
+    fn retry_backs_off() {
+        let policy = RetryPolicy::new().with_backoff(Duration::from_millis(10), Some(Duration::from_millis(15)));
+        assert_eq!(policy.delay(0), Duration::from_millis(10));
+        assert_eq!(policy.delay(1), Duration::from_millis(15));
+        assert_eq!(policy.delay(100), Duration::from_millis(15));
+
+        let start = Instant::now();
+        let r: GDResult<()> = retry(2, policy, || Err(PacketReceive.context("test")));
+        assert_eq!(r.unwrap_err().kind, PacketReceive);
+        assert!(start.elapsed() >= Duration::from_millis(25));
+
+        let jittered = policy.with_jitter(true);
+        assert!((0 .. 10).all(|retry| jittered.delay(retry) <= Duration::from_millis(15)));
+    }
+
+    #[test]
+    fn retry_grows_read_timeout() {
+        let policy = RetryPolicy::new().with_read_timeout_growth(50);
+        let timeout = Some(Duration::from_secs(2));
+
+        let mut timeouts = Vec::new();
+        let r: GDResult<()> = retry(2, policy, || {
+            timeouts.push(read_timeout(timeout).unwrap());
+            Err(PacketReceive.context("test"))
+        });
+        assert!(r.is_err());
+        assert_eq!(
+            timeouts,
+            vec![
+                Duration::from_secs(2),
+                Duration::from_secs(3),
+                Duration::from_millis(4500)
+            ]
+        );
+
+        assert_eq!(read_timeout(timeout), timeout);
+    }
+
+    #[test]
+    fn read_timeout_restored_after_panic() {
+        let timeout = Some(Duration::from_secs(2));
+        let panicked = std::panic::catch_unwind(|| scope(2.0, || panic!("try panicked")));
+
+        assert!(panicked.is_err());
+        assert_eq!(read_timeout(timeout), timeout);
+    }
+
+    #[cfg(feature = "games")]
+    #[test]
+    fn retry_doesnt_wait_past_deadline() {
+        let timeout_settings = TimeoutSettings::default()
+            .with_total_timeout(Duration::from_millis(50))
+            .unwrap();
+        let policy = RetryPolicy::new().with_backoff(Duration::from_secs(10), None);
+
+        let start = Instant::now();
+        let mut tries = 0;
+        let r: GDResult<()> = deadline::scope(&Some(timeout_settings), || {
+            retry(5, policy, || {
+                tries += 1;
+                Err(PacketReceive.context("test"))
+            })
+        });
+        assert_eq!(r.unwrap_err().kind, PacketReceive);
+        assert_eq!(tries, 1);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn retry_async_success_on_third() {
+        let mut i = 0u8;
+        let r: GDResult<u8> = retry_async!(
+            2,
+            RetryPolicy::new(),
+            async {
+                i += 1;
+                if i < 3 {
+                    Err(PacketReceive.context("test"))
+                } else {
+                    Ok(i)
+                }
+            }
+            .await
+        );
+        assert_eq!(r.unwrap(), 3);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn retry_async_with_non_timeout_error() {
+        let mut i = 0u8;
+        let r: GDResult<()> = retry_async!(
+            50,
+            RetryPolicy::new(),
+            async {
+                i += 1;
+                match i {
+                    1 => Err(PacketReceive.context("test")),
+                    _ => Err(PacketBad.context("test")),
+                }
+            }
+            .await
+        );
+        assert_eq!(r.unwrap_err().kind, PacketBad);
+        assert_eq!(i, 2);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn retry_async_grows_read_timeout() {
+        let policy = RetryPolicy::new().with_read_timeout_growth(100);
+        let timeout = Some(Duration::from_secs(1));
+
+        let mut timeouts = Vec::new();
+        let r: GDResult<()> = retry_async!(
+            1,
+            policy,
+            async {
+                tokio::task::yield_now().await;
+                timeouts.push(read_timeout(timeout).unwrap());
+                Err(PacketReceive.context("test"))
+            }
+            .await
+        );
+        assert!(r.is_err());
+        assert_eq!(
+            timeouts,
+            vec![Duration::from_secs(1), Duration::from_secs(2)]
+        );
+    }
+}
@@ -1,13 +1,21 @@
+#[cfg(feature = "async")]
+use crate::http::AsyncHttpClient;
 use crate::http::HttpClient;
 use crate::minetest_master_server::types::Response;
 use crate::{GDResult, TimeoutSettings};
 
+const MASTER_SERVER_URL: &str = "https://servers.minetest.net";
+
 pub fn query(timeout_settings: TimeoutSettings) -> GDResult<Response> {
-    let mut client = HttpClient::from_url(
-        "https://servers.minetest.net",
-        &Some(timeout_settings),
-        None,
-    )?;
+    let mut client = HttpClient::from_url(MASTER_SERVER_URL, &Some(timeout_settings), None)?;
 
     client.get_json("/list", None)
 }
+
+/// Async counterpart of [query].
+#[cfg(feature = "async")]
+pub async fn query_async(timeout_settings: TimeoutSettings) -> GDResult<Response> {
+    let mut client = AsyncHttpClient::from_url(MASTER_SERVER_URL, &Some(timeout_settings), None).await?;
+
+    client.get_json("/list", None).await
+}
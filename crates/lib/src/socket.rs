@@ -1,16 +1,54 @@
 use crate::{
-    protocols::types::TimeoutSettings,
+    deadline,
+    metrics,
+    protocols::types::{NetworkSettings, TimeoutSettings},
+    report,
+    retry,
+    trace,
+    transport::{current_factory, Transport, TransportFactory},
     GDErrorKind::{PacketReceive, PacketSend, SocketBind, SocketConnect},
     GDResult,
+    QueryStage,
 };
 
 use std::{
+    cell::RefCell,
     io::{Read, Write},
+    marker::PhantomData,
     net::{self, SocketAddr},
+    time::{Duration, Instant},
 };
 
+#[cfg(feature = "async")]
+use crate::GDErrorKind;
+#[cfg(feature = "async")]
+use std::future::Future;
+#[cfg(all(feature = "async", feature = "games"))]
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
 const DEFAULT_PACKET_SIZE: usize = 1024;
 
+/// Times the exchanges made over a socket.
+#[derive(Debug, Default, Clone, Copy)]
+struct ExchangeTimer {
+    sent_at: Option<Instant>,
+    latency: Option<Duration>,
+}
+
+impl ExchangeTimer {
+    fn sent(&mut self) {
+        self.sent_at = Some(Instant::now());
+        self.latency = None;
+    }
+
+    /// Only the first receive after a send is timed.
+    fn received(&mut self) {
+        if let Some(sent_at) = self.sent_at.take() {
+            self.latency = Some(sent_at.elapsed());
+        }
+    }
+}
+
 /// A trait defining the basic functionalities of a network socket.
 pub trait Socket {
     /// Create a new socket and connect to the remote address.
@@ -51,12 +89,29 @@ pub trait Socket {
     /// A result containing received data or an error.
     fn receive(&mut self, size: Option<usize>) -> GDResult<Vec<u8>>;
 
+    /// Receive the data available, without waiting for the server to stop
+    /// sending: a datagram for UDP, a single read for TCP.
+    ///
+    /// # Arguments
+    /// * `size` - Optional size of data to receive.
+    ///
+    /// # Returns
+    /// A result containing received data or an error.
+    fn receive_available(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> { self.receive(size) }
+
     /// Get the remote port of the socket.
     ///
     /// # Returns
     /// The port number.
     fn port(&self) -> u16;
 
+    /// Get the round-trip time of the last exchange made over the socket: the
+    /// time between the last send and the first data received after it.
+    ///
+    /// # Returns
+    /// The latency, `None` if nothing was received since the last send.
+    fn latency(&self) -> Option<Duration>;
+
     /// Get the local SocketAddr.
     ///
     /// # Returns
@@ -71,21 +126,65 @@ pub struct TcpSocketImpl {
     socket: net::TcpStream,
     /// The address of the remote host.
     address: SocketAddr,
+    /// The read timeout of the settings.
+    read_timeout: Option<Duration>,
+    /// The write timeout of the settings.
+    write_timeout: Option<Duration>,
+    /// Timer of the exchanges.
+    timer: ExchangeTimer,
+}
+
+impl TcpSocketImpl {
+    /// Apply the current try's read timeout and clamp the socket's timeouts
+    /// to the time left until the query's deadline.
+    fn apply_deadline(&self) -> GDResult<()> {
+        // Unwrapping because clamped timeouts are never 0
+        self.socket
+            .set_read_timeout(deadline::clamp(retry::read_timeout(self.read_timeout))?)
+            .unwrap();
+        self.socket
+            .set_write_timeout(deadline::clamp(self.write_timeout)?)
+            .unwrap();
+
+        Ok(())
+    }
+
+    /// Read what is available, timing the exchange if it is data.
+    fn read_available(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> {
+        let mut buf = vec![0; size.unwrap_or(DEFAULT_PACKET_SIZE).max(1)];
+        self.apply_deadline()?;
+
+        let read = self
+            .socket
+            .read(&mut buf)
+            .map_err(|e| deadline::timed_out(PacketReceive).context(e))?;
+        if read > 0 {
+            self.timer.received();
+        }
+
+        buf.truncate(read);
+        Ok(buf)
+    }
 }
 
 impl Socket for TcpSocketImpl {
     fn new(address: &SocketAddr, timeout_settings: &Option<TimeoutSettings>) -> GDResult<Self> {
-        let socket = TimeoutSettings::get_connect_or_default(timeout_settings).map_or_else(
+        let socket = deadline::clamp(TimeoutSettings::get_connect_or_default(timeout_settings))?.map_or_else(
             || net::TcpStream::connect(address),
             |timeout| net::TcpStream::connect_timeout(address, timeout),
         );
+        let (read_timeout, write_timeout) = TimeoutSettings::get_read_and_write_or_defaults(timeout_settings);
 
         let socket = Self {
-            socket: socket.map_err(|e| SocketConnect.context(e))?,
+            socket: socket.map_err(|e| deadline::timed_out(SocketConnect).context(e))?,
             address: *address,
+            read_timeout,
+            write_timeout,
+            timer: ExchangeTimer::default(),
         };
 
         socket.apply_timeout(timeout_settings)?;
+        trace::event!(DEBUG, %address, "TCP connected");
 
         Ok(socket)
     }
@@ -99,40 +198,180 @@ impl Socket for TcpSocketImpl {
     }
 
     fn send(&mut self, data: &[u8]) -> GDResult<()> {
-        self.socket.write(data).map_err(|e| PacketSend.context(e))?;
+        self.apply_deadline()?;
+        let written = self
+            .socket
+            .write(data)
+            .map_err(|e| deadline::timed_out(PacketSend).context(e))?;
+        self.timer.sent();
+        report::record_sent(written);
+        metrics::sent(written);
+        trace::event!(TRACE, bytes = written, "TCP sent");
         Ok(())
     }
 
     fn receive(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> {
-        let mut buf = Vec::with_capacity(size.unwrap_or(DEFAULT_PACKET_SIZE));
-        self.socket
-            .read_to_end(&mut buf)
-            .map_err(|e| PacketReceive.context(e))?;
+        // Wait for the first bytes to time the exchange, then read the rest.
+        let mut buf = self.read_available(size)?;
+
+        if !buf.is_empty() {
+            self.apply_deadline()?;
+            self.socket
+                .read_to_end(&mut buf)
+                .map_err(|e| deadline::timed_out(PacketReceive).context(e))?;
+        }
 
+        report::record_received(buf.len());
+
+        metrics::received(buf.len());
+        trace::event!(TRACE, bytes = buf.len(), "TCP received");
+        Ok(buf)
+    }
+
+    fn receive_available(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> {
+        let buf = self.read_available(size)?;
+
+        report::record_received(buf.len());
+        metrics::received(buf.len());
+        trace::event!(TRACE, bytes = buf.len(), "TCP received");
         Ok(buf)
     }
 
     fn port(&self) -> u16 { self.address.port() }
 
+    fn latency(&self) -> Option<Duration> { self.timer.latency }
+
     #[cfg(feature = "packet_capture")]
     fn local_addr(&self) -> std::io::Result<SocketAddr> { self.socket.local_addr() }
 }
 
+/// An idle UDP socket along with its receive buffer.
+type PooledUdpSocket = (net::UdpSocket, Vec<u8>);
+
+thread_local! {
+    /// Idle UDP sockets kept open for reuse by the next queries made on this
+    /// thread, `None` when reuse is disabled.
+    static UDP_SOCKET_POOL: RefCell<Option<Vec<PooledUdpSocket>>> = const { RefCell::new(None) };
+}
+
+/// Guard enabling UDP socket reuse on the current thread for as long as it is
+/// alive: sockets are returned to a pool when dropped instead of being closed,
+/// so sequential queries don't need a fresh socket each.
+///
+/// The pooled sockets are closed when the guard is dropped.
+#[cfg(feature = "games")]
+pub(crate) struct UdpSocketReuse {
+    // The pool is thread local, so must be the guard.
+    _not_send: PhantomData<*const ()>,
+}
+
+#[cfg(feature = "games")]
+impl UdpSocketReuse {
+    pub(crate) fn enable() -> Self {
+        UDP_SOCKET_POOL.with_borrow_mut(|pool| {
+            pool.get_or_insert_with(Vec::new);
+        });
+
+        Self {
+            _not_send: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "games")]
+impl Drop for UdpSocketReuse {
+    fn drop(&mut self) { UDP_SOCKET_POOL.with_borrow_mut(Option::take); }
+}
+
 /// Implementation of a UDP socket.
 pub struct UdpSocketImpl {
     /// The underlying UDP socket.
     socket: net::UdpSocket,
     /// The address of the remote host.
     address: SocketAddr,
+    /// Buffer received datagrams are read into.
+    buffer: Vec<u8>,
+    /// Whether the socket goes back to the thread's pool when dropped.
+    pooled: bool,
+    /// The read timeout of the settings.
+    read_timeout: Option<Duration>,
+    /// The write timeout of the settings.
+    write_timeout: Option<Duration>,
+    /// Timer of the exchanges.
+    timer: ExchangeTimer,
+}
+
+impl UdpSocketImpl {
+    /// Take an idle socket from the thread's pool that is bound as the network
+    /// settings ask to reach `address`.
+    fn take_pooled(address: &SocketAddr, network: &NetworkSettings) -> Option<PooledUdpSocket> {
+        UDP_SOCKET_POOL.with_borrow_mut(|pool| {
+            let pool = pool.as_mut()?;
+            let index = pool.iter().position(|(socket, _)| {
+                socket
+                    .local_addr()
+                    .is_ok_and(|local| network.accepts(&local, address))
+            })?;
+
+            Some(pool.swap_remove(index))
+        })
+    }
+
+    /// Bind a new socket to reach `address`, on the first available local
+    /// address of the network settings.
+    fn bind(address: &SocketAddr, network: &NetworkSettings) -> GDResult<net::UdpSocket> {
+        let mut last_error = None;
+        for local in network.bind_addresses(address)? {
+            match net::UdpSocket::bind(local) {
+                Ok(socket) => return Ok(socket),
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        Err(last_error.map_or_else(|| SocketBind.into(), |e| SocketBind.context(e)))
+    }
+
+    /// Apply the current try's read timeout and clamp the socket's timeouts
+    /// to the time left until the query's deadline.
+    fn apply_deadline(&self) -> GDResult<()> {
+        // Unwrapping because clamped timeouts are never 0
+        self.socket
+            .set_read_timeout(deadline::clamp(retry::read_timeout(self.read_timeout))?)
+            .unwrap();
+        self.socket
+            .set_write_timeout(deadline::clamp(self.write_timeout)?)
+            .unwrap();
+
+        Ok(())
+    }
 }
 
 impl Socket for UdpSocketImpl {
     fn new(address: &SocketAddr, timeout_settings: &Option<TimeoutSettings>) -> GDResult<Self> {
-        let socket = net::UdpSocket::bind("0.0.0.0:0").map_err(|e| SocketBind.context(e))?;
+        let pooled = UDP_SOCKET_POOL.with_borrow(Option::is_some);
+        let network = TimeoutSettings::get_network_settings_or_default(timeout_settings);
+
+        let (socket, buffer) = match Self::take_pooled(address, &network) {
+            Some(reused) => {
+                trace::event!(DEBUG, %address, "UDP socket reused");
+                reused
+            }
+            None => {
+                let socket = Self::bind(address, &network)?;
+                trace::event!(DEBUG, %address, local = ?socket.local_addr().ok(), "UDP socket bound");
+                (socket, Vec::new())
+            }
+        };
+        let (read_timeout, write_timeout) = TimeoutSettings::get_read_and_write_or_defaults(timeout_settings);
 
         let socket = Self {
             socket,
             address: *address,
+            buffer,
+            pooled,
+            read_timeout,
+            write_timeout,
+            timer: ExchangeTimer::default(),
         };
 
         socket.apply_timeout(timeout_settings)?;
@@ -149,38 +388,487 @@ impl Socket for UdpSocketImpl {
     }
 
     fn send(&mut self, data: &[u8]) -> GDResult<()> {
-        self.socket
+        self.apply_deadline()?;
+        let sent = self
+            .socket
             .send_to(data, self.address)
-            .map_err(|e| PacketSend.context(e))?;
+            .map_err(|e| deadline::timed_out(PacketSend).context(e))?;
+        self.timer.sent();
+        report::record_sent(sent);
+        metrics::sent(sent);
+        trace::event!(TRACE, bytes = sent, "UDP sent");
 
         Ok(())
     }
 
     fn receive(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> {
-        let mut buf: Vec<u8> = vec![0; size.unwrap_or(DEFAULT_PACKET_SIZE)];
-        let (number_of_bytes_received, _) = self
-            .socket
-            .recv_from(&mut buf)
-            .map_err(|e| PacketReceive.context(e))?;
+        self.buffer.resize(size.unwrap_or(DEFAULT_PACKET_SIZE), 0);
 
-        Ok(buf[.. number_of_bytes_received].to_vec())
+        loop {
+            self.apply_deadline()?;
+            let (number_of_bytes_received, source) = self
+                .socket
+                .recv_from(&mut self.buffer)
+                .map_err(|e| deadline::timed_out(PacketReceive).context(e))?;
+
+            // A reused socket might still get (late) replies to a previous query.
+            if !self.pooled || source == self.address {
+                self.timer.received();
+                report::record_received(number_of_bytes_received);
+                metrics::received(number_of_bytes_received);
+                trace::event!(TRACE, bytes = number_of_bytes_received, "UDP received");
+                return Ok(self.buffer[.. number_of_bytes_received].to_vec());
+            }
+
+            trace::event!(DEBUG, %source, bytes = number_of_bytes_received, "UDP datagram from another address ignored");
+        }
     }
 
     fn port(&self) -> u16 { self.address.port() }
 
+    fn latency(&self) -> Option<Duration> { self.timer.latency }
+
     #[cfg(feature = "packet_capture")]
     fn local_addr(&self) -> std::io::Result<SocketAddr> { self.socket.local_addr() }
 }
 
+impl Drop for UdpSocketImpl {
+    fn drop(&mut self) {
+        if !self.pooled {
+            return;
+        }
+
+        // Cloning only duplicates the handle, the original one is closed right after.
+        if let Ok(socket) = self.socket.try_clone() {
+            let buffer = std::mem::take(&mut self.buffer);
+
+            UDP_SOCKET_POOL.with_borrow_mut(|pool| {
+                if let Some(pool) = pool {
+                    pool.push((socket, buffer));
+                }
+            });
+        }
+    }
+}
+
+impl Transport for TcpSocketImpl {
+    fn send(&mut self, data: &[u8]) -> GDResult<()> { Socket::send(self, data) }
+
+    fn receive(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> { Socket::receive(self, size) }
+
+    fn receive_available(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> { Socket::receive_available(self, size) }
+
+    fn latency(&self) -> Option<Duration> { Socket::latency(self) }
+}
+
+impl Transport for UdpSocketImpl {
+    fn send(&mut self, data: &[u8]) -> GDResult<()> { Socket::send(self, data) }
+
+    fn receive(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> { Socket::receive(self, size) }
+
+    fn latency(&self) -> Option<Duration> { Socket::latency(self) }
+}
+
+/// The sockets made by the [default transport
+/// factory](crate::transport::DefaultTransportFactory).
 #[cfg(not(feature = "packet_capture"))]
-pub type UdpSocket = UdpSocketImpl;
+pub(crate) type DefaultUdpSocket = UdpSocketImpl;
 #[cfg(not(feature = "packet_capture"))]
-pub type TcpSocket = TcpSocketImpl;
+pub(crate) type DefaultTcpSocket = TcpSocketImpl;
 
 #[cfg(feature = "packet_capture")]
-pub(crate) type UdpSocket = crate::capture::socket::CapturedUdpSocket;
+pub(crate) type DefaultUdpSocket = crate::capture::socket::CapturedUdpSocket;
 #[cfg(feature = "packet_capture")]
-pub(crate) type TcpSocket = crate::capture::socket::CapturedTcpSocket;
+pub(crate) type DefaultTcpSocket = crate::capture::socket::CapturedTcpSocket;
+
+/// The kind of transport a [TransportSocket] asks the factory for.
+pub(crate) trait TransportKind {
+    fn make(
+        factory: &dyn TransportFactory,
+        address: &SocketAddr,
+        timeout_settings: &Option<TimeoutSettings>,
+    ) -> GDResult<Box<dyn Transport>>;
+}
+
+pub(crate) struct Udp;
+impl TransportKind for Udp {
+    fn make(
+        factory: &dyn TransportFactory,
+        address: &SocketAddr,
+        timeout_settings: &Option<TimeoutSettings>,
+    ) -> GDResult<Box<dyn Transport>> {
+        factory.udp(address, timeout_settings)
+    }
+}
+
+#[cfg(feature = "games")]
+pub(crate) struct Tcp;
+#[cfg(feature = "games")]
+impl TransportKind for Tcp {
+    fn make(
+        factory: &dyn TransportFactory,
+        address: &SocketAddr,
+        timeout_settings: &Option<TimeoutSettings>,
+    ) -> GDResult<Box<dyn Transport>> {
+        factory.tcp(address, timeout_settings)
+    }
+}
+
+/// A socket whose transport is made by the thread's current
+/// [TransportFactory], this is what the protocols use.
+///
+/// The errors of its transport are given the socket's address.
+pub(crate) struct TransportSocket<K: TransportKind> {
+    transport: Box<dyn Transport>,
+    address: SocketAddr,
+    _kind: PhantomData<K>,
+}
+
+impl<K: TransportKind> Socket for TransportSocket<K> {
+    fn new(address: &SocketAddr, timeout_settings: &Option<TimeoutSettings>) -> GDResult<Self> {
+        let transport = K::make(&*current_factory(), address, timeout_settings)
+            .map_err(|e| e.with_address(*address).with_stage(QueryStage::Connect))?;
+
+        Ok(Self {
+            transport,
+            address: *address,
+            _kind: PhantomData,
+        })
+    }
+
+    // The factory applies the timeouts when making the transport.
+    fn apply_timeout(&self, _: &Option<TimeoutSettings>) -> GDResult<()> { Ok(()) }
+
+    fn send(&mut self, data: &[u8]) -> GDResult<()> {
+        self.transport
+            .send(data)
+            .map_err(|e| e.with_address(self.address))
+    }
+
+    fn receive(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> {
+        self.transport
+            .receive(size)
+            .map_err(|e| e.with_address(self.address))
+    }
+
+    fn receive_available(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> {
+        self.transport
+            .receive_available(size)
+            .map_err(|e| e.with_address(self.address))
+    }
+
+    fn port(&self) -> u16 { self.address.port() }
+
+    fn latency(&self) -> Option<Duration> { self.transport.latency() }
+
+    #[cfg(feature = "packet_capture")]
+    fn local_addr(&self) -> std::io::Result<SocketAddr> { Err(std::io::ErrorKind::Unsupported.into()) }
+}
+
+pub(crate) type UdpSocket = TransportSocket<Udp>;
+#[cfg(feature = "games")]
+pub(crate) type TcpSocket = TransportSocket<Tcp>;
+
+/// A trait defining the basic functionalities of an asynchronous network
+/// socket, this mirrors [Socket].
+#[cfg(feature = "async")]
+pub trait AsyncSocket {
+    /// Create a new socket and connect to the remote address.
+    ///
+    /// # Arguments
+    /// * `address` - The address to connect the socket to.
+    /// * `timeout_settings` - Optional timeout settings for the socket.
+    ///
+    /// # Returns
+    /// A result containing the socket instance or an error.
+    fn new(
+        address: &SocketAddr,
+        timeout_settings: &Option<TimeoutSettings>,
+    ) -> impl Future<Output = GDResult<Self>> + Send
+    where
+        Self: Sized;
+
+    /// Apply read and write timeouts to the socket.
+    ///
+    /// # Arguments
+    /// * `timeout_settings` - Optional timeout settings to apply.
+    ///
+    /// # Returns
+    /// A result indicating success or error in applying timeouts.
+    fn apply_timeout(&mut self, timeout_settings: &Option<TimeoutSettings>) -> GDResult<()>;
+
+    /// Send data over the socket.
+    ///
+    /// # Arguments
+    /// * `data` - Data to be sent.
+    ///
+    /// # Returns
+    /// A result indicating success or error in sending data.
+    fn send(&mut self, data: &[u8]) -> impl Future<Output = GDResult<()>> + Send;
+
+    /// Receive data from the socket.
+    ///
+    /// # Arguments
+    /// * `size` - Optional size of data to receive.
+    ///
+    /// # Returns
+    /// A result containing received data or an error.
+    fn receive(&mut self, size: Option<usize>) -> impl Future<Output = GDResult<Vec<u8>>> + Send;
+
+    /// Receive the data available, without waiting for the server to stop
+    /// sending: a datagram for UDP, a single read for TCP.
+    ///
+    /// # Arguments
+    /// * `size` - Optional size of data to receive.
+    ///
+    /// # Returns
+    /// A result containing received data or an error.
+    fn receive_available(&mut self, size: Option<usize>) -> impl Future<Output = GDResult<Vec<u8>>> + Send {
+        self.receive(size)
+    }
+
+    /// Get the remote port of the socket.
+    ///
+    /// # Returns
+    /// The port number.
+    fn port(&self) -> u16;
+
+    /// Get the round-trip time of the last exchange made over the socket: the
+    /// time between the last send and the first data received after it.
+    ///
+    /// # Returns
+    /// The latency, `None` if nothing was received since the last send.
+    fn latency(&self) -> Option<Duration>;
+}
+
+/// Await an IO future, failing with `kind` if it doesn't complete within
+/// `timeout` (no timeout when `None`) or with
+/// [DeadlineExceeded](GDErrorKind::DeadlineExceeded) if the query's deadline
+/// comes first.
+#[cfg(feature = "async")]
+async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    kind: GDErrorKind,
+    future: impl Future<Output = std::io::Result<T>>,
+) -> GDResult<T> {
+    match deadline::clamp(timeout)? {
+        None => future.await.map_err(|e| kind.context(e)),
+        Some(timeout) => {
+            match tokio::time::timeout(timeout, future).await {
+                Ok(result) => result.map_err(|e| kind.context(e)),
+                Err(e) => Err(deadline::timed_out(kind).context(e)),
+            }
+        }
+    }
+}
+
+/// Implementation of an asynchronous TCP socket.
+#[cfg(all(feature = "async", feature = "games"))]
+pub struct AsyncTcpSocketImpl {
+    /// The underlying TCP socket stream.
+    socket: tokio::net::TcpStream,
+    /// The address of the remote host.
+    address: SocketAddr,
+    /// The read timeout applied on each receive.
+    read_timeout: Option<Duration>,
+    /// The write timeout applied on each send.
+    write_timeout: Option<Duration>,
+    /// Timer of the exchanges.
+    timer: ExchangeTimer,
+}
+
+#[cfg(all(feature = "async", feature = "games"))]
+impl AsyncTcpSocketImpl {
+    /// Read what is available, timing the exchange if it is data.
+    async fn read_available(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> {
+        let mut buf = vec![0; size.unwrap_or(DEFAULT_PACKET_SIZE).max(1)];
+
+        let read = with_timeout(
+            retry::read_timeout(self.read_timeout),
+            PacketReceive,
+            self.socket.read(&mut buf),
+        )
+        .await?;
+        if read > 0 {
+            self.timer.received();
+        }
+
+        buf.truncate(read);
+        Ok(buf)
+    }
+}
+
+#[cfg(all(feature = "async", feature = "games"))]
+impl AsyncSocket for AsyncTcpSocketImpl {
+    async fn new(address: &SocketAddr, timeout_settings: &Option<TimeoutSettings>) -> GDResult<Self> {
+        let socket = with_timeout(
+            TimeoutSettings::get_connect_or_default(timeout_settings),
+            SocketConnect,
+            tokio::net::TcpStream::connect(address),
+        )
+        .await
+        .map_err(|e| e.with_address(*address).with_stage(QueryStage::Connect))?;
+
+        let mut socket = Self {
+            socket,
+            address: *address,
+            read_timeout: None,
+            write_timeout: None,
+            timer: ExchangeTimer::default(),
+        };
+
+        socket.apply_timeout(timeout_settings)?;
+        trace::event!(DEBUG, %address, "TCP connected");
+
+        Ok(socket)
+    }
+
+    fn apply_timeout(&mut self, timeout_settings: &Option<TimeoutSettings>) -> GDResult<()> {
+        (self.read_timeout, self.write_timeout) = TimeoutSettings::get_read_and_write_or_defaults(timeout_settings);
+
+        Ok(())
+    }
+
+    async fn send(&mut self, data: &[u8]) -> GDResult<()> {
+        let written = with_timeout(self.write_timeout, PacketSend, self.socket.write(data)).await?;
+        self.timer.sent();
+        report::record_sent(written);
+        metrics::sent(written);
+        trace::event!(TRACE, bytes = written, "TCP sent");
+        Ok(())
+    }
+
+    async fn receive(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> {
+        // Wait for the first bytes to time the exchange, then read the rest.
+        let mut buf = self.read_available(size).await?;
+
+        if !buf.is_empty() {
+            with_timeout(
+                retry::read_timeout(self.read_timeout),
+                PacketReceive,
+                self.socket.read_to_end(&mut buf),
+            )
+            .await?;
+        }
+
+        report::record_received(buf.len());
+
+        metrics::received(buf.len());
+        trace::event!(TRACE, bytes = buf.len(), "TCP received");
+        Ok(buf)
+    }
+
+    async fn receive_available(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> {
+        let buf = self.read_available(size).await?;
+
+        report::record_received(buf.len());
+        metrics::received(buf.len());
+        trace::event!(TRACE, bytes = buf.len(), "TCP received");
+        Ok(buf)
+    }
+
+    fn port(&self) -> u16 { self.address.port() }
+
+    fn latency(&self) -> Option<Duration> { self.timer.latency }
+}
+
+/// Implementation of an asynchronous UDP socket.
+#[cfg(feature = "async")]
+pub struct AsyncUdpSocketImpl {
+    /// The underlying UDP socket.
+    socket: tokio::net::UdpSocket,
+    /// The address of the remote host.
+    address: SocketAddr,
+    /// The read timeout applied on each receive.
+    read_timeout: Option<Duration>,
+    /// The write timeout applied on each send.
+    write_timeout: Option<Duration>,
+    /// Timer of the exchanges.
+    timer: ExchangeTimer,
+}
+
+#[cfg(feature = "async")]
+impl AsyncSocket for AsyncUdpSocketImpl {
+    async fn new(address: &SocketAddr, timeout_settings: &Option<TimeoutSettings>) -> GDResult<Self> {
+        let network = TimeoutSettings::get_network_settings_or_default(timeout_settings);
+
+        let mut bound = None;
+        let mut last_error = None;
+        for local in network.bind_addresses(address)? {
+            match tokio::net::UdpSocket::bind(local).await {
+                Ok(socket) => {
+                    bound = Some(socket);
+                    break;
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        let socket = bound
+            .ok_or_else(|| last_error.map_or_else(|| SocketBind.into(), |e| SocketBind.context(e)))
+            .map_err(|e| e.with_address(*address).with_stage(QueryStage::Connect))?;
+        trace::event!(DEBUG, %address, local = ?socket.local_addr().ok(), "UDP socket bound");
+
+        let mut socket = Self {
+            socket,
+            address: *address,
+            read_timeout: None,
+            write_timeout: None,
+            timer: ExchangeTimer::default(),
+        };
+
+        socket.apply_timeout(timeout_settings)?;
+
+        Ok(socket)
+    }
+
+    fn apply_timeout(&mut self, timeout_settings: &Option<TimeoutSettings>) -> GDResult<()> {
+        (self.read_timeout, self.write_timeout) = TimeoutSettings::get_read_and_write_or_defaults(timeout_settings);
+
+        Ok(())
+    }
+
+    async fn send(&mut self, data: &[u8]) -> GDResult<()> {
+        let sent = with_timeout(
+            self.write_timeout,
+            PacketSend,
+            self.socket.send_to(data, self.address),
+        )
+        .await?;
+        self.timer.sent();
+        report::record_sent(sent);
+        metrics::sent(sent);
+        trace::event!(TRACE, bytes = sent, "UDP sent");
+
+        Ok(())
+    }
+
+    async fn receive(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> {
+        let mut buf: Vec<u8> = vec![0; size.unwrap_or(DEFAULT_PACKET_SIZE)];
+        let (number_of_bytes_received, _) = with_timeout(
+            retry::read_timeout(self.read_timeout),
+            PacketReceive,
+            self.socket.recv_from(&mut buf),
+        )
+        .await?;
+        self.timer.received();
+        report::record_received(number_of_bytes_received);
+        metrics::received(number_of_bytes_received);
+        trace::event!(TRACE, bytes = number_of_bytes_received, "UDP received");
+
+        buf.truncate(number_of_bytes_received);
+        Ok(buf)
+    }
+
+    fn port(&self) -> u16 { self.address.port() }
+
+    fn latency(&self) -> Option<Duration> { self.timer.latency }
+}
+
+#[cfg(feature = "async")]
+pub(crate) type AsyncUdpSocket = AsyncUdpSocketImpl;
+#[cfg(all(feature = "async", feature = "games"))]
+pub(crate) type AsyncTcpSocket = AsyncTcpSocketImpl;
 
 #[cfg(test)]
 mod tests {
@@ -220,6 +908,31 @@ mod tests {
         assert_eq!(message, &received_message[..]);
     }
 
+    #[cfg(feature = "games")]
+    #[test]
+    fn test_udp_socket_reuse() {
+        let server = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let stray = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_address = server.local_addr().unwrap();
+
+        let _reuse = UdpSocketReuse::enable();
+
+        let socket = UdpSocketImpl::new(&server_address, &None).unwrap();
+        let local_port = socket.socket.local_addr().unwrap().port();
+        drop(socket);
+
+        // The dropped socket is handed out again
+        let mut socket = UdpSocketImpl::new(&server_address, &None).unwrap();
+        assert_eq!(socket.socket.local_addr().unwrap().port(), local_port);
+
+        // Datagrams that aren't from the queried server are ignored
+        let client_address = SocketAddr::new(server_address.ip(), local_port);
+        stray.send_to(b"late reply", client_address).unwrap();
+        server.send_to(b"reply", client_address).unwrap();
+
+        assert_eq!(Socket::receive(&mut socket, None).unwrap(), b"reply");
+    }
+
     #[test]
     fn test_udp_socket_send_and_receive() {
         // Spawn a thread to run the server
@@ -250,4 +963,116 @@ mod tests {
 
         assert_eq!(message, &received_message[..]);
     }
+
+    #[test]
+    fn test_udp_socket_ipv6() {
+        let Ok(server) = net::UdpSocket::bind("[::1]:0") else {
+            // No IPv6 support on this machine
+            return;
+        };
+        let server_address = server.local_addr().unwrap();
+
+        let mut socket = UdpSocket::new(&server_address, &None).unwrap();
+        socket.send(b"ping").unwrap();
+
+        let mut buf = [0; 16];
+        let (_, client_address) = server.recv_from(&mut buf).unwrap();
+        server.send_to(b"pong", client_address).unwrap();
+
+        assert_eq!(socket.receive(None).unwrap(), b"pong");
+    }
+
+    #[test]
+    fn test_udp_socket_network_settings() {
+        let server = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_address = server.local_addr().unwrap();
+
+        // Take a free port, so that the range starts with a busy one
+        let busy = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let busy_port = busy.local_addr().unwrap().port();
+        let network = NetworkSettings::new()
+            .local_address("127.0.0.1".parse().unwrap())
+            .local_port_range(busy_port ..= busy_port.saturating_add(16))
+            .unwrap();
+        let timeout_settings = Some(TimeoutSettings::default().with_network_settings(network));
+
+        let socket = UdpSocketImpl::new(&server_address, &timeout_settings).unwrap();
+        let local = socket.socket.local_addr().unwrap();
+        assert_eq!(local.ip(), network.get_local_address().unwrap());
+        assert!(local.port() > busy_port);
+        assert!(network
+            .get_local_port_range()
+            .unwrap()
+            .contains(&local.port()));
+
+        // A local address of another family can't reach the server
+        let network = NetworkSettings::new().local_address("::1".parse().unwrap());
+        let timeout_settings = Some(TimeoutSettings::default().with_network_settings(network));
+        assert_eq!(
+            UdpSocketImpl::new(&server_address, &timeout_settings)
+                .err()
+                .map(|e| e.kind),
+            Some(crate::GDErrorKind::InvalidInput)
+        );
+    }
+
+    #[test]
+    fn test_udp_socket_latency() {
+        let server = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_address = server.local_addr().unwrap();
+
+        let mut socket = UdpSocket::new(&server_address, &None).unwrap();
+        assert_eq!(socket.latency(), None);
+
+        socket.send(b"ping").unwrap();
+        let mut buf = [0; 16];
+        let (_, client_address) = server.recv_from(&mut buf).unwrap();
+        assert_eq!(socket.latency(), None);
+
+        server.send_to(b"pong", client_address).unwrap();
+        socket.receive(None).unwrap();
+        assert!(socket.latency().is_some());
+
+        // A new request resets the measurement
+        socket.send(b"ping").unwrap();
+        assert_eq!(socket.latency(), None);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_async_udp_socket_send_and_receive() {
+        let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let bound_address = socket.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let mut buf = [0; 1024];
+            let (size, src_addr) = socket.recv_from(&mut buf).await.unwrap();
+            socket.send_to(&buf[.. size], src_addr).await.unwrap();
+        });
+
+        let mut socket = AsyncUdpSocket::new(&bound_address, &None).await.unwrap();
+        let message = b"hello, world!";
+        socket.send(message).await.unwrap();
+
+        let received_message = socket.receive(None).await.unwrap();
+
+        server.await.expect("server task panicked");
+
+        assert_eq!(message, &received_message[..]);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_async_udp_socket_times_out() {
+        let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let bound_address = socket.local_addr().unwrap();
+
+        let timeout_settings = TimeoutSettings::new(Some(std::time::Duration::from_millis(50)), None, None, 0).ok();
+        let mut client = AsyncUdpSocket::new(&bound_address, &timeout_settings)
+            .await
+            .unwrap();
+        client.send(b"nobody is listening").await.unwrap();
+
+        let error = client.receive(None).await.unwrap_err();
+        assert_eq!(error, PacketReceive.into());
+    }
 }
@@ -0,0 +1,138 @@
+//! Instrumentation of the queries with [tracing](https://docs.rs/tracing),
+//! enabled by the `tracing` feature.
+//!
+//! Without the feature, the macros expand to nothing (or to a stand-in span)
+//! and `Span` and `Instrument` are stand-ins that do nothing, so the
+//! instrumented code needs no `cfg` of its own.
+//!
+//! Queries are wrapped in `DEBUG` spans (the game, the address and the
+//! protocol of a query), the steps of the protocols are `DEBUG` events and
+//! every packet sent and received is a `TRACE` event.
+
+#[cfg(all(feature = "tracing", feature = "async"))]
+pub(crate) use tracing::Instrument;
+
+/// Emit an event at a level (`TRACE`, `DEBUG`, `INFO`, `WARN` or `ERROR`),
+/// taking the fields and message of `tracing::event!`.
+#[cfg(feature = "tracing")]
+macro_rules! event {
+    ($level:ident, $($arg:tt)+) => {
+        ::tracing::event!(::tracing::Level::$level, $($arg)+)
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! event {
+    ($level:ident, $($arg:tt)+) => {
+        ()
+    };
+}
+
+/// Make a `DEBUG` span, taking the name and fields of `tracing::span!`.
+#[cfg(feature = "tracing")]
+macro_rules! span {
+    ($($arg:tt)+) => {
+        ::tracing::debug_span!($($arg)+)
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! span {
+    ($($arg:tt)+) => {
+        crate::trace::Span
+    };
+}
+
+pub(crate) use {event, span};
+
+/// Stand-in for a tracing span.
+#[cfg(not(feature = "tracing"))]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Span;
+
+#[cfg(not(feature = "tracing"))]
+impl Span {
+    pub(crate) fn in_scope<T>(&self, f: impl FnOnce() -> T) -> T { f() }
+}
+
+/// Stand-in for the tracing trait instrumenting futures with a span.
+#[cfg(all(not(feature = "tracing"), feature = "async"))]
+pub(crate) trait Instrument: Sized {
+    fn instrument(self, _: Span) -> Self { self }
+}
+
+#[cfg(all(not(feature = "tracing"), feature = "async"))]
+impl<F: std::future::Future> Instrument for F {}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use crate::protocols::quake::one;
+
+    use gamedig_fake_servers::{quake::Quake, FakeServer};
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::{span, Event, Metadata, Subscriber};
+
+    /// Records the names of the spans and the messages of the events.
+    #[derive(Clone, Default)]
+    struct Recorder {
+        spans: Arc<Mutex<Vec<&'static str>>>,
+        messages: Arc<Mutex<Vec<String>>>,
+    }
+
+    struct Message(String);
+
+    impl Visit for Message {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            if field.name() == "message" {
+                self.0 = format!("{value:?}");
+            }
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool { true }
+
+        fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+            let mut spans = self.spans.lock().unwrap();
+            spans.push(span.metadata().name());
+            span::Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut message = Message(String::new());
+            event.record(&mut message);
+            self.messages.lock().unwrap().push(message.0);
+        }
+
+        fn enter(&self, _: &span::Id) {}
+
+        fn exit(&self, _: &span::Id) {}
+    }
+
+    #[test]
+    fn traces_query() {
+        let server = FakeServer::start(&Quake::one()).unwrap();
+        let address = server.address();
+
+        let recorder = Recorder::default();
+        let response = tracing::subscriber::with_default(recorder.clone(), || one::query(&address, None)).unwrap();
+        assert_eq!(response.name, "Fake server");
+
+        assert_eq!(*recorder.spans.lock().unwrap(), ["quake"]);
+        assert_eq!(
+            *recorder.messages.lock().unwrap(),
+            [
+                "UDP socket bound",
+                "Quake status request",
+                "UDP sent",
+                "UDP received"
+            ]
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! Pluggable transports, what the (sync) queries send and receive through.
+//!
+//! By default queries open their own UDP and TCP sockets, to query through
+//! something else (a shared socket, a socket bound to a specific interface, an
+//! in-memory mock...) implement [Transport] and either:
+//! - run the query in [with_transport], making a transport for each socket the
+//!   query opens with a closure,
+//! - or implement [TransportFactory] and run the query in [with_factory], to
+//!   also tell the UDP sockets from the TCP ones.
+//!
+//! ```no_run
+//! use gamedig::protocols::quake::one;
+//! use gamedig::transport::{self, Transport};
+//! use gamedig::GDResult;
+//!
+//! struct Mock;
+//!
+//! impl Transport for Mock {
+//!     fn send(&mut self, data: &[u8]) -> GDResult<()> { Ok(()) }
+//!
+//!     fn receive(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> {
+//!         Ok(b"\xFF\xFF\xFF\xFFn\\hostname\\Test\\map\\e1m1\\maxclients\\8\n".to_vec())
+//!     }
+//! }
+//!
+//! let address = "127.0.0.1:27500".parse().unwrap();
+//! let response = transport::with_transport(|_| Ok(Mock), || one::query(&address, None)).unwrap();
+//! assert_eq!(response.name, "Test");
+//! ```
+
+use crate::protocols::types::TimeoutSettings;
+use crate::socket::{DefaultTcpSocket, DefaultUdpSocket, Socket};
+use crate::GDResult;
+
+use std::cell::RefCell;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+/// A connection to a server that queries send and receive through.
+pub trait Transport: Send {
+    /// Send data, a datagram for UDP transports.
+    fn send(&mut self, data: &[u8]) -> GDResult<()>;
+
+    /// Receive data, a whole datagram for UDP transports and everything until
+    /// the server stops sending for TCP ones.
+    ///
+    /// `size` is the expected maximum size, a buffer size hint.
+    ///
+    /// Timing out should be reported as a
+    /// [PacketReceive](crate::GDErrorKind::PacketReceive) error, so that the
+    /// query gets retried.
+    fn receive(&mut self, size: Option<usize>) -> GDResult<Vec<u8>>;
+
+    /// Receive the data available without waiting for the server to stop
+    /// sending (a single read for TCP transports), for exchanges where the
+    /// server waits for more requests before closing the connection.
+    ///
+    /// Defaults to [receive](Self::receive), which suits UDP transports.
+    fn receive_available(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> { self.receive(size) }
+
+    /// The round-trip time of the last exchange: the time between the last
+    /// send and the first data received after it, see
+    /// [CommonResponse::latency](crate::protocols::types::CommonResponse::latency).
+    fn latency(&self) -> Option<Duration> { None }
+}
+
+/// Makes the transports of the queries.
+pub trait TransportFactory: Send + Sync {
+    /// Make a UDP transport to `address`.
+    fn udp(&self, address: &SocketAddr, timeout_settings: &Option<TimeoutSettings>) -> GDResult<Box<dyn Transport>>;
+
+    /// Make a TCP transport to `address`.
+    fn tcp(&self, address: &SocketAddr, timeout_settings: &Option<TimeoutSettings>) -> GDResult<Box<dyn Transport>>;
+}
+
+/// The factory used when none is given, opening plain sockets.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultTransportFactory;
+
+impl TransportFactory for DefaultTransportFactory {
+    fn udp(&self, address: &SocketAddr, timeout_settings: &Option<TimeoutSettings>) -> GDResult<Box<dyn Transport>> {
+        Ok(Box::new(DefaultUdpSocket::new(address, timeout_settings)?))
+    }
+
+    fn tcp(&self, address: &SocketAddr, timeout_settings: &Option<TimeoutSettings>) -> GDResult<Box<dyn Transport>> {
+        Ok(Box::new(DefaultTcpSocket::new(address, timeout_settings)?))
+    }
+}
+
+/// A factory making its transports with a closure, see [with_transport].
+struct ClosureTransportFactory<F> {
+    make: Mutex<F>,
+}
+
+impl<Tr: Transport + 'static, F: FnMut(&SocketAddr) -> GDResult<Tr> + Send> ClosureTransportFactory<F> {
+    fn make(&self, address: &SocketAddr) -> GDResult<Box<dyn Transport>> {
+        let mut make = self.make.lock().unwrap_or_else(PoisonError::into_inner);
+        Ok(Box::new(make(address)?))
+    }
+}
+
+impl<Tr: Transport + 'static, F: FnMut(&SocketAddr) -> GDResult<Tr> + Send> TransportFactory
+    for ClosureTransportFactory<F>
+{
+    fn udp(&self, address: &SocketAddr, _: &Option<TimeoutSettings>) -> GDResult<Box<dyn Transport>> {
+        self.make(address)
+    }
+
+    fn tcp(&self, address: &SocketAddr, _: &Option<TimeoutSettings>) -> GDResult<Box<dyn Transport>> {
+        self.make(address)
+    }
+}
+
+thread_local! {
+    static FACTORY: RefCell<Option<Arc<dyn TransportFactory>>> = const { RefCell::new(None) };
+}
+
+/// Restores the previous factory of the thread when dropped.
+struct FactoryGuard {
+    previous: Option<Arc<dyn TransportFactory>>,
+}
+
+impl Drop for FactoryGuard {
+    fn drop(&mut self) { FACTORY.with_borrow_mut(|factory| *factory = self.previous.take()); }
+}
+
+/// Run a (sync) query, making its transports with `factory`.
+///
+/// The factory applies to the queries made on the current thread only, and
+/// only to the sync ones: async queries (and HTTP based ones) always open
+/// their own connections.
+pub fn with_factory<T>(factory: Arc<dyn TransportFactory>, query: impl FnOnce() -> T) -> T {
+    let _guard = FactoryGuard {
+        previous: FACTORY.with_borrow_mut(|current| current.replace(factory)),
+    };
+
+    query()
+}
+
+/// Run a (sync) query through the transports made by `make`.
+///
+/// `make` is called with the remote address of each socket the query opens,
+/// an error it returns fails the query like failing to connect would.
+pub fn with_transport<T, Tr: Transport + 'static>(
+    make: impl FnMut(&SocketAddr) -> GDResult<Tr> + Send + 'static,
+    query: impl FnOnce() -> T,
+) -> T {
+    let factory = ClosureTransportFactory {
+        make: Mutex::new(make),
+    };
+
+    with_factory(Arc::new(factory), query)
+}
+
+/// The factory the queries of the current thread should use.
+pub(crate) fn current_factory() -> Arc<dyn TransportFactory> {
+    FACTORY
+        .with_borrow(Clone::clone)
+        .unwrap_or_else(|| Arc::new(DefaultTransportFactory))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::quake::one;
+    use crate::socket::UdpSocket;
+    use crate::GDErrorKind;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Answers every request with the same data, recording the requests.
+    struct Mock {
+        requests: Arc<Mutex<Vec<Vec<u8>>>>,
+        answer: Vec<u8>,
+    }
+
+    impl Transport for Mock {
+        fn send(&mut self, data: &[u8]) -> GDResult<()> {
+            self.requests.lock().unwrap().push(data.to_vec());
+            Ok(())
+        }
+
+        fn receive(&mut self, _: Option<usize>) -> GDResult<Vec<u8>> { Ok(self.answer.clone()) }
+    }
+
+    #[test]
+    fn query_through_transport() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let mock = Mock {
+            requests: Arc::clone(&requests),
+            answer: b"\xFF\xFF\xFF\xFFn\\hostname\\Test\\map\\e1m1\\maxclients\\8\n".to_vec(),
+        };
+
+        let address = "127.0.0.1:27500".parse().unwrap();
+        let mut mock = Some(mock);
+        let response = with_transport(
+            move |_| Ok(mock.take().expect("a single socket")),
+            || one::query(&address, None),
+        )
+        .unwrap();
+
+        assert_eq!(response.name, "Test");
+        assert_eq!(response.map, "e1m1");
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![b"\xFF\xFF\xFF\xFFstatus\x00".to_vec()]
+        );
+    }
+
+    #[test]
+    fn transport_per_socket() {
+        let made = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&made);
+        let make = move |address: &SocketAddr| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            if address.port() == 0 {
+                return Err(GDErrorKind::SocketConnect.into());
+            }
+
+            Ok(Mock {
+                requests: Arc::default(),
+                answer: Vec::new(),
+            })
+        };
+
+        let address = "127.0.0.1:27500".parse().unwrap();
+        with_transport(make, || {
+            assert!(UdpSocket::new(&address, &None).is_ok());
+            assert!(UdpSocket::new(&address, &None).is_ok());
+            assert_eq!(
+                UdpSocket::new(&"127.0.0.1:0".parse().unwrap(), &None)
+                    .err()
+                    .map(|e| e.kind),
+                Some(GDErrorKind::SocketConnect)
+            );
+        });
+        assert_eq!(made.load(Ordering::Relaxed), 3);
+
+        // Outside of it the default factory is back
+        assert!(UdpSocket::new(&address, &None).is_ok());
+    }
+}
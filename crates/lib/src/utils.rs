@@ -1,4 +1,4 @@
-use crate::GDErrorKind::{PacketOverflow, PacketReceive, PacketSend, PacketUnderflow};
+use crate::GDErrorKind::{PacketOverflow, PacketUnderflow};
 use crate::GDResult;
 use std::cmp::Ordering;
 
@@ -12,23 +12,6 @@ pub fn error_by_expected_size(expected: usize, size: usize) -> GDResult<()> {
 
 pub const fn u8_lower_upper(n: u8) -> (u8, u8) { (n & 15, n >> 4) }
 
-/// Run a closure `retry_count+1` times while it returns [PacketReceive] or
-/// [PacketSend] errors, returning the first success, other Error, or after
-/// `retry_count+1` tries the last [PacketReceive] or [PacketSend] error.
-pub fn retry_on_timeout<T>(mut retry_count: usize, mut fetch: impl FnMut() -> GDResult<T>) -> GDResult<T> {
-    let mut last_err = PacketReceive.context("Retry count was 0");
-    retry_count += 1;
-    while retry_count > 0 {
-        last_err = match fetch() {
-            Ok(r) => return Ok(r),
-            Err(e) if e.kind == PacketReceive || e.kind == PacketSend => e,
-            Err(e) => return Err(e),
-        };
-        retry_count -= 1;
-    }
-    Err(last_err)
-}
-
 /// Run gather_fn based on the value of gather_toggle.
 ///
 /// # Parameters
@@ -66,6 +49,10 @@ pub fn retry_on_timeout<T>(mut retry_count: usize, mut fetch: impl FnMut() -> GD
 /// let response = maybe_gather!(GatherToggle::Required, query_fn());
 /// unreachable!();
 /// ```
+///
+/// Given a `Vec` of [Diagnostic](crate::protocols::types::Diagnostic)s and a
+/// stage, the error of an attempted gather is pushed to it, and the stage is
+/// set on the errors (that don't have one yet).
 macro_rules! maybe_gather {
     ($gather_toggle: expr, $gather_fn: expr) => {
         match $gather_toggle {
@@ -74,18 +61,34 @@ macro_rules! maybe_gather {
             crate::protocols::types::GatherToggle::Enforce => Some($gather_fn?),
         }
     };
+    ($gather_toggle: expr, $gather_fn: expr, $diagnostics: expr, $stage: expr) => {
+        match $gather_toggle {
+            crate::protocols::types::GatherToggle::Skip => None,
+            crate::protocols::types::GatherToggle::Try => {
+                match $gather_fn.map_err(|e| e.with_stage($stage)) {
+                    Ok(data) => Some(data),
+                    Err(e) => {
+                        crate::trace::event!(DEBUG, stage = ?$stage, kind = ?e.kind, "optional request failed");
+                        $diagnostics.push(crate::protocols::types::Diagnostic::new($stage, &e));
+                        None
+                    }
+                }
+            }
+            crate::protocols::types::GatherToggle::Enforce => Some($gather_fn.map_err(|e| e.with_stage($stage))?),
+        }
+    };
 }
 
 pub(crate) use maybe_gather;
 
 #[cfg(test)]
 mod tests {
-    use super::retry_on_timeout;
     use crate::{
-        protocols::types::GatherToggle,
+        protocols::types::{Diagnostic, GatherToggle},
         GDError,
-        GDErrorKind::{self, PacketBad, PacketReceive, PacketSend},
+        GDErrorKind,
         GDResult,
+        QueryStage,
     };
 
     #[test]
@@ -100,63 +103,6 @@ mod tests {
         assert!(super::error_by_expected_size(69, 70).is_err());
     }
 
-    #[test]
-    fn retry_success_on_first() {
-        let r = retry_on_timeout(0, || Ok(()));
-        assert!(r.is_ok());
-    }
-
-    #[test]
-    fn retry_no_success() {
-        let r: GDResult<()> = retry_on_timeout(100, || Err(PacketSend.context("test")));
-        assert!(r.is_err());
-        assert_eq!(r.unwrap_err().kind, PacketSend);
-    }
-
-    #[test]
-    fn retry_success_on_third() {
-        let mut i = 0u8;
-        let r = retry_on_timeout(2, || {
-            i += 1;
-            if i < 3 {
-                Err(PacketReceive.context("test"))
-            } else {
-                Ok(())
-            }
-        });
-        assert!(r.is_ok());
-    }
-
-    #[test]
-    fn retry_success_on_third_but_less_retries() {
-        let mut i = 0u8;
-        let r = retry_on_timeout(1, || {
-            i += 1;
-            if i < 3 {
-                Err(PacketReceive.context("test"))
-            } else {
-                Ok(())
-            }
-        });
-        assert!(r.is_err());
-        assert_eq!(r.unwrap_err().kind, PacketReceive);
-    }
-
-    #[test]
-    fn retry_with_non_timeout_error() {
-        let mut i = 0u8;
-        let r = retry_on_timeout(50, || {
-            i += 1;
-            match i {
-                1 => Err(PacketSend.context("test")),
-                2 => Err(PacketBad.context("test")),
-                _ => Ok(()),
-            }
-        });
-        assert!(r.is_err());
-        assert_eq!(r.unwrap_err().kind, PacketBad);
-    }
-
     fn gather_success(n: i32) -> GDResult<i32> { Ok(n) }
 
     fn gather_fail(err: &'static str) -> GDResult<i32> { Err(GDErrorKind::PacketSend.context(err)) }
@@ -205,4 +151,40 @@ mod tests {
         };
         assert!(inner().is_err());
     }
+
+    #[test]
+    fn gather_fail_attempt_gather_diagnosed() -> GDResult<()> {
+        let mut diagnostics = Vec::new();
+        let result = maybe_gather!(
+            GatherToggle::Try,
+            gather_fail("attempt"),
+            diagnostics,
+            QueryStage::Players
+        );
+        assert!(result.is_none());
+        assert_eq!(
+            diagnostics,
+            [Diagnostic {
+                stage: QueryStage::Players,
+                kind: GDErrorKind::PacketSend,
+                source: Some("attempt".to_string()),
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn gather_fail_required_staged() {
+        let inner = || {
+            let mut diagnostics = Vec::new();
+            maybe_gather!(
+                GatherToggle::Enforce,
+                gather_fail("required"),
+                diagnostics,
+                QueryStage::Rules
+            );
+            Ok::<(), GDError>(())
+        };
+        assert_eq!(inner().unwrap_err().stage(), Some(QueryStage::Rules));
+    }
 }
@@ -0,0 +1,25 @@
+//! Helpers shared by the integration tests that query fake servers.
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
+
+use gamedig::protocols::types::{CommonResponse, TimeoutSettings};
+use gamedig::GAMES;
+
+use gamedig_fake_servers::{Fake, FakeServer};
+
+pub const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+/// Short timeouts, as some protocols wait for more packets until one expires.
+pub fn timeout_settings(retries: usize) -> Option<TimeoutSettings> {
+    let timeout = Some(Duration::from_millis(300));
+    TimeoutSettings::new(timeout, timeout, timeout, retries).ok()
+}
+
+/// Serve a fake and query it as the given game.
+pub fn query(game_id: &str, fake: &impl Fake) -> Box<dyn CommonResponse> {
+    let server = FakeServer::start(fake).unwrap();
+    let game = GAMES.get(game_id).unwrap();
+
+    gamedig::games::query::query_with_timeout(game, &LOCALHOST, Some(server.port()), timeout_settings(0)).unwrap()
+}
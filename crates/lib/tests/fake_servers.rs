@@ -0,0 +1,280 @@
+#![cfg(all(test, feature = "game_defs"))]
+
+mod common;
+
+use std::net::SocketAddr;
+
+use gamedig::protocols::types::{CommonResponse, GenericResponse};
+use gamedig::{GDErrorKind, QueryStage, GAMES};
+
+use gamedig_fake_servers::gamespy::{GameSpy1, GameSpy2, GameSpy3};
+use gamedig_fake_servers::mindustry::Mindustry;
+use gamedig_fake_servers::minecraft::{Bedrock, Java, Legacy, LegacyVersion};
+use gamedig_fake_servers::quake::Quake;
+use gamedig_fake_servers::savage2::Savage2;
+use gamedig_fake_servers::unreal2::Unreal2;
+use gamedig_fake_servers::valve::Valve;
+use gamedig_fake_servers::{Fake, FakeServer};
+
+use common::{query, timeout_settings, LOCALHOST};
+
+fn player_names(response: &dyn CommonResponse) -> Vec<String> {
+    response
+        .players()
+        .unwrap_or_default()
+        .iter()
+        .map(|player| player.name().to_string())
+        .collect()
+}
+
+fn tf2() -> Valve {
+    let mut valve = Valve::new()
+        .player("Alice", 10, 60.0)
+        .player("Bob", 3, 12.5)
+        .rule("mp_timelimit", "30");
+    valve.name = "Fake TF2".to_string();
+    valve
+}
+
+fn assert_valve(response: &dyn CommonResponse) {
+    assert_eq!(response.name(), Some("Fake TF2"));
+    assert_eq!(response.map(), Some("ctf_2fort"));
+    assert_eq!(response.players_online(), 2);
+    assert_eq!(player_names(response), ["Alice", "Bob"]);
+
+    let GenericResponse::Valve(valve) = response.as_original() else {
+        panic!("Expected a Valve response");
+    };
+    assert_eq!(
+        valve
+            .rules
+            .as_ref()
+            .and_then(|rules| rules.get("mp_timelimit"))
+            .map(String::as_str),
+        Some("30")
+    );
+}
+
+#[test]
+fn valve() { assert_valve(query("teamfortress2", &tf2()).as_ref()); }
+
+#[test]
+fn valve_challenge() { assert_valve(query("teamfortress2", &tf2().with_challenge(0x1234_5678)).as_ref()); }
+
+#[test]
+fn valve_split() { assert_valve(query("teamfortress2", &tf2().with_split(16, false)).as_ref()); }
+
+#[test]
+fn valve_split_compressed() {
+    assert_valve(
+        query(
+            "teamfortress2",
+            &tf2().with_challenge(42).with_split(32, true),
+        )
+        .as_ref(),
+    );
+}
+
+#[test]
+fn gamespy_one() {
+    let fake = GameSpy1::new()
+        .value("hostname", "Fake 1942")
+        .player(&[("playername", "Alice"), ("ping", "20"), ("frags", "3")])
+        .with_values_per_packet(3);
+    let response = query("battlefield1942", &fake);
+
+    assert_eq!(response.name(), Some("Fake 1942"));
+    assert_eq!(player_names(response.as_ref()), ["Alice"]);
+}
+
+#[test]
+fn gamespy_two() {
+    let fake = GameSpy2::new()
+        .value("hostname", "Fake Halo")
+        .player("Alice", 5, 40, 0)
+        .team("Red", 5);
+    let response = query("hce", &fake);
+
+    assert_eq!(response.name(), Some("Fake Halo"));
+    assert_eq!(response.players_online(), 1);
+    assert_eq!(player_names(response.as_ref()), ["Alice"]);
+}
+
+#[test]
+fn gamespy_three() {
+    let fake = GameSpy3::new()
+        .value("hostname", "Fake Crysis")
+        .player("Alice", 7, 30, 1)
+        .player("Bob", 2, 50, 2)
+        .team("US", 9)
+        .with_challenge(1_234_567)
+        .with_split();
+    let response = query("crysiswars", &fake);
+
+    assert_eq!(response.name(), Some("Fake Crysis"));
+    assert_eq!(player_names(response.as_ref()), ["Alice", "Bob"]);
+}
+
+#[test]
+fn quake() {
+    let one = Quake::one().player(r#"1 12 30 40 "Alice" "base" 4 5"#);
+    let two = Quake::two().player(r#"12 40 "Alice""#);
+    let three = Quake::three()
+        .value("sv_hostname", "Fake Arena")
+        .player(r#"12 40 "Alice""#);
+
+    for (game_id, fake) in [("quake1", one), ("quake2", two), ("q3a", three)] {
+        let response = query(game_id, &fake);
+
+        assert!(response.name().is_some());
+        assert_eq!(player_names(response.as_ref()), ["Alice"], "{game_id}");
+    }
+}
+
+#[test]
+fn unreal2() {
+    let fake = Unreal2::new()
+        .mutator("MutInstaGib")
+        .rule("GamePassword", "True")
+        .player("Alice", 40, 10)
+        .player("A rather long name for a bot", 0, 2);
+    let response = query("unrealtournament2004", &fake);
+
+    assert_eq!(response.name(), Some("Fake server"));
+    assert_eq!(response.has_password(), Some(true));
+    assert_eq!(response.players_online(), 2);
+
+    let GenericResponse::Unreal2(unreal2) = response.as_original() else {
+        panic!("Expected an Unreal 2 response");
+    };
+    assert!(unreal2.mutators_and_rules.mutators.contains("MutInstaGib"));
+    assert_eq!(unreal2.players.bots[0].name, "A rather long name for a bot");
+}
+
+#[test]
+fn minecraft() {
+    let java = query(
+        "minecraftjava",
+        &Java::new().player("Alice", "00000000-0000-0000-0000-000000000000"),
+    );
+    assert_eq!(java.players_online(), 1);
+    assert_eq!(player_names(java.as_ref()), ["Alice"]);
+
+    let bedrock = query("minecraftbedrock", &Bedrock::new());
+    assert_eq!(bedrock.name(), Some("Dedicated Server"));
+
+    for (game_id, version) in [
+        ("minecraftlegacy16", LegacyVersion::V1_6),
+        ("minecraftlegacy14", LegacyVersion::V1_4),
+        ("minecraftlegacyb18", LegacyVersion::VB1_8),
+    ] {
+        let mut fake = Legacy::new(version);
+        fake.players_online = 3;
+        assert_eq!(query(game_id, &fake).players_online(), 3, "{game_id}");
+    }
+}
+
+#[test]
+fn savage2() {
+    let response = query("savage2", &Savage2::new());
+
+    assert_eq!(response.name(), Some("Fake server"));
+    assert_eq!(response.map(), Some("eden2"));
+}
+
+#[test]
+fn mindustry() {
+    let mut fake = Mindustry::new();
+    fake.players = 4;
+    let response = query("mindustry", &fake);
+
+    assert_eq!(response.map(), Some("Ground Zero"));
+    assert_eq!(response.players_online(), 4);
+}
+
+#[test]
+fn retries_after_ignored_request() {
+    let server = FakeServer::udp(tf2().script().ignore([0xFF, 0xFF, 0xFF, 0xFF, 0x54], 1)).unwrap();
+    let game = GAMES.get("teamfortress2").unwrap();
+
+    let response =
+        gamedig::games::query::query_with_timeout(game, &LOCALHOST, Some(server.port()), timeout_settings(1)).unwrap();
+
+    assert_valve(response.as_ref());
+    assert_eq!(
+        server
+            .requests()
+            .iter()
+            .filter(|request| request.starts_with(&[0xFF, 0xFF, 0xFF, 0xFF, 0x54]))
+            .count(),
+        2
+    );
+}
+
+#[test]
+fn diagnoses_attempted_requests() {
+    let fake = tf2()
+        .script()
+        .ignore([0xFF, 0xFF, 0xFF, 0xFF, 0x55], usize::MAX);
+    let server = FakeServer::udp(fake).unwrap();
+    let game = GAMES.get("teamfortress2").unwrap();
+
+    let response =
+        gamedig::games::query::query_with_timeout(game, &LOCALHOST, Some(server.port()), timeout_settings(0)).unwrap();
+
+    assert!(response.players().is_none());
+    let diagnostics = response.diagnostics();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].stage, QueryStage::Players);
+    assert_eq!(diagnostics[0].kind, GDErrorKind::PacketReceive);
+}
+
+#[test]
+fn error_context() {
+    let server = FakeServer::udp(tf2().script().ignore([0xFF, 0xFF, 0xFF, 0xFF, 0x54], 2)).unwrap();
+    let game = GAMES.get("teamfortress2").unwrap();
+
+    let Err(error) =
+        gamedig::games::query::query_with_timeout(game, &LOCALHOST, Some(server.port()), timeout_settings(1))
+    else {
+        panic!("Expected the query to time out");
+    };
+
+    assert!(error.is_timeout());
+    assert!(!error.is_server_misbehaving());
+    assert_eq!(
+        error.address(),
+        Some(SocketAddr::new(LOCALHOST, server.port()))
+    );
+    assert_eq!(error.protocol(), Some(&game.protocol));
+    assert_eq!(error.stage(), Some(QueryStage::Info));
+    assert_eq!(error.attempt(), Some(1));
+}
+
+#[test]
+fn protocol_error_context() {
+    // Nothing answers on a socket that is bound but never read.
+    let silent = std::net::UdpSocket::bind((LOCALHOST, 0)).unwrap();
+    let address = silent.local_addr().unwrap();
+
+    let error = gamedig::protocols::quake::one::query(&address, timeout_settings(0)).unwrap_err();
+
+    assert!(error.is_timeout());
+    assert_eq!(error.address(), Some(address));
+    assert_eq!(error.protocol(), None);
+    assert_eq!(error.stage(), Some(QueryStage::Info));
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn valve_async() {
+    let server = FakeServer::start(&tf2().with_challenge(7).with_split(32, true)).unwrap();
+    let game = GAMES.get("teamfortress2").unwrap();
+
+    let response =
+        gamedig::games::query::query_with_timeout_async(game, &LOCALHOST, Some(server.port()), timeout_settings(0))
+            .await
+            .unwrap();
+
+    assert_valve(response.as_ref());
+}
@@ -0,0 +1,234 @@
+#![cfg(test)]
+
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use gamedig::machine::{Event, Machine, Step};
+use gamedig::protocols::gamespy::{one, three};
+use gamedig::protocols::unreal2::{self, MutatorsAndRulesMachine, PlayersMachine};
+use gamedig::protocols::valve::{self, Engine, RequestMachine, Response};
+use gamedig::responder::gamespy::GameSpy3Responder;
+use gamedig::responder::valve::ValveResponder;
+use gamedig::responder::Responder;
+
+use gamedig_fake_servers::gamespy::{GameSpy1, GameSpy3};
+use gamedig_fake_servers::unreal2::Unreal2;
+use gamedig_fake_servers::valve::Valve;
+
+const PEER: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1);
+
+/// Run a machine against a responder over (lossless) datagrams.
+fn run_udp<M: Machine>(machine: &mut M, responder: &mut impl Responder) -> M::Output {
+    let mut packets = machine.start();
+    let mut replies = VecDeque::new();
+    loop {
+        for packet in &packets {
+            replies.extend(responder.respond(packet, &PEER).unwrap_or_default());
+        }
+
+        let reply = replies
+            .pop_front()
+            .expect("The machine waits for a reply that isn't coming");
+        packets = match machine.handle(Event::Received(&reply)).unwrap() {
+            Step::Send(packets) => packets,
+            Step::Receive => Vec::new(),
+            Step::Done(output) => return output,
+        };
+    }
+}
+
+fn valve_response() -> Response {
+    let fake = Valve::new();
+    let engine = Engine::new(440);
+    let info = fake.info_packet();
+
+    Response {
+        info: valve::parse_info(&engine, &info).unwrap(),
+        players: Some(Vec::new()),
+        rules: Some(HashMap::from([(
+            "mp_timelimit".to_string(),
+            "30".to_string(),
+        )])),
+        latency: None,
+        diagnostics: Vec::new(),
+    }
+}
+
+#[test]
+fn valve() {
+    let response = valve_response();
+    let mut responder = ValveResponder::new(response.clone()).with_packet_size(32);
+    let engine = Engine::new(440);
+
+    let info = run_udp(&mut RequestMachine::info(engine), &mut responder);
+    assert_eq!(valve::parse_info(&engine, &info).unwrap(), response.info);
+
+    let protocol = response.info.protocol_version;
+    let rules = run_udp(&mut RequestMachine::rules(engine, protocol), &mut responder);
+    assert_eq!(valve::parse_rules(&engine, &rules).ok(), response.rules);
+}
+
+#[test]
+fn valve_restarts_after_timeout() {
+    let mut responder = ValveResponder::new(valve_response())
+        .without_challenge()
+        .with_packet_size(32);
+    let mut machine = RequestMachine::info(Engine::new(440));
+
+    // Only the first part of a split response is received.
+    let replies = responder.respond(&machine.start()[0], &PEER).unwrap();
+    assert_eq!(
+        machine.handle(Event::Received(&replies[0])).unwrap(),
+        Step::Receive
+    );
+    assert!(machine.handle(Event::TimedOut).is_err());
+
+    let info = run_udp(&mut machine, &mut responder);
+    assert!(valve::parse_info(&Engine::new(440), &info).is_ok());
+}
+
+#[test]
+fn gamespy_three() {
+    let fake = GameSpy3::new()
+        .value("hostname", "Fake Crysis")
+        .player("Alice", 7, 30, 1)
+        .team("US", 9);
+    let response = three::parse(&fake.packets()).unwrap();
+    let mut responder = GameSpy3Responder::new(response.clone()).with_packet_size(64);
+
+    assert_eq!(
+        run_udp(&mut three::QueryMachine::default(), &mut responder),
+        response
+    );
+}
+
+#[test]
+fn gamespy_one() {
+    let fake = GameSpy1::new()
+        .player(&[("player", "Bob"), ("ping", "20"), ("frags", "3")])
+        .with_values_per_packet(3);
+    let packets = fake.packets();
+    let mut machine = one::QueryMachine::new();
+    machine.start();
+
+    let (last, rest) = packets.split_last().unwrap();
+    for packet in rest {
+        assert_eq!(
+            machine.handle(Event::Received(packet)).unwrap(),
+            Step::Receive
+        );
+    }
+    assert_eq!(
+        machine.handle(Event::Received(last)).unwrap(),
+        Step::Done(one::parse(&packets).unwrap())
+    );
+}
+
+#[test]
+fn unreal2_gathers_until_timeout() {
+    let fake = Unreal2::new()
+        .rule("GamePassword", "False")
+        .player("Alice", 30, 7);
+
+    // The players' response is over once nothing more is received.
+    let mut machine = PlayersMachine::new(None);
+    machine.start();
+    assert_eq!(
+        machine
+            .handle(Event::Received(&fake.players_packet()))
+            .unwrap(),
+        Step::Receive
+    );
+    assert_eq!(
+        machine.handle(Event::TimedOut).unwrap(),
+        Step::Done(unreal2::parse_players(&[fake.players_packet()]).unwrap())
+    );
+
+    // Or once as many players as expected are.
+    let mut machine = PlayersMachine::new(Some(1));
+    machine.start();
+    assert!(matches!(
+        machine.handle(Event::Received(&fake.players_packet())),
+        Ok(Step::Done(_))
+    ));
+
+    // Nothing at all is still a timeout.
+    let mut machine = MutatorsAndRulesMachine::default();
+    machine.start();
+    assert!(machine.handle(Event::TimedOut).is_err());
+
+    // Another kind of packet ends the mutators and rules.
+    machine.start();
+    let packet = fake.mutators_and_rules_packet();
+    assert_eq!(
+        machine.handle(Event::Received(&packet)).unwrap(),
+        Step::Receive
+    );
+    assert_eq!(
+        machine
+            .handle(Event::Received(&fake.players_packet()))
+            .unwrap(),
+        Step::Done(unreal2::parse_mutators_and_rules(&[packet]).unwrap())
+    );
+}
+
+#[cfg(feature = "games")]
+#[test]
+fn minecraft_java() {
+    use gamedig::games::minecraft::{java, RequestSettings};
+    use gamedig::responder::minecraft::JavaResponder;
+    use gamedig_fake_servers::minecraft::Java;
+
+    let response = java::parse_status_json(
+        &Java::new()
+            .player("Alice", "00000000-0000-0000-0000-000000000000")
+            .status(),
+    )
+    .unwrap();
+    let mut responder = JavaResponder::new(response.clone());
+    let mut machine = java::StatusMachine::new(&RequestSettings::default(), 25565).unwrap();
+
+    let request = machine.start().concat();
+    let reply = responder.respond(&request, &PEER).unwrap();
+    assert_eq!(reply.len(), 1);
+
+    // The response is only whole once all of its bytes are received, the ping
+    // is sent after it.
+    let (first, rest) = reply[0].split_at(reply[0].len() / 2);
+    assert_eq!(
+        machine.handle(Event::Received(first)).unwrap(),
+        Step::Receive
+    );
+    let Step::Send(ping) = machine.handle(Event::Received(rest)).unwrap() else {
+        panic!("Expected the ping request");
+    };
+
+    let pong = &responder
+        .respond(&[request.as_slice(), &ping.concat()].concat(), &PEER)
+        .unwrap()[1];
+    assert_eq!(
+        machine.handle(Event::Received(pong)).unwrap(),
+        Step::Done(response.clone())
+    );
+
+    // A server that doesn't answer the ping still gives its status.
+    machine.start();
+    machine.handle(Event::Received(&reply[0])).unwrap();
+    assert_eq!(
+        machine.handle(Event::TimedOut).unwrap(),
+        Step::Done(response)
+    );
+}
+
+#[cfg(feature = "games")]
+#[test]
+fn minecraft_bedrock() {
+    use gamedig::games::minecraft::bedrock::PingMachine;
+    use gamedig::responder::minecraft::BedrockResponder;
+    use gamedig_fake_servers::minecraft::Bedrock;
+
+    let response = Bedrock::new().response();
+    let mut responder = BedrockResponder::new(response.clone());
+
+    assert_eq!(run_udp(&mut PingMachine, &mut responder), response);
+}
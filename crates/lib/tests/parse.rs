@@ -0,0 +1,284 @@
+#![cfg(test)]
+
+use gamedig::protocols::gamespy::{one, three, two};
+use gamedig::protocols::quake;
+use gamedig::protocols::unreal2;
+use gamedig::protocols::valve::{self, Engine};
+
+use gamedig_fake_servers::gamespy::{GameSpy1, GameSpy2, GameSpy3};
+use gamedig_fake_servers::quake::Quake;
+use gamedig_fake_servers::unreal2::Unreal2;
+use gamedig_fake_servers::valve::Valve;
+
+#[test]
+fn valve() {
+    let fake = Valve::new()
+        .player("Alice", 10, 60.0)
+        .rule("mp_timelimit", "30");
+    let engine = Engine::new(440);
+
+    let info = valve::parse_info(&engine, &fake.info_packet()).unwrap();
+    assert_eq!(info.name, "Fake server");
+    assert_eq!(info.map, "ctf_2fort");
+
+    let players = valve::parse_players(&engine, &fake.players_packet()).unwrap();
+    assert_eq!(players[0].name, "Alice");
+
+    let rules = valve::parse_rules(&engine, &fake.rules_packet()).unwrap();
+    assert_eq!(rules.get("mp_timelimit").map(String::as_str), Some("30"));
+}
+
+#[test]
+fn valve_rejects_other_packets() {
+    let fake = Valve::new();
+    let engine = Engine::new(440);
+
+    assert!(valve::parse_players(&engine, &fake.info_packet()).is_err());
+    assert!(valve::parse_info(&engine, &[0xFE, 0xFF, 0xFF, 0xFF, 0x49]).is_err());
+}
+
+#[test]
+fn gamespy() {
+    let fake = GameSpy1::new()
+        .value("hostname", "Fake 1942")
+        .player(&[("playername", "Alice"), ("ping", "20"), ("frags", "3")])
+        .with_values_per_packet(3);
+    let response = one::parse(&fake.packets()).unwrap();
+    assert_eq!(response.name, "Fake 1942");
+    assert_eq!(response.players[0].name, "Alice");
+
+    let fake = GameSpy2::new()
+        .value("hostname", "Fake Halo")
+        .player("Alice", 5, 40, 0);
+    let response = two::parse(&fake.packet()).unwrap();
+    assert_eq!(response.name, "Fake Halo");
+    assert_eq!(response.players[0].name, "Alice");
+
+    let fake = GameSpy3::new()
+        .value("hostname", "Fake Crysis")
+        .player("Alice", 7, 30, 1)
+        .team("US", 9)
+        .with_split();
+    let packets = fake.packets();
+    let response = three::parse(&packets).unwrap();
+    assert_eq!(response.name, "Fake Crysis");
+    assert_eq!(response.players[0].name, "Alice");
+
+    assert!(three::parse(&packets[1 ..]).is_err());
+}
+
+#[test]
+fn quake() {
+    let one = Quake::one().player(r#"1 12 30 40 "Alice" "base" 4 5"#);
+    assert_eq!(
+        quake::one::parse(&one.packet()).unwrap().players[0].name,
+        "Alice"
+    );
+
+    let two = Quake::two().player(r#"12 40 "Alice""#);
+    assert_eq!(
+        quake::two::parse(&two.packet()).unwrap().players[0].name,
+        "Alice"
+    );
+
+    let three = Quake::three().value("sv_hostname", "Fake Arena");
+    assert_eq!(
+        quake::three::parse(&three.packet()).unwrap().name,
+        "Fake Arena"
+    );
+
+    assert!(quake::two::parse(&three.packet()).is_err());
+}
+
+#[test]
+fn unreal2() {
+    let fake = Unreal2::new()
+        .mutator("MutInstaGib")
+        .player("Alice", 40, 10);
+
+    let info = unreal2::parse_server_info(&fake.server_info_packet()).unwrap();
+    assert_eq!(info.name, "Fake server");
+
+    let mutators_and_rules = unreal2::parse_mutators_and_rules(&[fake.mutators_and_rules_packet()]).unwrap();
+    assert!(mutators_and_rules.mutators.contains("MutInstaGib"));
+
+    let players = unreal2::parse_players(&[fake.players_packet()]).unwrap();
+    assert_eq!(players.players[0].name, "Alice");
+}
+
+#[cfg(feature = "games")]
+#[test]
+fn minecraft() {
+    use gamedig::games::minecraft::{bedrock, java, legacy_v1_4, legacy_v1_6, legacy_vb1_8};
+    use gamedig_fake_servers::minecraft::{Bedrock, Java, Legacy, LegacyVersion};
+
+    let fake = Java::new().player("Alice", "00000000-0000-0000-0000-000000000000");
+    assert_eq!(
+        java::parse_status(&fake.packet()).unwrap().players_online,
+        1
+    );
+    assert_eq!(
+        java::parse_status_json(&fake.status())
+            .unwrap()
+            .players
+            .unwrap()[0]
+            .name,
+        "Alice"
+    );
+
+    // Responses to any nonce are parsed.
+    let mut packet = Bedrock::new().packet();
+    packet[1 .. 9].fill(0);
+    assert_eq!(bedrock::parse(&packet).unwrap().name, "Dedicated Server");
+
+    let mut fake = Legacy::new(LegacyVersion::V1_6);
+    fake.players_online = 3;
+    assert_eq!(
+        legacy_v1_6::parse(&fake.packet()).unwrap().players_online,
+        3
+    );
+
+    fake.version = LegacyVersion::V1_4;
+    assert_eq!(
+        legacy_v1_4::parse(&fake.packet()).unwrap().players_online,
+        3
+    );
+
+    fake.version = LegacyVersion::VB1_8;
+    assert_eq!(
+        legacy_vb1_8::parse(&fake.packet()).unwrap().players_online,
+        3
+    );
+}
+
+#[cfg(feature = "games")]
+#[test]
+fn proprietary() {
+    use gamedig::games::{ffow, jc2m, mindustry, savage2};
+    use gamedig_fake_servers::mindustry::Mindustry;
+    use gamedig_fake_servers::savage2::Savage2;
+
+    assert_eq!(
+        savage2::parse(&Savage2::new().packet()).unwrap().name,
+        "Fake server"
+    );
+    assert_eq!(
+        mindustry::protocol::parse(&Mindustry::new().packet())
+            .unwrap()
+            .map,
+        "Ground Zero"
+    );
+
+    let mut packet = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x49, 7];
+    for string in [
+        "Fake FFOW",
+        "Liberty",
+        "ffow",
+        "Conquest",
+        "A server",
+        "1.0",
+    ] {
+        packet.extend_from_slice(string.as_bytes());
+        packet.push(0);
+    }
+    packet.extend_from_slice(&[0, 0, 3, 16, b'd', b'w', 0, 0, 30, 1, 5, 60, 0]);
+    let response = ffow::parse(&packet).unwrap();
+    assert_eq!(response.name, "Fake FFOW");
+    assert_eq!(response.rounds_maximum, 5);
+    assert!(ffow::parse(&packet[1 ..]).is_err());
+
+    let mut packet = vec![0, 0, 0, 0, 1];
+    packet.extend_from_slice(b"splitnum\0\x80\0");
+    for string in [
+        "hostname",
+        "Fake JC2MP",
+        "version",
+        "1.0",
+        "description",
+        "",
+        "password",
+        "0",
+        "maxplayers",
+        "16",
+        "",
+    ] {
+        packet.extend_from_slice(string.as_bytes());
+        packet.push(0);
+    }
+    packet.extend_from_slice(b"\0\x01Alice\0STEAM_0:1\0\0\x32");
+    let response = jc2m::parse(&[packet]).unwrap();
+    assert_eq!(response.name, "Fake JC2MP");
+    assert_eq!(response.players[0].name, "Alice");
+    assert_eq!(response.players_online, 1);
+}
+
+#[cfg(feature = "games")]
+#[test]
+fn eco() {
+    use gamedig::games::eco;
+
+    let mut root = eco::Root::default();
+    root.info.description = "Fake Eco".to_string();
+    let response = eco::parse(&serde_json::to_vec(&root).unwrap()).unwrap();
+    assert_eq!(response.description, "Fake Eco");
+}
+
+#[cfg(all(
+    feature = "games",
+    feature = "tls",
+    feature = "serde",
+    feature = "services"
+))]
+#[test]
+fn minetest() {
+    use gamedig::games::minetest;
+    use serde_json::json;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    let address = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let list = json!({
+        "total": { "servers": 1, "clients": 0 },
+        "total_max": { "servers": 1, "clients": 0 },
+        "list": [{
+            "address": "192.0.2.1", "clients": 0, "clients_list": null, "clients_max": 10, "creative": null,
+            "damage": true, "description": "", "game_time": 0, "gameid": "minetest", "lag": null,
+            "name": "Fake Minetest", "password": null, "port": 30000, "proto_max": 42, "proto_min": 37, "pvp": true,
+            "uptime": 0, "url": null, "version": "5.8.0", "ip": "192.0.2.1", "update_time": 0, "start": 0,
+            "clients_top": 0, "updates": 0, "total_clients": 0, "pop_v": 0.0, "geo_continent": null, "ping": 0.0
+        }]
+    });
+    let data = serde_json::to_vec(&list).unwrap();
+    assert_eq!(
+        minetest::parse(&data, &address, None).unwrap().name,
+        "Fake Minetest"
+    );
+    assert!(minetest::parse(&data, &address, Some(30001)).is_err());
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn epic() {
+    use gamedig::protocols::epic;
+    use serde_json::json;
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    let sessions = json!({
+        "sessions": [{
+            "attributes": {
+                "ADDRESSBOUND_s": "192.0.2.1",
+                "CUSTOMSERVERNAME_s": "Fake Epic",
+                "MAPNAME_s": "Island",
+                "SERVERPASSWORD_b": false
+            },
+            "settings": { "maxPublicPlayers": 8 },
+            "totalPlayers": 2
+        }]
+    });
+    let response = epic::parse(
+        &serde_json::to_vec(&sessions).unwrap(),
+        &SocketAddr::new(Ipv4Addr::new(192, 0, 2, 1).into(), 7777),
+    )
+    .unwrap();
+    assert_eq!(response.name, "Fake Epic");
+    assert_eq!(response.players_online, 2);
+}
@@ -0,0 +1,249 @@
+#![cfg(all(test, feature = "serde", feature = "game_defs"))]
+
+mod common;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use gamedig::games::{eco, ffow, jc2m, theship};
+use gamedig::protocols::types::{CommonResponse, Diagnostic};
+use gamedig::protocols::valve::{Environment, Server};
+use gamedig::protocols::GenericResponseOwned;
+use gamedig::{GDErrorKind, QueryStage};
+
+use gamedig_fake_servers::gamespy::{GameSpy1, GameSpy2, GameSpy3};
+use gamedig_fake_servers::mindustry::Mindustry;
+use gamedig_fake_servers::minecraft::{Bedrock, Java};
+use gamedig_fake_servers::quake::Quake;
+use gamedig_fake_servers::savage2::Savage2;
+use gamedig_fake_servers::unreal2::Unreal2;
+use gamedig_fake_servers::valve::Valve;
+
+use common::query;
+
+/// Check that a response survives a JSON round trip as a
+/// [GenericResponseOwned], and that it is stored the same way as the borrowed
+/// [GenericResponse](gamedig::protocols::GenericResponse).
+fn assert_round_trip(response: &dyn CommonResponse) {
+    let owned = GenericResponseOwned::from(response);
+    let json = serde_json::to_string(&owned).unwrap();
+    assert_eq!(
+        serde_json::to_string(&response.as_original()).unwrap(),
+        json
+    );
+
+    let deserialized: GenericResponseOwned = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized, owned);
+    assert_eq!(deserialized.as_original(), response.as_original());
+    assert_eq!(deserialized.as_common().as_json(), response.as_json());
+}
+
+#[test]
+fn valve() {
+    let fake = Valve::new()
+        .player("Alice", 10, 60.0)
+        .rule("mp_timelimit", "30");
+
+    assert_round_trip(query("teamfortress2", &fake).as_ref());
+}
+
+#[test]
+fn gamespy() {
+    let one = GameSpy1::new().value("hostname", "Fake 1942").player(&[
+        ("playername", "Alice"),
+        ("ping", "20"),
+        ("frags", "3"),
+    ]);
+    assert_round_trip(query("battlefield1942", &one).as_ref());
+
+    let two = GameSpy2::new()
+        .value("hostname", "Fake Halo")
+        .player("Alice", 5, 40, 0);
+    assert_round_trip(query("hce", &two).as_ref());
+
+    let three = GameSpy3::new()
+        .value("hostname", "Fake Crysis")
+        .player("Alice", 7, 30, 1)
+        .team("US", 9);
+    assert_round_trip(query("crysiswars", &three).as_ref());
+}
+
+#[test]
+fn quake() {
+    let one = Quake::one().player(r#"1 12 30 40 "Alice" "base" 4 5"#);
+    assert_round_trip(query("quake1", &one).as_ref());
+
+    let two = Quake::two().player(r#"12 40 "Alice""#);
+    assert_round_trip(query("quake2", &two).as_ref());
+
+    let three = Quake::three().value("sv_hostname", "Fake Arena");
+    assert_round_trip(query("q3a", &three).as_ref());
+}
+
+#[test]
+fn unreal2() {
+    let fake = Unreal2::new()
+        .mutator("MutInstaGib")
+        .player("Alice", 40, 10);
+
+    assert_round_trip(query("unrealtournament2004", &fake).as_ref());
+}
+
+#[test]
+fn minecraft() {
+    let java = Java::new().player("Alice", "00000000-0000-0000-0000-000000000000");
+    assert_round_trip(query("minecraftjava", &java).as_ref());
+
+    assert_round_trip(query("minecraftbedrock", &Bedrock::new()).as_ref());
+}
+
+#[test]
+fn savage2() { assert_round_trip(query("savage2", &Savage2::new()).as_ref()); }
+
+#[test]
+fn mindustry() { assert_round_trip(query("mindustry", &Mindustry::new()).as_ref()); }
+
+#[test]
+fn theship() {
+    assert_round_trip(&theship::Response {
+        protocol_version: 7,
+        name: "Fake ship".to_string(),
+        map: "batavier".to_string(),
+        game_mode: "Hunt".to_string(),
+        game_version: "1.0.0.4".to_string(),
+        players: vec![theship::TheShipPlayer {
+            name: "Alice".to_string(),
+            score: 3,
+            duration: 60.5,
+            deaths: 1,
+            money: 500,
+        }],
+        players_online: 1,
+        players_maximum: 16,
+        players_bots: 0,
+        server_type: Server::Dedicated,
+        has_password: false,
+        vac_secured: true,
+        port: Some(27015),
+        steam_id: None,
+        tv_port: None,
+        tv_name: None,
+        keywords: None,
+        rules: HashMap::from([("mp_timelimit".to_string(), "30".to_string())]),
+        mode: 0,
+        witnesses: 2,
+        duration: 5,
+        latency: Some(Duration::from_millis(20)),
+        diagnostics: vec![Diagnostic {
+            stage: QueryStage::Rules,
+            kind: GDErrorKind::PacketReceive,
+            source: None,
+        }],
+    });
+}
+
+#[test]
+fn ffow() {
+    assert_round_trip(&ffow::Response {
+        protocol_version: 1,
+        name: "Fake FFOW".to_string(),
+        active_mod: "ffow".to_string(),
+        game_mode: "Conquest".to_string(),
+        game_version: "1.0".to_string(),
+        description: "A fake server".to_string(),
+        map: "Gotham".to_string(),
+        players_online: 4,
+        players_maximum: 32,
+        server_type: Server::Dedicated,
+        environment_type: Environment::Windows,
+        has_password: false,
+        vac_secured: false,
+        round: 1,
+        rounds_maximum: 3,
+        time_left: 600,
+        latency: None,
+    });
+}
+
+#[test]
+fn jc2m() {
+    assert_round_trip(&jc2m::Response {
+        game_version: "0.2.4".to_string(),
+        description: "A fake server".to_string(),
+        name: "Fake JC2M".to_string(),
+        has_password: true,
+        players: vec![jc2m::Player {
+            name: "Alice".to_string(),
+            steam_id: "76561197960287930".to_string(),
+            ping: 40,
+        }],
+        players_maximum: 100,
+        players_online: 1,
+        latency: None,
+    });
+}
+
+#[test]
+fn eco() {
+    let mut root = eco::Root::default();
+    root.info.description = "Fake Eco".to_string();
+    root.info.online_players_names = vec!["Alice".to_string()];
+
+    assert_round_trip(&eco::Response::from(root));
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn epic() {
+    use gamedig::protocols::epic;
+
+    assert_round_trip(&epic::Response {
+        name: "Fake ASA".to_string(),
+        map: "TheIsland_WP".to_string(),
+        has_password: false,
+        players_online: 1,
+        players_maxmimum: 70,
+        players: vec![epic::Player {
+            name: "Alice".to_string(),
+        }],
+        game_version: Some("v32.11".to_string()),
+        raw: serde_json::json!({ "attributes": { "MAPNAME_s": "TheIsland_WP" } }),
+    });
+}
+
+#[cfg(all(feature = "services", feature = "tls"))]
+#[test]
+fn minetest() {
+    use gamedig::games::minetest;
+
+    assert_round_trip(&minetest::Response {
+        name: "Fake Minetest".to_string(),
+        description: "A fake server".to_string(),
+        game_version: "5.8.0".to_string(),
+        players_maximum: 15,
+        players_online: 1,
+        has_password: Some(false),
+        players: vec![minetest::Player {
+            name: "Alice".to_string(),
+        }],
+        id: "minetest".to_string(),
+        ip: "127.0.0.1".to_string(),
+        port: 30000,
+        creative: Some(true),
+        damage: false,
+        game_time: 3600,
+        lag: Some(0.5),
+        proto_max: 43,
+        proto_min: 37,
+        pvp: true,
+        uptime: 7200,
+        url: None,
+        update_time: 1_700_000_000,
+        start: 1_699_990_000,
+        clients_top: 4,
+        updates: 12,
+        pop_v: 1.25,
+        geo_continent: Some("EU".to_string()),
+        ping: 0.125,
+    });
+}